@@ -12,10 +12,17 @@ async fn main() -> Result<()> {
         Action::New { .. } => actions::new::handle(action)?,
         Action::Show => actions::show::handle(&globals)?,
         Action::Run { .. } => actions::run::handle(action, globals).await?,
+        Action::Upload { .. } => actions::upload::handle(action, globals).await?,
         Action::View { .. } => actions::view::handle(action, &globals)?,
         Action::Edit { .. } => actions::edit::handle(action, &globals)?,
-        Action::Restore { .. } => actions::restore::handle(action, &globals)?,
+        Action::Restore { .. } => actions::restore::handle(action, &globals).await?,
         Action::Verify { .. } => actions::verify::handle(action, &globals).await?,
+        Action::Prune { .. } => actions::prune::handle(action, &globals).await?,
+        Action::Gc { .. } => actions::gc::handle(action, &globals).await?,
+        Action::Recover { .. } => actions::recover::handle(action, &globals).await?,
+        Action::Rekey { .. } => actions::rekey::handle(action, &globals).await?,
+        Action::SplitKey { .. } => actions::split_key::handle(action, &globals)?,
+        Action::CombineKey => actions::combine_key::handle()?,
     }
 
     Ok(())
@@ -0,0 +1,19 @@
+use crate::{
+    cli::{actions::new::print_recovery_phrase, actions::run::prompt_shares},
+    utils::shamir::Share,
+};
+use anyhow::Result;
+use zeroize::Zeroizing;
+
+/// Handle the combine-key action.
+///
+/// # Errors
+/// Returns an error if a share is invalid, or the shares do not combine.
+pub fn handle() -> Result<()> {
+    let first = Zeroizing::new(rpassword::prompt_password("Enter a share: ")?);
+    let mnemonic = prompt_shares(Share::parse(&first)?)?;
+
+    print_recovery_phrase(&mnemonic.to_string());
+
+    Ok(())
+}
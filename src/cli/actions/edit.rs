@@ -1,5 +1,5 @@
 use crate::{
-    cli::{actions::Action, globals::GlobalArgs},
+    cli::{actions::Action, actions::run::prompt_recovery, globals::GlobalArgs},
     engine::edit::{EditBackupRequest, EditBackupResult, edit},
 };
 use anyhow::Result;
@@ -7,7 +7,8 @@ use anyhow::Result;
 /// Handle the edit action.
 ///
 /// # Errors
-/// Returns an error if the backup database is missing or cannot be updated.
+/// Returns an error if the backup database is missing or cannot be updated, or
+/// if recipients are added with the wrong mnemonic.
 pub fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
     if let Action::Edit {
         name,
@@ -17,8 +18,16 @@ pub fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
         remove_directories,
         remove_files,
         remove_destinations,
+        add_recipients,
     } = action
     {
+        // Re-wrapping keys to a new recipient takes the backup's own mnemonic.
+        let mnemonic = if add_recipients.is_empty() {
+            None
+        } else {
+            Some(prompt_recovery(&globals.home, &name, "add a recipient")?)
+        };
+
         let result = edit(EditBackupRequest {
             name: name.clone(),
             config_dir: globals.home.clone(),
@@ -28,6 +37,8 @@ pub fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
             remove_directories,
             remove_files,
             remove_destinations,
+            add_recipients,
+            mnemonic,
         })?;
 
         if !globals.quiet {
@@ -53,6 +64,13 @@ fn print_config(name: &str, result: &EditBackupResult) {
     print_section("Directories", &dirs);
     print_section("Files", &files);
     print_section("Destinations", &result.destinations);
+    print_section("Recipients", &result.recipients);
+    if result.wrapped_keys > 0 {
+        println!(
+            "Wrapped {} chunk key(s) to the new recipient(s).",
+            result.wrapped_keys
+        );
+    }
 }
 
 fn print_section(label: &str, entries: &[String]) {
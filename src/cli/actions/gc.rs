@@ -0,0 +1,71 @@
+use crate::{
+    cli::{actions::Action, globals::GlobalArgs},
+    engine::gc::{GcReport, GcRequest, gc},
+};
+use anyhow::{Result, anyhow};
+
+/// Handle the gc action.
+///
+/// # Errors
+/// Returns an error if the backup is missing, a destination cannot be opened,
+/// or some destinations could not be listed or orphans collected.
+pub async fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
+    if let Action::Gc {
+        name,
+        grace,
+        quarantine,
+        dry_run,
+    } = action
+    {
+        let report = gc(GcRequest {
+            name: name.clone(),
+            config_dir: globals.home.clone(),
+            grace,
+            quarantine,
+            dry_run,
+        })
+        .await?;
+
+        if !globals.quiet {
+            print_report(&name, quarantine, dry_run, &report);
+        }
+
+        if !report.failures.is_empty() {
+            return Err(anyhow!(
+                "{} destination(s) or object(s) could not be collected",
+                report.failures.len()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn print_report(name: &str, quarantine: bool, dry_run: bool, report: &GcReport) {
+    let verb = match (dry_run, quarantine) {
+        (true, false) => "Would remove",
+        (true, true) => "Would quarantine",
+        (false, false) => "Removed",
+        (false, true) => "Quarantined",
+    };
+    println!(
+        "{verb} {} unreferenced object(s) of \"{name}\", reclaiming {} bytes.",
+        report.collected.len(),
+        report.reclaimed_bytes
+    );
+    for orphan in &report.collected {
+        println!(
+            "  {}: {} ({} bytes)",
+            orphan.destination, orphan.key, orphan.size
+        );
+    }
+    if report.recent > 0 {
+        println!(
+            "{} unreferenced object(s) within the grace period were kept.",
+            report.recent
+        );
+    }
+    for failure in &report.failures {
+        println!("  could not collect {failure}");
+    }
+}
@@ -1,12 +1,20 @@
+pub mod combine_key;
 pub mod edit;
+pub mod gc;
 pub mod new;
+pub mod prune;
+pub mod recover;
+pub mod rekey;
 pub mod restore;
 pub mod run;
 pub mod show;
+pub mod split_key;
+pub mod upload;
 pub mod verify;
 pub mod view;
 
-use std::path::PathBuf;
+use crate::engine::{prune::RetentionPolicy, verify::Sample};
+use std::{path::PathBuf, time::Duration};
 
 #[derive(Debug)]
 pub enum Action {
@@ -16,6 +24,9 @@ pub enum Action {
         file: Option<Vec<PathBuf>>,
         destination: Vec<String>,
         config: PathBuf,
+        passphrase: bool,
+        shares: Option<u8>,
+        threshold: Option<u8>,
     },
     Show,
     Run {
@@ -23,6 +34,10 @@ pub enum Action {
         gitignore: bool,
         no_ignore: bool,
         dry_run: bool,
+        rehash: bool,
+    },
+    Upload {
+        name: String,
     },
     View {
         name: String,
@@ -38,6 +53,7 @@ pub enum Action {
         remove_directories: Vec<PathBuf>,
         remove_files: Vec<PathBuf>,
         remove_destinations: Vec<String>,
+        add_recipients: Vec<String>,
     },
     Restore {
         name: String,
@@ -48,5 +64,33 @@ pub enum Action {
     Verify {
         name: String,
         repair: bool,
+        deep: bool,
+        sample: Option<Sample>,
+    },
+    Prune {
+        name: String,
+        policy: RetentionPolicy,
+        dry_run: bool,
+    },
+    Gc {
+        name: String,
+        grace: Duration,
+        quarantine: bool,
+        dry_run: bool,
+    },
+    Recover {
+        name: String,
+        from: String,
+        passphrase: bool,
+    },
+    Rekey {
+        name: String,
+        restart: bool,
+    },
+    SplitKey {
+        name: String,
+        shares: u8,
+        threshold: u8,
     },
+    CombineKey,
 }
@@ -1,8 +1,10 @@
 use crate::{
-    cli::actions::Action,
+    cli::{actions::Action, actions::run::prompt_passphrase},
     engine::create::{CreateBackupRequest, create},
+    utils::shamir::{self, Share},
 };
 use anyhow::Result;
+use bip39::{Language, Mnemonic};
 
 /// Handle the create action.
 ///
@@ -15,23 +17,51 @@ pub fn handle(action: Action) -> Result<()> {
         directory,
         file,
         destination,
+        passphrase,
+        shares,
+        threshold,
     } = action
     {
+        // Checked before anything is created, so a bad split leaves no backup
+        // behind whose phrase was never shown.
+        let split = shares.zip(threshold);
+        if let Some((shares, threshold)) = split {
+            shamir::check_split(threshold, shares)?;
+        }
+
+        let passphrase = if passphrase {
+            Some(prompt_passphrase(&name, true)?)
+        } else {
+            None
+        };
+
         let result = create(CreateBackupRequest {
             name,
             config_dir: config,
             directories: directory.unwrap_or_default(),
             files: file.unwrap_or_default(),
             destinations: destination,
+            passphrase,
         })?;
 
-        print_recovery_phrase(&result.recovery_phrase);
+        match split {
+            Some((shares, threshold)) => {
+                let mnemonic =
+                    Mnemonic::parse_in_normalized(Language::English, &result.recovery_phrase)?;
+                print_shares(&shamir::split(&mnemonic, threshold, shares)?)?;
+            }
+            None => print_recovery_phrase(&result.recovery_phrase),
+        }
+        println!(
+            "\nIts public key, to add this phrase as a recipient of another backup\n(`backup edit <name> --add-recipient`):\n\n  {}",
+            result.public_key
+        );
     }
 
     Ok(())
 }
 
-fn print_recovery_phrase(recovery_phrase: &str) {
+pub(crate) fn print_recovery_phrase(recovery_phrase: &str) {
     let words: Vec<&str> = recovery_phrase.split_whitespace().collect();
 
     println!("Your recovery phrase is:\n");
@@ -46,3 +76,23 @@ fn print_recovery_phrase(recovery_phrase: &str) {
 
     println!("\n\nPlease write this down and store it in a safe place.");
 }
+
+pub(crate) fn print_shares(shares: &[Share]) -> Result<()> {
+    let threshold = shares.first().map_or(0, Share::threshold);
+
+    println!(
+        "Your recovery phrase is split into {} shares; any {threshold} of them recover it:\n",
+        shares.len()
+    );
+    for share in shares {
+        println!("Share {} of {}:", share.number(), shares.len());
+        println!("[ {} ]\n", share.to_phrase()?.as_str());
+    }
+
+    println!(
+        "Give each share to a different keyholder to store in a safe place. Every prompt\n\
+         for the recovery mnemonic accepts {threshold} shares instead, and\n\
+         `backup combine-key` turns them back into the 12 words."
+    );
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use crate::{
+    cli::{actions::Action, globals::GlobalArgs},
+    engine::prune::{PruneReport, PruneRequest, prune},
+};
+use anyhow::{Result, anyhow};
+
+/// Handle the prune action.
+///
+/// # Errors
+/// Returns an error if the backup is missing, the catalog cannot be updated, or
+/// some unreferenced objects could not be removed from a destination.
+pub async fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
+    if let Action::Prune {
+        name,
+        policy,
+        dry_run,
+    } = action
+    {
+        let report = prune(PruneRequest {
+            name: name.clone(),
+            config_dir: globals.home.clone(),
+            policy,
+            dry_run,
+        })
+        .await?;
+
+        if !globals.quiet {
+            print_report(&name, dry_run, &report);
+        }
+
+        if !report.failed_removals.is_empty() {
+            return Err(anyhow!(
+                "{} object(s) could not be removed",
+                report.failed_removals.len()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn print_report(name: &str, dry_run: bool, report: &PruneReport) {
+    if report.removed.is_empty() {
+        println!(
+            "Nothing to prune for \"{name}\": the policy keeps all {} version(s).",
+            report.kept.len()
+        );
+        return;
+    }
+
+    let (verb, objects) = if dry_run {
+        ("Would remove", "would be removed")
+    } else {
+        ("Removed", "removed")
+    };
+    println!(
+        "{verb} {} version(s) of \"{name}\", keeping {}: {}",
+        report.removed.len(),
+        report.kept.len(),
+        join(&report.removed)
+    );
+    println!(
+        "{} entry(ies), {} file content(s) and {} chunk(s) no longer referenced.",
+        report.pruned.entries, report.pruned.files, report.pruned.chunks
+    );
+    let unreferenced = report.pruned.packs.len() + report.pruned.loose_chunks.len();
+    if dry_run {
+        println!("{unreferenced} stored object(s) {objects} from every destination.");
+    } else {
+        println!(
+            "{unreferenced} stored object(s) {objects} ({} deletion(s) across destinations).",
+            report.removed_objects
+        );
+    }
+    for failure in &report.failed_removals {
+        println!("  could not remove {failure}");
+    }
+}
+
+fn join(versions: &[i64]) -> String {
+    versions
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,72 @@
+use crate::{
+    cli::{
+        actions::Action,
+        actions::run::{prompt_mnemonic, prompt_passphrase},
+        globals::GlobalArgs,
+    },
+    engine::recover::{RecoverReport, RecoverRequest, recover},
+    utils::crypto::RecoveryKey,
+};
+use anyhow::Result;
+
+/// Handle the recover action.
+///
+/// # Errors
+/// Returns an error if the backup already exists locally, the destination holds
+/// no catalog of it, or none opens with the mnemonic.
+pub async fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
+    if let Action::Recover {
+        name,
+        from,
+        passphrase,
+    } = action
+    {
+        let mnemonic = RecoveryKey {
+            mnemonic: prompt_mnemonic(&name, "recover")?,
+            passphrase: if passphrase {
+                Some(prompt_passphrase(&name, false)?)
+            } else {
+                None
+            },
+        };
+
+        let report = recover(RecoverRequest {
+            name: name.clone(),
+            config_dir: globals.home.clone(),
+            from: from.clone(),
+            mnemonic,
+        })
+        .await?;
+
+        if !globals.quiet {
+            print_report(&name, &from, &report);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_report(name: &str, from: &str, report: &RecoverReport) {
+    for skipped in &report.skipped {
+        println!("Skipped catalog {skipped}");
+    }
+    println!(
+        "Recovered \"{name}\" from catalog generation {} in {from}: {}",
+        report.generation,
+        report.db_path.display()
+    );
+    match report.latest_version {
+        Some(version) => println!("Latest completed version: {version}."),
+        None => println!("No completed version yet."),
+    }
+    if !report
+        .destinations
+        .iter()
+        .any(|destination| destination == from)
+    {
+        println!(
+            "{from} is not among the configured destinations ({}); use `backup edit {name} --to {from}` if it has moved.",
+            report.destinations.join(", ")
+        );
+    }
+}
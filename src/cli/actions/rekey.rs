@@ -0,0 +1,65 @@
+use crate::{
+    cli::{
+        actions::Action,
+        actions::new::print_recovery_phrase,
+        actions::run::{print_replication, prompt_recovery},
+        globals::GlobalArgs,
+    },
+    engine::rekey::{RekeyReport, RekeyRequest, begin_rekey, finish_rekey},
+};
+use anyhow::Result;
+
+/// Handle the rekey action.
+///
+/// # Errors
+/// Returns an error if the backup is missing, the mnemonic does not unlock it,
+/// or the catalog cannot be updated.
+pub async fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
+    if let Action::Rekey { name, restart } = action {
+        let request = RekeyRequest {
+            name: name.clone(),
+            config_dir: globals.home.clone(),
+            mnemonic: prompt_recovery(&globals.home, &name, "rekey")?,
+            restart,
+        };
+
+        // The new phrase is shown before any key moves to it, so an
+        // interruption never leaves a backup locked to a phrase nobody saw.
+        let start = begin_rekey(&request)?;
+        match &start.recovery_phrase {
+            Some(phrase) => print_recovery_phrase(phrase),
+            None => println!(
+                "Resuming the rekey of \"{name}\" to the recovery phrase printed when it began \
+                 (public key {}). If that phrase was lost, run `backup rekey {name} --restart`.",
+                start.public_key
+            ),
+        }
+
+        let report = finish_rekey(&request).await?;
+        if !globals.quiet {
+            print_report(&name, &report);
+        }
+        print_old_copies_warning();
+    }
+
+    Ok(())
+}
+
+fn print_report(name: &str, report: &RekeyReport) {
+    println!(
+        "\nRekeyed \"{name}\": re-wrapped {} chunk key(s); only the new recovery phrase opens it now.",
+        report.wrapped_keys
+    );
+    println!("Public key: {}", report.public_key);
+    print_replication(&report.catalog);
+}
+
+/// Content keys are re-wrapped, not rotated, so the rekey cannot take back
+/// what the old phrase could already read. Printed even with `--quiet`.
+fn print_old_copies_warning() {
+    println!(
+        "\nWarning: the data stored so far keeps its content keys. A copy of the catalog \
+         taken before this rekey still opens it with the old recovery phrase; only data \
+         stored from now on is out of that phrase's reach."
+    );
+}
@@ -1,11 +1,19 @@
-use crate::cli::{actions::Action, globals::GlobalArgs};
-use anyhow::Result;
+use crate::{
+    cli::{actions::Action, actions::run::prompt_recovery, globals::GlobalArgs},
+    engine::{
+        restore::{RestoreReport, RestoreRequest, restore},
+        view::parse_target,
+    },
+};
+use anyhow::{Result, anyhow};
+use std::path::Path;
 
 /// Handle the restore action.
 ///
 /// # Errors
-/// Currently never errors — restore is not implemented yet.
-pub fn handle(action: Action, _globals: &GlobalArgs) -> Result<()> {
+/// Returns an error if the backup is missing, the mnemonic is wrong, the target
+/// cannot be resolved, or any file could not be restored.
+pub async fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
     if let Action::Restore {
         name,
         target,
@@ -13,23 +21,57 @@ pub fn handle(action: Action, _globals: &GlobalArgs) -> Result<()> {
         into,
     } = action
     {
-        // TODO: implement restore. Once content storage exists, this should:
-        //   1. resolve the target (file id / path / whole snapshot) at `version`,
-        //   2. unwrap each file key with the recovery mnemonic,
-        //   3. fetch the blob, decrypt (ChaCha20-Poly1305) and decompress it,
-        //   4. write it to `into` (or its original path), verifying the hash.
-        // Blocked on the data plane (compression/encryption/upload) which is
-        // not built yet.
-        let scope = target.unwrap_or_else(|| "everything".to_string());
-        let version = version.map_or_else(|| "latest".to_string(), |v| v.to_string());
-        let destination =
-            into.map_or_else(|| "original paths".to_string(), |p| p.display().to_string());
-
-        println!("`restore` is not implemented yet.");
-        println!(
-            "Planned: restore {scope} from backup \"{name}\" (version {version}) into {destination}."
-        );
+        // Parse the target before prompting, so a typo fails without asking for
+        // the mnemonic first.
+        let target = target.as_deref().map(parse_target).transpose()?;
+        let mnemonic = prompt_recovery(&globals.home, &name, "restore")?;
+
+        let Some(report) = restore(RestoreRequest {
+            name: name.clone(),
+            config_dir: globals.home.clone(),
+            target,
+            version,
+            into: into.clone(),
+            mnemonic,
+        })
+        .await?
+        else {
+            println!(
+                "No completed snapshot for \"{name}\" yet — run `backup run {name}` (a previous run may have been interrupted)."
+            );
+            return Ok(());
+        };
+
+        if !globals.quiet {
+            print_report(&name, into.as_deref(), &report);
+        }
+
+        if !report.failed.is_empty() {
+            return Err(anyhow!(
+                "{} file(s) could not be restored",
+                report.failed.len()
+            ));
+        }
     }
 
     Ok(())
 }
+
+fn print_report(name: &str, into: Option<&Path>, report: &RestoreReport) {
+    let destination =
+        into.map_or_else(|| "original paths".to_string(), |p| p.display().to_string());
+
+    println!(
+        "Restored {} file(s) and {} director(ies) ({} bytes) from \"{name}\" version {} into {destination}.",
+        report.restored_files, report.restored_dirs, report.restored_bytes, report.version
+    );
+
+    if report.failed.is_empty() {
+        return;
+    }
+
+    println!("Could NOT restore {} file(s):", report.failed.len());
+    for failure in &report.failed {
+        println!("  {}: {}", failure.path.display(), failure.reason);
+    }
+}
@@ -2,13 +2,18 @@ use crate::{
     cli::{actions::Action, globals::GlobalArgs},
     db::sqlite::SqliteCatalog,
     engine::{
+        replicate::ReplicateReport,
         run::{
             IgnoreRules, NamingKey, ProgressCallback, RunBackupRequest, RunProgress, run,
             scan_worker_count,
         },
         wkey,
     },
-    utils::{crypto::unseal_naming_key, format::format_duration},
+    utils::{
+        crypto::{RecoveryKey, unseal_naming_key},
+        format::format_duration,
+        shamir::{self, Share},
+    },
 };
 use anyhow::{Result, anyhow};
 use bip39::{Language, Mnemonic};
@@ -17,14 +22,16 @@ use std::{path::Path, sync::Arc, time::Duration};
 use tracing::instrument;
 use zeroize::Zeroizing;
 
-struct RunProgressRenderer {
+/// Progress bars for `run` and `upload`: an overall counter and one row per
+/// worker.
+pub(crate) struct RunProgressRenderer {
     multi: MultiProgress,
     spinner: ProgressBar,
     workers: Arc<Vec<ProgressBar>>,
 }
 
 impl RunProgressRenderer {
-    fn new() -> Result<Self> {
+    pub(crate) fn new() -> Result<Self> {
         let multi = MultiProgress::new();
         let spinner = multi.add(ProgressBar::new(0));
         let worker_style = ProgressStyle::with_template("{spinner:.green} worker {prefix}: {msg}")?;
@@ -53,7 +60,7 @@ impl RunProgressRenderer {
         })
     }
 
-    fn callback(&self) -> ProgressCallback {
+    pub(crate) fn callback(&self) -> ProgressCallback {
         let spinner = self.spinner.clone();
         let workers = self.workers.clone();
 
@@ -85,14 +92,14 @@ impl RunProgressRenderer {
                     }
                 }
             }
-            RunProgress::StorePhaseStarted(total_blobs) => match u64::try_from(total_blobs) {
-                Ok(total_blobs) => {
-                    spinner.set_length(total_blobs);
+            RunProgress::StorePhaseStarted(total_files) => match u64::try_from(total_files) {
+                Ok(total_files) => {
+                    spinner.set_length(total_files);
                     spinner.set_position(0);
-                    if total_blobs == 0 {
+                    if total_files == 0 {
                         spinner.set_message("No new data to store");
                     } else {
-                        spinner.set_message("Compressing & encrypting");
+                        spinner.set_message("Chunking, compressing & encrypting");
                     }
                 }
                 Err(err) => {
@@ -129,7 +136,8 @@ impl RunProgressRenderer {
         })
     }
 
-    fn finish(&self, scanned_files: usize, skipped_entries: usize, skipped_files_log: &Path) {
+    /// Remove the progress bars from the terminal.
+    pub(crate) fn clear(&self) {
         self.spinner.finish_and_clear();
         for worker in &*self.workers {
             worker.finish_and_clear();
@@ -138,8 +146,11 @@ impl RunProgressRenderer {
         if let Err(err) = self.multi.clear() {
             tracing::debug!("Failed to clear progress output: {err}");
         }
+    }
+
+    pub(crate) fn finish(&self, skipped_entries: usize, skipped_files_log: &Path) {
+        self.clear();
 
-        println!("Scanned {scanned_files} files.");
         if skipped_entries > 0 {
             println!(
                 "Skipped {skipped_entries} entries. See log: {}",
@@ -149,7 +160,7 @@ impl RunProgressRenderer {
     }
 }
 
-fn progress_renderer(quiet: bool) -> Result<Option<RunProgressRenderer>> {
+pub(crate) fn progress_renderer(quiet: bool) -> Result<Option<RunProgressRenderer>> {
     if quiet {
         return Ok(None);
     }
@@ -179,13 +190,9 @@ pub(crate) fn resolve_naming_key(config_dir: &Path, name: &str) -> Result<Naming
 
     let sealed = SqliteCatalog::open(&db_file)?.sealed_naming_key()?;
 
-    let phrase = Zeroizing::new(rpassword::prompt_password(format!(
-        "Enter the recovery mnemonic for \"{name}\" to unlock: "
-    ))?);
-    let mnemonic = Mnemonic::parse_in_normalized(Language::English, phrase.trim())
-        .map_err(|_| anyhow!("Invalid recovery mnemonic"))?;
+    let recovery = prompt_recovery(config_dir, name, "unlock")?;
 
-    let naming_key = unseal_naming_key(&sealed, &mnemonic)
+    let naming_key = unseal_naming_key(&sealed, &recovery)
         .map_err(|_| anyhow!("Incorrect mnemonic: could not unlock backup \"{name}\""))?;
 
     wkey::write_naming_key(config_dir, name, &naming_key)?;
@@ -193,6 +200,86 @@ pub(crate) fn resolve_naming_key(config_dir: &Path, name: &str) -> Result<Naming
     Ok(Arc::new(naming_key))
 }
 
+/// Prompt for the recovery mnemonic of backup `name`; `purpose` completes the
+/// prompt ("... to unlock", "... to restore"). A share of a split mnemonic is
+/// accepted instead, and the rest of its threshold are then asked for.
+pub(crate) fn prompt_mnemonic(name: &str, purpose: &str) -> Result<Mnemonic> {
+    let phrase = Zeroizing::new(rpassword::prompt_password(format!(
+        "Enter the recovery mnemonic (or one of its shares) for \"{name}\" to {purpose}: "
+    ))?);
+
+    if Share::is_share(&phrase) {
+        return prompt_shares(Share::parse(&phrase)?);
+    }
+    Mnemonic::parse_in_normalized(Language::English, phrase.trim())
+        .map_err(|_| anyhow!("Invalid recovery mnemonic"))
+}
+
+/// Prompt for shares after `first` until its threshold is reached, and combine
+/// them into the mnemonic.
+pub(crate) fn prompt_shares(first: Share) -> Result<Mnemonic> {
+    let threshold = first.threshold();
+    let mut shares = vec![first];
+    while shares.len() < usize::from(threshold) {
+        let phrase = Zeroizing::new(rpassword::prompt_password(format!(
+            "Enter share {} of the {threshold} needed: ",
+            shares.len() + 1
+        ))?);
+        shares.push(Share::parse(&phrase)?);
+    }
+    shamir::combine(&shares)
+}
+
+/// Prompt for what derives backup `name`'s keypair: the recovery mnemonic and,
+/// if its catalog records that it was created with one, the passphrase.
+pub(crate) fn prompt_recovery(config_dir: &Path, name: &str, purpose: &str) -> Result<RecoveryKey> {
+    let mnemonic = prompt_mnemonic(name, purpose)?;
+
+    let db_file = config_dir.join(format!("{name}.db"));
+    let passphrase = if db_file.exists() && SqliteCatalog::open(&db_file)?.passphrase_required()? {
+        Some(prompt_passphrase(name, false)?)
+    } else {
+        None
+    };
+
+    Ok(RecoveryKey {
+        mnemonic,
+        passphrase,
+    })
+}
+
+/// Prompt for the passphrase of backup `name`, twice when `confirm` is set (on
+/// creation, where a typo would derive a key nobody can reproduce).
+pub(crate) fn prompt_passphrase(name: &str, confirm: bool) -> Result<Zeroizing<String>> {
+    let passphrase = Zeroizing::new(rpassword::prompt_password(format!(
+        "Enter the passphrase for \"{name}\": "
+    ))?);
+    if confirm {
+        if passphrase.is_empty() {
+            return Err(anyhow!("The passphrase cannot be empty"));
+        }
+        let again = Zeroizing::new(rpassword::prompt_password("Enter it again: ")?);
+        if again != passphrase {
+            return Err(anyhow!("The passphrases do not match"));
+        }
+    }
+    Ok(passphrase)
+}
+
+/// Report where the sealed catalog was stored, and what failed.
+pub(crate) fn print_replication(report: &ReplicateReport) {
+    if report.destination_count == 0 {
+        return;
+    }
+    println!(
+        "Catalog generation {} sealed to {} of {} destination(s).",
+        report.generation, report.stored, report.destination_count
+    );
+    for failure in &report.failures {
+        println!("Catalog replication: {failure}");
+    }
+}
+
 /// Handle the run action.
 ///
 /// # Errors
@@ -206,6 +293,7 @@ pub async fn handle(action: Action, globals: GlobalArgs) -> Result<()> {
         gitignore,
         no_ignore,
         dry_run,
+        rehash,
     } = action
     {
         let ignore_rules = if no_ignore {
@@ -217,9 +305,6 @@ pub async fn handle(action: Action, globals: GlobalArgs) -> Result<()> {
             }
         };
 
-        // Resolve the naming key before rendering progress, since unlocking may
-        // prompt for the mnemonic interactively.
-        let naming_key = resolve_naming_key(&globals.home, &name)?;
         let backup_name = name.clone();
 
         let progress = progress_renderer(globals.quiet)?;
@@ -230,17 +315,13 @@ pub async fn handle(action: Action, globals: GlobalArgs) -> Result<()> {
             config_dir: globals.home,
             ignore_rules,
             dry_run,
+            rehash,
             progress: progress_callback,
-            naming_key,
         })
         .await?;
 
         if let Some(progress) = progress {
-            progress.finish(
-                result.scanned_files,
-                result.skipped_entries,
-                &result.skipped_files_log,
-            );
+            progress.finish(result.skipped_entries, &result.skipped_files_log);
         } else if !globals.quiet && result.skipped_entries > 0 {
             println!(
                 "Skipped {} entries. See log: {}",
@@ -250,25 +331,25 @@ pub async fn handle(action: Action, globals: GlobalArgs) -> Result<()> {
         }
 
         if !globals.quiet {
-            if !dry_run {
-                if result.destination_count == 0 {
-                    println!(
-                        "No destinations configured — recorded metadata only (no data stored). Add one with `backup edit {backup_name} --to <path>`."
-                    );
-                } else {
-                    println!(
-                        "Stored {} new object(s) to {} destination(s).",
-                        result.stored_blobs, result.destination_count
-                    );
+            if dry_run {
+                println!(
+                    "Dry run: {} file(s) in {} director(ies) scanned, nothing recorded.\n",
+                    result.scanned_files, result.scanned_dirs
+                );
+            } else {
+                println!(
+                    "Recorded version {}: {} file(s) in {} director(ies) scanned, {} pending upload.",
+                    result.version, result.scanned_files, result.scanned_dirs, result.pending_files
+                );
+                if result.pending_files > 0 {
+                    println!("Run `backup upload {backup_name}` to store them.");
+                }
+                if let Some(catalog) = &result.catalog {
+                    print_replication(catalog);
                 }
+                println!();
             }
 
-            println!(
-                "Backup{} version: {}\n",
-                if dry_run { " (dry-run)" } else { "" },
-                result.version
-            );
-
             println!(
                 "Backup{} completed successfully in: {}.",
                 if dry_run { " (dry-run)" } else { "" },
@@ -0,0 +1,34 @@
+use crate::{
+    cli::{
+        actions::Action, actions::new::print_shares, actions::run::prompt_recovery,
+        globals::GlobalArgs,
+    },
+    engine::split_key::{SplitKeyRequest, split_key},
+};
+use anyhow::Result;
+
+/// Handle the split-key action.
+///
+/// # Errors
+/// Returns an error if the split is invalid, the backup is missing, or the
+/// mnemonic does not unlock it.
+pub fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
+    if let Action::SplitKey {
+        name,
+        shares,
+        threshold,
+    } = action
+    {
+        let shares = split_key(&SplitKeyRequest {
+            name: name.clone(),
+            config_dir: globals.home.clone(),
+            mnemonic: prompt_recovery(&globals.home, &name, "split")?,
+            threshold,
+            shares,
+        })?;
+
+        print_shares(&shares)?;
+    }
+
+    Ok(())
+}
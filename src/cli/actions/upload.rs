@@ -0,0 +1,92 @@
+use crate::{
+    cli::{
+        actions::{
+            Action,
+            run::{RunProgressRenderer, print_replication, progress_renderer, resolve_naming_key},
+        },
+        globals::GlobalArgs,
+    },
+    engine::upload::{UploadRequest, UploadResult, upload},
+    utils::format::format_duration,
+};
+use anyhow::Result;
+use tracing::instrument;
+
+/// Handle the upload action.
+///
+/// # Errors
+/// Returns an error if the backup is missing, has no reachable destination, or
+/// the catalog cannot be updated.
+#[instrument(skip(action, globals))]
+pub async fn handle(action: Action, globals: GlobalArgs) -> Result<()> {
+    let timer = globals.timer.start();
+
+    if let Action::Upload { name } = action {
+        // Resolve the naming key before rendering progress, since unlocking may
+        // prompt for the mnemonic interactively.
+        let naming_key = resolve_naming_key(&globals.home, &name)?;
+
+        let progress = progress_renderer(globals.quiet)?;
+        let progress_callback = progress.as_ref().map(RunProgressRenderer::callback);
+
+        let result = upload(UploadRequest {
+            name,
+            config_dir: globals.home,
+            naming_key,
+            progress: progress_callback,
+        })
+        .await?;
+
+        if let Some(progress) = progress {
+            progress.finish(result.skipped_entries, &result.skipped_files_log);
+        }
+
+        if !globals.quiet {
+            print_result(&result);
+            println!("Upload completed in: {}.", format_duration(timer.elapsed()));
+        }
+    }
+
+    Ok(())
+}
+
+fn print_result(result: &UploadResult) {
+    println!(
+        "Uploaded {} file(s): {} new chunk(s) in {} pack(s) to {} destination(s).",
+        result.uploaded_files, result.stored_chunks, result.stored_packs, result.destination_count
+    );
+    if result.changed_files > 0 {
+        println!(
+            "{} file(s) changed since `run`; their current content was stored.",
+            result.changed_files
+        );
+    }
+    if result.vanished_files > 0 {
+        println!(
+            "{} file(s) were deleted before they could be read.",
+            result.vanished_files
+        );
+    }
+    if result.copied_packs > 0 {
+        println!(
+            "Copied {} pack(s) to destinations that were missing them.",
+            result.copied_packs
+        );
+    }
+    if result.unavailable_packs > 0 {
+        println!(
+            "{} pack(s) are missing from some destinations and no destination could provide them. Run `backup verify --repair`.",
+            result.unavailable_packs
+        );
+    }
+    for destination in &result.failed_destinations {
+        println!("Could not write to {destination}; it will be caught up next upload.");
+    }
+    if let Some(version) = result.latest_version {
+        println!(
+            "Version {version} is restorable from {} of {} destination(s).",
+            result.sealed_destinations, result.destination_count
+        );
+    }
+    print_replication(&result.catalog);
+}
@@ -1,25 +1,51 @@
 use crate::{
-    cli::{actions::Action, actions::run::resolve_naming_key, globals::GlobalArgs},
-    engine::verify::{VerifyReport, verify},
+    cli::{
+        actions::Action,
+        actions::run::{prompt_recovery, resolve_naming_key},
+        globals::GlobalArgs,
+    },
+    engine::verify::{VerifyReport, VerifyRequest, verify},
 };
 use anyhow::Result;
 
 /// Handle the verify action.
 ///
 /// # Errors
-/// Returns an error if the backup is missing or a destination/catalog op fails.
+/// Returns an error if the backup is missing, the mnemonic is wrong, or a
+/// destination/catalog op fails.
 pub async fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
-    if let Action::Verify { name, repair } = action {
-        // Re-sealing missing-everywhere blobs reads the source files and needs the
-        // naming key to confirm they still match; resolving it may prompt for the
+    if let Action::Verify {
+        name,
+        repair,
+        deep,
+        sample,
+    } = action
+    {
+        // A deep check — sampled or not — opens blobs, so it needs the mnemonic
+        // itself (and derives the naming key from it). Otherwise re-sealing
+        // missing-everywhere blobs reads the source files and needs the naming
+        // key to confirm they still match; resolving it may prompt for the
         // mnemonic. An existence-only check needs no secret.
-        let naming_key = if repair {
+        let deep = if deep || sample.is_some() {
+            Some(prompt_recovery(&globals.home, &name, "verify")?)
+        } else {
+            None
+        };
+        let naming_key = if repair && deep.is_none() {
             Some(resolve_naming_key(&globals.home, &name)?)
         } else {
             None
         };
 
-        let report = verify(&globals.home, &name, repair, naming_key).await?;
+        let report = verify(VerifyRequest {
+            name: name.clone(),
+            config_dir: globals.home.clone(),
+            repair,
+            naming_key,
+            deep,
+            sample,
+        })
+        .await?;
 
         if !globals.quiet {
             print_report(&name, repair, &report);
@@ -30,20 +56,48 @@ pub async fn handle(action: Action, globals: &GlobalArgs) -> Result<()> {
 }
 
 fn print_report(name: &str, repair: bool, report: &VerifyReport) {
-    println!(
-        "Verified {} content object(s) across {} destination(s) for \"{name}\".",
-        report.content_ids, report.destinations
-    );
+    if report.unchecked == 0 {
+        println!(
+            "Verified {} chunk(s) in {} object(s) across {} destination(s) for \"{name}\".",
+            report.chunks, report.objects, report.destinations
+        );
+    } else {
+        println!(
+            "Verified a sample of {} of {} object(s), least recently verified first, across \
+             {} destination(s) for \"{name}\".",
+            report.objects,
+            report.objects + report.unchecked,
+            report.destinations
+        );
+    }
 
-    if report.missing == 0 {
-        println!("All blobs present. Nothing to repair.");
+    if report.missing == 0 && report.corrupt.is_empty() {
+        println!("All objects present. Nothing to repair.");
         return;
     }
 
-    println!("Missing blob copies: {}.", report.missing);
+    if report.missing > 0 {
+        println!("Missing object copies: {}.", report.missing);
+    }
+    if !report.corrupt.is_empty() {
+        println!("Corrupt object copies: {}.", report.corrupt.len());
+        for copy in &report.corrupt {
+            println!(
+                "  {}: {} ({} damaged chunk(s))",
+                copy.destination,
+                copy.object,
+                copy.chunks.len()
+            );
+        }
+    }
 
     if !repair {
-        println!("Run `backup verify {name} --repair` to restore them.");
+        let deep = if report.corrupt.is_empty() {
+            ""
+        } else {
+            " --deep"
+        };
+        println!("Run `backup verify {name}{deep} --repair` to restore them.");
         return;
     }
 
@@ -55,15 +109,15 @@ fn print_report(name: &str, repair: bool, report: &VerifyReport) {
     }
     if report.repaired_by_reseal > 0 {
         println!(
-            "Re-sealed {} object(s) from source files.",
+            "Re-sealed {} chunk(s) from source files into new packs.",
             report.repaired_by_reseal
         );
     }
     if report.unrecoverable.is_empty() {
-        println!("All missing blobs repaired.");
+        println!("All missing objects repaired.");
     } else {
         println!(
-            "{} object(s) could NOT be recovered (gone from every destination and the \
+            "{} chunk(s) could NOT be recovered (gone from every destination and the \
              source files are missing or changed):",
             report.unrecoverable.len()
         );
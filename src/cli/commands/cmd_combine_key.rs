@@ -0,0 +1,27 @@
+use clap::Command;
+
+pub fn command() -> Command {
+    Command::new("combine-key")
+        .about("Recombine shares made by `split-key` or `new --shares` into the recovery mnemonic")
+        .long_about(
+            "Prompt for shares of a split recovery mnemonic, one at a time, until the \
+             threshold recorded in them is reached, and print the 12 words they \
+             recombine to. No backup is read: each share carries a checksum, and shares \
+             of different splits are refused.",
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_no_arguments() {
+        assert!(command().try_get_matches_from(vec!["combine-key"]).is_ok());
+        assert!(
+            command()
+                .try_get_matches_from(vec!["combine-key", "demo"])
+                .is_err()
+        );
+    }
+}
@@ -55,6 +55,14 @@ pub fn command() -> Command {
                 .help("Remove a configured destination")
                 .value_parser(NonEmptyStringValueParser::new()),
         )
+        .arg(
+            Arg::new("add-recipient")
+                .action(ArgAction::Append)
+                .long("add-recipient")
+                .value_name("PUBLIC_KEY")
+                .help("Also wrap every key to another keyholder's public key, so their mnemonic restores this backup (repeatable; prompts for the recovery mnemonic)")
+                .value_parser(validators::is_public_key()),
+        )
 }
 
 #[cfg(test)]
@@ -133,4 +141,20 @@ mod tests {
         assert_eq!(rm, vec!["/old"]);
         Ok(())
     }
+
+    #[test]
+    fn add_recipient_takes_a_hex_public_key() -> Result<()> {
+        let key = "AB".repeat(32);
+        let matches = matches_for(&["edit", "demo", "--add-recipient", &key])?;
+        let recipients: Vec<&String> = matches
+            .get_many::<String>("add-recipient")
+            .unwrap_or_default()
+            .collect();
+        assert_eq!(recipients, vec![&"ab".repeat(32)]);
+
+        for bad in ["abcd", &"zz".repeat(32), ""] {
+            assert!(matches_for(&["edit", "demo", "--add-recipient", bad]).is_err());
+        }
+        Ok(())
+    }
 }
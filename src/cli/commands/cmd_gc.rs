@@ -0,0 +1,71 @@
+use crate::cli::commands::validators;
+use clap::{Arg, ArgAction, Command, value_parser};
+
+pub fn command() -> Command {
+    Command::new("gc")
+        .about("Reclaim stored objects the backup no longer references")
+        .long_about(
+            "List every destination and remove the objects the catalog does not \
+             reference: packs left by an interrupted upload or repair, or that prune \
+             could not remove. Objects younger than the grace period are kept, since \
+             an upload running at the same time may not have recorded them yet. A \
+             destination that also holds another backup is reported and left alone.",
+        )
+        .arg(
+            Arg::new("name")
+                .help("Name of the backup. Use \"show\" to see current configurations")
+                .required(true)
+                .value_parser(validators::is_alphanumeric()),
+        )
+        .arg(
+            Arg::new("grace")
+                .long("grace")
+                .value_name("HOURS")
+                .help("Only collect objects older than this many hours")
+                .default_value("24")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("quarantine")
+                .long("quarantine")
+                .help("Move unreferenced objects to the destination's quarantine/ instead of deleting them")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Do not delete anything, only show what would be reclaimed")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn grace_defaults_to_a_day() -> Result<()> {
+        let matches = command().try_get_matches_from(vec!["gc", "demo"])?;
+        assert_eq!(matches.get_one::<u64>("grace").copied(), Some(24));
+        assert!(!matches.get_flag("quarantine"));
+        assert!(!matches.get_flag("dry-run"));
+        Ok(())
+    }
+
+    #[test]
+    fn parses_grace_quarantine_and_dry_run() -> Result<()> {
+        let matches = command().try_get_matches_from(vec![
+            "gc",
+            "demo",
+            "--grace",
+            "0",
+            "--quarantine",
+            "--dry-run",
+        ])?;
+        assert_eq!(matches.get_one::<u64>("grace").copied(), Some(0));
+        assert!(matches.get_flag("quarantine"));
+        assert!(matches.get_flag("dry-run"));
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
-use crate::cli::commands::validators;
+use crate::cli::commands::{
+    cmd_split_key::{shares_arg, threshold_arg},
+    validators,
+};
 use clap::{Arg, ArgAction, Command, builder::NonEmptyStringValueParser};
 
 pub fn command() -> Command {
@@ -34,6 +37,14 @@ pub fn command() -> Command {
                 .help("Add a destination to store the backup (path or S3 target); repeatable")
                 .value_parser(NonEmptyStringValueParser::new()),
         )
+        .arg(
+            Arg::new("passphrase")
+                .long("passphrase")
+                .help("Derive the keypair from the mnemonic and a passphrase (prompted for), both needed to recover")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(shares_arg().requires("threshold"))
+        .arg(threshold_arg().requires("shares"))
 }
 
 #[cfg(test)]
@@ -85,6 +96,46 @@ mod tests {
             .unwrap_or_default()
             .collect();
         assert_eq!(dests, vec!["/mnt/a", "s3://bucket/x"]);
+        assert!(!matches.get_flag("passphrase"));
+        Ok(())
+    }
+
+    #[test]
+    fn passphrase_is_a_flag() -> anyhow::Result<()> {
+        let matches = command().try_get_matches_from(vec!["new", "demo", "--passphrase"])?;
+        assert!(matches.get_flag("passphrase"));
+        // The passphrase itself is prompted for, never given on the command line.
+        assert!(
+            command()
+                .try_get_matches_from(vec!["new", "demo", "--passphrase", "secret"])
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shares_need_a_threshold() -> anyhow::Result<()> {
+        let matches = command().try_get_matches_from(vec![
+            "new",
+            "demo",
+            "--shares",
+            "5",
+            "--threshold",
+            "3",
+        ])?;
+        assert_eq!(matches.get_one::<u8>("shares").copied(), Some(5));
+        assert_eq!(matches.get_one::<u8>("threshold").copied(), Some(3));
+
+        assert!(
+            command()
+                .try_get_matches_from(vec!["new", "demo", "--shares", "5"])
+                .is_err()
+        );
+        assert!(
+            command()
+                .try_get_matches_from(vec!["new", "demo", "--threshold", "3"])
+                .is_err()
+        );
         Ok(())
     }
 }
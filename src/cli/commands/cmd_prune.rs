@@ -0,0 +1,97 @@
+use crate::cli::commands::validators;
+use clap::{Arg, ArgAction, ArgGroup, Command, value_parser};
+
+pub fn command() -> Command {
+    Command::new("prune")
+        .visible_alias("forget")
+        .about("Delete old versions under a retention policy and reclaim their space")
+        .long_about(
+            "Keep the versions the policy selects and delete the rest: the newest \
+             --keep-last versions, plus the newest version of each of the last \
+             --keep-daily days, --keep-weekly weeks and --keep-monthly months (UTC). \
+             A version kept by any rule stays, and the latest version is always kept.\n\n\
+             Content only the deleted versions referenced is dropped from the catalog \
+             and its packs are removed from every destination. Pass at least one \
+             --keep-* option.",
+        )
+        .arg(
+            Arg::new("name")
+                .help("Name of the backup. Use \"show\" to see current configurations")
+                .required(true)
+                .value_parser(validators::is_alphanumeric()),
+        )
+        .arg(keep_arg("keep-last", "Keep the N most recent versions"))
+        .arg(keep_arg(
+            "keep-daily",
+            "Keep the newest version of each of the last N days",
+        ))
+        .arg(keep_arg(
+            "keep-weekly",
+            "Keep the newest version of each of the last N weeks",
+        ))
+        .arg(keep_arg(
+            "keep-monthly",
+            "Keep the newest version of each of the last N months",
+        ))
+        .group(
+            ArgGroup::new("policy")
+                .args(["keep-last", "keep-daily", "keep-weekly", "keep-monthly"])
+                .multiple(true)
+                .required(true),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Do not delete anything, only show what would be removed")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+fn keep_arg(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name)
+        .long(name)
+        .value_name("N")
+        .help(help)
+        .value_parser(value_parser!(usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn a_policy_is_required() {
+        assert!(
+            command()
+                .try_get_matches_from(vec!["prune", "demo"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_policy_and_dry_run() -> Result<()> {
+        let matches = command().try_get_matches_from(vec![
+            "prune",
+            "demo",
+            "--keep-last",
+            "3",
+            "--keep-monthly",
+            "12",
+            "--dry-run",
+        ])?;
+        assert_eq!(matches.get_one::<usize>("keep-last").copied(), Some(3));
+        assert_eq!(matches.get_one::<usize>("keep-daily").copied(), None);
+        assert_eq!(matches.get_one::<usize>("keep-monthly").copied(), Some(12));
+        assert!(matches.get_flag("dry-run"));
+        Ok(())
+    }
+
+    #[test]
+    fn forget_is_an_alias() -> Result<()> {
+        let matches = crate::cli::commands::new(std::path::PathBuf::from("."))
+            .try_get_matches_from(vec!["backup", "forget", "demo", "--keep-daily", "7"])?;
+        assert_eq!(matches.subcommand_name(), Some("prune"));
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::cli::commands::validators;
+use clap::{Arg, ArgAction, Command, builder::NonEmptyStringValueParser};
+
+pub fn command() -> Command {
+    Command::new("recover")
+        .about("Rebuild a backup on a new machine from a destination and the mnemonic")
+        .long_about(
+            "Find the newest sealed catalog of the backup in the destination, open it \
+             with the recovery mnemonic and write it back to the configuration \
+             directory, along with the naming-key cache. The destination only needs \
+             to be readable; the backup can then be restored, or backed up to again.\n\n\
+             A backup of the same name must not exist locally. A backup created with \
+             --passphrase needs it here too: the catalog that records it is sealed.",
+        )
+        .arg(
+            Arg::new("name")
+                .help("Name of the backup to recover")
+                .required(true)
+                .value_parser(validators::is_alphanumeric()),
+        )
+        .arg(
+            Arg::new("from")
+                .long("from")
+                .value_name("DESTINATION")
+                .help("Destination holding the backup: a path or an s3:// URL")
+                .required(true)
+                .value_parser(NonEmptyStringValueParser::new()),
+        )
+        .arg(
+            Arg::new("passphrase")
+                .long("passphrase")
+                .help("The backup was created with a passphrase: prompt for it after the mnemonic")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_and_source_are_required() {
+        assert!(command().try_get_matches_from(vec!["recover"]).is_err());
+        assert!(
+            command()
+                .try_get_matches_from(vec!["recover", "demo"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_name_and_source() -> anyhow::Result<()> {
+        let matches =
+            command().try_get_matches_from(vec!["recover", "demo", "--from", "/mnt/backup"])?;
+        assert_eq!(
+            matches.get_one::<String>("name").map(String::as_str),
+            Some("demo")
+        );
+        assert_eq!(
+            matches.get_one::<String>("from").map(String::as_str),
+            Some("/mnt/backup")
+        );
+        assert!(!matches.get_flag("passphrase"));
+
+        let matches = command().try_get_matches_from(vec![
+            "recover",
+            "demo",
+            "--from",
+            "/mnt/backup",
+            "--passphrase",
+        ])?;
+        assert!(matches.get_flag("passphrase"));
+        Ok(())
+    }
+}
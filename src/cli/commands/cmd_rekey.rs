@@ -0,0 +1,52 @@
+use crate::cli::commands::validators;
+use clap::{Arg, ArgAction, Command};
+
+pub fn command() -> Command {
+    Command::new("rekey")
+        .about("Move a backup to a new recovery mnemonic, e.g. after the old one was exposed")
+        .long_about(
+            "Generate a new recovery mnemonic and re-wrap the naming key and every chunk \
+             key to it, with the current mnemonic. Only the catalog changes: no pack is \
+             read or written again. The pass is resumable: run it again with the current \
+             mnemonic after an interruption, and it carries on to the phrase it printed. \
+             Until it completes, the current mnemonic keeps working.\n\n\
+             Content keys are not rotated: a copy of the catalog taken before the rekey \
+             still opens the content stored then with the old mnemonic.",
+        )
+        .arg(
+            Arg::new("name")
+                .help("Name of the backup to rekey")
+                .required(true)
+                .value_parser(validators::is_alphanumeric()),
+        )
+        .arg(
+            Arg::new("restart")
+                .long("restart")
+                .help("Drop an unfinished rekey (e.g. its new phrase was lost) and begin again with a fresh mnemonic")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_is_required() {
+        assert!(command().try_get_matches_from(vec!["rekey"]).is_err());
+    }
+
+    #[test]
+    fn parses_name_and_restart() -> anyhow::Result<()> {
+        let matches = command().try_get_matches_from(vec!["rekey", "demo"])?;
+        assert_eq!(
+            matches.get_one::<String>("name").map(String::as_str),
+            Some("demo")
+        );
+        assert!(!matches.get_flag("restart"));
+
+        let matches = command().try_get_matches_from(vec!["rekey", "demo", "--restart"])?;
+        assert!(matches.get_flag("restart"));
+        Ok(())
+    }
+}
@@ -3,7 +3,13 @@ use clap::{Arg, Command, builder::NonEmptyStringValueParser, value_parser};
 
 pub fn command() -> Command {
     Command::new("restore")
-        .about("Restore files from a backup (not implemented yet)")
+        .about("Restore files from a backup")
+        .long_about(
+            "Restore a whole snapshot, a single file id, or a directory subtree. Each \
+             blob is fetched from the first destination holding a good copy, decrypted \
+             with the recovery mnemonic (prompted), checked against its content id, and \
+             written atomically.",
+        )
         .arg(
             Arg::new("name")
                 .help("Name of the backup. Use \"show\" to see current configurations")
@@ -29,6 +29,12 @@ pub fn command() -> Command {
                 .help("Do not create the backup, only show what would be done")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("rehash")
+                .long("rehash")
+                .help("Do not trust file size and timestamps: leave every file for upload to re-read and verify")
+                .action(ArgAction::SetTrue),
+        )
 }
 
 #[cfg(test)]
@@ -50,6 +56,7 @@ mod tests {
         assert_eq!(matches.get_one::<bool>("gitignore").copied(), Some(false));
         assert_eq!(matches.get_one::<bool>("no-ignore").copied(), Some(false));
         assert_eq!(matches.get_one::<bool>("dry-run").copied(), Some(false));
+        assert_eq!(matches.get_one::<bool>("rehash").copied(), Some(false));
         Ok(())
     }
 
@@ -75,6 +82,13 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_argumets_rehash() -> Result<()> {
+        let matches = matches_for(&["run", "test", "--rehash"])?;
+        assert_eq!(matches.get_one::<bool>("rehash").copied(), Some(true));
+        Ok(())
+    }
+
     #[test]
     fn test_argumets_gitignore_and_dry_run() -> Result<()> {
         let matches = matches_for(&["run", "test", "--gitignore", "--dry-run"])?;
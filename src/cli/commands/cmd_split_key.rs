@@ -0,0 +1,91 @@
+use crate::{cli::commands::validators, utils::shamir::MAX_SHARES};
+use clap::{Arg, Command, value_parser};
+
+pub fn command() -> Command {
+    Command::new("split-key")
+        .about("Split a backup's recovery mnemonic into shares, any threshold of which recover it")
+        .long_about(
+            "Check the recovery mnemonic against the backup, then split it into --shares \
+             word shares (Shamir secret sharing), any --threshold of which recover it; \
+             fewer reveal nothing about it. Every prompt for the mnemonic accepts shares \
+             instead, and `backup combine-key` turns them back into the 12 words.\n\n\
+             Splitting again makes a new, independent set: shares of different splits \
+             do not combine. A passphrase, if the backup has one, is not split and is \
+             still needed.",
+        )
+        .arg(
+            Arg::new("name")
+                .help("Name of the backup whose mnemonic to split")
+                .required(true)
+                .value_parser(validators::is_alphanumeric()),
+        )
+        .arg(shares_arg().required(true))
+        .arg(threshold_arg().required(true))
+}
+
+/// `--shares N`, shared with `backup new`.
+pub(crate) fn shares_arg() -> Arg {
+    Arg::new("shares")
+        .long("shares")
+        .value_name("N")
+        .help("Split the recovery mnemonic into N shares (2 to 16)")
+        .value_parser(value_parser!(u8).range(2..=i64::from(MAX_SHARES)))
+}
+
+/// `--threshold K`, shared with `backup new`.
+pub(crate) fn threshold_arg() -> Arg {
+    Arg::new("threshold")
+        .long("threshold")
+        .value_name("K")
+        .help("Shares needed to recover the mnemonic (2 to --shares)")
+        .value_parser(value_parser!(u8).range(2..=i64::from(MAX_SHARES)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_and_threshold_are_required() {
+        assert!(
+            command()
+                .try_get_matches_from(vec!["split-key", "demo", "--shares", "5"])
+                .is_err()
+        );
+        assert!(
+            command()
+                .try_get_matches_from(vec!["split-key", "demo", "--threshold", "3"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_a_split_within_bounds() -> anyhow::Result<()> {
+        let matches = command().try_get_matches_from(vec![
+            "split-key",
+            "demo",
+            "--shares",
+            "5",
+            "--threshold",
+            "3",
+        ])?;
+        assert_eq!(matches.get_one::<u8>("shares").copied(), Some(5));
+        assert_eq!(matches.get_one::<u8>("threshold").copied(), Some(3));
+
+        for (shares, threshold) in [("17", "3"), ("5", "1"), ("5", "x")] {
+            assert!(
+                command()
+                    .try_get_matches_from(vec![
+                        "split-key",
+                        "demo",
+                        "--shares",
+                        shares,
+                        "--threshold",
+                        threshold,
+                    ])
+                    .is_err()
+            );
+        }
+        Ok(())
+    }
+}
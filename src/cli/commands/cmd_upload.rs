@@ -0,0 +1,42 @@
+use crate::cli::commands::validators;
+use clap::{Arg, Command};
+
+pub fn command() -> Command {
+    Command::new("upload")
+        .about("Store the content recorded by `run` in every destination")
+        .long_about(
+            "Read the files that `run` found new or changed, chunk, compress and \
+             encrypt them into packs, and store the packs in every destination. \
+             Destinations missing packs from an earlier upload (one was unreachable, \
+             or was added later) are caught up from one that has them.\n\n\
+             Progress is committed as it goes, so an interrupted upload resumes \
+             where it stopped. A version can be restored from a destination once \
+             everything it references has been uploaded there.",
+        )
+        .arg(
+            Arg::new("name")
+                .help("Name of the backup. Use \"show\" to see current configurations")
+                .required(true)
+                .value_parser(validators::is_alphanumeric()),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_is_required() {
+        assert!(command().try_get_matches_from(vec!["upload"]).is_err());
+    }
+
+    #[test]
+    fn parses_name() -> anyhow::Result<()> {
+        let matches = command().try_get_matches_from(vec!["upload", "demo"])?;
+        assert_eq!(
+            matches.get_one::<String>("name").map(String::as_str),
+            Some("demo")
+        );
+        Ok(())
+    }
+}
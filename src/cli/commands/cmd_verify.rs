@@ -3,14 +3,20 @@ use clap::{Arg, ArgAction, Command};
 
 pub fn command() -> Command {
     Command::new("verify")
-        .about("Check that every stored blob still exists in each destination")
+        .about("Check that every stored blob still exists in each destination, and is intact")
         .long_about(
             "Re-check each destination against the catalog: are all content blobs \
              still present? `run` trusts the catalog when deciding what to upload, so \
              blobs deleted directly from a destination would otherwise go unnoticed.\n\n\
-             With --repair, missing blobs are restored: copied from a healthy \
-             destination when one still has the blob, or re-sealed from the source \
-             file when it is gone everywhere. Re-sealing reads the original files and \
+             With --deep, every blob is also downloaded, decrypted and re-hashed, so a \
+             damaged or truncated copy is caught too; this asks for the recovery \
+             mnemonic.\n\n\
+             --sample and --max-bytes limit a deep check to part of the blobs, those \
+             verified least recently first, so that a nightly run gets through all of \
+             them over a few nights.\n\n\
+             With --repair, missing (and, with --deep, corrupt) blobs are restored: \
+             copied from a healthy destination when one still has a good copy, or \
+             re-sealed from the source file when it is gone everywhere. Re-sealing reads the original files and \
              may prompt for the recovery mnemonic.",
         )
         .arg(
@@ -25,6 +31,26 @@ pub fn command() -> Command {
                 .help("Restore missing blobs (copy from a healthy destination, else re-seal from source)")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("deep")
+                .long("deep")
+                .help("Download, decrypt and re-hash every blob, not just check it exists")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sample")
+                .long("sample")
+                .value_name("PERCENT")
+                .help("Deep-check only this share of the blobs (e.g. 5%), least recently verified first")
+                .value_parser(validators::is_percent()),
+        )
+        .arg(
+            Arg::new("max-bytes")
+                .long("max-bytes")
+                .value_name("SIZE")
+                .help("Deep-check only as many blobs as fit in this much download (e.g. 20G)")
+                .value_parser(validators::is_byte_size()),
+        )
 }
 
 #[cfg(test)]
@@ -47,6 +73,48 @@ mod tests {
     fn parses_repair_flag() -> anyhow::Result<()> {
         let matches = command().try_get_matches_from(vec!["verify", "demo", "--repair"])?;
         assert!(matches.get_flag("repair"));
+        assert!(!matches.get_flag("deep"));
+        Ok(())
+    }
+
+    #[test]
+    fn parses_sample_and_byte_budget() -> anyhow::Result<()> {
+        let matches = command().try_get_matches_from(vec![
+            "verify",
+            "demo",
+            "--sample",
+            "5%",
+            "--max-bytes",
+            "20G",
+        ])?;
+        assert_eq!(matches.get_one::<u8>("sample").copied(), Some(5));
+        assert_eq!(matches.get_one::<u64>("max-bytes").copied(), Some(20 << 30));
+
+        let matches =
+            command().try_get_matches_from(vec!["verify", "demo", "--max-bytes", "1500"])?;
+        assert_eq!(matches.get_one::<u64>("max-bytes").copied(), Some(1500));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_bad_sample_and_byte_budget() {
+        for args in [
+            ["--sample", "0%"],
+            ["--sample", "101"],
+            ["--max-bytes", "20X"],
+            ["--max-bytes", "G"],
+        ] {
+            let argv = ["verify", "demo"].into_iter().chain(args);
+            assert!(command().try_get_matches_from(argv).is_err());
+        }
+    }
+
+    #[test]
+    fn parses_deep_flag() -> anyhow::Result<()> {
+        let matches =
+            command().try_get_matches_from(vec!["verify", "demo", "--deep", "--repair"])?;
+        assert!(matches.get_flag("deep"));
+        assert!(matches.get_flag("repair"));
         Ok(())
     }
 }
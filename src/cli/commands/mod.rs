@@ -1,8 +1,15 @@
+pub mod cmd_combine_key;
 pub mod cmd_edit;
+pub mod cmd_gc;
 pub mod cmd_new;
+pub mod cmd_prune;
+pub mod cmd_recover;
+pub mod cmd_rekey;
 pub mod cmd_restore;
 pub mod cmd_run;
 pub mod cmd_show;
+pub mod cmd_split_key;
+pub mod cmd_upload;
 pub mod cmd_verify;
 pub mod cmd_view;
 pub mod validators;
@@ -43,11 +50,18 @@ pub fn new(config_path: PathBuf) -> Command {
                 .global(true)
                 .action(clap::ArgAction::SetTrue),
         )
+        .subcommand(cmd_combine_key::command())
         .subcommand(cmd_edit::command())
+        .subcommand(cmd_gc::command())
         .subcommand(cmd_new::command())
+        .subcommand(cmd_prune::command())
+        .subcommand(cmd_recover::command())
+        .subcommand(cmd_rekey::command())
         .subcommand(cmd_restore::command())
         .subcommand(cmd_run::command())
         .subcommand(cmd_show::command())
+        .subcommand(cmd_split_key::command())
+        .subcommand(cmd_upload::command())
         .subcommand(cmd_verify::command())
         .subcommand(cmd_view::command())
 }
@@ -48,3 +48,54 @@ pub fn is_dir() -> ValueParser {
         ))
     })
 }
+
+/// Accept a whole percentage from 1 to 100, with or without a trailing `%`.
+#[must_use]
+pub fn is_percent() -> ValueParser {
+    ValueParser::from(move |s: &str| -> std::result::Result<u8, String> {
+        match s.strip_suffix('%').unwrap_or(s).parse::<u8>() {
+            Ok(percent @ 1..=100) => Ok(percent),
+            _ => Err(format!("Expected a percentage from 1% to 100%: '{s}'")),
+        }
+    })
+}
+
+/// Accept a byte size: a whole number, optionally followed by `K`, `M`, `G` or
+/// `T` (binary multiples, so `20G` is 20 GiB).
+#[must_use]
+pub fn is_byte_size() -> ValueParser {
+    ValueParser::from(move |s: &str| -> std::result::Result<u64, String> {
+        let invalid = || format!("Expected a size such as 500M or 20G: '{s}'");
+        let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let shift = match s
+            .get(digits.len()..)
+            .map(str::to_ascii_uppercase)
+            .as_deref()
+        {
+            Some("") => 0,
+            Some("K") => 10,
+            Some("M") => 20,
+            Some("G") => 30,
+            Some("T") => 40,
+            _ => return Err(invalid()),
+        };
+        digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|value| value.checked_mul(1 << shift))
+            .filter(|bytes| *bytes > 0)
+            .ok_or_else(invalid)
+    })
+}
+
+/// Accept a public key as 64 hex characters (as `backup new` prints it),
+/// normalized to lowercase.
+#[must_use]
+pub fn is_public_key() -> ValueParser {
+    ValueParser::from(move |s: &str| -> std::result::Result<String, String> {
+        match hex::decode(s) {
+            Ok(bytes) if bytes.len() == 32 => Ok(s.to_ascii_lowercase()),
+            _ => Err(format!("Expected a public key of 64 hex characters: '{s}'")),
+        }
+    })
+}
@@ -0,0 +1,5 @@
+use crate::cli::actions::Action;
+
+pub const fn dispatch() -> Action {
+    Action::CombineKey
+}
@@ -45,5 +45,11 @@ pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
             .unwrap_or_default()
             .cloned()
             .collect(),
+
+        add_recipients: matches
+            .get_many::<String>("add-recipient")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
     })
 }
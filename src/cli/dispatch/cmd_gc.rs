@@ -0,0 +1,17 @@
+use crate::cli::actions::Action;
+use anyhow::Result;
+use clap::ArgMatches;
+use std::time::Duration;
+
+pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
+    let hours = matches.get_one::<u64>("grace").copied().unwrap_or(24);
+    Ok(Action::Gc {
+        name: matches
+            .get_one("name")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Name required"))?,
+        grace: Duration::from_secs(hours.saturating_mul(60 * 60)),
+        quarantine: matches.get_flag("quarantine"),
+        dry_run: matches.get_flag("dry-run"),
+    })
+}
@@ -36,5 +36,11 @@ pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
             .unwrap_or_default()
             .cloned()
             .collect(),
+
+        passphrase: matches.get_flag("passphrase"),
+
+        shares: matches.get_one::<u8>("shares").copied(),
+
+        threshold: matches.get_one::<u8>("threshold").copied(),
     })
 }
@@ -0,0 +1,20 @@
+use crate::{cli::actions::Action, engine::prune::RetentionPolicy};
+use anyhow::Result;
+use clap::ArgMatches;
+
+pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
+    let keep = |rule: &str| matches.get_one::<usize>(rule).copied().unwrap_or(0);
+    Ok(Action::Prune {
+        name: matches
+            .get_one("name")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Name required"))?,
+        policy: RetentionPolicy {
+            keep_last: keep("keep-last"),
+            keep_daily: keep("keep-daily"),
+            keep_weekly: keep("keep-weekly"),
+            keep_monthly: keep("keep-monthly"),
+        },
+        dry_run: matches.get_flag("dry-run"),
+    })
+}
@@ -0,0 +1,17 @@
+use crate::cli::actions::Action;
+use anyhow::Result;
+use clap::ArgMatches;
+
+pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
+    Ok(Action::Recover {
+        name: matches
+            .get_one("name")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Name required"))?,
+        from: matches
+            .get_one("from")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Destination required"))?,
+        passphrase: matches.get_flag("passphrase"),
+    })
+}
@@ -0,0 +1,13 @@
+use crate::cli::actions::Action;
+use anyhow::Result;
+use clap::ArgMatches;
+
+pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
+    Ok(Action::Rekey {
+        name: matches
+            .get_one("name")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Name required"))?,
+        restart: matches.get_flag("restart"),
+    })
+}
@@ -11,5 +11,6 @@ pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
         gitignore: matches.get_one("gitignore").copied().unwrap_or(false),
         no_ignore: matches.get_one("no-ignore").copied().unwrap_or(false),
         dry_run: matches.get_one("dry-run").copied().unwrap_or(false),
+        rehash: matches.get_one("rehash").copied().unwrap_or(false),
     })
 }
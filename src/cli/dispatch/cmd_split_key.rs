@@ -0,0 +1,20 @@
+use crate::cli::actions::Action;
+use anyhow::Result;
+use clap::ArgMatches;
+
+pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
+    Ok(Action::SplitKey {
+        name: matches
+            .get_one("name")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Name required"))?,
+        shares: matches
+            .get_one::<u8>("shares")
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Shares required"))?,
+        threshold: matches
+            .get_one::<u8>("threshold")
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Threshold required"))?,
+    })
+}
@@ -1,4 +1,4 @@
-use crate::cli::actions::Action;
+use crate::{cli::actions::Action, engine::verify::Sample};
 use anyhow::Result;
 use clap::ArgMatches;
 
@@ -9,5 +9,16 @@ pub fn dispatch(matches: &ArgMatches) -> Result<Action> {
             .cloned()
             .ok_or_else(|| anyhow::anyhow!("Name required"))?,
         repair: matches.get_flag("repair"),
+        deep: matches.get_flag("deep"),
+        sample: sample(matches),
     })
 }
+
+/// The sample limits, if either was given.
+fn sample(matches: &ArgMatches) -> Option<Sample> {
+    let sample = Sample {
+        percent: matches.get_one::<u8>("sample").copied(),
+        max_bytes: matches.get_one::<u64>("max-bytes").copied(),
+    };
+    (sample != Sample::default()).then_some(sample)
+}
@@ -1,8 +1,15 @@
+pub mod cmd_combine_key;
 pub mod cmd_edit;
+pub mod cmd_gc;
 pub mod cmd_new;
+pub mod cmd_prune;
+pub mod cmd_recover;
+pub mod cmd_rekey;
 pub mod cmd_restore;
 pub mod cmd_run;
 pub mod cmd_show;
+pub mod cmd_split_key;
+pub mod cmd_upload;
 pub mod cmd_verify;
 pub mod cmd_view;
 
@@ -24,10 +31,17 @@ pub fn handler(matches: &clap::ArgMatches) -> Result<Action> {
         Some("new") => cmd_new::dispatch(get_subcommand_matches(matches, "new")?),
         Some("show") => Ok(cmd_show::dispatch()),
         Some("run") => cmd_run::dispatch(get_subcommand_matches(matches, "run")?),
+        Some("upload") => cmd_upload::dispatch(get_subcommand_matches(matches, "upload")?),
         Some("view") => cmd_view::dispatch(get_subcommand_matches(matches, "view")?),
         Some("edit") => cmd_edit::dispatch(get_subcommand_matches(matches, "edit")?),
         Some("restore") => cmd_restore::dispatch(get_subcommand_matches(matches, "restore")?),
         Some("verify") => cmd_verify::dispatch(get_subcommand_matches(matches, "verify")?),
+        Some("prune") => cmd_prune::dispatch(get_subcommand_matches(matches, "prune")?),
+        Some("gc") => cmd_gc::dispatch(get_subcommand_matches(matches, "gc")?),
+        Some("recover") => cmd_recover::dispatch(get_subcommand_matches(matches, "recover")?),
+        Some("rekey") => cmd_rekey::dispatch(get_subcommand_matches(matches, "rekey")?),
+        Some("split-key") => cmd_split_key::dispatch(get_subcommand_matches(matches, "split-key")?),
+        Some("combine-key") => Ok(cmd_combine_key::dispatch()),
 
         _ => Err(anyhow!("Unsupported command")),
     }
@@ -1,39 +1,256 @@
-use crate::utils::crypto::{content_key_aad, encrypt, generate_file_key};
+use crate::utils::sparse::SparseMap;
 use anyhow::{Result, anyhow};
 use base64::{Engine as _, engine::general_purpose};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{Connection, OptionalExtension, params};
+use rusqlite::{Connection, OptionalExtension, backup::Backup, params};
+use rustix::{
+    fs::{FlockOperation, flock},
+    io::Errno,
+};
 use std::{
     cmp,
-    collections::HashMap,
+    ffi::OsStr,
+    fs::{File, OpenOptions},
+    os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 use x25519_dalek::PublicKey;
 
-/// Wrapped content keys for newly-stored content, keyed by content id (hash):
-/// `hash -> (wrapped_key, ephemeral_public_key)`. Produced by the engine when it
-/// seals + uploads a blob, and recorded verbatim in `Files`.
-pub type SealedKeys = HashMap<String, (Vec<u8>, [u8; 32])>;
+/// Where a chunk's sealed blob lives: a byte range of a stored pack object.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PackLocation {
+    pub pack_id: String,
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// A sealed chunk: its plaintext size, wrapped content key and pack location,
+/// recorded verbatim in `Chunks`, and its key wrapped to each additional
+/// recipient, recorded in `ChunkKeys`.
+#[derive(Clone, Debug)]
+pub struct SealedChunk {
+    pub size: u64,
+    pub wrapped_key: Vec<u8>,
+    pub ephemeral_public_key: [u8; 32],
+    pub pack: PackLocation,
+    pub recipient_keys: Vec<RecipientKey>,
+}
+
+/// A content key wrapped to an additional recipient (DESIGN §6.3), who is
+/// named by their public key in hex.
+#[derive(Clone, Debug)]
+pub struct RecipientKey {
+    pub recipient: String,
+    pub wrapped_key: Vec<u8>,
+    pub ephemeral_public_key: [u8; 32],
+}
+
+/// A chunk id with its primary wrapped content key: `(hash, encrypted_key,
+/// ephemeral_public_key)`.
+pub type WrappedChunkKey = (String, Vec<u8>, [u8; 32]);
+
+/// A pack that was just stored, for [`SqliteCatalog::record_pack`]: its size,
+/// the chunks it holds, and the destinations that accepted it.
+#[derive(Clone, Debug)]
+pub struct StoredPack {
+    pub id: String,
+    pub size: u64,
+    pub chunks: Vec<(String, SealedChunk)>,
+    pub destinations: Vec<String>,
+}
+
+/// One chunk of a file manifest, with the wrapped key and location restore
+/// needs to fetch and open it.
+#[derive(Clone, Debug)]
+pub struct ManifestChunk {
+    pub id: String,
+    pub wrapped_key: Vec<u8>,
+    pub ephemeral_public_key: [u8; 32],
+    /// `None` for a chunk stored before packs, as its own object keyed by `id`.
+    pub location: Option<PackLocation>,
+}
+
+/// What `run` records about a file instead of reading it: if the signature
+/// matches the previous version's, the file is taken as unchanged. The inode
+/// and ctime catch what size and mtime miss: a file replaced by another one
+/// (e.g. an atomic rename) or an mtime set back by a tool.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StatSignature {
+    pub size: u64,
+    pub mtime_ns: i64,
+    pub inode: u64,
+    pub ctime_ns: i64,
+}
+
+/// POSIX metadata captured with an entry at scan time and re-applied on
+/// restore.
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct EntryMetadata {
+    /// Permission bits, including setuid/setgid/sticky.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime_ns: i64,
+    pub atime_ns: i64,
+    /// Extended attributes as `(name, value)`, sorted by name. On Linux this
+    /// includes POSIX ACLs (`system.posix_acl_access` / `_default`).
+    pub xattrs: Vec<(Vec<u8>, Vec<u8>)>,
+    /// `(device, inode)` of a file hardlinked to another entry of the same
+    /// scan: entries active at a version that share it are one file.
+    pub hardlink: Option<(u64, u64)>,
+}
+
+/// What an entry is. Only a regular file has content for `upload` to store;
+/// every other kind is captured whole by `run`.
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub enum EntryKind {
+    #[default]
+    File,
+    /// A directory: recorded for its own metadata, so empty ones survive too.
+    Dir,
+    /// A symbolic link, with its target exactly as stored in the link: the
+    /// scan never follows it.
+    Symlink(PathBuf),
+    /// A named pipe.
+    Fifo,
+    /// A character device node, with its device number (`st_rdev`).
+    CharDevice(u64),
+    /// A block device node, with its device number (`st_rdev`).
+    BlockDevice(u64),
+    /// A unix domain socket's filesystem entry.
+    Socket,
+}
+
+impl EntryKind {
+    /// The `FileNames.kind` value.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Dir => "dir",
+            Self::Symlink(_) => "symlink",
+            Self::Fifo => "fifo",
+            Self::CharDevice(_) => "char",
+            Self::BlockDevice(_) => "block",
+            Self::Socket => "socket",
+        }
+    }
+
+    /// The `FileNames.link_target` value: the target's raw bytes.
+    fn link_target(&self) -> Option<&[u8]> {
+        match self {
+            Self::Symlink(target) => Some(target.as_os_str().as_bytes()),
+            _ => None,
+        }
+    }
+
+    /// The `FileNames.rdev` value: a device node's device number.
+    fn rdev(&self) -> Option<i64> {
+        match self {
+            Self::CharDevice(rdev) | Self::BlockDevice(rdev) => Some(rdev.cast_signed()),
+            _ => None,
+        }
+    }
+
+    fn from_columns(kind: &str, link_target: Option<&[u8]>, rdev: Option<i64>) -> Result<Self> {
+        match (kind, link_target, rdev) {
+            ("file", None, None) => Ok(Self::File),
+            ("dir", None, None) => Ok(Self::Dir),
+            ("symlink", Some(target), None) => {
+                Ok(Self::Symlink(PathBuf::from(OsStr::from_bytes(target))))
+            }
+            ("fifo", None, None) => Ok(Self::Fifo),
+            ("char", None, Some(rdev)) => Ok(Self::CharDevice(rdev.cast_unsigned())),
+            ("block", None, Some(rdev)) => Ok(Self::BlockDevice(rdev.cast_unsigned())),
+            ("socket", None, None) => Ok(Self::Socket),
+            _ => Err(anyhow!("unsupported entry kind: {kind}")),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct ScannedFile {
     pub path: PathBuf,
-    pub hash: String,
+    pub kind: EntryKind,
+    pub signature: StatSignature,
+    pub metadata: EntryMetadata,
+}
+
+/// An entry pinned by `run` whose content `upload` has not stored yet.
+#[derive(Clone, Debug)]
+pub struct PendingEntry {
+    pub name_id: i64,
+    pub path: PathBuf,
+    /// The signature the file had when the version was snapshotted.
+    pub signature: StatSignature,
+}
+
+/// How `upload` resolved a pending entry.
+#[derive(Clone, Debug)]
+pub enum EntryUpload {
+    Stored {
+        /// Whole-file content id of the bytes read.
+        hash: String,
+        /// Ordered chunk ids; every one must already be recorded.
+        manifest: Vec<String>,
+        /// Where the chunks' bytes go, for a file with holes; `None` when the
+        /// manifest is the whole file.
+        sparse: Option<SparseMap>,
+        /// The file no longer matched its snapshot signature (or changed while
+        /// it was read): the bytes stored are the ones read, not the snapshot's.
+        changed: bool,
+        /// Keep the snapshot signature so the next `run` can trust it. `false`
+        /// when the file was modified too recently to rule out a same-size
+        /// rewrite within the filesystem's timestamp granularity.
+        trusted: bool,
+    },
+    /// The file was gone by the time it was read.
+    Vanished,
+}
+
+#[derive(Clone, Debug)]
+pub struct UploadedEntry {
+    pub name_id: i64,
+    pub upload: EntryUpload,
 }
 
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct RestoreEntry {
     pub path: PathBuf,
-    pub hash: String,
+    pub kind: EntryKind,
+    /// Content id; `None` for a file whose content has not been uploaded, and
+    /// for every other kind.
+    pub hash: Option<String>,
+    /// `None` for entries recorded before metadata was captured.
+    pub metadata: Option<EntryMetadata>,
 }
 
-/// A file entry for browsing: its stable id (`FileNames.name_id`) and full path.
+/// An entry for browsing: its stable id (`FileNames.name_id`) and full path.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct ViewEntry {
     pub id: i64,
     pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// What [`SqliteCatalog::prune_versions`] removed from the catalog, and the
+/// stored objects nothing references any more.
+#[derive(Clone, Debug, Default)]
+pub struct PrunedVersions {
+    pub entries: usize,
+    pub files: usize,
+    pub chunks: usize,
+    /// Packs none of whose chunks is referenced.
+    pub packs: Vec<String>,
+    /// Unreferenced chunks stored before packs, as objects of their own.
+    pub loose_chunks: Vec<String>,
+}
+
+/// The exclusive hold [`SqliteCatalog::lock`] takes; released when dropped.
+pub struct CatalogLock {
+    _file: File,
 }
 
 #[derive(Clone)]
@@ -73,6 +290,35 @@ impl SqliteCatalog {
         &self.db_path
     }
 
+    /// Take the catalog's exclusive lock, which `upload` and `prune` hold for
+    /// their whole run: an upload records a pack's chunks before the entries
+    /// that reference them, and relies on chunks it found already stored, so a
+    /// prune in between would drop chunks it is about to reference.
+    ///
+    /// # Errors
+    /// Returns an error if another upload or prune holds the lock, or the lock
+    /// file (`<name>.db.lock`, next to the catalog) cannot be opened.
+    pub fn lock(&self) -> Result<CatalogLock> {
+        let mut path = self.db_path.clone().into_os_string();
+        path.push(".lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        match flock(&file, FlockOperation::NonBlockingLockExclusive) {
+            Ok(()) => Ok(CatalogLock { _file: file }),
+            Err(Errno::WOULDBLOCK) => Err(anyhow!(
+                "backup \"{}\" is busy: an upload or prune is already running",
+                self.db_path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            )),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     /// Read the backup public key.
     ///
     /// # Errors
@@ -164,6 +410,76 @@ impl SqliteCatalog {
         Ok(general_purpose::STANDARD.decode(sealed_b64)?)
     }
 
+    /// Record that the backup keypair is derived with a BIP-39 passphrase as
+    /// well as the mnemonic. Only the fact is stored, never the passphrase, so
+    /// recovery knows to ask for it.
+    ///
+    /// # Errors
+    /// Returns an error if the flag cannot be stored.
+    pub fn save_passphrase_required(&self) -> Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO Config (name, value) VALUES ('passphrase', 'required')",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Whether the backup keypair is derived with a passphrase
+    /// ([`Self::save_passphrase_required`]).
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried.
+    pub fn passphrase_required(&self) -> Result<bool> {
+        let conn = self.pool.get()?;
+        let value: Option<String> = conn
+            .query_row(
+                "SELECT value FROM Config WHERE name = 'passphrase'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value.as_deref() == Some("required"))
+    }
+
+    /// Take the next generation number for a replicated catalog: one more than
+    /// the last. It is kept in `Config`, so a recovered catalog carries on
+    /// counting from the generation it was restored from.
+    ///
+    /// # Errors
+    /// Returns an error if the counter cannot be updated.
+    pub fn next_catalog_generation(&self) -> Result<u64> {
+        let conn = self.pool.get()?;
+
+        let generation: String = conn.query_row(
+            "INSERT INTO Config (name, value) VALUES ('catalog_generation', '1')
+             ON CONFLICT(name) DO UPDATE SET value = CAST(value AS INTEGER) + 1
+             RETURNING value",
+            [],
+            |row| row.get(0),
+        )?;
+
+        Ok(generation.parse()?)
+    }
+
+    /// Write a consistent copy of the catalog to a new database at `dest`, with
+    /// `SQLite`'s online backup API: a copy taken while another connection
+    /// writes is still a valid snapshot, never a torn file.
+    ///
+    /// # Errors
+    /// Returns an error if `dest` cannot be created or the copy fails.
+    pub fn snapshot_to(&self, dest: &Path) -> Result<()> {
+        let conn = self.pool.get()?;
+        let mut copy = Connection::open(dest)?;
+
+        Backup::new(&conn, &mut copy)?.run_to_completion(
+            SNAPSHOT_PAGES_PER_STEP,
+            Duration::from_millis(10),
+            None,
+        )?;
+        Ok(())
+    }
+
     /// Save configured backup directories.
     ///
     /// # Errors
@@ -269,221 +585,903 @@ impl SqliteCatalog {
         configured_files(&conn)
     }
 
-    /// Whether content with this id already has a stored blob (a `Files` row).
+    /// Whether content with this id has a recorded manifest (its chunks are stored).
     ///
     /// # Errors
     /// Returns an error if the catalog cannot be queried.
     pub fn is_stored(&self, hash: &str) -> Result<bool> {
         let conn = self.pool.get()?;
-        Ok(get_file_id(&conn, hash)?.is_some())
+        let stored = conn
+            .query_row(
+                "SELECT chunk_count IS NOT NULL FROM Files WHERE hash = ?1",
+                params![hash],
+                |row| row.get::<_, bool>(0),
+            )
+            .optional()?;
+        Ok(stored.unwrap_or(false))
+    }
+
+    /// Return the manifest for a file content id: its chunks in order, each with
+    /// the wrapped key restore needs. `None` if the content was never stored
+    /// (e.g. recorded by a metadata-only run).
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried or a stored key is malformed.
+    pub fn file_manifest(&self, hash: &str) -> Result<Option<Vec<ManifestChunk>>> {
+        let conn = self.pool.get()?;
+
+        let chunk_count = conn
+            .query_row(
+                "SELECT chunk_count FROM Files WHERE hash = ?1",
+                params![hash],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .optional()?
+            .flatten();
+        let Some(chunk_count) = chunk_count else {
+            return Ok(None);
+        };
+
+        let chunks = conn
+            .prepare(
+                "SELECT Chunks.hash, Chunks.encrypted_key, Chunks.ephemeral_public_key,
+                        Chunks.pack_id, Chunks.pack_offset, Chunks.pack_length
+                 FROM Files
+                 JOIN FileChunks ON FileChunks.file_id = Files.file_id
+                 JOIN Chunks ON Chunks.chunk_id = FileChunks.chunk_id
+                 WHERE Files.hash = ?1
+                 ORDER BY FileChunks.seq",
+            )?
+            .query_map(params![hash], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, Vec<u8>>(2)?,
+                    (row.get(3)?, row.get(4)?, row.get(5)?),
+                ))
+            })?
+            .map(|row| {
+                let (id, wrapped_key, eph, location) = row?;
+                Ok(ManifestChunk {
+                    id,
+                    wrapped_key,
+                    ephemeral_public_key: ephemeral_key(eph)?,
+                    location: pack_location(location)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if i64::try_from(chunks.len())? != chunk_count {
+            return Err(anyhow!(
+                "manifest for {hash} is incomplete: expected {chunk_count} chunks, found {}",
+                chunks.len()
+            ));
+        }
+
+        Ok(Some(chunks))
+    }
+
+    /// Return the extent map of a sparse file's content: where its manifest's
+    /// bytes go. `None` for content stored whole.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried or the map is malformed.
+    pub fn file_extents(&self, hash: &str) -> Result<Option<SparseMap>> {
+        let conn = self.pool.get()?;
+        conn.query_row(
+            "SELECT extents FROM Files WHERE hash = ?1",
+            params![hash],
+            |row| row.get::<_, Option<Vec<u8>>>(0),
+        )
+        .optional()?
+        .flatten()
+        .map(|bytes| SparseMap::decode(&bytes))
+        .transpose()
     }
 
     /// Return the wrapped content key (`encrypted_key`, `ephemeral_public_key`)
-    /// for a content id — what restore needs to unwrap and decrypt the blob.
+    /// for a chunk id.
     ///
     /// # Errors
     /// Returns an error if the catalog cannot be queried or the stored key is malformed.
-    pub fn wrapped_content_key(&self, hash: &str) -> Result<Option<(Vec<u8>, [u8; 32])>> {
+    pub fn wrapped_chunk_key(&self, hash: &str) -> Result<Option<(Vec<u8>, [u8; 32])>> {
         let conn = self.pool.get()?;
 
         let row = conn
             .query_row(
-                "SELECT encrypted_key, ephemeral_public_key FROM Files WHERE hash = ?1",
+                "SELECT encrypted_key, ephemeral_public_key FROM Chunks WHERE hash = ?1",
                 params![hash],
                 |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?)),
             )
             .optional()?;
 
-        match row {
-            Some((wrapped, eph)) => {
-                let eph: [u8; 32] = eph
-                    .try_into()
-                    .map_err(|_| anyhow!("stored ephemeral public key has wrong length"))?;
-                Ok(Some((wrapped, eph)))
-            }
-            None => Ok(None),
-        }
+        row.map(|(wrapped, eph)| Ok((wrapped, ephemeral_key(eph)?)))
+            .transpose()
     }
 
-    /// Return every stored content id (`Files.hash`).
+    /// Return the additional recipients' public keys, in hex (DESIGN §6.3).
     ///
     /// # Errors
     /// Returns an error if the catalog cannot be queried.
-    pub fn all_content_ids(&self) -> Result<Vec<String>> {
+    pub fn recipients(&self) -> Result<Vec<String>> {
         let conn = self.pool.get()?;
-        let ids = conn
-            .prepare("SELECT hash FROM Files")?
+        let recipients = conn
+            .prepare("SELECT public_key FROM Recipients ORDER BY public_key")?
             .query_map([], |row| row.get::<_, String>(0))?
             .collect::<rusqlite::Result<Vec<_>>>()?;
-        Ok(ids)
+        Ok(recipients)
     }
 
-    /// Replace the wrapped content key for a content id (used by repair when a
-    /// blob is re-sealed with a fresh key).
+    /// Return the naming key sealed to an additional recipient, or `None` if
+    /// `recipient` is not one.
     ///
     /// # Errors
-    /// Returns an error if the update fails.
-    pub fn update_content_key(&self, hash: &str, wrapped: &[u8], eph: &[u8; 32]) -> Result<()> {
+    /// Returns an error if the catalog cannot be queried.
+    pub fn recipient_sealed_naming_key(&self, recipient: &str) -> Result<Option<Vec<u8>>> {
         let conn = self.pool.get()?;
-        let rows = conn.execute(
-            "UPDATE Files SET encrypted_key = ?2, ephemeral_public_key = ?3 WHERE hash = ?1",
-            params![hash, wrapped, eph.as_slice()],
-        )?;
-        // The caller re-sealed a blob with a fresh key; if no row matched, the new
-        // key would be silently dropped, leaving the blob unrecoverable. Fail loudly.
-        if rows != 1 {
-            return Err(anyhow!(
-                "update_content_key: expected 1 Files row for hash {hash}, updated {rows}"
-            ));
-        }
-        Ok(())
+        Ok(conn
+            .query_row(
+                "SELECT sealed_naming_key FROM Recipients WHERE public_key = ?1",
+                params![recipient],
+                |row| row.get(0),
+            )
+            .optional()?)
     }
 
-    /// Save backup destinations (filesystem paths and/or S3 targets).
+    /// Return a chunk's content key wrapped to an additional recipient, or
+    /// `None` if it has not been wrapped to them.
     ///
     /// # Errors
-    /// Returns an error if any destination cannot be stored.
-    pub fn save_destinations(&self, destinations: &[String]) -> Result<()> {
-        let mut conn = self.pool.get()?;
-        let tx = conn.transaction()?;
+    /// Returns an error if the catalog cannot be queried or the stored key is malformed.
+    pub fn recipient_chunk_key(
+        &self,
+        hash: &str,
+        recipient: &str,
+    ) -> Result<Option<(Vec<u8>, [u8; 32])>> {
+        let conn = self.pool.get()?;
 
-        let mut stmt =
-            tx.prepare("INSERT OR IGNORE INTO config_destinations (target) VALUES (?1)")?;
-        for target in destinations {
-            stmt.execute(params![target])?;
-        }
+        let row = conn
+            .query_row(
+                "SELECT ChunkKeys.encrypted_key, ChunkKeys.ephemeral_public_key
+                 FROM Chunks
+                 JOIN ChunkKeys ON ChunkKeys.chunk_id = Chunks.chunk_id
+                 WHERE Chunks.hash = ?1 AND ChunkKeys.recipient_pubkey = ?2",
+                params![hash, recipient],
+                |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?)),
+            )
+            .optional()?;
 
-        drop(stmt);
-        tx.commit()?;
+        row.map(|(wrapped, eph)| Ok((wrapped, ephemeral_key(eph)?)))
+            .transpose()
+    }
 
-        Ok(())
+    /// Return the primary wrapped key of every chunk not yet wrapped to
+    /// `recipient`, as `(hash, encrypted_key, ephemeral_public_key)`.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried or a stored key is malformed.
+    pub fn chunks_without_key(&self, recipient: &str) -> Result<Vec<WrappedChunkKey>> {
+        let conn = self.pool.get()?;
+        conn.prepare(
+            "SELECT hash, encrypted_key, ephemeral_public_key FROM Chunks
+             WHERE NOT EXISTS (SELECT 1 FROM ChunkKeys
+                               WHERE ChunkKeys.chunk_id = Chunks.chunk_id
+                                 AND ChunkKeys.recipient_pubkey = ?1)
+             ORDER BY hash",
+        )?
+        .query_map(params![recipient], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Vec<u8>>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+            ))
+        })?
+        .map(|row| {
+            let (hash, wrapped, eph) = row?;
+            Ok((hash, wrapped, ephemeral_key(eph)?))
+        })
+        .collect()
     }
 
-    /// Replace the configured destinations with the given set.
+    /// Add (or top up) an additional recipient: their sealed naming key and
+    /// chunk keys, in one transaction. A recipient already present keeps the
+    /// time they were first added. Keys of chunks pruned meanwhile are dropped.
     ///
     /// # Errors
-    /// Returns an error if the destinations cannot be stored.
-    pub fn set_destinations(&self, destinations: &[String]) -> Result<()> {
+    /// Returns an error if the catalog cannot be updated.
+    pub fn add_recipient(
+        &self,
+        recipient: &str,
+        sealed_naming_key: &[u8],
+        chunk_keys: &[(String, RecipientKey)],
+    ) -> Result<()> {
         let mut conn = self.pool.get()?;
         let tx = conn.transaction()?;
 
-        tx.execute("DELETE FROM config_destinations", [])?;
-
-        let mut stmt =
-            tx.prepare("INSERT OR IGNORE INTO config_destinations (target) VALUES (?1)")?;
-        for target in destinations {
-            stmt.execute(params![target])?;
+        tx.execute(
+            "INSERT INTO Recipients (public_key, sealed_naming_key, added_at)
+             VALUES (?1, ?2, strftime('%s', 'now'))
+             ON CONFLICT(public_key) DO UPDATE SET
+                 sealed_naming_key = excluded.sealed_naming_key",
+            params![recipient, sealed_naming_key],
+        )?;
+        for (hash, key) in chunk_keys {
+            insert_chunk_key(&tx, hash, key)?;
         }
 
-        drop(stmt);
         tx.commit()?;
-
         Ok(())
     }
 
-    /// Return configured backup destinations.
+    /// Record chunk keys wrapped to a recipient already added, in one
+    /// transaction.
     ///
     /// # Errors
-    /// Returns an error if the destinations cannot be read.
-    pub fn configured_destinations(&self) -> Result<Vec<String>> {
-        let conn = self.pool.get()?;
-
-        let destinations = conn
-            .prepare("SELECT target FROM config_destinations")?
-            .query_map([], |row| row.get::<_, String>(0))?
-            .collect::<rusqlite::Result<Vec<_>>>()?;
-
-        Ok(destinations)
+    /// Returns an error if the catalog cannot be updated.
+    pub fn record_chunk_keys(&self, chunk_keys: &[(String, RecipientKey)]) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        for (hash, key) in chunk_keys {
+            insert_chunk_key(&tx, hash, key)?;
+        }
+        tx.commit()?;
+        Ok(())
     }
 
-    /// Create a backup version.
+    /// Return the public key (in hex) a rekey begun earlier moves the backup
+    /// to, if one is under way.
     ///
     /// # Errors
-    /// Returns an error if the version cannot be stored.
-    pub fn create_version(&self) -> Result<i64> {
+    /// Returns an error if the catalog cannot be queried.
+    pub fn pending_rekey(&self) -> Result<Option<String>> {
         let conn = self.pool.get()?;
-
-        conn.execute(
-            "INSERT INTO BackupVersions (timestamp) VALUES (strftime('%s', 'now'))",
-            [],
-        )?;
-
-        Ok(conn.last_insert_rowid())
+        Ok(conn
+            .query_row(
+                "SELECT value FROM Config WHERE name = 'rekey_to'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
     }
 
-    /// Record a completed scan into version metadata.
+    /// Begin moving the backup to a new keypair: the new public key becomes a
+    /// recipient (so uploads meanwhile wrap to it too) and is marked as the one
+    /// [`Self::finish_rekey`] swaps in.
     ///
     /// # Errors
-    /// Returns an error if scanned files cannot be written atomically.
-    pub fn record_scan(
-        &self,
-        public_key: PublicKey,
-        sealed_keys: &SealedKeys,
-        version: i64,
-        scanned_files: &[ScannedFile],
-        close_missing_files: bool,
-        progress: Option<&dyn Fn(usize)>,
-    ) -> Result<()> {
+    /// Returns an error if the catalog cannot be updated.
+    pub fn begin_rekey(&self, public_key: &str, sealed_naming_key: &[u8]) -> Result<()> {
         let mut conn = self.pool.get()?;
         let tx = conn.transaction()?;
 
         tx.execute(
-            "CREATE TEMP TABLE IF NOT EXISTS seen_files (
-                path_id INTEGER NOT NULL,
-                name TEXT NOT NULL,
-                PRIMARY KEY (path_id, name)
-            )",
-            [],
+            "INSERT OR REPLACE INTO Recipients (public_key, sealed_naming_key, added_at)
+             VALUES (?1, ?2, strftime('%s', 'now'))",
+            params![public_key, sealed_naming_key],
         )?;
-        tx.execute("DELETE FROM seen_files", [])?;
-
-        let scanned_file_count = scanned_files.len();
-
-        for (index, scanned_file) in scanned_files.iter().enumerate() {
-            upsert_scanned_file(&tx, public_key, sealed_keys, version, scanned_file)?;
-            if let Some(progress) = progress {
-                let written_files = index + 1;
-                if written_files == scanned_file_count || written_files % 100 == 0 {
-                    progress(written_files);
-                }
-            }
-        }
-
-        if close_missing_files {
-            close_deleted_files(&tx, version)?;
-        }
-
-        // Mark the version complete in the same transaction as its metadata, so
-        // "complete" means the metadata is committed (and, since uploads run
-        // first, all its blobs are stored). Interrupted runs never reach here.
         tx.execute(
-            "UPDATE BackupVersions SET completed_at = strftime('%s', 'now') WHERE version_id = ?1",
-            params![version],
+            "INSERT OR REPLACE INTO Config (name, value) VALUES ('rekey_to', ?1)",
+            params![public_key],
         )?;
 
         tx.commit()?;
-
         Ok(())
     }
 
-    /// Query restorable path/hash entries for a version.
+    /// Drop a rekey under way, with every key wrapped to its public key.
     ///
     /// # Errors
-    /// Returns an error if restore metadata cannot be read.
-    pub fn restore_entries(&self, version: i64) -> Result<Vec<RestoreEntry>> {
-        let conn = self.pool.get()?;
-        restore_entries(&conn, version)
+    /// Returns an error if the catalog cannot be updated.
+    pub fn abandon_rekey(&self) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        tx.execute_batch(
+            "DELETE FROM ChunkKeys WHERE recipient_pubkey =
+                 (SELECT value FROM Config WHERE name = 'rekey_to');
+             DELETE FROM Recipients WHERE public_key =
+                 (SELECT value FROM Config WHERE name = 'rekey_to');
+             DELETE FROM Config WHERE name = 'rekey_to';",
+        )?;
+
+        tx.commit()?;
+        Ok(())
     }
 
-    /// List browsable file entries active at a version, optionally scoped to a
-    /// directory subtree.
+    /// Make the rekey's public key the backup's own, in one transaction: its
+    /// wrapped chunk keys and sealed naming key replace the primary ones
+    /// inline, and it stops being a recipient.
     ///
     /// # Errors
-    /// Returns an error if the metadata cannot be read.
-    pub fn view_entries(&self, version: i64, root: Option<&Path>) -> Result<Vec<ViewEntry>> {
-        let conn = self.pool.get()?;
-        view_entries(&conn, version, root)
-    }
+    /// Returns an error if no rekey to `public_key` is under way, if a chunk
+    /// has no key wrapped to it yet, or if the catalog cannot be updated.
+    pub fn finish_rekey(&self, public_key: &PublicKey) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let recipient = hex::encode(public_key.as_bytes());
+
+        let pending: Option<String> = tx
+            .query_row(
+                "SELECT value FROM Config WHERE name = 'rekey_to'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if pending.as_deref() != Some(recipient.as_str()) {
+            return Err(anyhow!("no rekey to {recipient} is under way"));
+        }
+        let missing: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM Chunks
+             WHERE NOT EXISTS (SELECT 1 FROM ChunkKeys
+                               WHERE ChunkKeys.chunk_id = Chunks.chunk_id
+                                 AND ChunkKeys.recipient_pubkey = ?1)",
+            params![recipient],
+            |row| row.get(0),
+        )?;
+        if missing > 0 {
+            return Err(anyhow!(
+                "{missing} chunk key(s) are not wrapped to the new key yet"
+            ));
+        }
+        let sealed_naming_key: Vec<u8> = tx.query_row(
+            "SELECT sealed_naming_key FROM Recipients WHERE public_key = ?1",
+            params![recipient],
+            |row| row.get(0),
+        )?;
+
+        tx.execute(
+            "UPDATE Chunks SET (encrypted_key, ephemeral_public_key) = (
+                 SELECT encrypted_key, ephemeral_public_key FROM ChunkKeys
+                 WHERE ChunkKeys.chunk_id = Chunks.chunk_id
+                   AND ChunkKeys.recipient_pubkey = ?1)",
+            params![recipient],
+        )?;
+        tx.execute(
+            "DELETE FROM ChunkKeys WHERE recipient_pubkey = ?1",
+            params![recipient],
+        )?;
+        tx.execute(
+            "DELETE FROM Recipients WHERE public_key = ?1",
+            params![recipient],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO Config (name, value) VALUES ('public_key', ?1)",
+            params![general_purpose::STANDARD.encode(public_key.as_bytes())],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO Config (name, value) VALUES ('sealed_naming_key', ?1)",
+            params![general_purpose::STANDARD.encode(&sealed_naming_key)],
+        )?;
+        tx.execute("DELETE FROM Config WHERE name = 'rekey_to'", [])?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Return every stored chunk id (`Chunks.hash`).
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried.
+    pub fn all_chunk_ids(&self) -> Result<Vec<String>> {
+        let conn = self.pool.get()?;
+        let ids = conn
+            .prepare("SELECT hash FROM Chunks")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(ids)
+    }
+
+    /// Return the pack holding a chunk's blob; `None` for an unknown chunk or
+    /// one stored as its own object (before packs).
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried.
+    pub fn chunk_location(&self, hash: &str) -> Result<Option<PackLocation>> {
+        let conn = self.pool.get()?;
+        let location = conn
+            .query_row(
+                "SELECT pack_id, pack_offset, pack_length FROM Chunks WHERE hash = ?1",
+                params![hash],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        Ok(location.map(pack_location).transpose()?.flatten())
+    }
+
+    /// Return every recorded pack id.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried.
+    pub fn all_pack_ids(&self) -> Result<Vec<String>> {
+        let conn = self.pool.get()?;
+        let ids = conn
+            .prepare("SELECT pack_id FROM Packs")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(ids)
+    }
+
+    /// Return the ids of chunks stored as their own object rather than in a pack
+    /// (everything stored before packs).
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried.
+    pub fn loose_chunk_ids(&self) -> Result<Vec<String>> {
+        let conn = self.pool.get()?;
+        let ids = conn
+            .prepare("SELECT hash FROM Chunks WHERE pack_id IS NULL")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(ids)
+    }
+
+    /// Return the ids of the chunks stored in a pack.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried.
+    pub fn pack_chunk_ids(&self, pack_id: &str) -> Result<Vec<String>> {
+        let conn = self.pool.get()?;
+        let ids = conn
+            .prepare("SELECT hash FROM Chunks WHERE pack_id = ?1")?
+            .query_map(params![pack_id], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(ids)
+    }
+
+    /// Return every stored object — each pack, and each chunk stored before
+    /// packs — with its size, least recently deep-verified first (never
+    /// verified before all), so a sampled check rotates through them. A loose
+    /// chunk's size is its plaintext size, close to what is stored.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried.
+    pub fn objects_by_last_verified(&self) -> Result<Vec<(String, u64)>> {
+        let conn = self.pool.get()?;
+        // NULLs sort first: objects never verified lead the queue.
+        let objects = conn
+            .prepare(
+                "SELECT pack_id, size, verified_at FROM Packs
+                 UNION ALL
+                 SELECT hash, COALESCE(size, 0), verified_at FROM Chunks WHERE pack_id IS NULL
+                 ORDER BY verified_at, 1",
+            )?
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .map(|row| {
+                let (key, size) = row?;
+                Ok((key, u64::try_from(size)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(objects)
+    }
+
+    /// Record that a deep `verify` found every copy of these objects intact
+    /// at `at` (unix seconds).
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be updated.
+    pub fn record_verified(&self, keys: &[String], at: i64) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        {
+            let mut pack = tx.prepare("UPDATE Packs SET verified_at = ?2 WHERE pack_id = ?1")?;
+            let mut chunk = tx.prepare(
+                "UPDATE Chunks SET verified_at = ?2 WHERE hash = ?1 AND pack_id IS NULL",
+            )?;
+            for key in keys {
+                pack.execute(params![key, at])?;
+                chunk.execute(params![key, at])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Return the chunks a stored object holds — a pack's, or the one chunk
+    /// stored before packs under its own id — with the wrapped keys needed to
+    /// open them, in pack order.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried or a stored key is malformed.
+    pub fn object_chunks(&self, key: &str) -> Result<Vec<ManifestChunk>> {
+        let conn = self.pool.get()?;
+        conn.prepare(
+            "SELECT hash, encrypted_key, ephemeral_public_key, pack_id, pack_offset, pack_length
+             FROM Chunks
+             WHERE pack_id = ?1 OR (pack_id IS NULL AND hash = ?1)
+             ORDER BY pack_offset",
+        )?
+        .query_map(params![key], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Vec<u8>>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+                (row.get(3)?, row.get(4)?, row.get(5)?),
+            ))
+        })?
+        .map(|row| {
+            let (id, wrapped_key, eph, location) = row?;
+            Ok(ManifestChunk {
+                id,
+                wrapped_key,
+                ephemeral_public_key: ephemeral_key(eph)?,
+                location: pack_location(location)?,
+            })
+        })
+        .collect()
+    }
+
+    /// Map each chunk id referenced at `version` to the source paths of the files
+    /// containing it, so a lost chunk can be re-read from a live file.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata cannot be read.
+    pub fn chunk_sources(&self, version: i64) -> Result<Vec<(String, PathBuf)>> {
+        let conn = self.pool.get()?;
+        let sources = conn
+            .prepare(
+                "SELECT DISTINCT Chunks.hash, Paths.path, FileNames.name
+                 FROM FileNames
+                 JOIN Paths ON Paths.path_id = FileNames.path_id
+                 JOIN FileChunks ON FileChunks.file_id = FileNames.file_id
+                 JOIN Chunks ON Chunks.chunk_id = FileChunks.chunk_id
+                 WHERE FileNames.first_version <= ?1
+                   AND (FileNames.last_version IS NULL OR FileNames.last_version >= ?1)",
+            )?
+            .query_map(params![version], |row| {
+                let parent: String = row.get(1)?;
+                let name: String = row.get(2)?;
+                Ok((row.get::<_, String>(0)?, PathBuf::from(parent).join(name)))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(sources)
+    }
+
+    /// Record a pack right after it was stored: the pack, each chunk's key and
+    /// location (replacing those of a chunk re-sealed by repair), and the
+    /// destinations holding it — one transaction, so its chunks are either all
+    /// known or not at all.
+    ///
+    /// # Errors
+    /// Returns an error if the pack cannot be recorded.
+    pub fn record_pack(&self, pack: &StoredPack) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        insert_pack(&tx, &pack.id, pack.size)?;
+        for (hash, chunk) in &pack.chunks {
+            upsert_chunk(&tx, hash, chunk)?;
+        }
+        for destination in &pack.destinations {
+            insert_pack_upload(&tx, &pack.id, destination)?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record that a destination now holds a pack.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be updated.
+    pub fn record_pack_upload(&self, pack_id: &str, destination: &str) -> Result<()> {
+        let conn = self.pool.get()?;
+        insert_pack_upload(&conn, pack_id, destination)
+    }
+
+    /// Every pack with the destinations it has been uploaded to.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be queried.
+    pub fn pack_uploads(&self) -> Result<Vec<(String, Vec<String>)>> {
+        let conn = self.pool.get()?;
+        let rows = conn
+            .prepare(
+                "SELECT Packs.pack_id, PackUploads.destination
+                 FROM Packs
+                 LEFT JOIN PackUploads ON PackUploads.pack_id = Packs.pack_id
+                 ORDER BY Packs.pack_id",
+            )?
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(group_by_pack(rows))
+    }
+
+    /// Forget packs no chunk references any more (e.g. after repair moved their
+    /// chunks into new packs).
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be updated.
+    pub fn forget_unreferenced_packs(&self) -> Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute_batch(
+            "DELETE FROM PackUploads
+             WHERE NOT EXISTS (SELECT 1 FROM Chunks WHERE Chunks.pack_id = PackUploads.pack_id);
+             DELETE FROM Packs
+             WHERE NOT EXISTS (SELECT 1 FROM Chunks WHERE Chunks.pack_id = Packs.pack_id);",
+        )?;
+        Ok(())
+    }
+
+    /// Save backup destinations (filesystem paths and/or S3 targets).
+    ///
+    /// # Errors
+    /// Returns an error if any destination cannot be stored.
+    pub fn save_destinations(&self, destinations: &[String]) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        let mut stmt =
+            tx.prepare("INSERT OR IGNORE INTO config_destinations (target) VALUES (?1)")?;
+        for target in destinations {
+            stmt.execute(params![target])?;
+        }
+
+        drop(stmt);
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Replace the configured destinations with the given set.
+    ///
+    /// # Errors
+    /// Returns an error if the destinations cannot be stored.
+    pub fn set_destinations(&self, destinations: &[String]) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM config_destinations", [])?;
+
+        let mut stmt =
+            tx.prepare("INSERT OR IGNORE INTO config_destinations (target) VALUES (?1)")?;
+        for target in destinations {
+            stmt.execute(params![target])?;
+        }
+
+        drop(stmt);
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Return configured backup destinations.
+    ///
+    /// # Errors
+    /// Returns an error if the destinations cannot be read.
+    pub fn configured_destinations(&self) -> Result<Vec<String>> {
+        let conn = self.pool.get()?;
+
+        let destinations = conn
+            .prepare("SELECT target FROM config_destinations")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(destinations)
+    }
+
+    /// Create a backup version.
+    ///
+    /// # Errors
+    /// Returns an error if the version cannot be stored.
+    pub fn create_version(&self) -> Result<i64> {
+        let conn = self.pool.get()?;
+
+        conn.execute(
+            "INSERT INTO BackupVersions (timestamp) VALUES (strftime('%s', 'now'))",
+            [],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Pin a version's file set from a stat-only scan (DESIGN §6.7). A file
+    /// whose signature matches its active entry carries that entry forward;
+    /// a new or changed file gets a fresh entry with no content yet, left
+    /// pending for `upload`; any other new or changed entry (a symlink) is
+    /// complete as scanned. With `rehash`, no stored file is trusted: every
+    /// file is left pending, and `upload` folds the ones whose content proves
+    /// unchanged back into their entry. Returns how many entries are pending.
+    ///
+    /// # Errors
+    /// Returns an error if scanned files cannot be written atomically.
+    pub fn record_snapshot(
+        &self,
+        version: i64,
+        scanned_files: &[ScannedFile],
+        close_missing_files: bool,
+        rehash: bool,
+        progress: Option<&dyn Fn(usize)>,
+    ) -> Result<usize> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "CREATE TEMP TABLE IF NOT EXISTS seen_files (
+                path_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                PRIMARY KEY (path_id, name)
+            )",
+            [],
+        )?;
+        tx.execute("DELETE FROM seen_files", [])?;
+
+        let scanned_file_count = scanned_files.len();
+        let mut pending = 0;
+
+        for (index, scanned_file) in scanned_files.iter().enumerate() {
+            if upsert_scanned_file(&tx, version, scanned_file, rehash)? {
+                pending += 1;
+            }
+            if let Some(progress) = progress {
+                let written_files = index + 1;
+                if written_files == scanned_file_count || written_files % 100 == 0 {
+                    progress(written_files);
+                }
+            }
+        }
+
+        if close_missing_files {
+            close_deleted_files(&tx, version)?;
+        }
+
+        // The file set is pinned once this commits; content may still be
+        // pending, and `upload` fills it in later.
+        tx.execute(
+            "UPDATE BackupVersions SET completed_at = strftime('%s', 'now') WHERE version_id = ?1",
+            params![version],
+        )?;
+
+        tx.commit()?;
+
+        Ok(pending)
+    }
+
+    /// Every entry whose content is still pending, oldest version first.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata cannot be read.
+    pub fn pending_entries(&self) -> Result<Vec<PendingEntry>> {
+        let conn = self.pool.get()?;
+        conn.prepare(
+            "SELECT FileNames.name_id, Paths.path, FileNames.name,
+                    FileNames.size, FileNames.mtime_ns, FileNames.inode, FileNames.ctime_ns
+             FROM FileNames
+             JOIN Paths ON Paths.path_id = FileNames.path_id
+             WHERE FileNames.status = 'pending'
+             ORDER BY FileNames.first_version, FileNames.name_id",
+        )?
+        .query_map([], |row| {
+            let parent: String = row.get(1)?;
+            let name: String = row.get(2)?;
+            Ok((
+                row.get::<_, i64>(0)?,
+                PathBuf::from(parent).join(name),
+                StoredSignature::from_row(row, 3)?,
+            ))
+        })?
+        .map(|row| {
+            let (name_id, path, stored) = row?;
+            Ok(PendingEntry {
+                name_id,
+                path,
+                signature: stored
+                    .signature()?
+                    .ok_or_else(|| anyhow!("pending entry {name_id} has no signature"))?,
+            })
+        })
+        .collect()
+    }
+
+    /// Give pending entries the content `upload` stored for them, in one
+    /// transaction. An entry whose content turns out identical to the entry it
+    /// replaced (e.g. a file only touched) is merged back into it, so history
+    /// only grows when content really changes.
+    ///
+    /// # Errors
+    /// Returns an error if a manifest references an unrecorded chunk or the
+    /// update fails.
+    pub fn record_uploaded(&self, entries: &[UploadedEntry]) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        for entry in entries {
+            match &entry.upload {
+                EntryUpload::Stored {
+                    hash,
+                    manifest,
+                    sparse,
+                    changed,
+                    trusted,
+                } => {
+                    let file_id = get_or_insert_file(&tx, hash)?;
+                    record_manifest(&tx, file_id, manifest, sparse.as_ref())?;
+                    tx.execute(
+                        "UPDATE FileNames
+                         SET file_id = ?2,
+                             status = ?3,
+                             size = CASE WHEN ?4 THEN size END,
+                             mtime_ns = CASE WHEN ?4 THEN mtime_ns END,
+                             inode = CASE WHEN ?4 THEN inode END,
+                             ctime_ns = CASE WHEN ?4 THEN ctime_ns END
+                         WHERE name_id = ?1 AND status = 'pending'",
+                        params![
+                            entry.name_id,
+                            file_id,
+                            if *changed { "changed" } else { "stored" },
+                            trusted,
+                        ],
+                    )?;
+                    merge_unchanged_entry(&tx, entry.name_id)?;
+                }
+                EntryUpload::Vanished => {
+                    tx.execute(
+                        "UPDATE FileNames SET status = 'vanished'
+                         WHERE name_id = ?1 AND status = 'pending'",
+                        params![entry.name_id],
+                    )?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Whether `version` is restorable from `destination`: none of its entries
+    /// is still pending, and every pack holding its content reached it.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata cannot be read.
+    pub fn is_sealed(&self, version: i64, destination: &str) -> Result<bool> {
+        let conn = self.pool.get()?;
+        Ok(conn.query_row(
+            "SELECT NOT EXISTS (
+                 SELECT 1 FROM FileNames
+                 WHERE status = 'pending'
+                   AND first_version <= ?1
+                   AND (last_version IS NULL OR last_version >= ?1)
+             ) AND NOT EXISTS (
+                 SELECT 1
+                 FROM FileNames
+                 JOIN FileChunks ON FileChunks.file_id = FileNames.file_id
+                 JOIN Chunks ON Chunks.chunk_id = FileChunks.chunk_id
+                 WHERE FileNames.first_version <= ?1
+                   AND (FileNames.last_version IS NULL OR FileNames.last_version >= ?1)
+                   AND Chunks.pack_id IS NOT NULL
+                   AND NOT EXISTS (
+                       SELECT 1 FROM PackUploads
+                       WHERE PackUploads.pack_id = Chunks.pack_id
+                         AND PackUploads.destination = ?2
+                   )
+             )",
+            params![version, destination],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Query restorable path/hash entries for a version.
+    ///
+    /// # Errors
+    /// Returns an error if restore metadata cannot be read.
+    pub fn restore_entries(&self, version: i64) -> Result<Vec<RestoreEntry>> {
+        let conn = self.pool.get()?;
+        restore_entries(&conn, version)
+    }
+
+    /// List browsable entries (files, symlinks and directories) active at a
+    /// version, optionally scoped to a directory subtree.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata cannot be read.
+    pub fn view_entries(&self, version: i64, root: Option<&Path>) -> Result<Vec<ViewEntry>> {
+        let conn = self.pool.get()?;
+        view_entries(&conn, version, root)
+    }
 
     /// Resolve a file id (`FileNames.name_id`) to its full path, if it is active
     /// at the given version.
@@ -513,6 +1511,30 @@ impl SqliteCatalog {
         Ok(row)
     }
 
+    /// Resolve a file id (`FileNames.name_id`) to its restore entry (path and
+    /// content id), if it is active at the given version.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata cannot be read.
+    pub fn restore_entry(&self, name_id: i64, version: i64) -> Result<Option<RestoreEntry>> {
+        let conn = self.pool.get()?;
+
+        let row = conn
+            .query_row(
+                &format!(
+                    "{RESTORE_ENTRY_SELECT}
+                     WHERE FileNames.name_id = ?1
+                       AND FileNames.first_version <= ?2
+                       AND (FileNames.last_version IS NULL OR FileNames.last_version >= ?2)"
+                ),
+                params![name_id, version],
+                restore_entry_row,
+            )
+            .optional()?;
+
+        row.map(RestoreRow::into_entry).transpose()
+    }
+
     /// Return the most recent backup version, or `None` if no runs are recorded.
     ///
     /// # Errors
@@ -527,6 +1549,113 @@ impl SqliteCatalog {
         )?)
     }
 
+    /// Return every completed version with its unix timestamp (seconds),
+    /// oldest first.
+    ///
+    /// # Errors
+    /// Returns an error if the version metadata cannot be read.
+    pub fn completed_versions(&self) -> Result<Vec<(i64, i64)>> {
+        let conn = self.pool.get()?;
+        let versions = conn
+            .prepare(
+                "SELECT version_id, timestamp FROM BackupVersions
+                 WHERE completed_at IS NOT NULL
+                 ORDER BY version_id",
+            )?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(versions)
+    }
+
+    /// Drop `versions`, in one transaction: entries no remaining version sees
+    /// are deleted and the others' intervals trimmed to the versions left,
+    /// then content, chunks and packs nothing references any more go too.
+    /// Returns what went, including the packs and loose chunks to remove from
+    /// the destinations; with `dry_run` the transaction is rolled back.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog cannot be updated.
+    pub fn prune_versions(&self, versions: &[i64], dry_run: bool) -> Result<PrunedVersions> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        let mut stmt = tx.prepare("DELETE FROM BackupVersions WHERE version_id = ?1")?;
+        for version in versions {
+            stmt.execute(params![version])?;
+        }
+        drop(stmt);
+
+        let mut pruned = PrunedVersions::default();
+        tx.execute(
+            &format!(
+                "DELETE FROM EntryMetadata WHERE name_id IN
+                     (SELECT name_id FROM FileNames WHERE NOT EXISTS ({SEEN_BY_A_VERSION}))"
+            ),
+            [],
+        )?;
+        pruned.entries = tx.execute(
+            &format!("DELETE FROM FileNames WHERE NOT EXISTS ({SEEN_BY_A_VERSION})"),
+            [],
+        )?;
+        tx.execute_batch(
+            "UPDATE FileNames SET first_version = (
+                 SELECT MIN(version_id) FROM BackupVersions
+                 WHERE version_id >= FileNames.first_version
+                   AND (FileNames.last_version IS NULL
+                        OR version_id <= FileNames.last_version));
+             UPDATE FileNames SET last_version = (
+                 SELECT MAX(version_id) FROM BackupVersions
+                 WHERE version_id BETWEEN FileNames.first_version AND FileNames.last_version)
+             WHERE last_version IS NOT NULL;
+             DELETE FROM Paths
+             WHERE NOT EXISTS (SELECT 1 FROM FileNames WHERE FileNames.path_id = Paths.path_id);
+             DELETE FROM FileChunks WHERE file_id IN (
+                 SELECT file_id FROM Files
+                 WHERE NOT EXISTS (SELECT 1 FROM FileNames WHERE FileNames.file_id = Files.file_id));",
+        )?;
+        pruned.files = tx.execute(
+            "DELETE FROM Files
+             WHERE NOT EXISTS (SELECT 1 FROM FileNames WHERE FileNames.file_id = Files.file_id)",
+            [],
+        )?;
+        pruned.loose_chunks = tx
+            .prepare(
+                "SELECT hash FROM Chunks
+                 WHERE pack_id IS NULL
+                   AND NOT EXISTS (SELECT 1 FROM FileChunks
+                                   WHERE FileChunks.chunk_id = Chunks.chunk_id)
+                 ORDER BY hash",
+            )?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        pruned.chunks = tx.execute(
+            "DELETE FROM Chunks
+             WHERE NOT EXISTS (SELECT 1 FROM FileChunks WHERE FileChunks.chunk_id = Chunks.chunk_id)",
+            [],
+        )?;
+        pruned.packs = tx
+            .prepare(
+                "SELECT pack_id FROM Packs
+                 WHERE NOT EXISTS (SELECT 1 FROM Chunks WHERE Chunks.pack_id = Packs.pack_id)
+                 ORDER BY pack_id",
+            )?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        tx.execute_batch(
+            "DELETE FROM PackUploads
+             WHERE NOT EXISTS (SELECT 1 FROM Chunks WHERE Chunks.pack_id = PackUploads.pack_id);
+             DELETE FROM Packs
+             WHERE NOT EXISTS (SELECT 1 FROM Chunks WHERE Chunks.pack_id = Packs.pack_id);",
+        )?;
+
+        if dry_run {
+            tx.rollback()?;
+        } else {
+            tx.commit()?;
+        }
+        Ok(pruned)
+    }
+
     /// Return the unix timestamp (seconds) a version was recorded.
     ///
     /// # Errors
@@ -552,7 +1681,10 @@ impl SqliteCatalog {
     /// Returns an error if the table cannot be queried.
     #[cfg(test)]
     pub(crate) fn count_rows(&self, table: &str) -> Result<i64> {
-        if !matches!(table, "Files" | "FileNames") {
+        if !matches!(
+            table,
+            "Files" | "FileNames" | "Chunks" | "FileChunks" | "Packs" | "PackUploads"
+        ) {
             return Err(anyhow!("Unsupported count table"));
         }
 
@@ -562,6 +1694,66 @@ impl SqliteCatalog {
         Ok(stmt.query_row([], |row| row.get(0))?)
     }
 
+    /// Snapshot `files` at `version` and resolve every pending entry to the
+    /// given content id (with an empty manifest), as `run` then `upload` would.
+    /// Each file's signature is derived from its content id.
+    ///
+    /// # Errors
+    /// Returns an error if the entries cannot be recorded.
+    #[cfg(test)]
+    pub(crate) fn record_contents(
+        &self,
+        version: i64,
+        files: &[(PathBuf, &str)],
+        close_missing_files: bool,
+    ) -> Result<()> {
+        let hashes: std::collections::HashMap<&Path, &str> = files
+            .iter()
+            .map(|(path, hash)| (path.as_path(), *hash))
+            .collect();
+        let scanned: Vec<ScannedFile> = files
+            .iter()
+            .map(|(path, hash)| {
+                let digest = blake3::hash(hash.as_bytes());
+                let mut mtime = [0u8; 8];
+                mtime.copy_from_slice(digest.as_bytes().get(..8).unwrap_or(&[0; 8]));
+                Ok(ScannedFile {
+                    path: path.clone(),
+                    kind: EntryKind::File,
+                    signature: StatSignature {
+                        size: u64::try_from(hash.len())?,
+                        mtime_ns: i64::from_le_bytes(mtime),
+                        inode: 1,
+                        ctime_ns: 1,
+                    },
+                    metadata: EntryMetadata::default(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        self.record_snapshot(version, &scanned, close_missing_files, false, None)?;
+
+        let uploaded = self
+            .pending_entries()?
+            .into_iter()
+            .map(|entry| {
+                let hash = hashes
+                    .get(entry.path.as_path())
+                    .ok_or_else(|| anyhow!("no content for {}", entry.path.display()))?;
+                Ok(UploadedEntry {
+                    name_id: entry.name_id,
+                    upload: EntryUpload::Stored {
+                        hash: (*hash).to_string(),
+                        manifest: Vec::new(),
+                        sparse: None,
+                        changed: false,
+                        trusted: true,
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.record_uploaded(&uploaded)
+    }
+
     /// Count distinct file hashes.
     ///
     /// # Errors
@@ -577,7 +1769,7 @@ impl SqliteCatalog {
         )
     }
 
-    /// Count active filename rows.
+    /// Count active filename rows of regular files.
     ///
     /// # Errors
     /// Returns an error if filename metadata cannot be queried.
@@ -586,7 +1778,22 @@ impl SqliteCatalog {
         let conn = self.pool.get()?;
 
         Ok(conn.query_row(
-            "SELECT COUNT(*) FROM FileNames WHERE last_version IS NULL",
+            "SELECT COUNT(*) FROM FileNames WHERE last_version IS NULL AND kind = 'file'",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Count filename rows of regular files, active or not.
+    ///
+    /// # Errors
+    /// Returns an error if filename metadata cannot be queried.
+    #[cfg(test)]
+    pub(crate) fn count_file_names(&self) -> Result<i64> {
+        let conn = self.pool.get()?;
+
+        Ok(conn.query_row(
+            "SELECT COUNT(*) FROM FileNames WHERE kind = 'file'",
             [],
             |row| row.get(0),
         )?)
@@ -609,25 +1816,21 @@ impl SqliteCatalog {
         )?)
     }
 
-    /// Return one wrapped file key for test verification.
+    /// Return one wrapped chunk key for test verification.
     ///
     /// # Errors
-    /// Returns an error if file key metadata cannot be read.
+    /// Returns an error if chunk key metadata cannot be read.
     #[cfg(test)]
-    pub(crate) fn first_wrapped_file_key(&self) -> Result<(String, Vec<u8>, [u8; 32])> {
+    pub(crate) fn first_wrapped_chunk_key(&self) -> Result<(String, Vec<u8>, [u8; 32])> {
         let conn = self.pool.get()?;
         let (hash, encrypted_key, ephemeral_public_key): (String, Vec<u8>, Vec<u8>) = conn
             .query_row(
-                "SELECT hash, encrypted_key, ephemeral_public_key FROM Files LIMIT 1",
+                "SELECT hash, encrypted_key, ephemeral_public_key FROM Chunks LIMIT 1",
                 [],
                 |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
             )?;
 
-        let ephemeral_public_key = ephemeral_public_key
-            .try_into()
-            .map_err(|_| anyhow!("Invalid ephemeral public key length"))?;
-
-        Ok((hash, encrypted_key, ephemeral_public_key))
+        Ok((hash, encrypted_key, ephemeral_key(ephemeral_public_key)?))
     }
 }
 
@@ -644,102 +1847,587 @@ pub fn create_metadata_schema(db_path: &Path) -> Result<()> {
             value TEXT NOT NULL
         );
 
+        -- A file's content, by whole-file keyed hash. chunk_count is NULL until
+        -- the content is stored and its manifest (FileChunks) recorded. A
+        -- sparse file's manifest holds only its data; extents maps it back.
         CREATE TABLE IF NOT EXISTS Files (
             file_id INTEGER PRIMARY KEY,
             hash TEXT NOT NULL UNIQUE,
-            encrypted_key BLOB NOT NULL,
-            ephemeral_public_key BLOB NOT NULL
+            chunk_count INTEGER,
+            extents BLOB
+        );
+
+        CREATE TABLE IF NOT EXISTS Paths (
+            path_id INTEGER PRIMARY KEY,
+            path TEXT NOT NULL UNIQUE
+        );
+
+        -- One entry per path per interval of versions. `run` pins it with the
+        -- entry's stat signature (size, mtime_ns, inode, ctime_ns). A file starts
+        -- 'pending'; `upload` sets file_id and moves it to 'stored' ('changed'
+        -- if the file changed before it was read, 'vanished' if it was gone).
+        -- Every other kind has no content and is 'stored' as scanned; a
+        -- symlink keeps its target's raw bytes in link_target, a device node
+        -- its device number in rdev.
+        CREATE TABLE IF NOT EXISTS FileNames (
+            name_id INTEGER PRIMARY KEY,
+            path_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            file_id INTEGER,
+            first_version INTEGER NOT NULL,
+            last_version INTEGER,
+            size INTEGER,
+            mtime_ns INTEGER,
+            inode INTEGER,
+            ctime_ns INTEGER,
+            status TEXT NOT NULL DEFAULT 'stored'
+                CHECK(status IN ('pending', 'stored', 'changed', 'vanished')),
+            kind TEXT NOT NULL DEFAULT 'file'
+                CHECK(kind IN ('file', 'dir', 'symlink', 'fifo', 'char', 'block', 'socket')),
+            link_target BLOB,
+            rdev INTEGER CHECK((rdev IS NOT NULL) = (kind IN ('char', 'block'))),
+
+            FOREIGN KEY (path_id) REFERENCES Paths(path_id),
+            FOREIGN KEY (file_id) REFERENCES Files(file_id),
+            CHECK(last_version IS NULL OR last_version >= first_version),
+            CHECK(kind <> 'file' OR (file_id IS NULL) = (status IN ('pending', 'vanished'))),
+            CHECK(kind = 'file' OR (file_id IS NULL AND status = 'stored')),
+            CHECK((link_target IS NOT NULL) = (kind = 'symlink')),
+
+            UNIQUE(path_id, name, first_version)
+        );
+
+        -- timestamp and completed_at are Unix seconds (INTEGER), matching how
+        -- create_version writes them via strftime('%s','now').
+        CREATE TABLE IF NOT EXISTS BackupVersions (
+            version_id INTEGER PRIMARY KEY,
+            timestamp INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+            completed_at INTEGER
+        );
+
+        CREATE TABLE IF NOT EXISTS config_directories (
+            id INTEGER PRIMARY KEY,
+            path TEXT NOT NULL UNIQUE
+        );
+
+        CREATE TABLE IF NOT EXISTS config_files (
+            id INTEGER PRIMARY KEY,
+            path TEXT NOT NULL UNIQUE
         );
 
-        CREATE TABLE IF NOT EXISTS Paths (
-            path_id INTEGER PRIMARY KEY,
-            path TEXT NOT NULL UNIQUE
-        );
+        CREATE TABLE IF NOT EXISTS config_destinations (
+            id INTEGER PRIMARY KEY,
+            target TEXT NOT NULL UNIQUE
+        );",
+    )?;
+    conn.execute_batch(FILE_NAMES_INDEXES)?;
+    conn.execute_batch(CHUNK_SCHEMA)?;
+    conn.execute_batch(PACK_SCHEMA)?;
+    conn.execute_batch(UPLOAD_SCHEMA)?;
+    conn.execute_batch(ENTRY_METADATA_SCHEMA)?;
+    conn.execute_batch(RECIPIENT_SCHEMA)?;
+
+    Ok(())
+}
+
+/// Pages [`SqliteCatalog::snapshot_to`] copies per step; writers get the lock
+/// back between steps.
+const SNAPSHOT_PAGES_PER_STEP: std::ffi::c_int = 1024;
+
+const FILE_NAMES_INDEXES: &str = "
+    CREATE INDEX IF NOT EXISTS idx_files_version
+        ON FileNames(first_version, last_version);
+
+    CREATE UNIQUE INDEX IF NOT EXISTS idx_filenames_one_active
+        ON FileNames(path_id, name)
+        WHERE last_version IS NULL;
+
+    CREATE INDEX IF NOT EXISTS idx_filenames_path_history
+        ON FileNames(path_id, name, first_version, last_version);
+
+    CREATE INDEX IF NOT EXISTS idx_filenames_pending
+        ON FileNames(first_version)
+        WHERE status = 'pending';";
+
+/// Content-defined chunks and the per-file manifests that order them. A chunk
+/// is the unit of dedup; `size` is the plaintext length (NULL for chunks
+/// migrated from whole-file blobs). A chunk's blob is a byte range of a pack;
+/// chunks stored before packs have no pack and are an object of their own.
+const CHUNK_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS Chunks (
+        chunk_id INTEGER PRIMARY KEY,
+        hash TEXT NOT NULL UNIQUE,
+        size INTEGER,
+        encrypted_key BLOB NOT NULL,
+        ephemeral_public_key BLOB NOT NULL,
+        pack_id TEXT REFERENCES Packs(pack_id),
+        pack_offset INTEGER,
+        pack_length INTEGER,
+        verified_at INTEGER
+    );
+
+    CREATE TABLE IF NOT EXISTS FileChunks (
+        file_id INTEGER NOT NULL,
+        seq INTEGER NOT NULL,
+        chunk_id INTEGER NOT NULL,
+
+        PRIMARY KEY (file_id, seq),
+        FOREIGN KEY (file_id) REFERENCES Files(file_id),
+        FOREIGN KEY (chunk_id) REFERENCES Chunks(chunk_id)
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_filechunks_chunk ON FileChunks(chunk_id);";
+
+/// Stored pack objects (`size` in bytes), and the lookup from a pack to its
+/// chunks. Applied after [`migrate_pack_columns`] so `Chunks.pack_id` exists.
+/// `verified_at` (here, and on `Chunks` for chunks stored before packs) is when
+/// a deep `verify` last found every copy of the object intact.
+const PACK_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS Packs (
+        pack_id TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        verified_at INTEGER
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_chunks_pack ON Chunks(pack_id);";
+
+/// Which destinations each pack has reached: the per-destination upload state
+/// `upload` resumes from. A configured destination with no row for a pack
+/// still needs it.
+const UPLOAD_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS PackUploads (
+        pack_id TEXT NOT NULL REFERENCES Packs(pack_id),
+        destination TEXT NOT NULL,
+
+        PRIMARY KEY (pack_id, destination)
+    );";
+
+/// Each entry's POSIX metadata; entries recorded before it was captured have
+/// no row. `xattrs` is NULL when the entry has none (see [`encode_xattrs`]);
+/// `link_dev`/`link_ino` are NULL unless the entry is hardlinked to another.
+const ENTRY_METADATA_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS EntryMetadata (
+        name_id INTEGER PRIMARY KEY REFERENCES FileNames(name_id),
+        mode INTEGER NOT NULL,
+        uid INTEGER NOT NULL,
+        gid INTEGER NOT NULL,
+        mtime_ns INTEGER NOT NULL,
+        atime_ns INTEGER NOT NULL,
+        xattrs BLOB,
+        link_dev INTEGER,
+        link_ino INTEGER
+    );";
+
+/// Additional keyholders (DESIGN §6.3): each recipient's public key in hex
+/// with the naming key sealed to it, and every chunk's content key wrapped to
+/// it. The primary recipient, the backup public key, keeps its keys inline in
+/// `Config` and `Chunks`. A chunk re-sealed by repair drops its old rows, and
+/// a pruned chunk takes them with it.
+const RECIPIENT_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS Recipients (
+        public_key TEXT PRIMARY KEY,
+        sealed_naming_key BLOB NOT NULL,
+        added_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS ChunkKeys (
+        chunk_id INTEGER NOT NULL REFERENCES Chunks(chunk_id) ON DELETE CASCADE,
+        recipient_pubkey TEXT NOT NULL REFERENCES Recipients(public_key),
+        encrypted_key BLOB NOT NULL,
+        ephemeral_public_key BLOB NOT NULL,
+
+        PRIMARY KEY (chunk_id, recipient_pubkey)
+    );";
+
+/// Apply lightweight, idempotent migrations to an existing catalog.
+///
+/// # Errors
+/// Returns an error if a migration statement fails.
+fn migrate(conn: &Connection) -> Result<()> {
+    // Add `BackupVersions.completed_at` to older catalogs that lack it, and
+    // backfill existing non-empty versions as complete (they predate the flag).
+    let has_completed = conn
+        .prepare("PRAGMA table_info(BackupVersions)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .iter()
+        .any(|name| name == "completed_at");
+
+    if !has_completed {
+        conn.execute(
+            "ALTER TABLE BackupVersions ADD COLUMN completed_at INTEGER",
+            [],
+        )?;
+        conn.execute(
+            "UPDATE BackupVersions SET completed_at = timestamp
+             WHERE completed_at IS NULL
+               AND EXISTS (
+                   SELECT 1 FROM FileNames
+                   WHERE FileNames.first_version <= BackupVersions.version_id
+                     AND (FileNames.last_version IS NULL
+                          OR FileNames.last_version >= BackupVersions.version_id)
+               )",
+            [],
+        )?;
+    }
+
+    migrate_whole_file_blobs(conn)?;
+    migrate_pack_columns(conn)?;
+    migrate_pack_uploads(conn)?;
+    migrate_snapshot_entries(conn)?;
+    migrate_signature_identity(conn)?;
+    migrate_entry_kinds(conn)?;
+    conn.execute_batch(ENTRY_METADATA_SCHEMA)?;
+    migrate_hardlinks(conn)?;
+    migrate_sparse_extents(conn)?;
+    migrate_device_numbers(conn)?;
+    migrate_verified_at(conn)?;
+    conn.execute_batch(RECIPIENT_SCHEMA)?;
+
+    Ok(())
+}
+
+/// Convert a pre-chunking catalog, where each `Files` row held the wrapped key
+/// of one whole-file blob, to manifests: every such blob becomes a single chunk
+/// with the same id, so nothing is re-uploaded and stored blobs stay readable.
+///
+/// `Files` is rebuilt without its key columns (`SQLite` cannot drop them in
+/// place), with foreign keys off so `FileNames` keeps pointing at the same ids.
+///
+/// # Errors
+/// Returns an error if a migration statement fails.
+fn migrate_whole_file_blobs(conn: &Connection) -> Result<()> {
+    let is_legacy = conn
+        .prepare("PRAGMA table_info(Files)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .iter()
+        .any(|name| name == "encrypted_key");
+
+    if !is_legacy {
+        return conn.execute_batch(CHUNK_SCHEMA).map_err(Into::into);
+    }
+
+    // `foreign_keys` can only change outside a transaction.
+    conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+    let migrated = (|| -> Result<()> {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(CHUNK_SCHEMA)?;
+        tx.execute_batch(
+            "INSERT OR IGNORE INTO Chunks (hash, encrypted_key, ephemeral_public_key)
+                 SELECT hash, encrypted_key, ephemeral_public_key FROM Files;
+
+             INSERT OR IGNORE INTO FileChunks (file_id, seq, chunk_id)
+                 SELECT Files.file_id, 0, Chunks.chunk_id
+                 FROM Files JOIN Chunks ON Chunks.hash = Files.hash;
+
+             CREATE TABLE Files_chunked (
+                 file_id INTEGER PRIMARY KEY,
+                 hash TEXT NOT NULL UNIQUE,
+                 chunk_count INTEGER
+             );
+             INSERT INTO Files_chunked (file_id, hash, chunk_count)
+                 SELECT file_id, hash, 1 FROM Files;
+             DROP TABLE Files;
+             ALTER TABLE Files_chunked RENAME TO Files;",
+        )?;
+        tx.commit()?;
+        Ok(())
+    })();
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
+
+    migrated
+}
+
+/// Add the pack location columns to a `Chunks` table from before packs. Its
+/// chunks keep a NULL pack: they stay readable as objects of their own.
+///
+/// # Errors
+/// Returns an error if a migration statement fails.
+fn migrate_pack_columns(conn: &Connection) -> Result<()> {
+    let has_pack = conn
+        .prepare("PRAGMA table_info(Chunks)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .iter()
+        .any(|name| name == "pack_id");
+
+    if !has_pack {
+        conn.execute_batch(
+            "ALTER TABLE Chunks ADD COLUMN pack_id TEXT REFERENCES Packs(pack_id);
+             ALTER TABLE Chunks ADD COLUMN pack_offset INTEGER;
+             ALTER TABLE Chunks ADD COLUMN pack_length INTEGER;",
+        )?;
+    }
+
+    conn.execute_batch(PACK_SCHEMA).map_err(Into::into)
+}
+
+/// Create `PackUploads` for a catalog from before it existed. Packs were then
+/// only recorded once written to every destination, so each is marked as held
+/// by every configured one.
+///
+/// # Errors
+/// Returns an error if a migration statement fails.
+fn migrate_pack_uploads(conn: &Connection) -> Result<()> {
+    if table_exists(conn, "PackUploads")? {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(UPLOAD_SCHEMA)?;
+    if table_exists(&tx, "config_destinations")? {
+        tx.execute(
+            "INSERT INTO PackUploads (pack_id, destination)
+             SELECT Packs.pack_id, config_destinations.target
+             FROM Packs, config_destinations",
+            [],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
 
-        CREATE TABLE IF NOT EXISTS FileNames (
-            name_id INTEGER PRIMARY KEY,
-            path_id INTEGER NOT NULL,
-            name TEXT NOT NULL,
-            file_id INTEGER NOT NULL,
-            first_version INTEGER NOT NULL,
-            last_version INTEGER,
+fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        params![table],
+        |row| row.get(0),
+    )?)
+}
 
-            FOREIGN KEY (path_id) REFERENCES Paths(path_id),
-            FOREIGN KEY (file_id) REFERENCES Files(file_id),
-            CHECK(last_version IS NULL OR last_version >= first_version),
+/// Rebuild a `FileNames` table from before the snapshot/upload split, whose
+/// `file_id` was required: entries gain a stat signature (unknown, so the next
+/// `run` re-checks every file once) and a status (`'stored'`).
+///
+/// # Errors
+/// Returns an error if a migration statement fails.
+fn migrate_snapshot_entries(conn: &Connection) -> Result<()> {
+    let has_status = conn
+        .prepare("PRAGMA table_info(FileNames)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .iter()
+        .any(|name| name == "status");
 
-            UNIQUE(path_id, name, first_version)
-        );
+    if has_status {
+        return conn.execute_batch(FILE_NAMES_INDEXES).map_err(Into::into);
+    }
 
-        -- timestamp and completed_at are Unix seconds (INTEGER), matching how
-        -- create_version writes them via strftime('%s','now').
-        CREATE TABLE IF NOT EXISTS BackupVersions (
-            version_id INTEGER PRIMARY KEY,
-            timestamp INTEGER NOT NULL DEFAULT (strftime('%s','now')),
-            completed_at INTEGER
-        );
+    // `foreign_keys` can only change outside a transaction.
+    conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+    let migrated = (|| -> Result<()> {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(
+            "DROP INDEX IF EXISTS idx_files_version;
+             DROP INDEX IF EXISTS idx_filenames_one_active;
+             DROP INDEX IF EXISTS idx_filenames_path_history;
+
+             CREATE TABLE FileNames_entries (
+                 name_id INTEGER PRIMARY KEY,
+                 path_id INTEGER NOT NULL,
+                 name TEXT NOT NULL,
+                 file_id INTEGER,
+                 first_version INTEGER NOT NULL,
+                 last_version INTEGER,
+                 size INTEGER,
+                 mtime_ns INTEGER,
+                 status TEXT NOT NULL DEFAULT 'stored'
+                     CHECK(status IN ('pending', 'stored', 'changed', 'vanished')),
+
+                 FOREIGN KEY (path_id) REFERENCES Paths(path_id),
+                 FOREIGN KEY (file_id) REFERENCES Files(file_id),
+                 CHECK(last_version IS NULL OR last_version >= first_version),
+                 CHECK((file_id IS NULL) = (status IN ('pending', 'vanished'))),
+
+                 UNIQUE(path_id, name, first_version)
+             );
+             INSERT INTO FileNames_entries
+                 (name_id, path_id, name, file_id, first_version, last_version)
+                 SELECT name_id, path_id, name, file_id, first_version, last_version
+                 FROM FileNames;
+             DROP TABLE FileNames;
+             ALTER TABLE FileNames_entries RENAME TO FileNames;",
+        )?;
+        tx.execute_batch(FILE_NAMES_INDEXES)?;
+        tx.commit()?;
+        Ok(())
+    })();
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
 
-        CREATE TABLE IF NOT EXISTS config_directories (
-            id INTEGER PRIMARY KEY,
-            path TEXT NOT NULL UNIQUE
-        );
+    migrated
+}
 
-        CREATE TABLE IF NOT EXISTS config_files (
-            id INTEGER PRIMARY KEY,
-            path TEXT NOT NULL UNIQUE
-        );
+/// Add the inode and ctime to entry signatures. Entries recorded before have
+/// neither; the next `run` compares them by size and mtime alone and fills the
+/// rest in, so upgrading does not re-read every file.
+///
+/// # Errors
+/// Returns an error if a migration statement fails.
+fn migrate_signature_identity(conn: &Connection) -> Result<()> {
+    let has_inode = conn
+        .prepare("PRAGMA table_info(FileNames)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .iter()
+        .any(|name| name == "inode");
 
-        CREATE TABLE IF NOT EXISTS config_destinations (
-            id INTEGER PRIMARY KEY,
-            target TEXT NOT NULL UNIQUE
-        );
+    if !has_inode {
+        conn.execute_batch(
+            "ALTER TABLE FileNames ADD COLUMN inode INTEGER;
+             ALTER TABLE FileNames ADD COLUMN ctime_ns INTEGER;",
+        )?;
+    }
+    Ok(())
+}
 
-        CREATE INDEX IF NOT EXISTS idx_files_version
-            ON FileNames(first_version, last_version);
+/// Rebuild a `FileNames` table from before entry kinds, whose entries were all
+/// regular files and had to hold content once stored: every existing entry
+/// becomes a `'file'`.
+///
+/// # Errors
+/// Returns an error if a migration statement fails.
+fn migrate_entry_kinds(conn: &Connection) -> Result<()> {
+    let has_kind = conn
+        .prepare("PRAGMA table_info(FileNames)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .iter()
+        .any(|name| name == "kind");
 
-        CREATE UNIQUE INDEX IF NOT EXISTS idx_filenames_one_active
-            ON FileNames(path_id, name)
-            WHERE last_version IS NULL;
+    if has_kind {
+        return Ok(());
+    }
 
-        CREATE INDEX IF NOT EXISTS idx_filenames_path_history
-            ON FileNames(path_id, name, first_version, last_version);",
-    )?;
+    // `foreign_keys` can only change outside a transaction.
+    conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+    let migrated = (|| -> Result<()> {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(
+            "DROP INDEX IF EXISTS idx_files_version;
+             DROP INDEX IF EXISTS idx_filenames_one_active;
+             DROP INDEX IF EXISTS idx_filenames_path_history;
+             DROP INDEX IF EXISTS idx_filenames_pending;
+
+             CREATE TABLE FileNames_kinds (
+                 name_id INTEGER PRIMARY KEY,
+                 path_id INTEGER NOT NULL,
+                 name TEXT NOT NULL,
+                 file_id INTEGER,
+                 first_version INTEGER NOT NULL,
+                 last_version INTEGER,
+                 size INTEGER,
+                 mtime_ns INTEGER,
+                 inode INTEGER,
+                 ctime_ns INTEGER,
+                 status TEXT NOT NULL DEFAULT 'stored'
+                     CHECK(status IN ('pending', 'stored', 'changed', 'vanished')),
+                 kind TEXT NOT NULL DEFAULT 'file'
+                     CHECK(kind IN ('file', 'dir', 'symlink', 'fifo', 'char', 'block', 'socket')),
+                 link_target BLOB,
+
+                 FOREIGN KEY (path_id) REFERENCES Paths(path_id),
+                 FOREIGN KEY (file_id) REFERENCES Files(file_id),
+                 CHECK(last_version IS NULL OR last_version >= first_version),
+                 CHECK(kind <> 'file' OR (file_id IS NULL) = (status IN ('pending', 'vanished'))),
+                 CHECK(kind = 'file' OR (file_id IS NULL AND status = 'stored')),
+                 CHECK((link_target IS NOT NULL) = (kind = 'symlink')),
+
+                 UNIQUE(path_id, name, first_version)
+             );
+             INSERT INTO FileNames_kinds
+                 (name_id, path_id, name, file_id, first_version, last_version,
+                  size, mtime_ns, inode, ctime_ns, status)
+                 SELECT name_id, path_id, name, file_id, first_version, last_version,
+                        size, mtime_ns, inode, ctime_ns, status
+                 FROM FileNames;
+             DROP TABLE FileNames;
+             ALTER TABLE FileNames_kinds RENAME TO FileNames;",
+        )?;
+        tx.execute_batch(FILE_NAMES_INDEXES)?;
+        tx.commit()?;
+        Ok(())
+    })();
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
 
-    Ok(())
+    migrated
 }
 
-/// Apply lightweight, idempotent migrations to an existing catalog.
+/// Add hardlink identity to `EntryMetadata`. Entries recorded before have none
+/// and restore as separate files, as they were backed up.
 ///
 /// # Errors
 /// Returns an error if a migration statement fails.
-fn migrate(conn: &Connection) -> Result<()> {
-    // Add `BackupVersions.completed_at` to older catalogs that lack it, and
-    // backfill existing non-empty versions as complete (they predate the flag).
-    let has_completed = conn
-        .prepare("PRAGMA table_info(BackupVersions)")?
+fn migrate_hardlinks(conn: &Connection) -> Result<()> {
+    let has_link = conn
+        .prepare("PRAGMA table_info(EntryMetadata)")?
         .query_map([], |row| row.get::<_, String>(1))?
         .collect::<rusqlite::Result<Vec<String>>>()?
         .iter()
-        .any(|name| name == "completed_at");
+        .any(|name| name == "link_dev");
 
-    if !has_completed {
-        conn.execute(
-            "ALTER TABLE BackupVersions ADD COLUMN completed_at INTEGER",
-            [],
+    if !has_link {
+        conn.execute_batch(
+            "ALTER TABLE EntryMetadata ADD COLUMN link_dev INTEGER;
+             ALTER TABLE EntryMetadata ADD COLUMN link_ino INTEGER;",
         )?;
+    }
+    Ok(())
+}
+
+/// Add `Files.extents` (sparse files' data maps); existing content is dense.
+fn migrate_sparse_extents(conn: &Connection) -> Result<()> {
+    let has_extents = conn
+        .prepare("PRAGMA table_info(Files)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .iter()
+        .any(|name| name == "extents");
+
+    if !has_extents {
+        conn.execute("ALTER TABLE Files ADD COLUMN extents BLOB", [])?;
+    }
+    Ok(())
+}
+
+/// Add `FileNames.rdev` (device nodes' device numbers); no existing entry is
+/// a device node, so every row passes its check as NULL.
+fn migrate_device_numbers(conn: &Connection) -> Result<()> {
+    let has_rdev = conn
+        .prepare("PRAGMA table_info(FileNames)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .iter()
+        .any(|name| name == "rdev");
+
+    if !has_rdev {
         conn.execute(
-            "UPDATE BackupVersions SET completed_at = timestamp
-             WHERE completed_at IS NULL
-               AND EXISTS (
-                   SELECT 1 FROM FileNames
-                   WHERE FileNames.first_version <= BackupVersions.version_id
-                     AND (FileNames.last_version IS NULL
-                          OR FileNames.last_version >= BackupVersions.version_id)
-               )",
+            "ALTER TABLE FileNames ADD COLUMN rdev INTEGER
+                 CHECK((rdev IS NOT NULL) = (kind IN ('char', 'block')))",
             [],
         )?;
     }
+    Ok(())
+}
 
+/// Add `Packs.verified_at` and `Chunks.verified_at`; no object has been
+/// deep-verified yet, so sampled checks start with every one.
+fn migrate_verified_at(conn: &Connection) -> Result<()> {
+    for table in ["Packs", "Chunks"] {
+        let has_verified = conn
+            .prepare(&format!("PRAGMA table_info({table})"))?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<String>>>()?
+            .iter()
+            .any(|name| name == "verified_at");
+
+        if !has_verified {
+            conn.execute(
+                &format!("ALTER TABLE {table} ADD COLUMN verified_at INTEGER"),
+                [],
+            )?;
+        }
+    }
     Ok(())
 }
 
@@ -804,38 +2492,150 @@ fn configured_files(conn: &Connection) -> Result<Vec<PathBuf>> {
     Ok(files.iter().map(PathBuf::from).collect())
 }
 
+/// Whether a remaining version falls in a `FileNames` row's interval.
+const SEEN_BY_A_VERSION: &str = "
+    SELECT 1 FROM BackupVersions
+    WHERE BackupVersions.version_id >= FileNames.first_version
+      AND (FileNames.last_version IS NULL
+           OR BackupVersions.version_id <= FileNames.last_version)";
+
+/// Columns [`restore_entry_row`] reads, for an entry joined to its path,
+/// content and metadata.
+const RESTORE_ENTRY_SELECT: &str = "
+    SELECT Paths.path, FileNames.name, Files.hash,
+           EntryMetadata.mode, EntryMetadata.uid, EntryMetadata.gid,
+           EntryMetadata.mtime_ns, EntryMetadata.atime_ns, EntryMetadata.xattrs,
+           FileNames.kind, FileNames.link_target,
+           EntryMetadata.link_dev, EntryMetadata.link_ino, FileNames.rdev
+    FROM FileNames
+    JOIN Paths ON Paths.path_id = FileNames.path_id
+    LEFT JOIN Files ON Files.file_id = FileNames.file_id
+    LEFT JOIN EntryMetadata ON EntryMetadata.name_id = FileNames.name_id";
+
+/// A [`RESTORE_ENTRY_SELECT`] row, before its kind and xattrs are decoded.
+struct RestoreRow {
+    path: PathBuf,
+    hash: Option<String>,
+    metadata: Option<(u32, u32, u32, i64, i64)>,
+    xattrs: Option<Vec<u8>>,
+    kind: String,
+    link_target: Option<Vec<u8>>,
+    hardlink: Option<(i64, i64)>,
+    rdev: Option<i64>,
+}
+
+impl RestoreRow {
+    fn into_entry(self) -> Result<RestoreEntry> {
+        let metadata = self
+            .metadata
+            .map(|(mode, uid, gid, mtime_ns, atime_ns)| {
+                Ok::<_, anyhow::Error>(EntryMetadata {
+                    mode,
+                    uid,
+                    gid,
+                    mtime_ns,
+                    atime_ns,
+                    xattrs: decode_xattrs(self.xattrs.as_deref())?,
+                    hardlink: self
+                        .hardlink
+                        .map(|(dev, ino)| (dev.cast_unsigned(), ino.cast_unsigned())),
+                })
+            })
+            .transpose()?;
+        Ok(RestoreEntry {
+            kind: EntryKind::from_columns(&self.kind, self.link_target.as_deref(), self.rdev)?,
+            path: self.path,
+            hash: self.hash,
+            metadata,
+        })
+    }
+}
+
+fn restore_entry_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RestoreRow> {
+    let parent: String = row.get(0)?;
+    let name: String = row.get(1)?;
+    let mode: Option<u32> = row.get(3)?;
+    let metadata = match mode {
+        Some(mode) => Some((mode, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?)),
+        None => None,
+    };
+    Ok(RestoreRow {
+        path: PathBuf::from(parent).join(name),
+        hash: row.get(2)?,
+        metadata,
+        xattrs: row.get(8)?,
+        kind: row.get(9)?,
+        link_target: row.get(10)?,
+        hardlink: match (row.get(11)?, row.get(12)?) {
+            (Some(dev), Some(ino)) => Some((dev, ino)),
+            _ => None,
+        },
+        rdev: row.get(13)?,
+    })
+}
+
 fn restore_entries(conn: &Connection, version: i64) -> Result<Vec<RestoreEntry>> {
-    let mut stmt = conn.prepare(
-        "SELECT Paths.path, FileNames.name, Files.hash
-         FROM FileNames
-         JOIN Paths ON Paths.path_id = FileNames.path_id
-         JOIN Files ON Files.file_id = FileNames.file_id
+    let mut stmt = conn.prepare(&format!(
+        "{RESTORE_ENTRY_SELECT}
          WHERE FileNames.first_version <= ?1
            AND (
                FileNames.last_version IS NULL
                OR FileNames.last_version >= ?1
            )
-         ORDER BY Paths.path, FileNames.name",
-    )?;
+         ORDER BY Paths.path, FileNames.name"
+    ))?;
 
     let mut entries = stmt
-        .query_map(params![version], |row| {
-            let parent: String = row.get(0)?;
-            let name: String = row.get(1)?;
-            let hash: String = row.get(2)?;
-
-            Ok(RestoreEntry {
-                path: PathBuf::from(parent).join(name),
-                hash,
-            })
-        })?
-        .collect::<rusqlite::Result<Vec<_>>>()?;
+        .query_map(params![version], restore_entry_row)?
+        .map(|row| row?.into_entry())
+        .collect::<Result<Vec<_>>>()?;
 
     entries.sort();
 
     Ok(entries)
 }
 
+/// Serialize xattrs as `(u32 LE length, bytes)` name/value pairs in name order,
+/// so equal sets encode equally; `None` for no xattrs.
+fn encode_xattrs(xattrs: &[(Vec<u8>, Vec<u8>)]) -> Result<Option<Vec<u8>>> {
+    if xattrs.is_empty() {
+        return Ok(None);
+    }
+    let mut sorted: Vec<&(Vec<u8>, Vec<u8>)> = xattrs.iter().collect();
+    sorted.sort();
+    let mut encoded = Vec::new();
+    for (name, value) in sorted {
+        for field in [name, value] {
+            encoded.extend_from_slice(&u32::try_from(field.len())?.to_le_bytes());
+            encoded.extend_from_slice(field);
+        }
+    }
+    Ok(Some(encoded))
+}
+
+fn decode_xattrs(encoded: Option<&[u8]>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    fn field(rest: &mut &[u8]) -> Result<Vec<u8>> {
+        let (len, tail) = rest
+            .split_first_chunk::<4>()
+            .ok_or_else(|| anyhow!("truncated xattrs"))?;
+        let len = usize::try_from(u32::from_le_bytes(*len))?;
+        let (value, tail) = tail
+            .split_at_checked(len)
+            .ok_or_else(|| anyhow!("truncated xattrs"))?;
+        *rest = tail;
+        Ok(value.to_vec())
+    }
+
+    let mut rest = encoded.unwrap_or_default();
+    let mut xattrs = Vec::new();
+    while !rest.is_empty() {
+        let name = field(&mut rest)?;
+        let value = field(&mut rest)?;
+        xattrs.push((name, value));
+    }
+    Ok(xattrs)
+}
+
 fn view_entries(conn: &Connection, version: i64, root: Option<&Path>) -> Result<Vec<ViewEntry>> {
     // Optional subtree scope: an index-friendly range over Paths.path that
     // matches the directory itself and everything under "<root>/...".
@@ -852,7 +2652,7 @@ fn view_entries(conn: &Connection, version: i64, root: Option<&Path>) -> Result<
     };
 
     let sql = format!(
-        "SELECT FileNames.name_id, Paths.path, FileNames.name
+        "SELECT FileNames.name_id, Paths.path, FileNames.name, FileNames.kind = 'dir'
          FROM FileNames
          JOIN Paths ON Paths.path_id = FileNames.path_id
          WHERE FileNames.first_version <= ?1
@@ -870,6 +2670,7 @@ fn view_entries(conn: &Connection, version: i64, root: Option<&Path>) -> Result<
         Ok(ViewEntry {
             id,
             path: PathBuf::from(parent).join(name),
+            is_dir: row.get(3)?,
         })
     };
 
@@ -887,13 +2688,14 @@ fn view_entries(conn: &Connection, version: i64, root: Option<&Path>) -> Result<
     Ok(entries)
 }
 
+/// Record one scanned entry at `version`; returns whether it is pending (a
+/// file, new or changed since its active entry).
 fn upsert_scanned_file(
     conn: &Connection,
-    public_key: PublicKey,
-    sealed_keys: &SealedKeys,
     version: i64,
     scanned_file: &ScannedFile,
-) -> Result<()> {
+    rehash: bool,
+) -> Result<bool> {
     let path = scanned_file
         .path
         .parent()
@@ -909,7 +2711,6 @@ fn upsert_scanned_file(
         .to_string();
 
     let path_id = get_or_insert_path(conn, &path)?;
-    let file_id = get_or_insert_file(conn, &scanned_file.hash, public_key, sealed_keys)?;
 
     conn.execute(
         "INSERT OR IGNORE INTO seen_files (path_id, name)
@@ -917,25 +2718,59 @@ fn upsert_scanned_file(
         params![path_id, file_name],
     )?;
 
-    let active_file_id = get_active_file_id(conn, path_id, &file_name)?;
-
-    match active_file_id {
-        Some(active_file_id) if active_file_id == file_id => {}
-        Some(_) => {
-            conn.execute(
-                "UPDATE FileNames
-                 SET last_version = ?1 - 1
-                 WHERE path_id = ?2
-                   AND name = ?3
-                   AND last_version IS NULL",
-                params![version, path_id, file_name],
-            )?;
-
-            insert_file_name(conn, path_id, &file_name, file_id, version)?;
-        }
-        None => insert_file_name(conn, path_id, &file_name, file_id, version)?,
+    let active = get_active_entry(conn, path_id, &file_name)?;
+    if let Some(active) = &active
+        && active.unchanged(scanned_file)?
+        // Re-hashing re-reads every stored file; a pending one is read anyway,
+        // and other kinds have no content to read.
+        && (!rehash || active.status == "pending" || scanned_file.kind != EntryKind::File)
+    {
+        // Unchanged: the entry (stored, or still pending) carries forward,
+        // completing a signature recorded before inodes and ctimes were.
+        conn.execute(
+            "UPDATE FileNames SET inode = ?2, ctime_ns = ?3
+             WHERE name_id = ?1 AND inode IS NULL",
+            params![
+                active.name_id,
+                scanned_file.signature.inode.cast_signed(),
+                scanned_file.signature.ctime_ns
+            ],
+        )?;
+        return Ok(active.status == "pending");
     }
+    if active.is_some() {
+        conn.execute(
+            "UPDATE FileNames
+             SET last_version = ?1 - 1
+             WHERE path_id = ?2
+               AND name = ?3
+               AND last_version IS NULL",
+            params![version, path_id, file_name],
+        )?;
+    }
+
+    let name_id = insert_file_name(conn, path_id, &file_name, scanned_file, version)?;
+    insert_entry_metadata(conn, name_id, &scanned_file.metadata)?;
+    Ok(scanned_file.kind == EntryKind::File)
+}
 
+fn insert_entry_metadata(conn: &Connection, name_id: i64, metadata: &EntryMetadata) -> Result<()> {
+    conn.execute(
+        "INSERT INTO EntryMetadata
+             (name_id, mode, uid, gid, mtime_ns, atime_ns, xattrs, link_dev, link_ino)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            name_id,
+            metadata.mode,
+            metadata.uid,
+            metadata.gid,
+            metadata.mtime_ns,
+            metadata.atime_ns,
+            encode_xattrs(&metadata.xattrs)?,
+            metadata.hardlink.map(|(dev, _)| dev.cast_signed()),
+            metadata.hardlink.map(|(_, ino)| ino.cast_signed())
+        ],
+    )?;
     Ok(())
 }
 
@@ -950,28 +2785,14 @@ fn get_or_insert_path(conn: &Connection, path: &str) -> Result<i64> {
     Ok(stmt.query_row(params![path], |row| row.get(0))?)
 }
 
-fn get_or_insert_file(
-    conn: &Connection,
-    hash: &str,
-    public_key: PublicKey,
-    sealed_keys: &SealedKeys,
-) -> Result<i64> {
+fn get_or_insert_file(conn: &Connection, hash: &str) -> Result<i64> {
     if let Some(file_id) = get_file_id(conn, hash)? {
         return Ok(file_id);
     }
 
-    // Prefer the wrapped key the engine produced when it sealed+stored the blob;
-    // fall back to generating one for metadata-only runs (no destinations).
-    let (wrapped, e_public) = match sealed_keys.get(hash) {
-        Some((wrapped, e_public)) => (wrapped.clone(), *e_public),
-        None => encrypted_file_key(public_key, hash)?,
-    };
-
-    conn.execute(
-        "INSERT INTO Files (hash, encrypted_key, ephemeral_public_key)
-         VALUES (?1, ?2, ?3)",
-        params![hash, wrapped, e_public],
-    )?;
+    // No manifest yet (`chunk_count` NULL): set by `record_manifest` once the
+    // content is stored; metadata-only runs leave it unset.
+    conn.execute("INSERT INTO Files (hash) VALUES (?1)", params![hash])?;
 
     get_file_id(conn, hash)?.ok_or_else(|| anyhow!("Failed to get inserted file id"))
 }
@@ -983,39 +2804,349 @@ fn get_file_id(conn: &Connection, hash: &str) -> Result<Option<i64>> {
     rows.next()?.map_or(Ok(None), |row| Ok(Some(row.get(0)?)))
 }
 
-fn encrypted_file_key(public_key: PublicKey, hash: &str) -> Result<(Vec<u8>, [u8; 32])> {
-    let file_key = generate_file_key();
-    encrypt(&file_key, &public_key, &content_key_aad(hash))
+/// Insert or refresh a chunk sealed this run. The stores now hold the blob
+/// sealed with *this* key, so an existing row (e.g. left by an interrupted run)
+/// takes the new key; the row id is kept so manifests stay valid.
+fn upsert_chunk(conn: &Connection, hash: &str, chunk: &SealedChunk) -> Result<()> {
+    conn.execute(
+        "INSERT INTO Chunks (hash, size, encrypted_key, ephemeral_public_key,
+                             pack_id, pack_offset, pack_length)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(hash) DO UPDATE SET
+             size = excluded.size,
+             encrypted_key = excluded.encrypted_key,
+             ephemeral_public_key = excluded.ephemeral_public_key,
+             pack_id = excluded.pack_id,
+             pack_offset = excluded.pack_offset,
+             pack_length = excluded.pack_length",
+        params![
+            hash,
+            i64::try_from(chunk.size)?,
+            chunk.wrapped_key,
+            chunk.ephemeral_public_key.as_slice(),
+            chunk.pack.pack_id,
+            i64::try_from(chunk.pack.offset)?,
+            i64::try_from(chunk.pack.length)?,
+        ],
+    )?;
+
+    // Keys wrapped for an earlier seal of this chunk no longer open it.
+    conn.execute(
+        "DELETE FROM ChunkKeys
+         WHERE chunk_id = (SELECT chunk_id FROM Chunks WHERE hash = ?1)",
+        params![hash],
+    )?;
+    for key in &chunk.recipient_keys {
+        insert_chunk_key(conn, hash, key)?;
+    }
+    Ok(())
+}
+
+/// Record a chunk's content key wrapped to a recipient, replacing any there.
+fn insert_chunk_key(conn: &Connection, hash: &str, key: &RecipientKey) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO ChunkKeys
+             (chunk_id, recipient_pubkey, encrypted_key, ephemeral_public_key)
+         SELECT chunk_id, ?2, ?3, ?4 FROM Chunks WHERE hash = ?1",
+        params![
+            hash,
+            key.recipient,
+            key.wrapped_key,
+            key.ephemeral_public_key.as_slice()
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_pack(conn: &Connection, pack_id: &str, size: u64) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO Packs (pack_id, size) VALUES (?1, ?2)",
+        params![pack_id, i64::try_from(size)?],
+    )?;
+    Ok(())
+}
+
+/// Fold `(pack_id, destination)` rows sorted by pack into each pack with its
+/// destinations.
+fn group_by_pack(rows: Vec<(String, Option<String>)>) -> Vec<(String, Vec<String>)> {
+    let mut packs: Vec<(String, Vec<String>)> = Vec::new();
+    for (pack_id, destination) in rows {
+        match packs.last_mut() {
+            Some((last, destinations)) if *last == pack_id => {
+                destinations.extend(destination);
+            }
+            _ => packs.push((pack_id, destination.into_iter().collect())),
+        }
+    }
+    packs
+}
+
+fn insert_pack_upload(conn: &Connection, pack_id: &str, destination: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO PackUploads (pack_id, destination) VALUES (?1, ?2)",
+        params![pack_id, destination],
+    )?;
+    Ok(())
+}
+
+/// Convert a nullable `(pack_id, pack_offset, pack_length)` column triple.
+fn pack_location(
+    (pack_id, offset, length): (Option<String>, Option<i64>, Option<i64>),
+) -> Result<Option<PackLocation>> {
+    let Some(pack_id) = pack_id else {
+        return Ok(None);
+    };
+    Ok(Some(PackLocation {
+        pack_id,
+        offset: u64::try_from(offset.ok_or_else(|| anyhow!("pack chunk has no offset"))?)?,
+        length: u64::try_from(length.ok_or_else(|| anyhow!("pack chunk has no length"))?)?,
+    }))
 }
 
-fn get_active_file_id(conn: &Connection, path_id: i64, file_name: &str) -> Result<Option<i64>> {
+/// Record a file's manifest, unless it already has one (content is immutable
+/// per id, so the first recorded manifest stays authoritative).
+fn record_manifest(
+    conn: &Connection,
+    file_id: i64,
+    manifest: &[String],
+    sparse: Option<&SparseMap>,
+) -> Result<()> {
+    let has_manifest: bool = conn.query_row(
+        "SELECT chunk_count IS NOT NULL FROM Files WHERE file_id = ?1",
+        params![file_id],
+        |row| row.get(0),
+    )?;
+    if has_manifest {
+        return Ok(());
+    }
+
     let mut stmt = conn.prepare(
-        "SELECT file_id
-         FROM FileNames
-         WHERE path_id = ?1
-           AND name = ?2
-           AND last_version IS NULL",
+        "INSERT INTO FileChunks (file_id, seq, chunk_id)
+         SELECT ?1, ?2, chunk_id FROM Chunks WHERE hash = ?3",
+    )?;
+    for (seq, chunk) in manifest.iter().enumerate() {
+        if stmt.execute(params![file_id, i64::try_from(seq)?, chunk])? != 1 {
+            return Err(anyhow!("manifest references unknown chunk {chunk}"));
+        }
+    }
+
+    conn.execute(
+        "UPDATE Files SET chunk_count = ?2, extents = ?3 WHERE file_id = ?1",
+        params![
+            file_id,
+            i64::try_from(manifest.len())?,
+            sparse.map(SparseMap::encode)
+        ],
     )?;
+    Ok(())
+}
 
-    let mut rows = stmt.query(params![path_id, file_name])?;
+fn ephemeral_key(bytes: Vec<u8>) -> Result<[u8; 32]> {
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("stored ephemeral public key has wrong length"))
+}
 
-    rows.next()?.map_or(Ok(None), |row| Ok(Some(row.get(0)?)))
+/// The open (`last_version IS NULL`) entry for a path.
+struct ActiveEntry {
+    name_id: i64,
+    status: String,
+    kind: String,
+    link_target: Option<Vec<u8>>,
+    rdev: Option<i64>,
+    signature: StoredSignature,
+}
+
+impl ActiveEntry {
+    /// Whether a scanned entry can be taken as this one unread: same kind (and
+    /// target or device number) and signature. Entries whose content was read after a change, or
+    /// that were never given a trusted signature, never match.
+    fn unchanged(&self, scanned: &ScannedFile) -> Result<bool> {
+        if !matches!(self.status.as_str(), "pending" | "stored")
+            || self.kind != scanned.kind.as_str()
+            || self.link_target.as_deref() != scanned.kind.link_target()
+            || self.rdev != scanned.kind.rdev()
+        {
+            return Ok(false);
+        }
+        let signature = scanned.signature;
+        let stored = &self.signature;
+        let (Some(size), Some(mtime_ns)) = (stored.size, stored.mtime_ns) else {
+            return Ok(false);
+        };
+        if u64::try_from(size)? != signature.size || mtime_ns != signature.mtime_ns {
+            return Ok(false);
+        }
+        // Recorded before inodes and ctimes were: size and mtime decide.
+        Ok(stored
+            .inode
+            .is_none_or(|inode| inode.cast_unsigned() == signature.inode)
+            && stored
+                .ctime_ns
+                .is_none_or(|ctime_ns| ctime_ns == signature.ctime_ns))
+    }
+}
+
+fn get_active_entry(
+    conn: &Connection,
+    path_id: i64,
+    file_name: &str,
+) -> Result<Option<ActiveEntry>> {
+    Ok(conn
+        .query_row(
+            "SELECT name_id, status, kind, link_target, rdev, size, mtime_ns, inode, ctime_ns
+             FROM FileNames
+             WHERE path_id = ?1
+               AND name = ?2
+               AND last_version IS NULL",
+            params![path_id, file_name],
+            |row| {
+                Ok(ActiveEntry {
+                    name_id: row.get(0)?,
+                    status: row.get(1)?,
+                    kind: row.get(2)?,
+                    link_target: row.get(3)?,
+                    rdev: row.get(4)?,
+                    signature: StoredSignature::from_row(row, 5)?,
+                })
+            },
+        )
+        .optional()?)
+}
+
+/// An entry's signature columns, any of which may be NULL: the entry has no
+/// trusted signature, or predates the inode/ctime columns.
+struct StoredSignature {
+    size: Option<i64>,
+    mtime_ns: Option<i64>,
+    inode: Option<i64>,
+    ctime_ns: Option<i64>,
+}
+
+impl StoredSignature {
+    /// Read the four signature columns starting at `first`.
+    fn from_row(row: &rusqlite::Row<'_>, first: usize) -> rusqlite::Result<Self> {
+        Ok(Self {
+            size: row.get(first)?,
+            mtime_ns: row.get(first + 1)?,
+            inode: row.get(first + 2)?,
+            ctime_ns: row.get(first + 3)?,
+        })
+    }
+
+    /// The complete signature, if every column is set.
+    fn signature(&self) -> Result<Option<StatSignature>> {
+        match (self.size, self.mtime_ns, self.inode, self.ctime_ns) {
+            (Some(size), Some(mtime_ns), Some(inode), Some(ctime_ns)) => Ok(Some(StatSignature {
+                size: u64::try_from(size)?,
+                mtime_ns,
+                inode: inode.cast_unsigned(),
+                ctime_ns,
+            })),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// If a just-uploaded entry holds the same content and metadata as the entry
+/// it replaced (the one for the same path ending the version before it starts),
+/// extend that one over its versions and drop it. Access times are not
+/// compared: reading a file changes them.
+fn merge_unchanged_entry(conn: &Connection, name_id: i64) -> Result<()> {
+    let previous = conn
+        .query_row(
+            "SELECT prev.name_id, cur.last_version
+             FROM FileNames AS cur
+             JOIN FileNames AS prev
+               ON prev.path_id = cur.path_id
+              AND prev.name = cur.name
+              AND prev.last_version = cur.first_version - 1
+              AND prev.file_id = cur.file_id
+              AND prev.status = cur.status
+             LEFT JOIN EntryMetadata AS prev_meta ON prev_meta.name_id = prev.name_id
+             LEFT JOIN EntryMetadata AS cur_meta ON cur_meta.name_id = cur.name_id
+             WHERE cur.name_id = ?1
+               AND (prev_meta.name_id IS NULL
+                    OR (prev_meta.mode IS cur_meta.mode
+                        AND prev_meta.uid IS cur_meta.uid
+                        AND prev_meta.gid IS cur_meta.gid
+                        AND prev_meta.mtime_ns IS cur_meta.mtime_ns
+                        AND prev_meta.xattrs IS cur_meta.xattrs
+                        AND prev_meta.link_dev IS cur_meta.link_dev
+                        AND prev_meta.link_ino IS cur_meta.link_ino))",
+            params![name_id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?)),
+        )
+        .optional()?;
+
+    if let Some((previous_id, last_version)) = previous {
+        // It takes the newer signature, which is what the next scan compares
+        // against, and the newer metadata (the same, bar access time, unless it
+        // had none); copy them before the newer entry goes.
+        conn.execute(
+            "UPDATE FileNames
+             SET (size, mtime_ns, inode, ctime_ns) =
+                 (SELECT size, mtime_ns, inode, ctime_ns FROM FileNames WHERE name_id = ?2)
+             WHERE name_id = ?1",
+            params![previous_id, name_id],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO EntryMetadata
+                 (name_id, mode, uid, gid, mtime_ns, atime_ns, xattrs, link_dev, link_ino)
+             SELECT ?1, mode, uid, gid, mtime_ns, atime_ns, xattrs, link_dev, link_ino
+             FROM EntryMetadata WHERE name_id = ?2",
+            params![previous_id, name_id],
+        )?;
+        conn.execute(
+            "DELETE FROM EntryMetadata WHERE name_id = ?1",
+            params![name_id],
+        )?;
+        // Delete before extending: the merged entry may become the active one,
+        // and only one active entry per path is allowed.
+        conn.execute("DELETE FROM FileNames WHERE name_id = ?1", params![name_id])?;
+        conn.execute(
+            "UPDATE FileNames SET last_version = ?2 WHERE name_id = ?1",
+            params![previous_id, last_version],
+        )?;
+    }
+    Ok(())
 }
 
+/// Insert an entry: a file is pending, its content read later by `upload`;
+/// any other kind is stored as scanned. Returns its `name_id`.
 fn insert_file_name(
     conn: &Connection,
     path_id: i64,
     file_name: &str,
-    file_id: i64,
+    scanned: &ScannedFile,
     version: i64,
-) -> Result<()> {
+) -> Result<i64> {
+    let signature = scanned.signature;
     conn.execute(
-        "INSERT INTO FileNames (path_id, name, file_id, first_version)
-         VALUES (?1, ?2, ?3, ?4)",
-        params![path_id, file_name, file_id, version],
+        "INSERT INTO FileNames
+             (path_id, name, first_version, size, mtime_ns, inode, ctime_ns, status,
+              kind, link_target, rdev)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            path_id,
+            file_name,
+            version,
+            i64::try_from(signature.size)?,
+            signature.mtime_ns,
+            signature.inode.cast_signed(),
+            signature.ctime_ns,
+            if scanned.kind == EntryKind::File {
+                "pending"
+            } else {
+                "stored"
+            },
+            scanned.kind.as_str(),
+            scanned.kind.link_target(),
+            scanned.kind.rdev()
+        ],
     )?;
 
-    Ok(())
+    Ok(conn.last_insert_rowid())
 }
 
 fn close_deleted_files(conn: &Connection, version: i64) -> Result<()> {
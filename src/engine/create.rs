@@ -1,12 +1,13 @@
 use crate::{
     db::sqlite::SqliteCatalog,
     engine::wkey,
-    utils::crypto::{content_keypair, generate_naming_key, seal_naming_key},
+    utils::crypto::{RecoveryKey, generate_naming_key, seal_naming_key},
 };
 use anyhow::{Result, anyhow};
 use bip39::{Language, Mnemonic};
 use std::path::PathBuf;
 use tracing::debug;
+use zeroize::Zeroizing;
 
 pub struct CreateBackupRequest {
     pub name: String,
@@ -14,10 +15,16 @@ pub struct CreateBackupRequest {
     pub directories: Vec<PathBuf>,
     pub files: Vec<PathBuf>,
     pub destinations: Vec<String>,
+    /// BIP-39 passphrase to derive the keypair with alongside the mnemonic;
+    /// every path that needs the mnemonic then needs it too.
+    pub passphrase: Option<Zeroizing<String>>,
 }
 
 pub struct CreateBackupResult {
     pub recovery_phrase: String,
+    /// The backup public key in hex, which another backup can add as a
+    /// recipient (`backup edit --add-recipient`).
+    pub public_key: String,
     pub db_path: PathBuf,
 }
 
@@ -37,8 +44,11 @@ pub fn create(request: CreateBackupRequest) -> Result<CreateBackupResult> {
 
     let catalog = SqliteCatalog::initialize(&db_path)?;
 
-    let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
-    let (_, public_key) = content_keypair(&mnemonic)?;
+    let recovery = RecoveryKey {
+        mnemonic: Mnemonic::generate_in(Language::English, 12)?,
+        passphrase: request.passphrase,
+    };
+    let (_, public_key) = recovery.keypair()?;
 
     debug!("Public Key: {:?}", hex::encode(public_key.as_bytes()));
 
@@ -48,6 +58,9 @@ pub fn create(request: CreateBackupRequest) -> Result<CreateBackupResult> {
     let naming_key = generate_naming_key();
     let sealed = seal_naming_key(&naming_key, &public_key)?;
     catalog.save_keys(&public_key, &sealed)?;
+    if recovery.passphrase.is_some() {
+        catalog.save_passphrase_required()?;
+    }
     wkey::write_naming_key(&request.config_dir, &request.name, &naming_key)?;
 
     let backup_dirs = get_unique_dir_parents(request.directories);
@@ -56,7 +69,8 @@ pub fn create(request: CreateBackupRequest) -> Result<CreateBackupResult> {
     catalog.save_destinations(&request.destinations)?;
 
     Ok(CreateBackupResult {
-        recovery_phrase: mnemonic.to_string(),
+        recovery_phrase: recovery.mnemonic.to_string(),
+        public_key: hex::encode(public_key.as_bytes()),
         db_path,
     })
 }
@@ -79,7 +93,7 @@ pub(crate) fn get_unique_dir_parents(mut dirs: Vec<PathBuf>) -> Vec<PathBuf> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::utils::crypto::unseal_naming_key;
+    use crate::utils::crypto::{content_keypair, unseal_naming_key};
 
     #[test]
     fn create_writes_wkey_and_naming_key_recovers_from_mnemonic() -> Result<()> {
@@ -92,6 +106,7 @@ mod tests {
             directories: Vec::new(),
             files: Vec::new(),
             destinations: Vec::new(),
+            passphrase: None,
         })?;
 
         // The cache exists after create and holds a 32-byte naming key.
@@ -104,7 +119,7 @@ mod tests {
 
         let mnemonic = Mnemonic::parse_in_normalized(Language::English, &result.recovery_phrase)?;
         let catalog = SqliteCatalog::open(&result.db_path)?;
-        let recovered = unseal_naming_key(&catalog.sealed_naming_key()?, &mnemonic)?;
+        let recovered = unseal_naming_key(&catalog.sealed_naming_key()?, &mnemonic.clone().into())?;
 
         assert_eq!(*cached, *recovered);
 
@@ -112,6 +127,41 @@ mod tests {
         // matches the one derived from the recovery mnemonic.
         let (_, expected_public) = content_keypair(&mnemonic)?;
         assert_eq!(catalog.public_key()?.to_bytes(), expected_public.to_bytes());
+        assert_eq!(result.public_key, hex::encode(expected_public.as_bytes()));
+        assert!(!catalog.passphrase_required()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_with_a_passphrase_records_that_it_is_required() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let passphrase = Zeroizing::new("not in the words".to_string());
+
+        let result = create(CreateBackupRequest {
+            name: "demo".to_string(),
+            config_dir: temp_dir.path().to_path_buf(),
+            directories: Vec::new(),
+            files: Vec::new(),
+            destinations: Vec::new(),
+            passphrase: Some(passphrase.clone()),
+        })?;
+        let catalog = SqliteCatalog::open(&result.db_path)?;
+        assert!(catalog.passphrase_required()?);
+
+        // The words alone derive another key; with the passphrase, this one.
+        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &result.recovery_phrase)?;
+        let sealed = catalog.sealed_naming_key()?;
+        assert!(unseal_naming_key(&sealed, &mnemonic.clone().into()).is_err());
+        let recovery = RecoveryKey {
+            mnemonic,
+            passphrase: Some(passphrase),
+        };
+        assert!(unseal_naming_key(&sealed, &recovery).is_ok());
+        assert_eq!(
+            catalog.public_key()?.to_bytes(),
+            recovery.keypair()?.1.to_bytes()
+        );
 
         Ok(())
     }
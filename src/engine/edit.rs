@@ -3,10 +3,23 @@
 //! Applies additions and removals, then re-establishes the same invariant
 //! `create` does: directories are collapsed to non-overlapping parents, and any
 //! configured file that falls under a configured directory is dropped.
-
-use crate::{db::sqlite::SqliteCatalog, engine::create::get_unique_dir_parents};
+//!
+//! It also adds recipients (DESIGN §6.3): another keyholder's public key, to
+//! which the naming key and every chunk key are re-wrapped with the backup's
+//! mnemonic. Later uploads wrap new chunk keys to them as well, so their own
+//! mnemonic restores the backup.
+
+use crate::{
+    db::sqlite::{RecipientKey, SqliteCatalog, WrappedChunkKey},
+    engine::create::get_unique_dir_parents,
+    utils::crypto::{
+        RecoveryKey, parse_public_key, rewrap_content_key, seal_naming_key,
+        unseal_naming_key_with_secret,
+    },
+};
 use anyhow::{Result, anyhow};
 use std::path::PathBuf;
+use x25519_dalek::{PublicKey, StaticSecret};
 
 pub struct EditBackupRequest {
     pub name: String,
@@ -17,18 +30,27 @@ pub struct EditBackupRequest {
     pub remove_directories: Vec<PathBuf>,
     pub remove_files: Vec<PathBuf>,
     pub remove_destinations: Vec<String>,
+    /// Public keys (in hex) to add as recipients.
+    pub add_recipients: Vec<String>,
+    /// The backup's recovery mnemonic; needed only to add recipients.
+    pub mnemonic: Option<RecoveryKey>,
 }
 
 pub struct EditBackupResult {
     pub directories: Vec<PathBuf>,
     pub files: Vec<PathBuf>,
     pub destinations: Vec<String>,
+    /// Every additional recipient, in hex.
+    pub recipients: Vec<String>,
+    /// Chunk keys wrapped to the recipients just added.
+    pub wrapped_keys: usize,
 }
 
 /// Apply edits to a backup's configuration and return the resulting sets.
 ///
 /// # Errors
-/// Returns an error if the backup database is missing or cannot be updated.
+/// Returns an error if the backup database is missing or cannot be updated, or
+/// if recipients are added with a missing or wrong mnemonic.
 pub fn edit(request: EditBackupRequest) -> Result<EditBackupResult> {
     let db_path = request.config_dir.join(format!("{}.db", request.name));
 
@@ -41,6 +63,17 @@ pub fn edit(request: EditBackupRequest) -> Result<EditBackupResult> {
 
     let catalog = SqliteCatalog::open(&db_path)?;
 
+    // Recipients first: a wrong mnemonic fails before anything else changes.
+    let wrapped_keys = if request.add_recipients.is_empty() {
+        0
+    } else {
+        let mnemonic = request
+            .mnemonic
+            .as_ref()
+            .ok_or_else(|| anyhow!("Adding a recipient needs the backup's recovery mnemonic"))?;
+        add_recipients(&catalog, &request.name, mnemonic, &request.add_recipients)?
+    };
+
     // Directories: (existing ∪ added) \ removed, then collapse to unique parents.
     let directories = get_unique_dir_parents(merge(
         catalog.configured_directories()?,
@@ -73,9 +106,87 @@ pub fn edit(request: EditBackupRequest) -> Result<EditBackupResult> {
         directories,
         files,
         destinations,
+        recipients: catalog.recipients()?,
+        wrapped_keys,
     })
 }
 
+/// Seal the naming key to each recipient and re-wrap to them every chunk key
+/// they lack; returns the chunk keys wrapped. Adding a recipient again only
+/// wraps the chunks stored since without their key (e.g. by an upload that
+/// was already running), so it is the way to top them up.
+fn add_recipients(
+    catalog: &SqliteCatalog,
+    name: &str,
+    mnemonic: &RecoveryKey,
+    recipients: &[String],
+) -> Result<usize> {
+    let recipients = recipients
+        .iter()
+        .map(|hex_key| parse_public_key(hex_key))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (private_key, public_key) = mnemonic.keypair()?;
+    if public_key != catalog.public_key()? {
+        return Err(anyhow!(
+            "Incorrect mnemonic: it does not unlock backup \"{name}\""
+        ));
+    }
+    let naming_key = unseal_naming_key_with_secret(&catalog.sealed_naming_key()?, &private_key)?;
+
+    let mut wrapped_keys = 0;
+    for recipient in recipients {
+        let recipient_hex = hex::encode(recipient.as_bytes());
+        if recipient == public_key {
+            return Err(anyhow!(
+                "{recipient_hex} is the backup's own public key, not another recipient"
+            ));
+        }
+
+        let chunk_keys = rewrap_chunk_keys(
+            catalog.chunks_without_key(&recipient_hex)?,
+            &private_key,
+            &recipient,
+        )?;
+        catalog.add_recipient(
+            &recipient_hex,
+            &seal_naming_key(&naming_key, &recipient)?,
+            &chunk_keys,
+        )?;
+        wrapped_keys += chunk_keys.len();
+    }
+    Ok(wrapped_keys)
+}
+
+/// Re-wrap chunk keys recorded for the backup public key to `recipient`.
+pub(crate) fn rewrap_chunk_keys(
+    chunks: Vec<WrappedChunkKey>,
+    private_key: &StaticSecret,
+    recipient: &PublicKey,
+) -> Result<Vec<(String, RecipientKey)>> {
+    let recipient_hex = hex::encode(recipient.as_bytes());
+    chunks
+        .into_iter()
+        .map(|(hash, wrapped_key, ephemeral_public_key)| {
+            let key = rewrap_content_key(
+                &wrapped_key,
+                &ephemeral_public_key,
+                private_key,
+                recipient,
+                &hash,
+            )?;
+            Ok((
+                hash,
+                RecipientKey {
+                    recipient: recipient_hex.clone(),
+                    wrapped_key: key.wrapped_key,
+                    ephemeral_public_key: key.ephemeral_public_key,
+                },
+            ))
+        })
+        .collect()
+}
+
 /// Combine `existing` with `add`, drop anything in `remove`, and de-duplicate
 /// while preserving a stable (sorted) order.
 fn merge(existing: Vec<PathBuf>, add: Vec<PathBuf>, remove: &[PathBuf]) -> Vec<PathBuf> {
@@ -106,7 +217,11 @@ fn merge_strings(existing: Vec<String>, add: Vec<String>, remove: &[String]) ->
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::engine::create::{CreateBackupRequest, create};
+    use crate::{
+        engine::create::{CreateBackupRequest, create},
+        utils::crypto::content_keypair,
+    };
+    use bip39::{Language, Mnemonic};
 
     fn setup(dirs: &[&str], files: &[&str]) -> Result<(tempfile::TempDir, String)> {
         let temp_dir = tempfile::tempdir()?;
@@ -118,6 +233,7 @@ mod tests {
             directories: dirs.iter().map(PathBuf::from).collect(),
             files: files.iter().map(PathBuf::from).collect(),
             destinations: Vec::new(),
+            passphrase: None,
         })?;
 
         Ok((temp_dir, name))
@@ -140,6 +256,8 @@ mod tests {
             remove_directories: rm_dirs.iter().map(PathBuf::from).collect(),
             remove_files: rm_files.iter().map(PathBuf::from).collect(),
             remove_destinations: Vec::new(),
+            add_recipients: Vec::new(),
+            mnemonic: None,
         }
     }
 
@@ -206,6 +324,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn adding_a_recipient_needs_the_backups_own_mnemonic() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let created = create(CreateBackupRequest {
+            name: "demo".to_string(),
+            config_dir: temp_dir.path().to_path_buf(),
+            directories: Vec::new(),
+            files: Vec::new(),
+            destinations: Vec::new(),
+            passphrase: None,
+        })?;
+        let own = Mnemonic::parse_in_normalized(Language::English, &created.recovery_phrase)?;
+        let other = Mnemonic::generate_in(Language::English, 12)?;
+        let other_key = hex::encode(content_keypair(&other)?.1.as_bytes());
+        let add = |recipient: &str, mnemonic: Option<Mnemonic>| {
+            let mut req = request(temp_dir.path(), "demo", &[], &[], &[], &[]);
+            req.add_recipients = vec![recipient.to_string()];
+            req.mnemonic = mnemonic.map(RecoveryKey::from);
+            edit(req)
+        };
+
+        assert!(add(&other_key, None).is_err());
+        assert!(add(&other_key, Some(other.clone())).is_err());
+        assert!(add(&created.public_key, Some(own.clone())).is_err());
+        let catalog = SqliteCatalog::open(&created.db_path)?;
+        assert!(catalog.recipients()?.is_empty());
+
+        let result = add(&other_key, Some(own.clone()))?;
+        assert_eq!(result.recipients, vec![other_key.clone()]);
+        // Their sealed naming key opens with their mnemonic.
+        let sealed = catalog
+            .recipient_sealed_naming_key(&other_key)?
+            .ok_or_else(|| anyhow!("recipient not recorded"))?;
+        let (other_secret, _) = content_keypair(&other)?;
+        let (own_secret, _) = content_keypair(&own)?;
+        assert_eq!(
+            *unseal_naming_key_with_secret(&sealed, &other_secret)?,
+            *unseal_naming_key_with_secret(&catalog.sealed_naming_key()?, &own_secret)?
+        );
+
+        // Adding them again is a no-op top-up.
+        assert_eq!(add(&other_key, Some(own))?.recipients, vec![other_key]);
+        Ok(())
+    }
+
     #[test]
     fn editing_missing_backup_errors() -> Result<()> {
         let temp_dir = tempfile::tempdir()?;
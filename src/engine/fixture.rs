@@ -0,0 +1,200 @@
+//! A backup for the engine tests to work on: "t", over one source directory,
+//! with filesystem destinations (`dest0`, `dest1`, …) and possibly others.
+
+use crate::{
+    db::sqlite::SqliteCatalog,
+    engine::{
+        create::{CreateBackupRequest, create},
+        restore::{RestoreRequest, restore},
+        run::{IgnoreRules, NamingKey, RunBackupRequest, RunBackupResult, run},
+        upload::{UploadRequest, UploadResult, upload},
+        view::ViewTarget,
+        wkey,
+    },
+    storage::{Storage, local::LocalStore},
+    utils::crypto::{content_key_aad, decrypt, open_content},
+};
+use anyhow::{Result, anyhow};
+use bip39::{Language, Mnemonic};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use zeroize::Zeroizing;
+
+pub(crate) struct Fixture {
+    pub(crate) tmp: tempfile::TempDir,
+    pub(crate) cfg: PathBuf,
+    pub(crate) src: PathBuf,
+    pub(crate) dests: Vec<PathBuf>,
+    pub(crate) mnemonic: Mnemonic,
+    pub(crate) naming_key: NamingKey,
+}
+
+impl Fixture {
+    /// Create the backup over `files` (paths relative to the source dir) with
+    /// `dest_count` filesystem destinations, then the `extra` ones (e.g.
+    /// `s3://…`), which are not in [`Fixture::dests`]. Nothing is backed up.
+    pub(crate) fn create(
+        files: &[(&str, &[u8])],
+        dest_count: usize,
+        extra: &[String],
+    ) -> Result<Self> {
+        let tmp = tempfile::tempdir()?;
+        let cfg = tmp.path().join("cfg");
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&cfg)?;
+        fs::create_dir_all(&src)?;
+        for (name, contents) in files {
+            let path = src.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, contents)?;
+        }
+
+        let dests: Vec<PathBuf> = (0..dest_count)
+            .map(|index| tmp.path().join(format!("dest{index}")))
+            .collect();
+        let created = create(CreateBackupRequest {
+            name: "t".to_string(),
+            config_dir: cfg.clone(),
+            directories: vec![src.clone()],
+            files: Vec::new(),
+            destinations: dests
+                .iter()
+                .map(|dest| dest.to_string_lossy().into_owned())
+                .chain(extra.iter().cloned())
+                .collect(),
+            passphrase: None,
+        })?;
+        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &created.recovery_phrase)?;
+        let naming_key: NamingKey =
+            Arc::new(wkey::load_naming_key(&cfg, "t")?.ok_or_else(|| anyhow!("missing wkey"))?);
+
+        Ok(Self {
+            tmp,
+            cfg,
+            src,
+            dests,
+            mnemonic,
+            naming_key,
+        })
+    }
+
+    pub(crate) fn catalog(&self) -> Result<SqliteCatalog> {
+        SqliteCatalog::open(&self.cfg.join("t.db"))
+    }
+
+    /// Filesystem destination `index`.
+    pub(crate) fn store(&self, index: usize) -> Result<LocalStore> {
+        self.dests
+            .get(index)
+            .map(LocalStore::new)
+            .ok_or_else(|| anyhow!("no destination {index}"))
+    }
+
+    /// Filesystem destination `index` as configured.
+    pub(crate) fn destination(&self, index: usize) -> Result<String> {
+        self.dests
+            .get(index)
+            .map(|dest| dest.to_string_lossy().into_owned())
+            .ok_or_else(|| anyhow!("no destination {index}"))
+    }
+
+    /// Snapshot the source dir.
+    pub(crate) async fn run(&self) -> Result<RunBackupResult> {
+        self.run_with(false).await
+    }
+
+    /// Snapshot the source dir, reading every file again if `rehash`.
+    pub(crate) async fn run_with(&self, rehash: bool) -> Result<RunBackupResult> {
+        run(RunBackupRequest {
+            name: "t".to_string(),
+            config_dir: self.cfg.clone(),
+            ignore_rules: IgnoreRules::backupignore_only(),
+            dry_run: false,
+            rehash,
+            progress: None,
+        })
+        .await
+    }
+
+    /// Upload what the last snapshot left pending.
+    pub(crate) async fn upload(&self) -> Result<UploadResult> {
+        upload(UploadRequest {
+            name: "t".to_string(),
+            config_dir: self.cfg.clone(),
+            naming_key: self.naming_key.clone(),
+            progress: None,
+        })
+        .await
+    }
+
+    /// Snapshot the source dir and upload what changed.
+    pub(crate) async fn back_up(&self) -> Result<UploadResult> {
+        self.run().await?;
+        self.upload().await
+    }
+
+    /// Decrypt the chunk `id` out of its pack on destination `index`.
+    pub(crate) async fn read_chunk(&self, index: usize, id: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let catalog = self.catalog()?;
+        let (wrapped, eph) = catalog
+            .wrapped_chunk_key(id)?
+            .ok_or_else(|| anyhow!("no wrapped key for {id}"))?;
+        let key_vec = decrypt(&wrapped, &eph, &self.mnemonic, &content_key_aad(id))?;
+        let key: [u8; 32] = key_vec
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("bad key length"))?;
+        let location = catalog
+            .chunk_location(id)?
+            .ok_or_else(|| anyhow!("chunk {id} is not in a pack"))?;
+        let blob = self
+            .store(index)?
+            .get_range(&location.pack_id, location.offset, location.length)
+            .await?;
+        open_content(&blob, id, &key)
+    }
+
+    /// Restore `target` (everything if `None`) of the latest version into
+    /// `into`, with the backup's mnemonic.
+    pub(crate) fn restore_request(
+        &self,
+        target: Option<ViewTarget>,
+        into: &Path,
+    ) -> RestoreRequest {
+        RestoreRequest {
+            name: "t".to_string(),
+            config_dir: self.cfg.clone(),
+            target,
+            version: None,
+            into: Some(into.to_path_buf()),
+            mnemonic: self.mnemonic.clone().into(),
+        }
+    }
+
+    /// Restore the latest version into `into` with `mnemonic`, failing if any
+    /// file cannot be restored.
+    pub(crate) async fn restore_into(&self, into: &Path, mnemonic: &Mnemonic) -> Result<()> {
+        let report = restore(RestoreRequest {
+            mnemonic: mnemonic.clone().into(),
+            ..self.restore_request(None, into)
+        })
+        .await?
+        .ok_or_else(|| anyhow!("no completed version"))?;
+        if report.failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("{} file(s) failed", report.failed.len()))
+        }
+    }
+
+    /// Where `relative` (under the source dir) lands when restored into `into`.
+    pub(crate) fn restored(&self, into: &Path, relative: &str) -> PathBuf {
+        into.join(self.src.strip_prefix("/").unwrap_or(&self.src))
+            .join(relative)
+    }
+}
@@ -0,0 +1,402 @@
+//! Reclaim objects in the destinations that the catalog does not reference.
+//!
+//! An interrupted `upload` or `verify --repair` writes a pack before recording
+//! it, and a failed removal during `prune` leaves one behind after the catalog
+//! forgot it: either way the destination keeps an object nothing points at.
+//! `gc` lists every destination, diffs the listing against the packs and loose
+//! chunks the catalog records, and removes (or quarantines) the unreferenced
+//! objects older than a grace period. The grace period protects packs an upload
+//! running concurrently has written but not yet recorded; the catalog is read
+//! after the listing for the same reason, so anything recorded by then is kept.
+//!
+//! Objects carry no backup name, so a destination that also holds another
+//! backup (its sealed catalog is there, §7) is not collected at all: that
+//! backup's packs would look unreferenced from this catalog. `run` replicates
+//! the catalog before any `upload` stores a pack, so a backup with packs in a
+//! destination has a catalog there too.
+
+use crate::{
+    db::sqlite::SqliteCatalog,
+    engine::run::scan_worker_count,
+    storage::{NamedStore, ObjectInfo, Storage, open_named},
+};
+use anyhow::{Result, anyhow};
+use futures::stream::{self, StreamExt};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+pub struct GcRequest {
+    pub name: String,
+    pub config_dir: PathBuf,
+    /// Unreferenced objects younger than this are left alone.
+    pub grace: Duration,
+    /// Move orphans to the destination's `quarantine/` instead of deleting them.
+    pub quarantine: bool,
+    /// Report what would be collected without changing anything.
+    pub dry_run: bool,
+}
+
+/// An unreferenced object in one destination.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Orphan {
+    pub destination: String,
+    pub key: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Orphans past the grace period: removed or quarantined, or that would be
+    /// on a dry run.
+    pub collected: Vec<Orphan>,
+    /// Orphans still within the grace period, or of unknown age, left alone.
+    pub recent: usize,
+    /// Bytes freed (or that would be) from the destinations' keyspace.
+    pub reclaimed_bytes: u64,
+    /// Destinations that could not be listed and orphans that could not be
+    /// collected, as `destination: reason` or `destination: key: reason`.
+    pub failures: Vec<String>,
+}
+
+/// Collect the unreferenced objects of a backup's destinations.
+///
+/// # Errors
+/// Returns an error if the backup is missing or the catalog or a destination
+/// cannot be opened. A destination that cannot be listed, or an object that
+/// cannot be collected, is reported in [`GcReport::failures`] instead.
+pub async fn gc(request: GcRequest) -> Result<GcReport> {
+    let db_file = request.config_dir.join(format!("{}.db", request.name));
+    if !db_file.exists() {
+        return Err(anyhow!(
+            "No backup named \"{}\" found. Create a new backup first.",
+            request.name
+        ));
+    }
+    let catalog = SqliteCatalog::open(&db_file)?;
+    let stores = open_named(&catalog.configured_destinations()?)?;
+
+    let mut report = GcReport::default();
+    let listings = list_all(&stores, &request.name, &mut report).await;
+
+    let referenced: HashSet<String> = catalog
+        .all_pack_ids()?
+        .into_iter()
+        .chain(catalog.loose_chunk_ids()?)
+        .collect();
+    let now = SystemTime::now();
+    for (named, objects) in listings {
+        for object in objects {
+            if referenced.contains(&object.key) {
+                continue;
+            }
+            if !past_grace(&object, now, request.grace) {
+                report.recent += 1;
+                continue;
+            }
+            report.collected.push(Orphan {
+                destination: named.destination.clone(),
+                key: object.key,
+                size: object.size,
+            });
+        }
+    }
+    report
+        .collected
+        .sort_by(|a, b| (&a.destination, &a.key).cmp(&(&b.destination, &b.key)));
+
+    if request.dry_run {
+        report.reclaimed_bytes = report.collected.iter().map(|orphan| orphan.size).sum();
+    } else {
+        collect(&stores, request.quarantine, &mut report).await;
+    }
+    report.failures.sort();
+    Ok(report)
+}
+
+/// List every destination, recording the ones that cannot be listed, or that
+/// other backups share, as failures so the others are still collected.
+async fn list_all<'a>(
+    stores: &'a [NamedStore],
+    name: &str,
+    report: &mut GcReport,
+) -> Vec<(&'a NamedStore, Vec<ObjectInfo>)> {
+    let listings: Vec<_> = stream::iter(stores)
+        .map(|named| async move {
+            let listing = async {
+                let others = other_backups(named.store.as_ref(), name).await?;
+                if !others.is_empty() {
+                    return Err(anyhow!(
+                        "shared with backup(s) {}; not collected",
+                        others.join(", ")
+                    ));
+                }
+                named
+                    .store
+                    .list("")
+                    .await
+                    .map_err(|err| anyhow!("cannot list: {err:#}"))
+            }
+            .await;
+            (named, listing)
+        })
+        .buffer_unordered(scan_worker_count())
+        .collect()
+        .await;
+
+    let mut listed = Vec::new();
+    for (named, listing) in listings {
+        match listing {
+            Ok(objects) => listed.push((named, objects)),
+            Err(err) => report
+                .failures
+                .push(format!("{}: {err:#}", named.destination)),
+        }
+    }
+    listed
+}
+
+/// The names of the other backups with a sealed catalog in `store`, sorted.
+async fn other_backups(store: &dyn Storage, name: &str) -> Result<Vec<String>> {
+    let mut others: Vec<String> = store
+        .list_catalogs()
+        .await
+        .map_err(|err| anyhow!("cannot list catalogs: {err:#}"))?
+        .into_iter()
+        .filter_map(|object| {
+            let (backup, generation) = object.key.rsplit_once('.')?;
+            generation.parse::<u64>().ok()?;
+            (backup != name).then(|| backup.to_string())
+        })
+        .collect();
+    others.sort();
+    others.dedup();
+    Ok(others)
+}
+
+/// Whether an object is old enough to collect. One whose age the backend does
+/// not report only goes with no grace period at all.
+fn past_grace(object: &ObjectInfo, now: SystemTime, grace: Duration) -> bool {
+    grace.is_zero()
+        || object
+            .modified
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= grace)
+}
+
+/// Remove or quarantine the collected orphans, dropping the ones that fail from
+/// [`GcReport::collected`].
+async fn collect(stores: &[NamedStore], quarantine: bool, report: &mut GcReport) {
+    let orphans = std::mem::take(&mut report.collected);
+    let outcomes: Vec<Result<Orphan, String>> = stream::iter(orphans)
+        .map(|orphan| async move {
+            let Some(named) = stores
+                .iter()
+                .find(|named| named.destination == orphan.destination)
+            else {
+                return Err(format!("{}: unknown destination", orphan.destination));
+            };
+            let outcome = if quarantine {
+                named.store.quarantine(&orphan.key).await
+            } else {
+                named.store.remove(&orphan.key).await
+            };
+            outcome
+                .map(|()| orphan.clone())
+                .map_err(|err| format!("{}: {}: {err:#}", orphan.destination, orphan.key))
+        })
+        .buffer_unordered(scan_worker_count())
+        .collect()
+        .await;
+
+    for outcome in outcomes {
+        match outcome {
+            Ok(orphan) => {
+                report.reclaimed_bytes += orphan.size;
+                report.collected.push(orphan);
+            }
+            Err(failure) => report.failures.push(failure),
+        }
+    }
+    report
+        .collected
+        .sort_by(|a, b| (&a.destination, &a.key).cmp(&(&b.destination, &b.key)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::{
+            create::{CreateBackupRequest, create},
+            fixture::Fixture,
+            run::{IgnoreRules, RunBackupRequest, run},
+            upload::{UploadRequest, upload},
+            wkey,
+        },
+        storage::Storage,
+    };
+    use std::{fs, sync::Arc};
+
+    const ORPHAN: &str = "abcd0123456789";
+
+    /// A backup with one version uploaded to its destination, plus an orphan
+    /// object there, as an interrupted upload would leave.
+    async fn backup_with_orphan() -> Result<Fixture> {
+        let fx = Fixture::create(&[("file.txt", b"backed up")], 1, &[])?;
+        fx.back_up().await?;
+        fx.store(0)?
+            .put(ORPHAN, b"left by an interrupted run")
+            .await?;
+        Ok(fx)
+    }
+
+    fn request(
+        cfg: &std::path::Path,
+        grace: Duration,
+        quarantine: bool,
+        dry_run: bool,
+    ) -> GcRequest {
+        GcRequest {
+            name: "t".to_string(),
+            config_dir: cfg.to_path_buf(),
+            grace,
+            quarantine,
+            dry_run,
+        }
+    }
+
+    #[tokio::test]
+    async fn gc_removes_old_orphans_and_keeps_referenced_packs() -> Result<()> {
+        let fx = backup_with_orphan().await?;
+        let store = fx.store(0)?;
+        let packs = fx.catalog()?.all_pack_ids()?;
+        assert_eq!(packs.len(), 1);
+
+        // Within the grace period, the orphan is left alone.
+        let report = gc(request(&fx.cfg, Duration::from_hours(1), false, false)).await?;
+        assert!(report.collected.is_empty());
+        assert_eq!(report.recent, 1);
+        assert!(store.exists(ORPHAN).await?);
+
+        // A dry run reports it without removing it.
+        let report = gc(request(&fx.cfg, Duration::ZERO, false, true)).await?;
+        assert_eq!(
+            report
+                .collected
+                .iter()
+                .map(|orphan| orphan.key.as_str())
+                .collect::<Vec<_>>(),
+            vec![ORPHAN]
+        );
+        assert_eq!(report.reclaimed_bytes, 26);
+        assert!(store.exists(ORPHAN).await?);
+
+        let report = gc(request(&fx.cfg, Duration::ZERO, false, false)).await?;
+        assert_eq!(report.collected.len(), 1);
+        assert_eq!(report.reclaimed_bytes, 26);
+        assert!(report.failures.is_empty());
+        assert!(!store.exists(ORPHAN).await?);
+        assert!(store.exists(&packs.concat()).await?);
+        // The sealed catalogs are outside the keyspace gc collects from.
+        assert!(!store.list_catalogs().await?.is_empty());
+
+        // Nothing is left to collect.
+        let report = gc(request(&fx.cfg, Duration::ZERO, false, false)).await?;
+        assert!(report.collected.is_empty());
+        assert_eq!(report.recent, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gc_can_quarantine_orphans_instead() -> Result<()> {
+        let fx = backup_with_orphan().await?;
+        let store = fx.store(0)?;
+
+        let report = gc(request(&fx.cfg, Duration::ZERO, true, false)).await?;
+        assert_eq!(report.collected.len(), 1);
+        assert!(!store.exists(ORPHAN).await?);
+        assert_eq!(
+            fs::read(fx.tmp.path().join("dest0/quarantine").join(ORPHAN))?,
+            b"left by an interrupted run"
+        );
+        // Quarantined objects are out of the keyspace, so gc does not see them.
+        let report = gc(request(&fx.cfg, Duration::ZERO, true, false)).await?;
+        assert!(report.collected.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gc_leaves_a_destination_shared_with_another_backup_alone() -> Result<()> {
+        let fx = backup_with_orphan().await?;
+        let store = fx.store(0)?;
+        // Backup "u" stores its own pack in the same destination.
+        let other_src = fx.tmp.path().join("other");
+        fs::create_dir_all(&other_src)?;
+        fs::write(other_src.join("other.txt"), b"not t's data")?;
+        create(CreateBackupRequest {
+            name: "u".to_string(),
+            config_dir: fx.cfg.clone(),
+            directories: vec![other_src],
+            files: Vec::new(),
+            destinations: vec![fx.destination(0)?],
+            passphrase: None,
+        })?;
+        run(RunBackupRequest {
+            name: "u".to_string(),
+            config_dir: fx.cfg.clone(),
+            ignore_rules: IgnoreRules::backupignore_only(),
+            dry_run: false,
+            rehash: false,
+            progress: None,
+        })
+        .await?;
+        let naming_key =
+            wkey::load_naming_key(&fx.cfg, "u")?.ok_or_else(|| anyhow!("missing wkey"))?;
+        upload(UploadRequest {
+            name: "u".to_string(),
+            config_dir: fx.cfg.clone(),
+            naming_key: Arc::new(naming_key),
+            progress: None,
+        })
+        .await?;
+        let other_packs = SqliteCatalog::open(&fx.cfg.join("u.db"))?.all_pack_ids()?;
+        assert_eq!(other_packs.len(), 1);
+
+        let report = gc(request(&fx.cfg, Duration::ZERO, false, false)).await?;
+        assert!(report.collected.is_empty());
+        assert_eq!(report.failures.len(), 1);
+        assert!(
+            report
+                .failures
+                .iter()
+                .any(|failure| failure.contains("shared with backup(s) u"))
+        );
+        assert!(store.exists(&other_packs.concat()).await?);
+        assert!(store.exists(ORPHAN).await?);
+
+        // Collecting as "u" is refused the same way.
+        let mut as_other = request(&fx.cfg, Duration::ZERO, false, false);
+        as_other.name = "u".to_string();
+        assert!(gc(as_other).await?.collected.is_empty());
+        assert!(
+            store
+                .exists(&fx.catalog()?.all_pack_ids()?.concat())
+                .await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gc_of_a_missing_backup_fails() {
+        let request = request(
+            std::path::Path::new("/nonexistent"),
+            Duration::ZERO,
+            false,
+            false,
+        );
+        assert!(gc(request).await.is_err());
+    }
+}
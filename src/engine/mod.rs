@@ -1,7 +1,17 @@
 pub mod create;
 pub mod edit;
+#[cfg(test)]
+pub(crate) mod fixture;
+pub mod gc;
+pub mod prune;
+pub mod recover;
+pub mod rekey;
+pub mod replicate;
+pub mod restore;
 pub mod run;
 pub mod show;
+pub mod split_key;
+pub mod upload;
 pub mod verify;
 pub mod view;
 pub mod wkey;
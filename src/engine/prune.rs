@@ -0,0 +1,426 @@
+//! Drop old versions under a retention policy and reclaim what they alone held.
+//!
+//! The policy picks the completed versions to keep (DESIGN §8): the newest
+//! `keep_last`, plus the newest version of each of the last `keep_daily` days,
+//! `keep_weekly` ISO weeks and `keep_monthly` months that have one (UTC). The
+//! newest completed version is always kept, and versions still being recorded
+//! are never touched. The catalog drops the rest in one transaction, then the
+//! packs and loose chunks no remaining version references are removed from
+//! every destination. The catalog goes first, so a crash or a failed removal
+//! leaves an unreferenced object behind for `gc`, never a catalog entry for a
+//! missing one. Pruning holds the catalog lock `upload` holds, so it never
+//! drops chunks an upload has stored or deduplicated against but not yet
+//! referenced from the entries it commits.
+
+use crate::{
+    db::sqlite::{PrunedVersions, SqliteCatalog},
+    engine::run::scan_worker_count,
+    storage::{NamedStore, open_named},
+};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike};
+use futures::stream::{self, StreamExt};
+use std::{collections::HashSet, path::PathBuf};
+
+/// How many versions to keep, per rule. A version kept by any rule stays.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+}
+
+impl RetentionPolicy {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub struct PruneRequest {
+    pub name: String,
+    pub config_dir: PathBuf,
+    pub policy: RetentionPolicy,
+    /// Report what would be removed without changing anything.
+    pub dry_run: bool,
+}
+
+pub struct PruneReport {
+    pub kept: Vec<i64>,
+    pub removed: Vec<i64>,
+    /// What the catalog dropped (or would drop, on a dry run).
+    pub pruned: PrunedVersions,
+    /// Objects removed from destinations, counted once per destination.
+    pub removed_objects: usize,
+    /// Objects that could not be removed, as `destination: key: reason`;
+    /// unreferenced now, they are left behind.
+    pub failed_removals: Vec<String>,
+}
+
+/// Apply a retention policy to a backup.
+///
+/// # Errors
+/// Returns an error if the policy keeps nothing, the backup is missing, an
+/// upload is running, or the catalog or a destination cannot be opened or
+/// updated.
+pub async fn prune(request: PruneRequest) -> Result<PruneReport> {
+    if request.policy.is_empty() {
+        return Err(anyhow!(
+            "no retention policy: pass at least one of --keep-last, --keep-daily, \
+             --keep-weekly, --keep-monthly"
+        ));
+    }
+
+    let db_file = request.config_dir.join(format!("{}.db", request.name));
+    if !db_file.exists() {
+        return Err(anyhow!(
+            "No backup named \"{}\" found. Create a new backup first.",
+            request.name
+        ));
+    }
+    let catalog = SqliteCatalog::open(&db_file)?;
+    // A dry run only reads: its transaction is rolled back.
+    let _lock = if request.dry_run {
+        None
+    } else {
+        Some(catalog.lock()?)
+    };
+
+    let (kept, removed) = select_versions(&catalog.completed_versions()?, request.policy);
+    let pruned = if removed.is_empty() {
+        PrunedVersions::default()
+    } else {
+        let catalog = catalog.clone();
+        let removed = removed.clone();
+        let dry_run = request.dry_run;
+        tokio::task::spawn_blocking(move || catalog.prune_versions(&removed, dry_run)).await??
+    };
+
+    let mut report = PruneReport {
+        kept,
+        removed,
+        pruned,
+        removed_objects: 0,
+        failed_removals: Vec::new(),
+    };
+    if request.dry_run {
+        return Ok(report);
+    }
+
+    let stores = open_named(&catalog.configured_destinations()?)?;
+    remove_objects(&stores, &mut report).await;
+    Ok(report)
+}
+
+/// Remove the packs and loose chunks the catalog no longer references from
+/// every destination.
+async fn remove_objects(stores: &[NamedStore], report: &mut PruneReport) {
+    let keys = report
+        .pruned
+        .packs
+        .iter()
+        .chain(&report.pruned.loose_chunks);
+    let removals = keys.flat_map(|key| stores.iter().map(move |named| (named, key)));
+
+    let outcomes: Vec<Result<(), String>> = stream::iter(removals)
+        .map(|(named, key)| async move {
+            named
+                .store
+                .remove(key)
+                .await
+                .map_err(|err| format!("{}: {key}: {err:#}", named.destination))
+        })
+        .buffer_unordered(scan_worker_count())
+        .collect()
+        .await;
+
+    for outcome in outcomes {
+        match outcome {
+            Ok(()) => report.removed_objects += 1,
+            Err(failure) => report.failed_removals.push(failure),
+        }
+    }
+    report.failed_removals.sort();
+}
+
+/// The calendar period (year and day, ISO week or month, UTC) a unix
+/// timestamp falls in.
+type PeriodOf = fn(i64) -> Option<(i32, u32)>;
+
+/// Split completed `(version, timestamp)` pairs into the versions `policy`
+/// keeps and the ones it drops, each in ascending order.
+#[must_use]
+pub fn select_versions(versions: &[(i64, i64)], policy: RetentionPolicy) -> (Vec<i64>, Vec<i64>) {
+    let mut newest_first = versions.to_vec();
+    newest_first.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut keep: HashSet<i64> = newest_first
+        .iter()
+        .take(policy.keep_last.max(1))
+        .map(|(version, _)| *version)
+        .collect();
+
+    let buckets: [(usize, PeriodOf); 3] = [
+        (policy.keep_daily, |timestamp| {
+            DateTime::from_timestamp(timestamp, 0).map(|at| (at.year(), at.ordinal()))
+        }),
+        (policy.keep_weekly, |timestamp| {
+            DateTime::from_timestamp(timestamp, 0).map(|at| {
+                let week = at.iso_week();
+                (week.year(), week.week())
+            })
+        }),
+        (policy.keep_monthly, |timestamp| {
+            DateTime::from_timestamp(timestamp, 0).map(|at| (at.year(), at.month()))
+        }),
+    ];
+    for (count, bucket_of) in buckets {
+        let mut seen = HashSet::new();
+        for (version, timestamp) in &newest_first {
+            if seen.len() == count {
+                break;
+            }
+            // The newest version of each period stands for it.
+            if seen.insert(bucket_of(*timestamp)) {
+                keep.insert(*version);
+            }
+        }
+    }
+
+    let (mut kept, mut removed): (Vec<i64>, Vec<i64>) = versions
+        .iter()
+        .map(|(version, _)| *version)
+        .partition(|version| keep.contains(version));
+    kept.sort_unstable();
+    removed.sort_unstable();
+    (kept, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::{
+            fixture::Fixture,
+            run::{ProgressCallback, RunProgress},
+            upload::{UploadRequest, upload},
+        },
+        storage::Storage,
+    };
+    use std::{
+        fs,
+        sync::{Arc, Mutex, PoisonError, mpsc},
+    };
+
+    const DAY: i64 = 24 * 60 * 60;
+    /// 2024-01-01 00:00:00 UTC, a Monday.
+    const START: i64 = 1_704_067_200;
+
+    fn policy(
+        keep_last: usize,
+        keep_daily: usize,
+        keep_weekly: usize,
+        keep_monthly: usize,
+    ) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last,
+            keep_daily,
+            keep_weekly,
+            keep_monthly,
+        }
+    }
+
+    #[test]
+    fn keep_last_keeps_the_newest() {
+        let versions: Vec<(i64, i64)> = (1..=5).map(|v| (v, START + v * 60)).collect();
+        let (kept, removed) = select_versions(&versions, policy(2, 0, 0, 0));
+        assert_eq!(kept, vec![4, 5]);
+        assert_eq!(removed, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn daily_keeps_the_newest_of_each_day() {
+        // Two versions a day for four days: 1,2 on day 0 … 7,8 on day 3.
+        let versions: Vec<(i64, i64)> = (0..8)
+            .map(|i| (i + 1, START + (i / 2) * DAY + (i % 2) * 3600))
+            .collect();
+        let (kept, removed) = select_versions(&versions, policy(0, 3, 0, 0));
+        assert_eq!(kept, vec![4, 6, 8]);
+        assert_eq!(removed, vec![1, 2, 3, 5, 7]);
+    }
+
+    #[test]
+    fn weekly_and_monthly_combine_with_other_rules() {
+        // One version a day through January and February 2024.
+        let versions: Vec<(i64, i64)> = (0..60).map(|day| (day + 1, START + day * DAY)).collect();
+
+        let (kept, _) = select_versions(&versions, policy(0, 0, 2, 0));
+        // Sundays close ISO weeks; the newest week (Feb 26–29) is partial.
+        assert_eq!(kept, vec![56, 60]);
+
+        let (kept, _) = select_versions(&versions, policy(1, 0, 0, 2));
+        assert_eq!(kept, vec![31, 60]);
+    }
+
+    #[test]
+    fn newest_is_always_kept() {
+        let versions = [(1, START), (2, START + DAY)];
+        let (kept, removed) = select_versions(&versions, policy(0, 0, 0, 1));
+        assert_eq!(kept, vec![2]);
+        assert_eq!(removed, vec![1]);
+    }
+
+    fn request(fx: &Fixture, keep_last: usize, dry_run: bool) -> PruneRequest {
+        PruneRequest {
+            name: "t".to_string(),
+            config_dir: fx.cfg.clone(),
+            policy: policy(keep_last, 0, 0, 0),
+            dry_run,
+        }
+    }
+
+    #[tokio::test]
+    async fn prune_drops_versions_and_removes_their_packs() -> Result<()> {
+        let fx = Fixture::create(
+            &[
+                ("kept.txt", b"in every version"),
+                ("old.txt", b"only in the first version"),
+            ],
+            1,
+            &[],
+        )?;
+        fx.back_up().await?;
+        fs::remove_file(fx.src.join("old.txt"))?;
+        fx.back_up().await?;
+
+        let catalog = fx.catalog()?;
+        let store = fx.store(0)?;
+        let packs_before = catalog.all_pack_ids()?;
+        assert_eq!(packs_before.len(), 1);
+
+        // A dry run reports without changing anything.
+        let dry = prune(request(&fx, 1, true)).await?;
+        assert_eq!(dry.removed, vec![1]);
+        assert_eq!(dry.pruned.files, 1);
+        assert_eq!(dry.removed_objects, 0);
+        assert_eq!(catalog.completed_versions()?.len(), 2);
+        assert_eq!(catalog.count_rows("Files")?, 2);
+
+        let report = prune(request(&fx, 1, false)).await?;
+        assert_eq!(report.kept, vec![2]);
+        assert_eq!(report.removed, vec![1]);
+        // `old.txt`'s entry and content; `kept.txt` is seen by version 2.
+        assert_eq!(report.pruned.files, 1);
+        assert_eq!(report.pruned.chunks, 1);
+        assert!(report.failed_removals.is_empty());
+        assert_eq!(catalog.completed_versions()?.len(), 1);
+        assert_eq!(catalog.count_rows("Files")?, 1);
+        assert_eq!(
+            catalog
+                .restore_entries(2)?
+                .iter()
+                .filter(|entry| entry.path == fx.src.join("kept.txt"))
+                .count(),
+            1
+        );
+        // The one pack still holds `kept.txt`'s chunk, so it stays.
+        assert!(report.pruned.packs.is_empty());
+        for pack in &packs_before {
+            assert!(store.exists(pack).await?);
+        }
+
+        // Once nothing references it, the pack goes from the destination too.
+        fs::remove_file(fx.src.join("kept.txt"))?;
+        fx.back_up().await?;
+        let report = prune(request(&fx, 1, false)).await?;
+        assert_eq!(report.removed, vec![2]);
+        assert_eq!(report.pruned.packs, packs_before);
+        assert_eq!(report.removed_objects, 1);
+        for pack in &packs_before {
+            assert!(!store.exists(pack).await?);
+        }
+        assert_eq!(catalog.count_rows("Packs")?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn prune_is_refused_while_an_upload_runs() -> Result<()> {
+        let fx = Fixture::create(
+            &[
+                ("kept.txt", b"in every version"),
+                ("old.txt", b"only in the first version"),
+            ],
+            1,
+            &[],
+        )?;
+        fx.back_up().await?;
+        fs::remove_file(fx.src.join("old.txt"))?;
+        fx.back_up().await?;
+
+        // Snapshot a third version, then hold its upload once it is storing.
+        fs::write(fx.src.join("new.txt"), b"stored while pruning")?;
+        fx.run().await?;
+        let (reached, reached_rx) = mpsc::channel();
+        let (resume, resume_rx) = mpsc::channel::<()>();
+        let resume_rx = Mutex::new(resume_rx);
+        let progress: ProgressCallback = Arc::new(move |event| {
+            if matches!(event, RunProgress::StorePhaseStarted(_)) {
+                reached.send(()).ok();
+                resume_rx
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv()
+                    .ok();
+            }
+        });
+        let uploading = tokio::spawn(upload(UploadRequest {
+            name: "t".to_string(),
+            config_dir: fx.cfg.clone(),
+            naming_key: fx.naming_key.clone(),
+            progress: Some(progress),
+        }));
+        tokio::task::spawn_blocking(move || reached_rx.recv()).await??;
+
+        let during = prune(request(&fx, 1, false)).await;
+        resume.send(())?;
+        uploading.await??;
+        let err = during
+            .err()
+            .ok_or_else(|| anyhow!("prune ran during an upload"))?;
+        assert!(err.to_string().contains("busy"), "{err:#}");
+
+        // Once the upload is done, the new version's entries reference its
+        // chunks and pruning the older versions keeps them.
+        let report = prune(request(&fx, 1, false)).await?;
+        assert_eq!(report.kept, vec![3]);
+        assert_eq!(report.removed, vec![1, 2]);
+        assert!(report.failed_removals.is_empty());
+        let catalog = fx.catalog()?;
+        let mut paths: Vec<PathBuf> = catalog
+            .restore_entries(3)?
+            .into_iter()
+            .filter(|entry| entry.hash.is_some())
+            .map(|entry| entry.path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec![fx.src.join("kept.txt"), fx.src.join("new.txt")]);
+        let store = fx.store(0)?;
+        for pack in catalog.all_pack_ids()? {
+            assert!(store.exists(&pack).await?);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn prune_without_a_policy_is_refused() {
+        let request = PruneRequest {
+            name: "t".to_string(),
+            config_dir: PathBuf::from("/nonexistent"),
+            policy: RetentionPolicy::default(),
+            dry_run: false,
+        };
+        assert!(prune(request).await.is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Rebuild a backup's local state from a destination (DESIGN §7).
+//!
+//! A host that lost `~/.backup` still has the mnemonic and the destinations,
+//! which hold the catalog sealed by [`replicate`](crate::engine::replicate).
+//! `recover` finds the newest generation of the backup's catalog there, opens
+//! it with the content key derived from the mnemonic, checks that the catalog
+//! belongs to that key, and writes it back as `<name>.db` along with a fresh
+//! `<name>.wkey` cache unsealed from it. A generation that cannot be read or
+//! opened is skipped for the next newest, so a damaged copy does not strand
+//! the backup.
+
+use crate::{
+    db::sqlite::SqliteCatalog,
+    engine::{replicate::catalog_generations, wkey},
+    storage::open,
+    utils::crypto::{RecoveryKey, open_catalog, unseal_naming_key_with_secret},
+};
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+pub struct RecoverRequest {
+    pub name: String,
+    pub config_dir: PathBuf,
+    /// Destination to read the sealed catalog from, as it would be configured
+    /// (a path or an `s3://` URL).
+    pub from: String,
+    /// The mnemonic, with the passphrase if the backup was created with one:
+    /// the flag that records it is inside the sealed catalog.
+    pub mnemonic: RecoveryKey,
+}
+
+#[derive(Debug)]
+pub struct RecoverReport {
+    pub db_path: PathBuf,
+    /// Generation of the catalog recovered.
+    pub generation: u64,
+    /// Newer generations that could not be opened, as `object: reason`.
+    pub skipped: Vec<String>,
+    /// Latest completed version in the recovered catalog.
+    pub latest_version: Option<i64>,
+    /// Destinations the recovered catalog is configured with.
+    pub destinations: Vec<String>,
+}
+
+/// Recover backup `name`'s catalog and naming-key cache from a destination.
+///
+/// # Errors
+/// Returns an error if a backup of that name already exists locally, the
+/// destination cannot be opened or holds no catalog of it, or no generation
+/// there opens with the mnemonic.
+pub async fn recover(request: RecoverRequest) -> Result<RecoverReport> {
+    let db_path = request.config_dir.join(format!("{}.db", request.name));
+    if db_path.exists() {
+        return Err(anyhow!(
+            "A backup with the name '{}' already exists; remove {} first to recover it",
+            request.name,
+            db_path.display()
+        ));
+    }
+
+    let store = open(&request.from)?;
+    let generations = catalog_generations(store.as_ref(), &request.name).await?;
+    if generations.is_empty() {
+        return Err(anyhow!(
+            "No catalog of \"{}\" found in {}",
+            request.name,
+            request.from
+        ));
+    }
+
+    let (private_key, public_key) = request.mnemonic.keypair()?;
+    let mut skipped = Vec::new();
+    for (generation, object) in generations {
+        let recovered = match store.get_catalog(&object).await {
+            Ok(sealed) => unseal_into(
+                &sealed,
+                &request.name,
+                &request.config_dir,
+                &private_key,
+                &public_key,
+            ),
+            Err(err) => Err(err),
+        };
+        let naming_key = match recovered {
+            Ok(naming_key) => naming_key,
+            Err(err) => {
+                skipped.push(format!("{object}: {err:#}"));
+                continue;
+            }
+        };
+
+        // The catalog is in place before the cache, so an interruption leaves
+        // a backup that merely prompts for the mnemonic once.
+        wkey::write_naming_key(&request.config_dir, &request.name, &naming_key)?;
+        let catalog = SqliteCatalog::open(&db_path)?;
+        return Ok(RecoverReport {
+            db_path,
+            generation,
+            skipped,
+            latest_version: catalog.latest_version()?,
+            destinations: catalog.configured_destinations()?,
+        });
+    }
+
+    Err(anyhow!(
+        "Could not recover \"{}\" from {}: no catalog there opens with this mnemonic ({})",
+        request.name,
+        request.from,
+        skipped.join("; ")
+    ))
+}
+
+/// Open a sealed catalog, check it belongs to `public_key`, and move it into
+/// place as `<config_dir>/<name>.db`; returns the naming key unsealed from it.
+/// The plaintext is checked in an owner-only scratch directory beside it, so a
+/// catalog that fails is never left where `run` would use it.
+fn unseal_into(
+    sealed: &[u8],
+    name: &str,
+    config_dir: &Path,
+    private_key: &StaticSecret,
+    public_key: &PublicKey,
+) -> Result<Zeroizing<[u8; 32]>> {
+    std::fs::create_dir_all(config_dir)?;
+    let scratch = tempfile::Builder::new()
+        .prefix(&format!(".{name}-recover"))
+        .tempdir_in(config_dir)?;
+    let candidate = scratch.path().join(format!("{name}.db"));
+    open_catalog(
+        sealed,
+        std::io::BufWriter::new(std::fs::File::create(&candidate)?),
+        private_key,
+        name,
+    )?;
+
+    let naming_key = {
+        let catalog = SqliteCatalog::open(&candidate)?;
+        if catalog.public_key()?.as_bytes() != public_key.as_bytes() {
+            return Err(anyhow!("catalog belongs to a different key"));
+        }
+        unseal_naming_key_with_secret(&catalog.sealed_naming_key()?, private_key)?
+    };
+
+    std::fs::rename(&candidate, config_dir.join(format!("{name}.db")))?;
+    Ok(naming_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::{
+            fixture::Fixture,
+            restore::{RestoreRequest, restore},
+        },
+        storage::Storage,
+    };
+    use bip39::{Language, Mnemonic};
+    use std::fs;
+
+    /// A backup of one file, uploaded to its destination.
+    async fn backed_up() -> Result<Fixture> {
+        let fx = Fixture::create(&[("file.txt", b"worth recovering")], 1, &[])?;
+        fx.back_up().await?;
+        Ok(fx)
+    }
+
+    fn request(fx: &Fixture, config_dir: &Path, mnemonic: Mnemonic) -> Result<RecoverRequest> {
+        Ok(RecoverRequest {
+            name: "t".to_string(),
+            config_dir: config_dir.to_path_buf(),
+            from: fx.destination(0)?,
+            mnemonic: mnemonic.into(),
+        })
+    }
+
+    #[tokio::test]
+    async fn recover_rebuilds_a_restorable_backup() -> Result<()> {
+        let fx = backed_up().await?;
+        // A fresh host: nothing but the destination and the mnemonic.
+        let fresh = fx.tmp.path().join("fresh");
+
+        let report = recover(request(&fx, &fresh, fx.mnemonic.clone())?).await?;
+        assert!(report.skipped.is_empty());
+        // run and upload each replicated a generation.
+        assert_eq!(report.generation, 2);
+        assert_eq!(report.latest_version, Some(1));
+        assert_eq!(report.destinations, vec![fx.destination(0)?]);
+        assert_eq!(
+            wkey::load_naming_key(&fresh, "t")?.as_deref(),
+            Some(&**fx.naming_key)
+        );
+
+        let into = fx.tmp.path().join("restored");
+        restore(RestoreRequest {
+            config_dir: fresh.clone(),
+            ..fx.restore_request(None, &into)
+        })
+        .await?;
+        assert_eq!(
+            fs::read(fx.restored(&into, "file.txt"))?,
+            b"worth recovering"
+        );
+
+        // The catalog now exists, so a second recovery refuses to replace it.
+        assert!(
+            recover(request(&fx, &fresh, fx.mnemonic.clone())?)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn recover_skips_a_damaged_generation() -> Result<()> {
+        let fx = backed_up().await?;
+        let store = fx.store(0)?;
+        store.put_catalog("t.2", b"not a sealed catalog").await?;
+        let fresh = fx.tmp.path().join("fresh");
+
+        let report = recover(request(&fx, &fresh, fx.mnemonic.clone())?).await?;
+        assert_eq!(report.generation, 1);
+        assert_eq!(report.skipped.len(), 1);
+        // Generation 1 predates the upload, so its files are still pending.
+        assert_eq!(report.latest_version, Some(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn recover_rejects_the_wrong_mnemonic() -> Result<()> {
+        let fx = backed_up().await?;
+        let fresh = fx.tmp.path().join("fresh");
+        let wrong = Mnemonic::generate_in(Language::English, 12)?;
+
+        assert!(recover(request(&fx, &fresh, wrong)?).await.is_err());
+        assert!(!fresh.join("t.db").exists());
+        assert!(wkey::load_naming_key(&fresh, "t")?.is_none());
+
+        // A backup with no catalog in the destination cannot be recovered.
+        let mut other = request(&fx, &fresh, fx.mnemonic.clone())?;
+        other.name = "other".to_string();
+        assert!(recover(other).await.is_err());
+        Ok(())
+    }
+}
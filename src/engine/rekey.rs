@@ -0,0 +1,367 @@
+//! Move a backup to a new keypair (DESIGN §9).
+//!
+//! `rekey` generates a new mnemonic and, with the old one, re-wraps the naming
+//! key and every chunk key to the new public key. Only catalog rows change: the
+//! content keys themselves do not, only what they are wrapped to, and a stored
+//! pack holds nothing sealed to the keypair (its index key derives from the
+//! naming key), so no object in a destination is read or written again. The
+//! new key is first added as a recipient (§6.3), so uploads meanwhile wrap
+//! their chunks to it too; its chunk keys are written in batches, and it is
+//! swapped in for the old key in one transaction once every chunk has moved.
+//! An interrupted rekey resumes where it stopped, and until the swap the old
+//! mnemonic keeps working.
+//!
+//! Sealed catalogs are replaced by one sealed to the new key. Because content
+//! keys are not rotated, a copy of the catalog taken before the rekey still
+//! opens what was stored then with the old mnemonic.
+
+use crate::{
+    db::sqlite::SqliteCatalog,
+    engine::{
+        edit::rewrap_chunk_keys,
+        replicate::{ReplicateReport, replicate_catalog},
+    },
+    utils::crypto::{
+        RecoveryKey, parse_public_key, seal_naming_key, unseal_naming_key_with_secret,
+    },
+};
+use anyhow::{Result, anyhow};
+use bip39::{Language, Mnemonic};
+use std::path::PathBuf;
+use x25519_dalek::StaticSecret;
+
+/// Chunk keys re-wrapped per transaction.
+const REKEY_BATCH: usize = 4096;
+
+pub struct RekeyRequest {
+    pub name: String,
+    pub config_dir: PathBuf,
+    /// The backup's current recovery mnemonic. Its passphrase, if the backup
+    /// has one, is kept: the new mnemonic derives its keypair with it too.
+    pub mnemonic: RecoveryKey,
+    /// Drop a rekey under way and begin again with a fresh mnemonic (e.g. the
+    /// phrase it printed was lost).
+    pub restart: bool,
+}
+
+pub struct RekeyStart {
+    /// The new recovery phrase; `None` when resuming a rekey begun earlier,
+    /// whose phrase was printed then.
+    pub recovery_phrase: Option<String>,
+    /// The new public key, in hex.
+    pub public_key: String,
+}
+
+#[derive(Debug)]
+pub struct RekeyReport {
+    /// The backup's public key now, in hex.
+    pub public_key: String,
+    /// Chunk keys re-wrapped by this pass.
+    pub wrapped_keys: usize,
+    pub catalog: ReplicateReport,
+}
+
+/// Begin moving backup `name` to a new keypair, or pick up the rekey under way.
+/// The new phrase must be written down before [`finish_rekey`] makes it the
+/// only one that opens the backup.
+///
+/// # Errors
+/// Returns an error if the backup is missing, the mnemonic does not unlock it,
+/// or the catalog cannot be updated.
+pub fn begin_rekey(request: &RekeyRequest) -> Result<RekeyStart> {
+    let catalog = open_backup(request)?;
+    let private_key = unlock(&catalog, request)?;
+
+    if request.restart {
+        catalog.abandon_rekey()?;
+    }
+    if let Some(public_key) = catalog.pending_rekey()? {
+        return Ok(RekeyStart {
+            recovery_phrase: None,
+            public_key,
+        });
+    }
+
+    let recovery = RecoveryKey {
+        mnemonic: Mnemonic::generate_in(Language::English, 12)?,
+        passphrase: request.mnemonic.passphrase.clone(),
+    };
+    let (_, public_key) = recovery.keypair()?;
+    let naming_key = unseal_naming_key_with_secret(&catalog.sealed_naming_key()?, &private_key)?;
+    let public_hex = hex::encode(public_key.as_bytes());
+    catalog.begin_rekey(&public_hex, &seal_naming_key(&naming_key, &public_key)?)?;
+
+    Ok(RekeyStart {
+        recovery_phrase: Some(recovery.mnemonic.to_string()),
+        public_key: public_hex,
+    })
+}
+
+/// Re-wrap every chunk key the rekey under way still lacks, swap the new key
+/// in and replicate a catalog sealed to it, removing those sealed to the old
+/// one.
+///
+/// # Errors
+/// Returns an error if the backup is missing, the mnemonic does not unlock it,
+/// no rekey is under way, or the catalog cannot be updated. A destination that
+/// cannot store the new sealed catalog is reported in [`RekeyReport::catalog`].
+pub async fn finish_rekey(request: &RekeyRequest) -> Result<RekeyReport> {
+    let catalog = open_backup(request)?;
+    let private_key = unlock(&catalog, request)?;
+    let public_hex = catalog
+        .pending_rekey()?
+        .ok_or_else(|| anyhow!("No rekey of \"{}\" is under way", request.name))?;
+    let public_key = parse_public_key(&public_hex)?;
+
+    let mut pending = catalog.chunks_without_key(&public_hex)?;
+    let mut wrapped_keys = 0;
+    while !pending.is_empty() {
+        let batch: Vec<_> = pending.drain(..pending.len().min(REKEY_BATCH)).collect();
+        let chunk_keys = rewrap_chunk_keys(batch, &private_key, &public_key)?;
+        catalog.record_chunk_keys(&chunk_keys)?;
+        wrapped_keys += chunk_keys.len();
+    }
+
+    catalog.finish_rekey(&public_key)?;
+
+    // Every older generation opens with the old mnemonic, so only the one
+    // sealed to the new key is kept.
+    let replicated = replicate_catalog(&catalog, &request.name, &request.config_dir, 1).await?;
+
+    Ok(RekeyReport {
+        public_key: public_hex,
+        wrapped_keys,
+        catalog: replicated,
+    })
+}
+
+fn open_backup(request: &RekeyRequest) -> Result<SqliteCatalog> {
+    let db_path = request.config_dir.join(format!("{}.db", request.name));
+    if !db_path.exists() {
+        return Err(anyhow!(
+            "No backup named \"{}\" found. Create a new backup first.",
+            request.name
+        ));
+    }
+    SqliteCatalog::open(&db_path)
+}
+
+/// The private key of the backup's current keypair, if the mnemonic is its.
+fn unlock(catalog: &SqliteCatalog, request: &RekeyRequest) -> Result<StaticSecret> {
+    let (private_key, public_key) = request.mnemonic.keypair()?;
+    if public_key != catalog.public_key()? {
+        return Err(anyhow!(
+            "Incorrect mnemonic: it does not unlock backup \"{}\"",
+            request.name
+        ));
+    }
+    Ok(private_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::{
+            create::{CreateBackupRequest, create},
+            fixture::Fixture,
+            replicate::catalog_generations,
+        },
+        storage::{Storage, pack::read_index},
+        utils::crypto::{content_keypair, open_catalog},
+    };
+    use std::fs;
+    use zeroize::Zeroizing;
+
+    /// A backup of `files`, uploaded to its destination.
+    async fn backed_up(files: &[(&str, &[u8])]) -> Result<Fixture> {
+        let fx = Fixture::create(files, 1, &[])?;
+        fx.back_up().await?;
+        Ok(fx)
+    }
+
+    fn request(fx: &Fixture, mnemonic: &Mnemonic) -> RekeyRequest {
+        RekeyRequest {
+            name: "t".to_string(),
+            config_dir: fx.cfg.clone(),
+            mnemonic: mnemonic.clone().into(),
+            restart: false,
+        }
+    }
+
+    fn phrase(start: &RekeyStart) -> Result<Mnemonic> {
+        let phrase = start
+            .recovery_phrase
+            .as_deref()
+            .ok_or_else(|| anyhow!("expected a new phrase"))?;
+        Ok(Mnemonic::parse_in_normalized(Language::English, phrase)?)
+    }
+
+    #[tokio::test]
+    async fn rekey_moves_the_backup_to_a_new_mnemonic() -> Result<()> {
+        let fx = backed_up(&[("a.txt", b"alpha"), ("b.txt", b"beta")]).await?;
+        let old = fx.mnemonic.clone();
+        let store = fx.store(0)?;
+        let mut packs = Vec::new();
+        for pack_id in fx.catalog()?.all_pack_ids()? {
+            let bytes = store.get(&pack_id).await?;
+            packs.push((pack_id, bytes));
+        }
+
+        let start = begin_rekey(&request(&fx, &old))?;
+        let new = phrase(&start)?;
+        let report = finish_rekey(&request(&fx, &old)).await?;
+        assert_eq!(report.wrapped_keys, 2);
+        assert_eq!(report.public_key, start.public_key);
+        assert_eq!(
+            hex::encode(fx.catalog()?.public_key()?.as_bytes()),
+            start.public_key
+        );
+        assert!(fx.catalog()?.recipients()?.is_empty());
+
+        // Only the new mnemonic opens the backup now, old content included.
+        let out = fx.tmp.path().join("new");
+        fx.restore_into(&out, &new).await?;
+        assert_eq!(fs::read(fx.restored(&out, "a.txt"))?, b"alpha");
+        assert!(
+            fx.restore_into(&fx.tmp.path().join("old"), &old)
+                .await
+                .is_err()
+        );
+        assert!(begin_rekey(&request(&fx, &old)).is_err());
+
+        // One sealed catalog is left, and it opens with the new key.
+        let generations = catalog_generations(&store, "t").await?;
+        let [(_, object)] = generations.as_slice() else {
+            return Err(anyhow!("expected one catalog, found {generations:?}"));
+        };
+        let sealed = store.get_catalog(object).await?;
+        let open = |mnemonic| -> Result<u64> {
+            open_catalog(
+                sealed.as_slice(),
+                std::io::sink(),
+                &content_keypair(mnemonic)?.0,
+                "t",
+            )
+        };
+        assert!(open(&new).is_ok());
+        assert!(open(&old).is_err());
+
+        // Only catalog rows moved: every pack is stored as it was, its index
+        // still opening with the naming key.
+        assert_eq!(packs.len(), 1);
+        for (pack_id, bytes) in &packs {
+            assert_eq!(&store.get(pack_id).await?, bytes);
+            assert_eq!(read_index(bytes, pack_id, &fx.naming_key)?.len(), 2);
+        }
+
+        // The naming key is unchanged, so backups carry on as before.
+        fs::write(fx.src.join("c.txt"), b"gamma")?;
+        fx.back_up().await?;
+        let out = fx.tmp.path().join("later");
+        fx.restore_into(&out, &new).await?;
+        assert_eq!(fs::read(fx.restored(&out, "c.txt"))?, b"gamma");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn an_interrupted_rekey_resumes() -> Result<()> {
+        let fx = backed_up(&[("a.txt", b"alpha"), ("b.txt", b"beta")]).await?;
+        let old = fx.mnemonic.clone();
+        let new = phrase(&begin_rekey(&request(&fx, &old))?)?;
+        let (private_key, _) = content_keypair(&old)?;
+        let (_, new_public) = content_keypair(&new)?;
+        let new_hex = hex::encode(new_public.as_bytes());
+
+        // Interrupted after one batch of one chunk.
+        let catalog = fx.catalog()?;
+        let first: Vec<_> = catalog
+            .chunks_without_key(&new_hex)?
+            .into_iter()
+            .take(1)
+            .collect();
+        catalog.record_chunk_keys(&rewrap_chunk_keys(first, &private_key, &new_public)?)?;
+        assert!(catalog.finish_rekey(&new_public).is_err());
+
+        // Until the swap, the old mnemonic still restores, and so does the new
+        // one; a chunk uploaded meanwhile is wrapped to both.
+        fs::write(fx.src.join("c.txt"), b"gamma")?;
+        fx.back_up().await?;
+        fx.restore_into(&fx.tmp.path().join("old"), &old).await?;
+
+        // Beginning again resumes the same rekey rather than starting another.
+        let resumed = begin_rekey(&request(&fx, &old))?;
+        assert!(resumed.recovery_phrase.is_none());
+        assert_eq!(resumed.public_key, new_hex);
+        let report = finish_rekey(&request(&fx, &old)).await?;
+        assert_eq!(report.wrapped_keys, 1);
+
+        let out = fx.tmp.path().join("new");
+        fx.restore_into(&out, &new).await?;
+        assert_eq!(fs::read(fx.restored(&out, "b.txt"))?, b"beta");
+        assert_eq!(fs::read(fx.restored(&out, "c.txt"))?, b"gamma");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_restarted_rekey_drops_the_unfinished_key() -> Result<()> {
+        let fx = backed_up(&[("a.txt", b"alpha")]).await?;
+        let old = fx.mnemonic.clone();
+        let wrong = Mnemonic::generate_in(Language::English, 12)?;
+        assert!(begin_rekey(&request(&fx, &wrong)).is_err());
+        assert!(finish_rekey(&request(&fx, &old)).await.is_err());
+
+        let lost = begin_rekey(&request(&fx, &old))?;
+        let mut restart = request(&fx, &old);
+        restart.restart = true;
+        let start = begin_rekey(&restart)?;
+        assert_ne!(start.public_key, lost.public_key);
+        assert_eq!(fx.catalog()?.recipients()?, vec![start.public_key.clone()]);
+
+        let new = phrase(&start)?;
+        finish_rekey(&request(&fx, &old)).await?;
+        let out = fx.tmp.path().join("new");
+        fx.restore_into(&out, &new).await?;
+        assert_eq!(fs::read(fx.restored(&out, "a.txt"))?, b"alpha");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rekey_keeps_the_passphrase() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let passphrase = Some(Zeroizing::new("kept apart".to_string()));
+        let created = create(CreateBackupRequest {
+            name: "t".to_string(),
+            config_dir: tmp.path().to_path_buf(),
+            directories: Vec::new(),
+            files: Vec::new(),
+            destinations: Vec::new(),
+            passphrase: passphrase.clone(),
+        })?;
+        let request = RekeyRequest {
+            name: "t".to_string(),
+            config_dir: tmp.path().to_path_buf(),
+            mnemonic: RecoveryKey {
+                mnemonic: Mnemonic::parse_in_normalized(
+                    Language::English,
+                    &created.recovery_phrase,
+                )?,
+                passphrase: passphrase.clone(),
+            },
+            restart: false,
+        };
+
+        let new = phrase(&begin_rekey(&request)?)?;
+        finish_rekey(&request).await?;
+        let catalog = SqliteCatalog::open(&created.db_path)?;
+        assert!(catalog.passphrase_required()?);
+        let (_, with_passphrase) = RecoveryKey {
+            mnemonic: new.clone(),
+            passphrase,
+        }
+        .keypair()?;
+        assert_eq!(catalog.public_key()?, with_passphrase);
+        assert_ne!(catalog.public_key()?, content_keypair(&new)?.1);
+        Ok(())
+    }
+}
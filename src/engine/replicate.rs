@@ -0,0 +1,277 @@
+//! Replicate the catalog to every destination (DESIGN §7).
+//!
+//! The stored packs are useless without the catalog that maps files to chunks
+//! and holds their wrapped keys, so after every `run` and `upload` the catalog
+//! is copied with `SQLite`'s online backup API, sealed to the backup public key
+//! and stored in each destination as `catalog/<name>.<generation>`. Sealing
+//! needs no secret, so a write-only host replicates as it backs up; opening a
+//! sealed catalog takes the mnemonic. The newest [`KEEP_GENERATIONS`] are kept
+//! per destination, so a catalog damaged locally before it was replicated can
+//! still be recovered from an older generation.
+//!
+//! Catalogs live outside the hex keyspace ([`Storage::put_catalog`]), so `gc`
+//! and `verify` never take them for unreferenced or missing objects.
+
+use crate::{
+    db::sqlite::SqliteCatalog,
+    engine::run::scan_worker_count,
+    storage::{Storage, open},
+    utils::crypto::seal_catalog,
+};
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use std::{path::Path, sync::Arc};
+
+/// Generations of a backup's catalog kept in each destination.
+pub const KEEP_GENERATIONS: usize = 5;
+
+#[derive(Debug, Default)]
+pub struct ReplicateReport {
+    /// Generation of the catalog just sealed.
+    pub generation: u64,
+    /// Destinations that stored it.
+    pub stored: usize,
+    pub destination_count: usize,
+    /// Destinations that could not store it, and older generations that could
+    /// not be removed, as `destination: reason`.
+    pub failures: Vec<String>,
+}
+
+/// Name a catalog generation is stored under: `<name>.<generation>`.
+#[must_use]
+pub fn catalog_object(name: &str, generation: u64) -> String {
+    format!("{name}.{generation}")
+}
+
+/// The generations of backup `name`'s catalog in `store`, newest first, with
+/// the name each is stored under.
+///
+/// # Errors
+/// Returns an error if the store's catalogs cannot be listed.
+pub async fn catalog_generations(store: &dyn Storage, name: &str) -> Result<Vec<(u64, String)>> {
+    let mut generations: Vec<(u64, String)> = store
+        .list_catalogs()
+        .await?
+        .into_iter()
+        .filter_map(|object| {
+            let generation = object
+                .key
+                .strip_prefix(name)?
+                .strip_prefix('.')?
+                .parse()
+                .ok()?;
+            Some((generation, object.key))
+        })
+        .collect();
+    generations.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(generations)
+}
+
+/// Seal a snapshot of backup `name`'s catalog and store it in every configured
+/// destination, keeping the newest `keep` generations in each.
+///
+/// # Errors
+/// Returns an error if the catalog cannot be snapshotted or sealed. A
+/// destination that cannot be opened or written is reported in
+/// [`ReplicateReport::failures`] instead, so an unreachable destination does
+/// not fail the run that replicates to it.
+pub async fn replicate_catalog(
+    catalog: &SqliteCatalog,
+    name: &str,
+    config_dir: &Path,
+    keep: usize,
+) -> Result<ReplicateReport> {
+    let destinations = catalog.configured_destinations()?;
+    let mut report = ReplicateReport {
+        destination_count: destinations.len(),
+        ..ReplicateReport::default()
+    };
+    if destinations.is_empty() {
+        return Ok(report);
+    }
+
+    report.generation = catalog.next_catalog_generation()?;
+    // The plaintext copy stays beside the catalog, owner-only, and is removed
+    // with its directory, as is the sealed copy streamed from it to the stores.
+    let scratch = tempfile::Builder::new()
+        .prefix(&format!(".{name}-snapshot"))
+        .tempdir_in(config_dir)?;
+    let snapshot = scratch.path().join(format!("{name}.db"));
+    catalog.snapshot_to(&snapshot)?;
+    let sealed = scratch.path().join(format!("{name}.sealed"));
+    let (public_key, backup) = (catalog.public_key()?, name.to_string());
+    let to = sealed.clone();
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let db = std::io::BufReader::new(std::fs::File::open(snapshot)?);
+        let writer = std::io::BufWriter::new(std::fs::File::create(to)?);
+        seal_catalog(db, writer, &public_key, &backup)
+    })
+    .await??;
+    let object = catalog_object(name, report.generation);
+
+    let outcomes: Vec<(String, Result<Vec<String>>)> = stream::iter(destinations)
+        .map(|destination| {
+            let (sealed, object) = (&sealed, &object);
+            async move {
+                let outcome = async {
+                    let store = open(&destination)?;
+                    store.put_catalog_file(object, sealed).await?;
+                    Ok(remove_old_generations(store, name, keep).await)
+                }
+                .await;
+                (destination, outcome)
+            }
+        })
+        .buffer_unordered(scan_worker_count())
+        .collect()
+        .await;
+
+    for (destination, outcome) in outcomes {
+        match outcome {
+            Ok(failures) => {
+                report.stored += 1;
+                report.failures.extend(
+                    failures
+                        .into_iter()
+                        .map(|failure| format!("{destination}: {failure}")),
+                );
+            }
+            Err(err) => report.failures.push(format!("{destination}: {err:#}")),
+        }
+    }
+    report.failures.sort();
+    Ok(report)
+}
+
+/// Remove every generation of backup `name`'s catalog in `store` but the
+/// newest `keep`; returns what could not be listed or removed. A store whose
+/// credential cannot delete keeps them all, which costs space but is safe.
+async fn remove_old_generations(store: Arc<dyn Storage>, name: &str, keep: usize) -> Vec<String> {
+    let generations = match catalog_generations(store.as_ref(), name).await {
+        Ok(generations) => generations,
+        Err(err) => return vec![format!("cannot list catalogs: {err:#}")],
+    };
+
+    let mut failures = Vec::new();
+    for (_, object) in generations.into_iter().skip(keep.max(1)) {
+        if let Err(err) = store.remove_catalog(&object).await {
+            failures.push(format!("cannot remove catalog {object}: {err:#}"));
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::create::{CreateBackupRequest, create},
+        storage::local::LocalStore,
+        utils::crypto::{content_keypair, open_catalog},
+    };
+    use anyhow::anyhow;
+    use bip39::{Language, Mnemonic};
+    use std::fs;
+
+    /// A fresh backup `t` with one destination, and the mnemonic it was
+    /// created with.
+    fn backup() -> Result<(tempfile::TempDir, std::path::PathBuf, LocalStore, Mnemonic)> {
+        let tmp = tempfile::tempdir()?;
+        let cfg = tmp.path().join("cfg");
+        let src = tmp.path().join("src");
+        let dest = tmp.path().join("dest");
+        fs::create_dir_all(&cfg)?;
+        fs::create_dir_all(&src)?;
+        let created = create(CreateBackupRequest {
+            name: "t".to_string(),
+            config_dir: cfg.clone(),
+            directories: vec![src],
+            files: Vec::new(),
+            destinations: vec![dest.to_string_lossy().into_owned()],
+            passphrase: None,
+        })?;
+        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &created.recovery_phrase)?;
+        Ok((tmp, cfg, LocalStore::new(dest), mnemonic))
+    }
+
+    #[tokio::test]
+    async fn replicated_catalog_opens_with_the_mnemonic() -> Result<()> {
+        let (_tmp, cfg, store, mnemonic) = backup()?;
+        let catalog = SqliteCatalog::open(&cfg.join("t.db"))?;
+
+        let report = replicate_catalog(&catalog, "t", &cfg, KEEP_GENERATIONS).await?;
+        assert_eq!((report.generation, report.stored), (1, 1));
+        assert!(report.failures.is_empty());
+
+        let (secret, _) = content_keypair(&mnemonic)?;
+        let sealed = store.get_catalog("t.1").await?;
+        let restored = cfg.join("restored.db");
+        open_catalog(
+            sealed.as_slice(),
+            fs::File::create(&restored)?,
+            &secret,
+            "t",
+        )?;
+        let restored = SqliteCatalog::open(&restored)?;
+        assert_eq!(
+            restored.public_key()?.to_bytes(),
+            catalog.public_key()?.to_bytes()
+        );
+        assert_eq!(
+            restored.configured_destinations()?,
+            catalog.configured_destinations()?
+        );
+        // The snapshot carries the generation it was sealed as.
+        assert_eq!(restored.next_catalog_generation()?, 2);
+
+        // No plaintext copy is left behind.
+        let leftovers: Vec<_> = fs::read_dir(&cfg)?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().contains("snapshot"))
+            .collect();
+        assert!(leftovers.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_the_newest_generations_are_kept() -> Result<()> {
+        let (_tmp, cfg, store, _) = backup()?;
+        let catalog = SqliteCatalog::open(&cfg.join("t.db"))?;
+        // Another backup's catalogs in the same destination are left alone.
+        store.put_catalog("other.1", b"sealed elsewhere").await?;
+
+        for _ in 0..4 {
+            replicate_catalog(&catalog, "t", &cfg, 2).await?;
+        }
+        let generations: Vec<u64> = catalog_generations(&store, "t")
+            .await?
+            .into_iter()
+            .map(|(generation, _)| generation)
+            .collect();
+        assert_eq!(generations, vec![4, 3]);
+        assert!(store.get_catalog("other.1").await.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn an_unreachable_destination_is_reported_not_fatal() -> Result<()> {
+        let (tmp, cfg, store, _) = backup()?;
+        let catalog = SqliteCatalog::open(&cfg.join("t.db"))?;
+        // A file where the second destination's directory should be.
+        let blocked = tmp.path().join("blocked");
+        fs::write(&blocked, b"not a directory")?;
+        let mut destinations = catalog.configured_destinations()?;
+        destinations.push(blocked.to_string_lossy().into_owned());
+        catalog.set_destinations(&destinations)?;
+
+        let report = replicate_catalog(&catalog, "t", &cfg, KEEP_GENERATIONS).await?;
+        assert_eq!((report.stored, report.destination_count), (1, 2));
+        let failure = report
+            .failures
+            .first()
+            .ok_or_else(|| anyhow!("expected a failure"))?;
+        assert!(failure.starts_with(&blocked.to_string_lossy().into_owned()));
+        assert!(store.get_catalog("t.1").await.is_ok());
+        Ok(())
+    }
+}
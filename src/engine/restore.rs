@@ -0,0 +1,1288 @@
+//! Restore files from a completed snapshot.
+//!
+//! Resolves a target — a file id, a path (one file or a whole subtree), or the
+//! entire snapshot — at a version, then for each file resolves its manifest and,
+//! chunk by chunk: unwraps the content key with the recovery mnemonic (the
+//! backup's own, or an additional recipient's), fetches
+//! the blob (a range read of its pack) from the first destination holding a
+//! good copy, decrypts + decompresses it, and re-checks its keyed BLAKE3 id. The
+//! reassembled file is checked against its whole-file id, given its recorded
+//! permissions, timestamps and xattrs (and owner, when running as root), and
+//! written atomically (temp file + rename) under `--into` or at its original
+//! path. A symlink is recreated the same way, pointing at its recorded target
+//! verbatim (even under `--into`). Files that were hardlinked together are
+//! restored once and linked again. Directories are created before anything is
+//! written into them and given their metadata after, deepest first.
+//!
+//! A file that cannot be restored (a chunk missing or corrupt everywhere) is
+//! reported rather than aborting the run, so one bad object doesn't stop a DR
+//! drill from recovering everything else.
+
+use crate::{
+    db::sqlite::{EntryKind, EntryMetadata, ManifestChunk, RestoreEntry, SqliteCatalog},
+    engine::{
+        run::scan_worker_count,
+        view::{ViewTarget, open_at_version},
+    },
+    storage::{Storage, open_stores},
+    utils::{
+        crypto::{RecoveryKey, open_content, unseal_naming_key_with_secret, unwrap_content_key},
+        hash::blake3_keyed_bytes,
+        sparse::{ExtentCursor, SparseMap},
+    },
+};
+use anyhow::{Result, anyhow};
+use futures::stream::{self, StreamExt};
+use rustix::{
+    fs::{AtFlags, CWD, FileType, Mode, Timespec, Timestamps},
+    io::Errno,
+};
+use std::{
+    cmp,
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs::Permissions,
+    io::SeekFrom,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tokio::{
+    fs,
+    io::{AsyncSeekExt, AsyncWriteExt},
+};
+use x25519_dalek::StaticSecret;
+use zeroize::Zeroizing;
+
+/// Per-process counter making restore temp filenames unique.
+static TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+pub struct RestoreRequest {
+    pub name: String,
+    pub config_dir: PathBuf,
+    /// What to restore; `None` restores the whole snapshot.
+    pub target: Option<ViewTarget>,
+    /// Snapshot version; `None` uses the latest completed version.
+    pub version: Option<i64>,
+    /// Root to restore under; `None` writes files back to their original paths.
+    pub into: Option<PathBuf>,
+    pub mnemonic: RecoveryKey,
+}
+
+pub struct RestoreReport {
+    pub version: i64,
+    /// Files, symlinks and special files restored.
+    pub restored_files: usize,
+    pub restored_dirs: usize,
+    pub restored_bytes: u64,
+    /// Entries that could not be restored, sorted by path.
+    pub failed: Vec<RestoreFailure>,
+}
+
+pub struct RestoreFailure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Keys and stores shared by every file restored in one run.
+struct RestoreCtx {
+    catalog: SqliteCatalog,
+    stores: Vec<Arc<dyn Storage>>,
+    private_key: StaticSecret,
+    /// The additional recipient (public key in hex) restoring, whose chunk
+    /// keys are in `ChunkKeys`; `None` for the backup's own mnemonic.
+    recipient: Option<String>,
+    naming_key: Arc<Zeroizing<[u8; 32]>>,
+    into: Option<PathBuf>,
+}
+
+impl RestoreCtx {
+    /// Unwrap a chunk's content key with the mnemonic restoring.
+    fn content_key(&self, chunk: &ManifestChunk) -> Result<Zeroizing<[u8; 32]>> {
+        let Some(recipient) = &self.recipient else {
+            return unwrap_content_key(
+                &chunk.wrapped_key,
+                &chunk.ephemeral_public_key,
+                &self.private_key,
+                &chunk.id,
+            );
+        };
+        let (wrapped_key, ephemeral_public_key) = self
+            .catalog
+            .recipient_chunk_key(&chunk.id, recipient)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "chunk {} is not wrapped to this recipient yet; run `backup edit --add-recipient` for them again",
+                    chunk.id
+                )
+            })?;
+        unwrap_content_key(
+            &wrapped_key,
+            &ephemeral_public_key,
+            &self.private_key,
+            &chunk.id,
+        )
+    }
+}
+
+/// Restore a target from a backup snapshot.
+///
+/// Returns `Ok(None)` when the backup exists but has no completed version yet.
+///
+/// # Errors
+/// Returns an error if the backup is missing, the mnemonic does not match, the
+/// target does not exist at the version, or no usable destination is configured.
+/// Per-file failures are reported in [`RestoreReport::failed`] instead.
+pub async fn restore(request: RestoreRequest) -> Result<Option<RestoreReport>> {
+    let Some((catalog, version)) =
+        open_at_version(&request.config_dir, &request.name, request.version)?
+    else {
+        return Ok(None);
+    };
+
+    let entries = select_entries(&catalog, version, request.target.as_ref())?;
+
+    let (private_key, public_key) = request.mnemonic.keypair()?;
+    let (recipient, sealed_naming_key) = if public_key == catalog.public_key()? {
+        (None, catalog.sealed_naming_key()?)
+    } else {
+        let recipient = hex::encode(public_key.as_bytes());
+        let Some(sealed) = catalog.recipient_sealed_naming_key(&recipient)? else {
+            return Err(anyhow!(
+                "Incorrect mnemonic: it does not unlock backup \"{}\"",
+                request.name
+            ));
+        };
+        (Some(recipient), sealed)
+    };
+    let naming_key = unseal_naming_key_with_secret(&sealed_naming_key, &private_key)?;
+
+    let stores = open_stores(&catalog.configured_destinations()?)?;
+    if stores.is_empty() {
+        return Err(anyhow!("no destinations configured"));
+    }
+
+    let ctx = RestoreCtx {
+        catalog,
+        stores,
+        private_key,
+        recipient,
+        naming_key: Arc::new(naming_key),
+        into: request.into,
+    };
+
+    let mut report = RestoreReport {
+        version,
+        restored_files: 0,
+        restored_dirs: 0,
+        restored_bytes: 0,
+        failed: Vec::new(),
+    };
+
+    let (dirs, entries): (Vec<RestoreEntry>, Vec<RestoreEntry>) = entries
+        .into_iter()
+        .partition(|entry| entry.kind == EntryKind::Dir);
+    let dirs = create_dirs(&ctx, dirs, &mut report).await;
+    let (entries, links) = split_hardlinks(entries);
+
+    // Files are independent, so restore them concurrently with the same bound
+    // the upload phase uses; each task owns its own temp file and target path.
+    let outcomes: Vec<(PathBuf, Result<u64>)> = stream::iter(entries)
+        .map(|entry| {
+            let ctx = &ctx;
+            async move {
+                let result = restore_one(ctx, &entry).await;
+                (entry.path, result)
+            }
+        })
+        .buffer_unordered(scan_worker_count())
+        .collect()
+        .await;
+    let restored = report.tally(outcomes);
+
+    // Then the other links of each hardlinked file, to the copy just restored;
+    // one whose first link failed is restored from its own content instead.
+    let outcomes: Vec<(PathBuf, Result<u64>)> = stream::iter(links)
+        .map(|(entry, first)| {
+            let ctx = &ctx;
+            let restored = &restored;
+            async move {
+                let result = if restored.contains(&first) {
+                    restore_hardlink(ctx, &entry, &first).await.map(|()| 0)
+                } else {
+                    restore_one(ctx, &entry).await
+                };
+                (entry.path, result)
+            }
+        })
+        .buffer_unordered(scan_worker_count())
+        .collect()
+        .await;
+    report.tally(outcomes);
+
+    finish_dirs(&ctx, dirs, &mut report).await;
+    report.failed.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(Some(report))
+}
+
+impl RestoreReport {
+    /// Count restored entries and record failures; returns the paths restored.
+    fn tally(&mut self, outcomes: Vec<(PathBuf, Result<u64>)>) -> HashSet<PathBuf> {
+        let mut restored = HashSet::new();
+        for (path, outcome) in outcomes {
+            match outcome {
+                Ok(bytes) => {
+                    self.restored_files += 1;
+                    self.restored_bytes += bytes;
+                    restored.insert(path);
+                }
+                Err(err) => self.failed.push(RestoreFailure {
+                    path,
+                    reason: format!("{err:#}"),
+                }),
+            }
+        }
+        restored
+    }
+}
+
+/// Split off the files hardlinked to one restored before them: every entry of
+/// a link group (same `(device, inode)` and content) but the first by path,
+/// each paired with that first one's path.
+fn split_hardlinks(
+    entries: Vec<RestoreEntry>,
+) -> (Vec<RestoreEntry>, Vec<(RestoreEntry, PathBuf)>) {
+    let mut firsts: HashMap<((u64, u64), String), PathBuf> = HashMap::new();
+    let mut independent = Vec::new();
+    let mut links = Vec::new();
+    // Entries come sorted by path, so the first seen of a group is its first.
+    for entry in entries {
+        let key = entry
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.hardlink)
+            .zip(entry.hash.clone());
+        let Some(key) = key else {
+            independent.push(entry);
+            continue;
+        };
+        if let Some(first) = firsts.get(&key) {
+            links.push((entry, first.clone()));
+        } else {
+            firsts.insert(key, entry.path.clone());
+            independent.push(entry);
+        }
+    }
+    (independent, links)
+}
+
+/// Create every directory up front, so the entries restored into them (and
+/// empty ones) have somewhere to land. Returns the ones created.
+async fn create_dirs(
+    ctx: &RestoreCtx,
+    dirs: Vec<RestoreEntry>,
+    report: &mut RestoreReport,
+) -> Vec<RestoreEntry> {
+    let mut created = Vec::new();
+    for dir in dirs {
+        match fs::create_dir_all(target_path(&dir.path, ctx.into.as_deref())).await {
+            Ok(()) => created.push(dir),
+            Err(err) => report.failed.push(RestoreFailure {
+                path: dir.path,
+                reason: format!("{err:#}"),
+            }),
+        }
+    }
+    created
+}
+
+/// Give the created directories their metadata once everything is in them,
+/// deepest first: restoring an entry moves its directory's mtime, and a
+/// read-only directory would refuse the entries after it.
+async fn finish_dirs(ctx: &RestoreCtx, mut dirs: Vec<RestoreEntry>, report: &mut RestoreReport) {
+    dirs.sort_by_key(|dir| cmp::Reverse(dir.path.components().count()));
+    for dir in dirs {
+        let result = match dir.metadata {
+            Some(metadata) => {
+                let target = target_path(&dir.path, ctx.into.as_deref());
+                tokio::task::spawn_blocking(move || apply_metadata(&target, &metadata, true))
+                    .await
+                    .map_err(Into::into)
+                    .and_then(|applied| applied)
+            }
+            None => Ok(()),
+        };
+        match result {
+            Ok(()) => report.restored_dirs += 1,
+            Err(err) => report.failed.push(RestoreFailure {
+                path: dir.path,
+                reason: format!("{err:#}"),
+            }),
+        }
+    }
+}
+
+/// Resolve the restore target to the file entries active at `version`.
+fn select_entries(
+    catalog: &SqliteCatalog,
+    version: i64,
+    target: Option<&ViewTarget>,
+) -> Result<Vec<RestoreEntry>> {
+    match target {
+        None => catalog.restore_entries(version),
+        Some(ViewTarget::Id(id)) => catalog
+            .restore_entry(*id, version)?
+            .map(|entry| vec![entry])
+            .ok_or_else(|| anyhow!("No file with id {id} in version {version}")),
+        Some(ViewTarget::Path(root)) => {
+            // `starts_with` is component-wise, so `/a/b` matches the file itself
+            // and everything under `/a/b/`, but not a sibling like `/a/bc`.
+            let entries: Vec<RestoreEntry> = catalog
+                .restore_entries(version)?
+                .into_iter()
+                .filter(|entry| entry.path.starts_with(root))
+                .collect();
+            if entries.is_empty() {
+                return Err(anyhow!(
+                    "Nothing under {} in version {version}",
+                    root.display()
+                ));
+            }
+            Ok(entries)
+        }
+    }
+}
+
+/// Restore one entry; returns the number of bytes written.
+async fn restore_one(ctx: &RestoreCtx, entry: &RestoreEntry) -> Result<u64> {
+    match &entry.kind {
+        EntryKind::File => restore_file(ctx, entry).await,
+        EntryKind::Symlink(link_target) => {
+            restore_symlink(ctx, entry, link_target).await?;
+            Ok(0)
+        }
+        EntryKind::Dir => Err(anyhow!("directories are restored on their own")),
+        EntryKind::Fifo => restore_node(ctx, entry, FileType::Fifo, 0)
+            .await
+            .map(|()| 0),
+        EntryKind::CharDevice(rdev) => restore_node(ctx, entry, FileType::CharacterDevice, *rdev)
+            .await
+            .map(|()| 0),
+        EntryKind::BlockDevice(rdev) => restore_node(ctx, entry, FileType::BlockDevice, *rdev)
+            .await
+            .map(|()| 0),
+        EntryKind::Socket => restore_node(ctx, entry, FileType::Socket, 0)
+            .await
+            .map(|()| 0),
+    }
+}
+
+/// Restore one file; returns the number of bytes written.
+///
+/// The file is written via a unique temp file in the same directory, fsynced,
+/// then renamed into place, so an interrupted restore never leaves a truncated
+/// file where a good one (or nothing) used to be.
+async fn restore_file(ctx: &RestoreCtx, entry: &RestoreEntry) -> Result<u64> {
+    let hash = entry
+        .hash
+        .as_deref()
+        .ok_or_else(|| anyhow!("content not uploaded (pending or vanished before upload)"))?;
+    let manifest = ctx
+        .catalog
+        .file_manifest(hash)?
+        .ok_or_else(|| anyhow!("content {hash} was never stored"))?;
+    let sparse = ctx.catalog.file_extents(hash)?;
+
+    let target = target_path(&entry.path, ctx.into.as_deref());
+    let tmp = temp_path(&target).await?;
+
+    let result = async {
+        let mut file = fs::File::create(&tmp).await?;
+        let written = write_chunks(ctx, hash, &manifest, sparse.as_ref(), &mut file).await?;
+        file.sync_all().await?;
+        drop(file);
+        if let Some(metadata) = entry.metadata.clone() {
+            let tmp = tmp.clone();
+            tokio::task::spawn_blocking(move || apply_metadata(&tmp, &metadata, true)).await??;
+        }
+        fs::rename(&tmp, &target).await?;
+        Ok(written)
+    }
+    .await;
+
+    if result.is_err() {
+        // Best-effort cleanup so a failed write doesn't leave a temp behind.
+        let _ = fs::remove_file(&tmp).await;
+    }
+    result
+}
+
+/// Recreate a symlink pointing at its recorded target, via a temp link renamed
+/// into place like a file.
+async fn restore_symlink(ctx: &RestoreCtx, entry: &RestoreEntry, link_target: &Path) -> Result<()> {
+    let target = target_path(&entry.path, ctx.into.as_deref());
+    let tmp = temp_path(&target).await?;
+
+    let result = async {
+        fs::symlink(link_target, &tmp).await?;
+        if let Some(metadata) = entry.metadata.clone() {
+            let tmp = tmp.clone();
+            tokio::task::spawn_blocking(move || apply_metadata(&tmp, &metadata, false)).await??;
+        }
+        fs::rename(&tmp, &target).await?;
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp).await;
+    }
+    result
+}
+
+/// Recreate a FIFO, device node or socket entry with `mknod`, via a temp name
+/// renamed into place like a file. Creating a device node takes privileges
+/// (`CAP_MKNOD`); without them the entry fails with that reason and the rest
+/// of the restore goes on. A socket comes back as a bare entry: nothing is
+/// listening on it.
+async fn restore_node(
+    ctx: &RestoreCtx,
+    entry: &RestoreEntry,
+    file_type: FileType,
+    rdev: u64,
+) -> Result<()> {
+    let target = target_path(&entry.path, ctx.into.as_deref());
+    let tmp = temp_path(&target).await?;
+
+    let result = async {
+        let node = tmp.clone();
+        let metadata = entry.metadata.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            rustix::fs::mknodat(CWD, &node, file_type, Mode::from_raw_mode(0o600), rdev).map_err(
+                |err| match err {
+                    Errno::PERM => anyhow!("creating a device node needs root: {err}"),
+                    err => err.into(),
+                },
+            )?;
+            if let Some(metadata) = metadata {
+                apply_metadata(&node, &metadata, true)?;
+            }
+            Ok(())
+        })
+        .await??;
+        fs::rename(&tmp, &target).await?;
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp).await;
+    }
+    result
+}
+
+/// Recreate `entry` as another link to the already restored `first`, via a
+/// temp link renamed into place. The link shares the inode, so it already has
+/// the metadata restored on `first`.
+async fn restore_hardlink(ctx: &RestoreCtx, entry: &RestoreEntry, first: &Path) -> Result<()> {
+    let target = target_path(&entry.path, ctx.into.as_deref());
+    let tmp = temp_path(&target).await?;
+
+    let result = async {
+        fs::hard_link(target_path(first, ctx.into.as_deref()), &tmp).await?;
+        fs::rename(&tmp, &target).await?;
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp).await;
+    }
+    result
+}
+
+/// Re-apply an entry's recorded metadata to a restored entry, never following
+/// a symlink. Ownership is only restored when running as root (anyone else can
+/// only own what they write); it goes first, as `chown` clears setuid/setgid
+/// bits. Permissions go last, so a read-only file still takes its xattrs, and
+/// only with `chmod` (a symlink's own mode cannot be set). An xattr the target
+/// filesystem or privileges do not allow (e.g. `security.*`) is skipped with a
+/// warning rather than failing the entry.
+fn apply_metadata(path: &Path, metadata: &EntryMetadata, chmod: bool) -> Result<()> {
+    if rustix::process::geteuid().is_root() {
+        std::os::unix::fs::lchown(path, Some(metadata.uid), Some(metadata.gid))?;
+    }
+
+    // Neither xattrs nor permissions touch these; writing content did.
+    rustix::fs::utimensat(
+        CWD,
+        path,
+        &Timestamps {
+            last_access: timespec(metadata.atime_ns),
+            last_modification: timespec(metadata.mtime_ns),
+        },
+        AtFlags::SYMLINK_NOFOLLOW,
+    )?;
+
+    for (name, value) in &metadata.xattrs {
+        let name = OsStr::from_bytes(name);
+        if let Err(err) = xattr::set(path, name, value) {
+            tracing::warn!(
+                "Cannot restore xattr {} on {}: {err}",
+                name.display(),
+                path.display()
+            );
+        }
+    }
+
+    if chmod {
+        std::fs::set_permissions(path, Permissions::from_mode(metadata.mode))?;
+    }
+    Ok(())
+}
+
+/// Nanoseconds since the epoch (negative before it) as a `Timespec`.
+fn timespec(ns: i64) -> Timespec {
+    Timespec {
+        tv_sec: ns.div_euclid(1_000_000_000),
+        tv_nsec: ns.rem_euclid(1_000_000_000),
+    }
+}
+
+/// Fetch a manifest's chunks in order and append them to `file` (or, for a
+/// sparse file, place them at their extents), so memory stays at one chunk
+/// however large the file is. The reassembled content is
+/// checked against its whole-file id before the caller renames it into place.
+async fn write_chunks(
+    ctx: &RestoreCtx,
+    hash: &str,
+    manifest: &[ManifestChunk],
+    sparse: Option<&SparseMap>,
+    file: &mut fs::File,
+) -> Result<u64> {
+    let mut whole = blake3::Hasher::new_keyed(&ctx.naming_key);
+    let mut written = 0;
+    // A sparse file starts as one hole of its full length; only its data
+    // extents are written, so the holes stay holes.
+    let mut cursor = sparse.map(SparseMap::cursor);
+    if let Some(sparse) = sparse {
+        file.set_len(sparse.size).await?;
+    }
+
+    for chunk in manifest {
+        let content_key = ctx.content_key(chunk)?;
+        let plaintext = fetch_verified(ctx, chunk, content_key).await?;
+
+        whole.update(&plaintext);
+        match cursor.as_mut() {
+            Some(cursor) => write_extents(cursor, &plaintext, file).await?,
+            None => file.write_all(&plaintext).await?,
+        }
+        written += u64::try_from(plaintext.len())?;
+    }
+
+    let data_id = whole.finalize().to_hex().to_string();
+    let id = match sparse {
+        Some(sparse) => sparse.content_id(&data_id, &ctx.naming_key),
+        None => data_id,
+    };
+    if id != hash {
+        return Err(anyhow!("restored content does not match its id"));
+    }
+
+    Ok(written)
+}
+
+/// Write the next bytes of a sparse file's data stream at their offsets.
+async fn write_extents(
+    cursor: &mut ExtentCursor,
+    mut data: &[u8],
+    file: &mut fs::File,
+) -> Result<()> {
+    while !data.is_empty() {
+        let (offset, len) = cursor
+            .next_span(data.len())
+            .ok_or_else(|| anyhow!("restored content overruns its extents"))?;
+        let (span, rest) = data.split_at(len);
+        file.seek(SeekFrom::Start(offset)).await?;
+        file.write_all(span).await?;
+        data = rest;
+    }
+    Ok(())
+}
+
+/// Fetch, decrypt, and verify a chunk from the first destination holding a good
+/// copy — a range read of its pack, or the whole object for a chunk stored
+/// before packs. A destination whose copy is missing, fails to authenticate, or
+/// doesn't hash back to the chunk id is skipped in favour of the next one.
+async fn fetch_verified(
+    ctx: &RestoreCtx,
+    chunk: &ManifestChunk,
+    content_key: Zeroizing<[u8; 32]>,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let id = chunk.id.as_str();
+    let mut last_err = anyhow!("no destinations to read from");
+
+    for store in &ctx.stores {
+        let fetched = match &chunk.location {
+            Some(pack) => {
+                store
+                    .get_range(&pack.pack_id, pack.offset, pack.length)
+                    .await
+            }
+            None => store.get(id).await,
+        };
+        let blob = match fetched {
+            Ok(blob) => blob,
+            Err(err) => {
+                last_err = err;
+                continue;
+            }
+        };
+
+        let owned_id = id.to_string();
+        let key = content_key.clone();
+        let naming_key = ctx.naming_key.clone();
+        let opened = tokio::task::spawn_blocking(move || -> Result<Zeroizing<Vec<u8>>> {
+            let plaintext = open_content(&blob, &owned_id, &key)?;
+            if blake3_keyed_bytes(&plaintext, &naming_key) != owned_id {
+                return Err(anyhow!("restored content does not match its id"));
+            }
+            Ok(plaintext)
+        })
+        .await?;
+
+        match opened {
+            Ok(plaintext) => return Ok(plaintext),
+            Err(err) => {
+                tracing::warn!("Corrupt copy of {id} in a destination, trying the next: {err}");
+                last_err = err;
+            }
+        }
+    }
+
+    Err(last_err.context(format!("no destination holds a valid copy of {id}")))
+}
+
+/// Where a file is written: under `into` (keeping its absolute layout, so
+/// `/home/u/a.txt` lands at `<into>/home/u/a.txt`) or at its original path.
+fn target_path(path: &Path, into: Option<&Path>) -> PathBuf {
+    match into {
+        Some(root) => {
+            let relative: PathBuf = path
+                .components()
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .collect();
+            root.join(relative)
+        }
+        None => path.to_path_buf(),
+    }
+}
+
+/// A unique temp path next to `path`, creating the parent directory.
+async fn temp_path(path: &Path) -> Result<PathBuf> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("restore path has no parent: {}", path.display()))?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("restore path has no file name: {}", path.display()))?
+        .to_string_lossy();
+    fs::create_dir_all(parent).await?;
+
+    let seq = TMP_SEQ.fetch_add(1, Ordering::Relaxed);
+    Ok(parent.join(format!(
+        ".{file_name}.{}.{seq}.restore.tmp",
+        std::process::id()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::sqlite::PackLocation,
+        engine::{
+            edit::{EditBackupRequest, edit},
+            fixture::Fixture,
+            run::{IgnoreRules, RunBackupRequest, run},
+        },
+        storage::{fake_s3::FakeS3, local::LocalStore},
+        utils::crypto::content_keypair,
+    };
+    use bip39::{Language, Mnemonic};
+    use std::{
+        fs::{self as stdfs, FileTimes},
+        io::{Seek, Write},
+        os::unix::fs::MetadataExt,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    /// Back up `files` (relative paths under a source dir) to `dest_count`
+    /// filesystem destinations.
+    async fn build(files: &[(&str, &[u8])], dest_count: usize) -> Result<Fixture> {
+        build_with(files, dest_count, &[]).await
+    }
+
+    /// [`build`], plus `extra` configured destinations (e.g. `s3://…`) after the
+    /// filesystem ones.
+    async fn build_with(
+        files: &[(&str, &[u8])],
+        dest_count: usize,
+        extra: &[String],
+    ) -> Result<Fixture> {
+        let fx = Fixture::create(files, dest_count, extra)?;
+        for (name, _) in files {
+            // Old enough for `upload` to trust its signature, so a later `run`
+            // carries it forward instead of reading it again.
+            stdfs::File::options()
+                .write(true)
+                .open(fx.src.join(name))?
+                .set_modified(SystemTime::now() - Duration::from_hours(1))?;
+        }
+        fx.back_up().await?;
+        Ok(fx)
+    }
+
+    /// The pack holding the chunk of `contents`.
+    fn pack_of(fx: &Fixture, contents: &[u8]) -> Result<PackLocation> {
+        let id = blake3_keyed_bytes(contents, &fx.naming_key);
+        SqliteCatalog::open(&fx.cfg.join("t.db"))?
+            .chunk_location(&id)?
+            .ok_or_else(|| anyhow!("chunk {id} is not in a pack"))
+    }
+
+    /// Overwrite one chunk's bytes inside its pack in `store`, leaving the rest of
+    /// the pack intact.
+    async fn rot_chunk(store: &LocalStore, location: &PackLocation) -> Result<()> {
+        let mut pack = store.get(&location.pack_id).await?;
+        let start = usize::try_from(location.offset)?;
+        let end = start + usize::try_from(location.length)?;
+        pack.get_mut(start..end)
+            .ok_or_else(|| anyhow!("chunk outside its pack"))?
+            .fill(0);
+        store.put(&location.pack_id, &pack).await
+    }
+
+    #[tokio::test]
+    async fn restores_whole_snapshot_into_directory() -> Result<()> {
+        let fx = build(
+            &[
+                ("a.txt", b"alpha"),
+                ("nested/b.txt", b"beta"),
+                ("nested/dup.txt", b"alpha"),
+            ],
+            1,
+        )
+        .await?;
+        let out = fx.tmp.path().join("out");
+
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("expected a snapshot"))?;
+
+        assert_eq!(report.version, 1);
+        assert_eq!(report.restored_files, 3);
+        assert_eq!(report.restored_bytes, 14);
+        assert!(report.failed.is_empty());
+        assert_eq!(stdfs::read(fx.restored(&out, "a.txt"))?, b"alpha");
+        assert_eq!(stdfs::read(fx.restored(&out, "nested/b.txt"))?, b"beta");
+        assert_eq!(stdfs::read(fx.restored(&out, "nested/dup.txt"))?, b"alpha");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reassembles_multi_chunk_and_empty_files() -> Result<()> {
+        let mut large = vec![0_u8; 6 * 1024 * 1024];
+        blake3::Hasher::new()
+            .update(b"large")
+            .finalize_xof()
+            .fill(&mut large);
+        let fx = build(&[("large.bin", &large), ("empty", b"")], 1).await?;
+        let out = fx.tmp.path().join("out");
+
+        let catalog = SqliteCatalog::open(&fx.cfg.join("t.db"))?;
+        let manifest = catalog
+            .file_manifest(&blake3_keyed_bytes(&large, &fx.naming_key))?
+            .ok_or_else(|| anyhow!("no manifest"))?;
+        assert!(manifest.len() > 1, "expected several chunks");
+
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("expected a snapshot"))?;
+
+        assert!(report.failed.is_empty());
+        assert_eq!(report.restored_files, 2);
+        assert_eq!(stdfs::read(fx.restored(&out, "large.bin"))?, large);
+        assert!(stdfs::read(fx.restored(&out, "empty"))?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restores_a_single_file_by_id() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha"), ("b.txt", b"beta")], 1).await?;
+        let out = fx.tmp.path().join("out");
+
+        let catalog = SqliteCatalog::open(&fx.cfg.join("t.db"))?;
+        let id = catalog
+            .view_entries(1, None)?
+            .into_iter()
+            .find(|entry| entry.path.ends_with("b.txt"))
+            .ok_or_else(|| anyhow!("b.txt not recorded"))?
+            .id;
+
+        let report = restore(fx.restore_request(Some(ViewTarget::Id(id)), &out))
+            .await?
+            .ok_or_else(|| anyhow!("expected a snapshot"))?;
+
+        assert_eq!(report.restored_files, 1);
+        assert_eq!(stdfs::read(fx.restored(&out, "b.txt"))?, b"beta");
+        assert!(!fx.restored(&out, "a.txt").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restores_only_the_requested_subtree() -> Result<()> {
+        let fx = build(
+            &[
+                ("keep/a.txt", b"alpha"),
+                ("keep/deep/b.txt", b"beta"),
+                ("keepnot/c.txt", b"gamma"),
+            ],
+            1,
+        )
+        .await?;
+        let out = fx.tmp.path().join("out");
+
+        let target = ViewTarget::Path(fx.src.join("keep"));
+        let report = restore(fx.restore_request(Some(target), &out))
+            .await?
+            .ok_or_else(|| anyhow!("expected a snapshot"))?;
+
+        assert_eq!(report.restored_files, 2);
+        assert!(fx.restored(&out, "keep/deep/b.txt").is_file());
+        // A sibling sharing the prefix string is not part of the subtree.
+        assert!(!fx.restored(&out, "keepnot/c.txt").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_next_destination_when_copy_is_corrupt() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha")], 2).await?;
+        let out = fx.tmp.path().join("out");
+        rot_chunk(&fx.store(0)?, &pack_of(&fx, b"alpha")?).await?;
+
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("expected a snapshot"))?;
+
+        assert!(report.failed.is_empty());
+        assert_eq!(stdfs::read(fx.restored(&out, "a.txt"))?, b"alpha");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restores_from_s3_when_filesystem_copy_is_lost() -> Result<()> {
+        let server = FakeS3::start().await?;
+        let fx = build_with(
+            &[("a.txt", b"alpha")],
+            1,
+            &[server.destination("bucket", "backups")],
+        )
+        .await?;
+        let out = fx.tmp.path().join("out");
+        let pack = pack_of(&fx, b"alpha")?;
+        let first = fx.store(0)?;
+        first.remove(&pack.pack_id).await?;
+        assert!(!first.exists(&pack.pack_id).await?);
+
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("expected a snapshot"))?;
+
+        assert!(report.failed.is_empty());
+        assert_eq!(stdfs::read(fx.restored(&out, "a.txt"))?, b"alpha");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unreadable_chunk_is_reported_not_fatal() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha"), ("b.txt", b"beta")], 1).await?;
+        let out = fx.tmp.path().join("out");
+        // Both chunks share a pack; only alpha's range is damaged.
+        rot_chunk(&fx.store(0)?, &pack_of(&fx, b"alpha")?).await?;
+
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("expected a snapshot"))?;
+
+        assert_eq!(report.restored_files, 1);
+        assert_eq!(report.failed.len(), 1);
+        assert!(
+            report
+                .failed
+                .first()
+                .is_some_and(|failure| failure.path.ends_with("a.txt"))
+        );
+        assert!(!fx.restored(&out, "a.txt").exists());
+        assert_eq!(stdfs::read(fx.restored(&out, "b.txt"))?, b"beta");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn wrong_mnemonic_is_rejected() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha")], 1).await?;
+        let out = fx.tmp.path().join("out");
+
+        let mut request = fx.restore_request(None, &out);
+        request.mnemonic = Mnemonic::generate_in(Language::English, 12)?.into();
+
+        assert!(restore(request).await.is_err());
+        assert!(!out.exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn an_added_recipient_restores_with_their_own_mnemonic() -> Result<()> {
+        let fx = build(&[("before.txt", b"stored before")], 1).await?;
+        let escrow = Mnemonic::generate_in(Language::English, 12)?;
+        let escrow_key = hex::encode(content_keypair(&escrow)?.1.as_bytes());
+
+        let added = edit(EditBackupRequest {
+            name: "t".to_string(),
+            config_dir: fx.cfg.clone(),
+            add_directories: Vec::new(),
+            add_files: Vec::new(),
+            add_destinations: Vec::new(),
+            remove_directories: Vec::new(),
+            remove_files: Vec::new(),
+            remove_destinations: Vec::new(),
+            add_recipients: vec![escrow_key.clone()],
+            mnemonic: Some(fx.mnemonic.clone().into()),
+        })?;
+        assert_eq!(added.recipients, vec![escrow_key.clone()]);
+        assert_eq!(added.wrapped_keys, 1);
+
+        // Content uploaded afterwards is wrapped to the recipient as it is sealed.
+        stdfs::write(fx.src.join("after.txt"), b"stored after")?;
+        fx.back_up().await?;
+
+        let out = fx.tmp.path().join("out");
+        let mut request = fx.restore_request(None, &out);
+        request.mnemonic = escrow.into();
+        let report = restore(request)
+            .await?
+            .ok_or_else(|| anyhow!("no completed version"))?;
+        assert!(report.failed.is_empty());
+        assert_eq!(
+            stdfs::read(fx.restored(&out, "before.txt"))?,
+            b"stored before"
+        );
+        assert_eq!(
+            stdfs::read(fx.restored(&out, "after.txt"))?,
+            b"stored after"
+        );
+
+        // The backup's own mnemonic still restores.
+        let out = fx.tmp.path().join("own");
+        restore(fx.restore_request(None, &out)).await?;
+        assert_eq!(
+            stdfs::read(fx.restored(&out, "after.txt"))?,
+            b"stored after"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restores_an_older_version() -> Result<()> {
+        let fx = build(&[("a.txt", b"first")], 1).await?;
+        stdfs::write(fx.src.join("a.txt"), b"second")?;
+        fx.back_up().await?;
+
+        let out = fx.tmp.path().join("out");
+        let mut request = fx.restore_request(None, &out);
+        request.version = Some(1);
+        restore(request).await?;
+
+        assert_eq!(stdfs::read(fx.restored(&out, "a.txt"))?, b"first");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn content_not_yet_uploaded_is_reported() -> Result<()> {
+        let fx = build(&[("a.txt", b"stored")], 1).await?;
+        stdfs::write(fx.src.join("b.txt"), b"pending")?;
+        run(RunBackupRequest {
+            name: "t".to_string(),
+            config_dir: fx.cfg.clone(),
+            ignore_rules: IgnoreRules::backupignore_only(),
+            dry_run: false,
+            rehash: false,
+            progress: None,
+        })
+        .await?;
+
+        let out = fx.tmp.path().join("out");
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("nothing restored"))?;
+
+        assert_eq!(stdfs::read(fx.restored(&out, "a.txt"))?, b"stored");
+        assert_eq!(report.failed.len(), 1);
+        let failure = report.failed.first().ok_or_else(|| anyhow!("no failure"))?;
+        assert_eq!(failure.path, fx.src.join("b.txt"));
+        assert!(
+            failure.reason.contains("not uploaded"),
+            "{}",
+            failure.reason
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restores_permissions_timestamps_and_xattrs() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha")], 1).await?;
+        let source = fx.src.join("a.txt");
+        let original = stdfs::metadata(&source)?;
+
+        // Change only metadata: the content is the same, but the new version
+        // still gets its own entry.
+        stdfs::set_permissions(&source, Permissions::from_mode(0o640))?;
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let atime = UNIX_EPOCH + Duration::from_secs(1_100_000_000);
+        stdfs::File::open(&source)?
+            .set_times(FileTimes::new().set_accessed(atime).set_modified(mtime))?;
+        // Not every filesystem takes user xattrs; only check them where it does.
+        let has_xattrs = xattr::set(&source, "user.note", b"keep me").is_ok();
+        fx.back_up().await?;
+
+        let out = fx.tmp.path().join("out");
+        restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("nothing restored"))?;
+        let restored = fx.restored(&out, "a.txt");
+        let metadata = stdfs::metadata(&restored)?;
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(metadata.modified()?, mtime);
+        assert_eq!(metadata.accessed()?, atime);
+        if has_xattrs {
+            assert_eq!(
+                xattr::get(&restored, "user.note")?.as_deref(),
+                Some(b"keep me".as_slice())
+            );
+        }
+
+        // The first version keeps the metadata it was taken with.
+        let first = fx.tmp.path().join("first");
+        let mut request = fx.restore_request(None, &first);
+        request.version = Some(1);
+        restore(request)
+            .await?
+            .ok_or_else(|| anyhow!("nothing restored"))?;
+        let metadata = stdfs::metadata(fx.restored(&first, "a.txt"))?;
+        assert_eq!(
+            metadata.permissions().mode() & 0o7777,
+            original.permissions().mode() & 0o7777
+        );
+        assert_eq!(metadata.modified()?, original.modified()?);
+        assert_eq!(xattr::get(fx.restored(&first, "a.txt"), "user.note")?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn recreates_symlinks_with_their_targets() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha")], 1).await?;
+        std::os::unix::fs::symlink("a.txt", fx.src.join("to-a"))?;
+        std::os::unix::fs::symlink("/nowhere/at/all", fx.src.join("dangling"))?;
+        fx.back_up().await?;
+
+        let out = fx.tmp.path().join("out");
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("nothing restored"))?;
+
+        assert!(report.failed.is_empty());
+        assert_eq!(report.restored_files, 3);
+        assert_eq!(report.restored_bytes, 5);
+        let to_a = fx.restored(&out, "to-a");
+        assert!(stdfs::symlink_metadata(&to_a)?.is_symlink());
+        assert_eq!(stdfs::read_link(&to_a)?, PathBuf::from("a.txt"));
+        // A relative link resolves inside the restored tree.
+        assert_eq!(stdfs::read(&to_a)?, b"alpha");
+        // An absolute one keeps its target verbatim, even under `--into`.
+        assert_eq!(
+            stdfs::read_link(fx.restored(&out, "dangling"))?,
+            PathBuf::from("/nowhere/at/all")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn relinks_hardlinked_files() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha"), ("other.txt", b"alpha")], 1).await?;
+        stdfs::hard_link(fx.src.join("a.txt"), fx.src.join("b.txt"))?;
+        fx.back_up().await?;
+
+        let out = fx.tmp.path().join("out");
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("nothing restored"))?;
+
+        assert!(report.failed.is_empty());
+        assert_eq!(report.restored_files, 3);
+        // The second link is not written again.
+        assert_eq!(report.restored_bytes, 10);
+        let a = stdfs::metadata(fx.restored(&out, "a.txt"))?;
+        let b = stdfs::metadata(fx.restored(&out, "b.txt"))?;
+        assert_eq!(a.ino(), b.ino());
+        assert_eq!(a.nlink(), 2);
+        assert_eq!(stdfs::read(fx.restored(&out, "b.txt"))?, b"alpha");
+        // Same content but never linked: still its own file.
+        let other = stdfs::metadata(fx.restored(&out, "other.txt"))?;
+        assert_ne!(other.ino(), a.ino());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restores_sparse_files_with_their_holes() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha")], 1).await?;
+        let image = fx.src.join("disk.img");
+        let mut file = stdfs::File::create(&image)?;
+        file.set_len(16 << 20)?;
+        file.seek(SeekFrom::Start(4 << 20))?;
+        file.write_all(b"boot sector")?;
+        file.sync_all()?;
+        drop(file);
+        fx.back_up().await?;
+
+        let out = fx.tmp.path().join("out");
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("nothing restored"))?;
+
+        assert!(report.failed.is_empty());
+        // Only data was stored and written back, not 16 MiB of zeros.
+        assert!(report.restored_bytes < 1 << 20);
+        let restored = fx.restored(&out, "disk.img");
+        assert_eq!(stdfs::read(&restored)?, stdfs::read(&image)?);
+        let restored = stdfs::metadata(&restored)?;
+        assert_eq!(restored.len(), 16 << 20);
+        assert!(restored.blocks() * 512 < 1 << 20);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn recreates_special_files_or_reports_them() -> Result<()> {
+        use rustix::fs::{FileType, Mode};
+        use std::os::unix::fs::FileTypeExt;
+
+        let fx = build(&[("a.txt", b"alpha")], 1).await?;
+        rustix::fs::mknodat(
+            CWD,
+            fx.src.join("pipe"),
+            FileType::Fifo,
+            Mode::from_raw_mode(0o640),
+            0,
+        )?;
+        let _listener = std::os::unix::net::UnixListener::bind(fx.src.join("sock"))?;
+        let is_root = rustix::process::geteuid().is_root();
+        // A null device, where privileges allow making one.
+        let null = stdfs::metadata("/dev/null")?.rdev();
+        if is_root {
+            rustix::fs::mknodat(
+                CWD,
+                fx.src.join("null"),
+                FileType::CharacterDevice,
+                Mode::from_raw_mode(0o666),
+                null,
+            )?;
+        }
+        fx.back_up().await?;
+
+        let out = fx.tmp.path().join("out");
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("nothing restored"))?;
+
+        assert!(report.failed.is_empty());
+        let pipe = stdfs::symlink_metadata(fx.restored(&out, "pipe"))?;
+        assert!(pipe.file_type().is_fifo());
+        assert_eq!(pipe.permissions().mode() & 0o7777, 0o640);
+        assert!(
+            stdfs::symlink_metadata(fx.restored(&out, "sock"))?
+                .file_type()
+                .is_socket()
+        );
+        if is_root {
+            assert_eq!(report.restored_files, 4);
+            let device = stdfs::symlink_metadata(fx.restored(&out, "null"))?;
+            assert!(device.file_type().is_char_device());
+            assert_eq!(device.rdev(), null);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rebuilds_empty_directories_and_directory_metadata() -> Result<()> {
+        let fx = build(&[("nested/a.txt", b"alpha")], 1).await?;
+        let empty = fx.src.join("empty");
+        stdfs::create_dir(&empty)?;
+        stdfs::set_permissions(&empty, Permissions::from_mode(0o700))?;
+        let nested_mtime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        stdfs::File::open(fx.src.join("nested"))?
+            .set_times(FileTimes::new().set_modified(nested_mtime))?;
+        fx.back_up().await?;
+
+        let out = fx.tmp.path().join("out");
+        let report = restore(fx.restore_request(None, &out))
+            .await?
+            .ok_or_else(|| anyhow!("nothing restored"))?;
+
+        assert!(report.failed.is_empty());
+        assert_eq!(report.restored_files, 1);
+        // The source root, `nested` and `empty`.
+        assert_eq!(report.restored_dirs, 3);
+        let restored_empty = stdfs::metadata(fx.restored(&out, "empty"))?;
+        assert!(restored_empty.is_dir());
+        assert_eq!(restored_empty.permissions().mode() & 0o7777, 0o700);
+        // Set after its file was written into it.
+        assert_eq!(
+            stdfs::metadata(fx.restored(&out, "nested"))?.modified()?,
+            nested_mtime
+        );
+        assert_eq!(stdfs::read(fx.restored(&out, "nested/a.txt"))?, b"alpha");
+
+        Ok(())
+    }
+
+    #[test]
+    fn target_path_keeps_layout_under_into() {
+        assert_eq!(
+            target_path(Path::new("/home/u/a.txt"), Some(Path::new("/restore"))),
+            PathBuf::from("/restore/home/u/a.txt")
+        );
+        assert_eq!(
+            target_path(Path::new("/home/u/a.txt"), None),
+            PathBuf::from("/home/u/a.txt")
+        );
+    }
+}
@@ -1,17 +1,18 @@
 use crate::{
-    db::sqlite::{ScannedFile, SealedKeys, SqliteCatalog},
-    storage::local::LocalStore,
-    utils::{
-        crypto::seal_content,
-        hash::{blake3_keyed, blake3_keyed_bytes},
-    },
+    db::sqlite::{EntryKind, EntryMetadata, ScannedFile, SqliteCatalog, StatSignature},
+    engine::replicate::{KEEP_GENERATIONS, ReplicateReport, replicate_catalog},
 };
 use anyhow::{Result, anyhow};
 use futures::stream::{FuturesUnordered, StreamExt};
 use ignore::WalkBuilder;
 use std::{
     cmp,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    fs::Metadata,
+    os::unix::{
+        ffi::OsStringExt,
+        fs::{FileTypeExt, MetadataExt},
+    },
     path::{Path, PathBuf},
     sync::{Arc, Mutex, PoisonError},
 };
@@ -20,11 +21,10 @@ use tokio::{
     io::{self, AsyncWriteExt},
     sync::Semaphore,
 };
-use tracing::{debug, instrument, warn};
-use x25519_dalek::PublicKey;
+use tracing::{debug, instrument};
 use zeroize::Zeroizing;
 
-/// Per-backup naming key shared across scan workers to key content identifiers.
+/// Per-backup naming key shared across upload workers to key content identifiers.
 pub type NamingKey = Arc<Zeroizing<[u8; 32]>>;
 
 const BACKUP_IGNORE_FILE: &str = ".backupignore";
@@ -42,7 +42,8 @@ pub enum RunProgress {
         path: PathBuf,
     },
     WorkerFinished(usize),
-    /// Start of the compress/encrypt/store phase, with the number of new blobs.
+    /// Start of the chunk/compress/encrypt/store phase, with the number of
+    /// files to read.
     StorePhaseStarted(usize),
 }
 
@@ -85,27 +86,24 @@ pub struct RunBackupRequest {
     pub config_dir: PathBuf,
     pub ignore_rules: IgnoreRules,
     pub dry_run: bool,
+    /// Trust no stat signature: leave every file pending, so `upload` re-reads
+    /// it and verifies its content.
+    pub rehash: bool,
     pub progress: Option<ProgressCallback>,
-    pub naming_key: NamingKey,
 }
 
 pub struct RunBackupResult {
     pub version: i64,
+    /// Files and symlinks scanned.
     pub scanned_files: usize,
+    pub scanned_dirs: usize,
     pub skipped_entries: usize,
     pub skipped_files_log: PathBuf,
-    /// Number of new content blobs sealed and written this run.
-    pub stored_blobs: usize,
-    /// Number of usable destinations the blobs were written to.
-    pub destination_count: usize,
-}
-
-/// Result of the upload phase.
-struct UploadOutcome {
-    sealed: SealedKeys,
-    storable: Vec<ScannedFile>,
-    stored_blobs: usize,
-    skipped: usize,
+    /// Files new or changed since the previous version, left for `upload`
+    /// (including ones still pending from earlier versions).
+    pub pending_files: usize,
+    /// The catalog sealed to the destinations afterwards; `None` on a dry run.
+    pub catalog: Option<ReplicateReport>,
 }
 
 struct QueuedScan {
@@ -119,7 +117,12 @@ struct ScanResults {
     skipped_entries: usize,
 }
 
-/// Run a backup metadata scan.
+/// Snapshot the configured files into a new version (DESIGN §6.7).
+///
+/// Only metadata is read: each file's stat signature is compared with the
+/// previous version's, and anything new or changed is left pending for
+/// [`upload`](crate::engine::upload::upload) to read and store. The version's
+/// file set is pinned as soon as this returns.
 ///
 /// # Errors
 /// Returns an error if the configured backup cannot be scanned or the metadata database cannot be
@@ -149,16 +152,13 @@ pub async fn run(request: RunBackupRequest) -> Result<RunBackupResult> {
     } else {
         catalog.create_version()?
     };
-    let public_key = catalog.public_key()?;
-
-    debug!("Public Key: {:?}", hex::encode(public_key));
 
     let queued_scan = queue_scan_tasks(
         &catalog.configured_directories()?,
+        &catalog.configured_files()?,
         request.ignore_rules,
         request.progress.clone(),
         &skipped_files_log,
-        &request.naming_key,
     )
     .await?;
 
@@ -166,296 +166,200 @@ pub async fn run(request: RunBackupRequest) -> Result<RunBackupResult> {
         progress(RunProgress::FilesDiscovered(queued_scan.queued_files));
     }
 
-    let scan_results = collect_scan_results(
+    let mut scan_results = collect_scan_results(
         queued_scan.tasks,
         request.progress.as_ref(),
         &skipped_files_log,
         queued_scan.skipped_entries,
     )
     .await?;
-    let mut skipped_entries = scan_results.skipped_entries;
-    let scanned_file_count = scan_results.files.len();
-
-    let mut stored_blobs = 0;
-    let mut destination_count = 0;
-
-    if !request.dry_run {
-        let (sb, dc, upload_skipped) = store_and_record(UploadCtx {
-            catalog: &catalog,
-            public_key,
-            naming_key: &request.naming_key,
-            files: &scan_results.files,
-            version: backup_version,
-            scan_skipped: skipped_entries,
-            skipped_files_log: &skipped_files_log,
-            progress: request.progress.as_ref(),
-        })
-        .await?;
-        stored_blobs = sb;
-        destination_count = dc;
-        skipped_entries += upload_skipped;
-    }
+    keep_hardlink_groups(&mut scan_results.files);
+    let skipped_entries = scan_results.skipped_entries;
+    let scanned_dir_count = scan_results
+        .files
+        .iter()
+        .filter(|scanned| scanned.kind == EntryKind::Dir)
+        .count();
+    let scanned_file_count = scan_results.files.len() - scanned_dir_count;
+
+    let pending_files = if request.dry_run {
+        0
+    } else {
+        record_snapshot(
+            &catalog,
+            backup_version,
+            scan_results.files,
+            skipped_entries == 0,
+            request.rehash,
+            request.progress.as_ref(),
+        )
+        .await?
+    };
 
     if skipped_entries == 0 {
         cleanup_skipped_log(&skipped_files_log).await?;
     }
 
+    let catalog = if request.dry_run {
+        None
+    } else {
+        Some(
+            replicate_catalog(
+                &catalog,
+                &request.name,
+                &request.config_dir,
+                KEEP_GENERATIONS,
+            )
+            .await?,
+        )
+    };
+
     Ok(RunBackupResult {
         version: backup_version,
         scanned_files: scanned_file_count,
+        scanned_dirs: scanned_dir_count,
         skipped_entries,
         skipped_files_log,
-        stored_blobs,
-        destination_count,
+        pending_files,
+        catalog,
     })
 }
 
-/// Inputs to [`store_and_record`].
-struct UploadCtx<'a> {
-    catalog: &'a SqliteCatalog,
-    public_key: PublicKey,
-    naming_key: &'a NamingKey,
-    files: &'a [ScannedFile],
+/// Record the scan as `version`'s file set; returns how many entries are left
+/// pending. Files are only closed as deleted when the scan skipped nothing, so
+/// a transient read error never drops a file from the version.
+async fn record_snapshot(
+    catalog: &SqliteCatalog,
     version: i64,
-    scan_skipped: usize,
-    skipped_files_log: &'a Path,
-    progress: Option<&'a ProgressCallback>,
-}
-
-/// Seal + store new content to all destinations, then record the scan metadata.
-/// Returns `(stored_blobs, destination_count, upload_skipped)`.
-async fn store_and_record(ctx: UploadCtx<'_>) -> Result<(usize, usize, usize)> {
-    let UploadCtx {
-        catalog,
-        public_key,
-        naming_key,
-        files,
-        version,
-        scan_skipped,
-        skipped_files_log,
-        progress,
-    } = ctx;
-
-    // Build a store per usable (filesystem) destination; S3 is not wired yet.
-    let mut stores = Vec::new();
-    for dest in catalog.configured_destinations()? {
-        if dest.starts_with("s3://") {
-            warn!("S3 destination not yet supported, skipping: {dest}");
-        } else {
-            stores.push(LocalStore::new(dest));
-        }
-    }
-    let destination_count = stores.len();
-
-    // Upload phase: seal + store new content; metadata-only if no destinations.
-    let upload = if stores.is_empty() {
-        UploadOutcome {
-            sealed: SealedKeys::new(),
-            storable: files.to_vec(),
-            stored_blobs: 0,
-            skipped: 0,
-        }
-    } else {
-        upload_new_content(
-            catalog,
-            &stores,
-            public_key,
-            naming_key,
-            files,
-            skipped_files_log,
-            progress,
-        )
-        .await?
-    };
-    let stored_blobs = upload.stored_blobs;
-    let upload_skipped = upload.skipped;
-
+    files: Vec<ScannedFile>,
+    close_missing: bool,
+    rehash: bool,
+    progress: Option<&ProgressCallback>,
+) -> Result<usize> {
     let catalog = catalog.clone();
     let progress = progress.cloned();
     if let Some(progress) = &progress {
-        progress(RunProgress::MetadataWriteStarted(upload.storable.len()));
+        progress(RunProgress::MetadataWriteStarted(files.len()));
     }
 
-    let sealed = upload.sealed;
-    let storable = upload.storable;
-    let close_missing = scan_skipped == 0 && upload_skipped == 0;
     tokio::task::spawn_blocking(move || {
         let progress_callback = progress.as_ref().map(|progress| -> Box<dyn Fn(usize)> {
             let progress = progress.clone();
             Box::new(move |written| progress(RunProgress::MetadataFilesWritten(written)))
         });
 
-        catalog.record_scan(
-            public_key,
-            &sealed,
+        catalog.record_snapshot(
             version,
-            &storable,
+            &files,
             close_missing,
+            rehash,
             progress_callback.as_deref(),
         )
     })
-    .await??;
-
-    Ok((stored_blobs, destination_count, upload_skipped))
+    .await?
 }
 
-/// Seal + store every new content id (one not already in the catalog) to all
-/// destinations, in parallel with a bounded worker pool (same bound as scanning,
-/// so memory/CPU stay in check). Returns the wrapped keys to record and the files
-/// safe to record (those whose content didn't change since the scan).
-async fn upload_new_content(
-    catalog: &SqliteCatalog,
-    stores: &[LocalStore],
-    public_key: PublicKey,
-    naming_key: &NamingKey,
-    files: &[ScannedFile],
-    skipped_files_log: &Path,
-    progress: Option<&ProgressCallback>,
-) -> Result<UploadOutcome> {
-    // Distinct content ids not yet stored, with a source path to read. Load the
-    // set of already-stored ids in one query rather than a blocking catalog hit
-    // per scanned file (which would stall the async runtime on large backups).
-    let stored_ids: HashSet<String> = catalog.all_content_ids()?.into_iter().collect();
-    let mut seen = HashSet::new();
-    let mut new_content: Vec<(String, PathBuf)> = Vec::new();
-    for file in files {
-        if seen.insert(file.hash.clone()) && !stored_ids.contains(&file.hash) {
-            new_content.push((file.hash.clone(), file.path.clone()));
-        }
-    }
-
-    if let Some(progress) = progress {
-        progress(RunProgress::StorePhaseStarted(new_content.len()));
-    }
-
-    let worker_count = scan_worker_count();
-    let semaphore = Arc::new(Semaphore::new(worker_count));
-    let available_workers = new_worker_pool(worker_count);
-    let stores = Arc::new(stores.to_vec());
-
-    let tasks = FuturesUnordered::new();
-    for (hash, path) in new_content {
-        let semaphore = semaphore.clone();
-        let available_workers = available_workers.clone();
-        let stores = stores.clone();
-        let naming_key = naming_key.clone();
-        let log = skipped_files_log.to_path_buf();
-        let progress = progress.cloned();
-
-        tasks.push(tokio::spawn(async move {
-            let _permit = semaphore.acquire_owned().await?;
-            // `worker` releases its id to the pool on drop (incl. on panic/error).
-            let worker = acquire_worker_id(&available_workers)?;
-            if let Some(progress) = &progress {
-                progress(RunProgress::ProcessingFile {
-                    worker_id: worker.id(),
-                    path: path.clone(),
-                });
-            }
-
-            let result = seal_one(&stores, public_key, &naming_key, &hash, &path, &log).await;
-
-            if let Some(progress) = &progress {
-                progress(RunProgress::WorkerFinished(worker.id()));
-            }
-
-            result.map(|wrapped| (hash, wrapped))
-        }));
-    }
+/// The stat signature `run` compares to decide whether a file changed.
+///
+/// # Errors
+/// Returns an error if a timestamp does not fit in nanoseconds.
+pub(crate) fn stat_signature(metadata: &Metadata) -> Result<StatSignature> {
+    Ok(StatSignature {
+        size: metadata.len(),
+        mtime_ns: nanos(metadata.mtime(), metadata.mtime_nsec())?,
+        inode: metadata.ino(),
+        ctime_ns: nanos(metadata.ctime(), metadata.ctime_nsec())?,
+    })
+}
 
-    let mut sealed = SealedKeys::new();
-    let mut skipped_hashes = HashSet::new();
-    let mut tasks = tasks;
-    while let Some(joined) = tasks.next().await {
-        let (hash, wrapped) = joined??;
-        match wrapped {
-            Some(wrapped) => {
-                sealed.insert(hash, wrapped);
-            }
-            None => {
-                skipped_hashes.insert(hash);
+/// The POSIX metadata restore re-applies: permission bits, owner, timestamps,
+/// and extended attributes (which carry POSIX ACLs on Linux). Xattrs that
+/// cannot be listed — e.g. the filesystem has none — are recorded as none. A
+/// symlink's are its own, not its target's.
+///
+/// # Errors
+/// Returns an error if a timestamp does not fit in nanoseconds.
+pub(crate) fn capture_metadata(path: &Path, metadata: &Metadata) -> Result<EntryMetadata> {
+    let xattrs = match xattr::list(path) {
+        Ok(names) => {
+            let mut xattrs = Vec::new();
+            for name in names {
+                // Gone between list and get: skip it like the listing missed it.
+                if let Ok(Some(value)) = xattr::get(path, &name) {
+                    xattrs.push((name.into_vec(), value));
+                }
             }
+            xattrs.sort();
+            xattrs
         }
-        if let Some(progress) = progress {
-            progress(RunProgress::FileFinished);
+        Err(err) => {
+            debug!("Cannot list xattrs of {}: {err}", path.display());
+            Vec::new()
         }
-    }
+    };
 
-    let stored_blobs = sealed.len();
-    let storable = files
-        .iter()
-        .filter(|file| !skipped_hashes.contains(&file.hash))
-        .cloned()
-        .collect();
-
-    Ok(UploadOutcome {
-        sealed,
-        storable,
-        stored_blobs,
-        skipped: skipped_hashes.len(),
+    Ok(EntryMetadata {
+        mode: metadata.mode() & 0o7777,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        mtime_ns: nanos(metadata.mtime(), metadata.mtime_nsec())?,
+        atime_ns: nanos(metadata.atime(), metadata.atime_nsec())?,
+        xattrs,
+        // Only a candidate until `keep_hardlink_groups` finds another link.
+        hardlink: (metadata.is_file() && metadata.nlink() > 1)
+            .then(|| (metadata.dev(), metadata.ino())),
     })
 }
 
-/// Read, verify, compress+encrypt, and store one content id to every store.
-/// `Ok(None)` means the file was skipped (unreadable or changed since the scan).
-async fn seal_one(
-    stores: &[LocalStore],
-    public_key: PublicKey,
-    naming_key: &NamingKey,
-    hash: &str,
-    path: &Path,
-    skipped_files_log: &Path,
-) -> Result<Option<(Vec<u8>, [u8; 32])>> {
-    let bytes = match tokio::fs::read(path).await {
-        Ok(bytes) => bytes,
-        Err(err) => {
-            log_skipped_entry(
-                skipped_files_log,
-                &format!("Read error for {}: {err}", path.display()),
-            )
-            .await?;
-            return Ok(None);
-        }
-    };
-
-    // Verify the bytes still match the scanned id (changed-during-backup).
-    if blake3_keyed_bytes(&bytes, naming_key) != hash {
-        log_skipped_entry(
-            skipped_files_log,
-            &format!("Changed during backup, skipped: {}", path.display()),
-        )
-        .await?;
-        return Ok(None);
+/// Keep the hardlink identity only on files that share it with another
+/// scanned file; a file whose other links are all outside the backup is
+/// recorded as a plain file.
+fn keep_hardlink_groups(files: &mut [ScannedFile]) {
+    let mut links: HashMap<(u64, u64), usize> = HashMap::new();
+    for key in files.iter().filter_map(|scanned| scanned.metadata.hardlink) {
+        *links.entry(key).or_default() += 1;
     }
-
-    let seal_id = hash.to_string();
-    let sealed =
-        tokio::task::spawn_blocking(move || seal_content(&bytes, &public_key, &seal_id)).await??;
-
-    // Write to every destination. If a later destination fails, earlier ones keep
-    // the blob: that's a tolerated orphan, not corruption — the run aborts before
-    // `record_scan`, so the version is never completed, a re-run overwrites the
-    // blob (LocalStore replaces), and a future `prune` reclaims any leftovers.
-    for store in stores {
-        store.put(hash, &sealed.blob).await?;
+    for scanned in files {
+        if let Some(key) = scanned.metadata.hardlink
+            && links.get(&key).is_none_or(|count| *count < 2)
+        {
+            scanned.metadata.hardlink = None;
+        }
     }
+}
 
-    Ok(Some((sealed.wrapped_key, sealed.ephemeral_public_key)))
+/// A unix `(seconds, nanoseconds)` timestamp as nanoseconds since the epoch.
+fn nanos(seconds: i64, nanoseconds: i64) -> Result<i64> {
+    seconds
+        .checked_mul(1_000_000_000)
+        .and_then(|ns| ns.checked_add(nanoseconds))
+        .ok_or_else(|| anyhow!("timestamp out of range: {seconds}s"))
 }
 
+/// Queue a stat task for every directory, file and symlink under the
+/// configured directories plus every individually configured file. Missing configured
+/// files and walk errors are logged to the skipped-files log and counted, not
+/// fatal.
 async fn queue_scan_tasks(
     directories: &[PathBuf],
+    files: &[PathBuf],
     ignore_rules: IgnoreRules,
     progress: Option<ProgressCallback>,
     skipped_files_log: &Path,
-    naming_key: &NamingKey,
 ) -> Result<QueuedScan> {
     let tasks = FuturesUnordered::new();
     let worker_count = scan_worker_count();
-    let semaphore = Arc::new(Semaphore::new(worker_count));
-    let available_workers = new_worker_pool(worker_count);
+    let spawner = ScanSpawner {
+        semaphore: Arc::new(Semaphore::new(worker_count)),
+        available_workers: new_worker_pool(worker_count),
+        skipped_files_log: skipped_files_log.to_path_buf(),
+        progress,
+    };
     let mut queued_files = 0_usize;
     let mut skipped_entries = 0_usize;
+    // Walked paths, so a configured file inside a configured directory is only
+    // scanned once.
+    let mut queued_paths = HashSet::new();
 
     for directory in directories {
         if !directory.exists() {
@@ -467,21 +371,11 @@ async fn queue_scan_tasks(
         for file_result in iterator {
             match file_result {
                 Ok(file_path) => {
-                    if file_path.exists() {
+                    // `symlink_metadata`, so a dangling symlink still counts.
+                    if file_path.symlink_metadata().is_ok() {
                         queued_files += 1;
-                        let semaphore = semaphore.clone();
-                        let log_file = skipped_files_log.to_path_buf();
-                        let progress = progress.clone();
-                        let available_workers = available_workers.clone();
-                        let naming_key = naming_key.clone();
-
-                        tasks.push(tokio::spawn(async move {
-                            let _permit = semaphore.acquire_owned().await?;
-                            // `worker` releases its id on drop (incl. on panic/error).
-                            let worker = acquire_worker_id(&available_workers)?;
-                            process_file(file_path, log_file, progress, worker.id(), &naming_key)
-                                .await
-                        }));
+                        queued_paths.insert(file_path.clone());
+                        tasks.push(spawner.spawn(file_path));
                     } else {
                         log_skipped_entry(
                             skipped_files_log,
@@ -499,6 +393,30 @@ async fn queue_scan_tasks(
         }
     }
 
+    // Individually configured files are scanned as-is: ignore rules only apply
+    // to directory walks, since naming a file explicitly is an opt-in. A
+    // configured symlink or special file is recorded as itself, like a walked one.
+    for file_path in files {
+        if queued_paths.contains(file_path) {
+            continue;
+        }
+
+        if file_path
+            .symlink_metadata()
+            .is_ok_and(|metadata| !metadata.is_dir())
+        {
+            queued_files += 1;
+            tasks.push(spawner.spawn(file_path.clone()));
+        } else {
+            log_skipped_entry(
+                skipped_files_log,
+                &format!("Missing file: {}", file_path.display()),
+            )
+            .await?;
+            skipped_entries += 1;
+        }
+    }
+
     Ok(QueuedScan {
         tasks,
         queued_files,
@@ -506,26 +424,50 @@ async fn queue_scan_tasks(
     })
 }
 
+/// Shared state for spawning bounded stat tasks during the scan.
+struct ScanSpawner {
+    semaphore: Arc<Semaphore>,
+    available_workers: WorkerPool,
+    skipped_files_log: PathBuf,
+    progress: Option<ProgressCallback>,
+}
+
+impl ScanSpawner {
+    fn spawn(&self, file_path: PathBuf) -> tokio::task::JoinHandle<Result<Option<ScannedFile>>> {
+        let semaphore = self.semaphore.clone();
+        let log_file = self.skipped_files_log.clone();
+        let progress = self.progress.clone();
+        let available_workers = self.available_workers.clone();
+
+        tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            // `worker` releases its id on drop (incl. on panic/error).
+            let worker = acquire_worker_id(&available_workers)?;
+            process_file(file_path, log_file, progress, worker.id()).await
+        })
+    }
+}
+
 /// Pool of free worker ids (used only for labelling progress rows). A plain
 /// `std::sync::Mutex` is fine: the critical section is just a pop/push, never held
 /// across an `.await`.
-type WorkerPool = Arc<Mutex<Vec<usize>>>;
+pub(crate) type WorkerPool = Arc<Mutex<Vec<usize>>>;
 
 #[must_use]
-fn new_worker_pool(worker_count: usize) -> WorkerPool {
+pub(crate) fn new_worker_pool(worker_count: usize) -> WorkerPool {
     Arc::new(Mutex::new((1..=worker_count).rev().collect()))
 }
 
 /// A borrowed worker id that returns itself to the pool on drop, so a panicking
 /// task can't leak its id (which would shrink the labelled-worker space on a retry
 /// within the same process).
-struct WorkerId {
+pub(crate) struct WorkerId {
     id: usize,
     pool: WorkerPool,
 }
 
 impl WorkerId {
-    fn id(&self) -> usize {
+    pub(crate) fn id(&self) -> usize {
         self.id
     }
 }
@@ -541,7 +483,7 @@ impl Drop for WorkerId {
     }
 }
 
-fn acquire_worker_id(pool: &WorkerPool) -> Result<WorkerId> {
+pub(crate) fn acquire_worker_id(pool: &WorkerPool) -> Result<WorkerId> {
     let id = pool
         .lock()
         .unwrap_or_else(PoisonError::into_inner)
@@ -595,7 +537,10 @@ async fn collect_scan_results(
     })
 }
 
-// Returns an iterator over files in a directory, using backup-specific ignore rules by default.
+// Returns an iterator over every entry of a tree — the root directory
+// included, and symlinks and special files as themselves — using
+// backup-specific ignore rules by default. Symlinks are never followed: a link
+// to a directory would duplicate it, loop, or escape the backup root.
 fn walk_directory(
     base_dir: &Path,
     ignore_rules: IgnoreRules,
@@ -603,7 +548,7 @@ fn walk_directory(
     let mut builder = WalkBuilder::new(base_dir);
 
     builder
-        .follow_links(true)
+        .follow_links(false)
         .hidden(false)
         .git_exclude(false)
         .git_global(false)
@@ -616,7 +561,10 @@ fn walk_directory(
     }
 
     builder.build().filter_map(|entry| match entry {
-        Ok(e) if e.path().is_file() => Some(Ok(e.into_path())),
+        // The filesystem root has no name to record it under.
+        Ok(e) if e.path().file_name().is_some() && e.file_type().is_some() => {
+            Some(Ok(e.into_path()))
+        }
         Ok(_) => None,
         Err(err) => Some(Err(err)),
     })
@@ -627,7 +575,6 @@ async fn process_file(
     skipped_files_log: PathBuf,
     progress: Option<ProgressCallback>,
     worker_id: usize,
-    naming_key: &NamingKey,
 ) -> Result<Option<ScannedFile>> {
     if let Some(progress) = &progress {
         progress(RunProgress::ProcessingFile {
@@ -636,40 +583,55 @@ async fn process_file(
         });
     }
 
-    let hash = match calculate_hash(file_path.clone(), naming_key).await {
-        Ok(h) => h,
-        Err(e) => {
-            log_skipped_entry(
-                &skipped_files_log,
-                &format!("Hash error for {}: {e}", file_path.display()),
-            )
-            .await?;
-            if let Some(progress) = &progress {
-                progress(RunProgress::WorkerFinished(worker_id));
+    // Listing xattrs is a blocking syscall per file, like the stat itself. A
+    // symlink is stat'ed as itself, never its target; a FIFO is never opened.
+    let path = file_path.clone();
+    let scanned = tokio::task::spawn_blocking(move || -> Result<ScannedFile> {
+        let metadata = std::fs::symlink_metadata(&path)?;
+        let kind = if metadata.is_symlink() {
+            EntryKind::Symlink(std::fs::read_link(&path)?)
+        } else if metadata.is_file() {
+            EntryKind::File
+        } else if metadata.is_dir() {
+            EntryKind::Dir
+        } else {
+            let file_type = metadata.file_type();
+            if file_type.is_fifo() {
+                EntryKind::Fifo
+            } else if file_type.is_char_device() {
+                EntryKind::CharDevice(metadata.rdev())
+            } else if file_type.is_block_device() {
+                EntryKind::BlockDevice(metadata.rdev())
+            } else if file_type.is_socket() {
+                EntryKind::Socket
+            } else {
+                return Err(anyhow!("unknown file type"));
             }
-            return Ok(None);
-        }
-    };
+        };
+        Ok(ScannedFile {
+            kind,
+            signature: stat_signature(&metadata)?,
+            metadata: capture_metadata(&path, &metadata)?,
+            path,
+        })
+    })
+    .await?;
 
     if let Some(progress) = &progress {
         progress(RunProgress::WorkerFinished(worker_id));
     }
 
-    Ok(Some(ScannedFile {
-        path: file_path,
-        hash,
-    }))
-}
-
-async fn calculate_hash(file_path: PathBuf, naming_key: &NamingKey) -> Result<String> {
-    let file_path_clone = file_path.clone();
-    let naming_key = naming_key.clone();
-    tokio::task::spawn_blocking(move || blake3_keyed(&file_path, &naming_key))
-        .await?
-        .map_err(|err| {
-            let path = file_path_clone.display();
-            anyhow!("Hash computation for: {path} failed: {err}")
-        })
+    match scanned {
+        Ok(scanned) => Ok(Some(scanned)),
+        Err(err) => {
+            log_skipped_entry(
+                &skipped_files_log,
+                &format!("Stat error for {}: {err}", file_path.display()),
+            )
+            .await?;
+            Ok(None)
+        }
+    }
 }
 
 // Check if the log file is empty
@@ -678,7 +640,7 @@ async fn is_log_file_empty(log_file: &Path) -> io::Result<bool> {
     Ok(metadata.len() == 0)
 }
 
-async fn cleanup_skipped_log(skipped_files_log: &Path) -> Result<()> {
+pub(crate) async fn cleanup_skipped_log(skipped_files_log: &Path) -> Result<()> {
     if is_log_file_empty(skipped_files_log).await? {
         remove_file(skipped_files_log).await?;
     }
@@ -686,7 +648,7 @@ async fn cleanup_skipped_log(skipped_files_log: &Path) -> Result<()> {
     Ok(())
 }
 
-async fn log_skipped_entry(log_file: &Path, message: &str) -> Result<()> {
+pub(crate) async fn log_skipped_entry(log_file: &Path, message: &str) -> Result<()> {
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
@@ -702,7 +664,8 @@ async fn log_skipped_entry(log_file: &Path, message: &str) -> Result<()> {
 mod tests {
     use super::*;
     use crate::{
-        db::sqlite::{RestoreEntry, SqliteCatalog},
+        db::sqlite::{PackLocation, RestoreEntry, SqliteCatalog},
+        engine::upload::{UploadRequest, upload},
         utils::crypto::{
             content_key_aad, content_keypair, decrypt, generate_naming_key, seal_naming_key,
         },
@@ -710,7 +673,7 @@ mod tests {
     use anyhow::Context;
     use bip39::{Language, Mnemonic};
     use std::{collections::BTreeMap, fs};
-    use x25519_dalek::{PublicKey, StaticSecret};
+    use x25519_dalek::PublicKey;
 
     struct ExpectedVersion {
         version: i64,
@@ -749,11 +712,6 @@ mod tests {
         }
     }
 
-    fn public_key() -> PublicKey {
-        let private_key = StaticSecret::from([7_u8; 32]);
-        PublicKey::from(&private_key)
-    }
-
     #[test]
     fn scan_worker_count_is_in_range() {
         // Always at least one worker, never more than the worker-id space (u8).
@@ -823,11 +781,27 @@ mod tests {
             .iter()
             .map(|(path, hash)| RestoreEntry {
                 path: path.clone(),
-                hash: hash.clone(),
+                kind: EntryKind::File,
+                hash: Some(hash.clone()),
+                metadata: None,
             })
             .collect()
     }
 
+    /// File restore entries with their metadata left out, to compare paths
+    /// and content alone.
+    fn restore_contents(catalog: &SqliteCatalog, version: i64) -> Result<Vec<RestoreEntry>> {
+        Ok(catalog
+            .restore_entries(version)?
+            .into_iter()
+            .filter(|entry| entry.kind == EntryKind::File)
+            .map(|entry| RestoreEntry {
+                metadata: None,
+                ..entry
+            })
+            .collect())
+    }
+
     async fn run_and_record_expected(
         config_dir: &Path,
         name: &str,
@@ -841,8 +815,15 @@ mod tests {
             config_dir: config_dir.to_path_buf(),
             ignore_rules: IgnoreRules::backupignore_only(),
             dry_run: false,
+            rehash: false,
             progress: None,
+        })
+        .await?;
+        upload(UploadRequest {
+            name: name.to_string(),
+            config_dir: config_dir.to_path_buf(),
             naming_key: naming_key.clone(),
+            progress: None,
         })
         .await?;
         let expected_version = i64::try_from(expected_versions.len() + 1)?;
@@ -878,7 +859,7 @@ mod tests {
             let path = entry.path.strip_prefix(root).unwrap_or(&entry.path);
             output.push_str(&path.display().to_string());
             output.push(' ');
-            output.push_str(&entry.hash);
+            output.push_str(entry.hash.as_deref().unwrap_or("(pending)"));
         }
 
         output
@@ -889,7 +870,7 @@ mod tests {
         root: &Path,
         expected: &ExpectedVersion,
     ) -> Result<()> {
-        let actual = catalog.restore_entries(expected.version)?;
+        let actual = restore_contents(catalog, expected.version)?;
 
         assert_eq!(
             format_entries(root, &actual),
@@ -917,7 +898,7 @@ mod tests {
         catalog: &SqliteCatalog,
         mnemonic: &Mnemonic,
     ) -> Result<()> {
-        let (hash, encrypted_key, ephemeral_public_key) = catalog.first_wrapped_file_key()?;
+        let (hash, encrypted_key, ephemeral_public_key) = catalog.first_wrapped_chunk_key()?;
         let file_key = decrypt(
             &encrypted_key,
             &ephemeral_public_key,
@@ -1111,6 +1092,11 @@ mod tests {
         catalog.save_public_key(&public_key)?;
         catalog.save_sealed_naming_key(&seal_naming_key(&naming_key, &public_key)?)?;
         catalog.save_directories(&paths.sources())?;
+        catalog.save_destinations(&[temp_dir
+            .path()
+            .join("store")
+            .to_string_lossy()
+            .to_string()])?;
 
         record_initial_versions(
             temp_dir.path(),
@@ -1146,16 +1132,19 @@ mod tests {
 
         assert_eq!(catalog.count_rows("Files")?, 6);
         assert_eq!(catalog.count_unique_hashes()?, 6);
-        assert_eq!(catalog.count_rows("FileNames")?, 10);
+        // Small files are one chunk each, so chunks mirror file contents.
+        assert_eq!(catalog.count_rows("Chunks")?, 6);
+        assert_eq!(catalog.count_rows("FileChunks")?, 6);
+        assert_eq!(catalog.count_file_names()?, 10);
         assert_eq!(catalog.count_active_file_names()?, 3);
         assert_eq!(
-            catalog.restore_entries(10)?,
+            restore_contents(&catalog, 10)?,
             snapshot_from_expected(&expected)
         );
 
         assert_recovery_material_is_not_stored(&catalog)?;
         assert_file_key_can_be_unwrapped(&catalog, &mnemonic)
-            .context("stored file key should unwrap with recovery mnemonic")?;
+            .context("stored chunk key should unwrap with recovery mnemonic")?;
 
         Ok(())
     }
@@ -1168,29 +1157,19 @@ mod tests {
     }
 
     fn scan_files(catalog: &SqliteCatalog, version: i64, files: &[(&str, &str)]) -> Result<()> {
-        let scanned_files = files
+        let files = files
             .iter()
-            .map(|(name, hash)| ScannedFile {
-                path: PathBuf::from(format!("/backup/{name}")),
-                hash: (*hash).to_string(),
-            })
+            .map(|(name, hash)| (PathBuf::from(format!("/backup/{name}")), *hash))
             .collect::<Vec<_>>();
 
-        catalog.record_scan(
-            public_key(),
-            &SealedKeys::new(),
-            version,
-            &scanned_files,
-            true,
-            None,
-        )
+        catalog.record_contents(version, &files, true)
     }
 
     fn restore_hashes(catalog: &SqliteCatalog, version: i64) -> Result<Vec<String>> {
         Ok(catalog
             .restore_entries(version)?
             .into_iter()
-            .map(|entry| entry.hash)
+            .filter_map(|entry| entry.hash)
             .collect())
     }
 
@@ -1198,6 +1177,7 @@ mod tests {
         let mut files = walk_directory(root, ignore_rules)
             .collect::<Result<Vec<_>, _>>()?
             .into_iter()
+            .filter(|path| !path.is_dir())
             .map(|path| {
                 path.strip_prefix(root)
                     .map(Path::to_path_buf)
@@ -1345,148 +1325,308 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn run_stores_blobs_that_decrypt_and_dedup() -> Result<()> {
-        use crate::{
-            engine::{
-                create::{CreateBackupRequest, create},
-                wkey,
-            },
-            storage::local::LocalStore,
-            utils::crypto::open_content,
-        };
+    async fn run_scans_configured_files() -> Result<()> {
+        use crate::engine::create::{CreateBackupRequest, create};
 
         let tmp = tempfile::tempdir()?;
         let cfg = tmp.path().join("cfg");
         let src = tmp.path().join("src");
-        let dest = tmp.path().join("dest");
+        let loose = tmp.path().join("loose");
         fs::create_dir_all(&cfg)?;
         fs::create_dir_all(&src)?;
+        fs::create_dir_all(&loose)?;
 
-        // Two files with identical content (dedup) + one distinct.
-        fs::write(src.join("a.txt"), b"hello world")?;
-        fs::write(src.join("b.txt"), b"hello world")?;
-        fs::write(src.join("c.txt"), b"different")?;
+        let in_dir = src.join("a.txt");
+        let standalone = loose.join("standalone.txt");
+        let missing = loose.join("missing.txt");
+        fs::write(&in_dir, b"in directory")?;
+        fs::write(&standalone, b"standalone")?;
 
-        let created = create(CreateBackupRequest {
+        create(CreateBackupRequest {
             name: "t".to_string(),
             config_dir: cfg.clone(),
             directories: vec![src.clone()],
-            files: Vec::new(),
-            destinations: vec![dest.to_string_lossy().into_owned()],
+            // `in_dir` is also covered by the directory and must be scanned once.
+            files: vec![standalone.clone(), in_dir.clone(), missing.clone()],
+            destinations: vec![tmp.path().join("dest").to_string_lossy().into_owned()],
+            passphrase: None,
         })?;
-        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &created.recovery_phrase)?;
-
-        let naming_key: NamingKey =
-            Arc::new(wkey::load_naming_key(&cfg, "t")?.ok_or_else(|| anyhow!("missing wkey"))?);
-
         let result = run(RunBackupRequest {
             name: "t".to_string(),
             config_dir: cfg.clone(),
             ignore_rules: IgnoreRules::backupignore_only(),
             dry_run: false,
+            rehash: false,
             progress: None,
-            naming_key: naming_key.clone(),
         })
         .await?;
 
-        // Three files, two unique contents -> two stored blobs to one destination.
-        assert_eq!(result.scanned_files, 3);
-        assert_eq!(result.stored_blobs, 2);
-        assert_eq!(result.destination_count, 1);
-
-        // The stored blob for "hello world" decrypts byte-for-byte.
-        let id = blake3_keyed_bytes(b"hello world", &naming_key);
-        let store = LocalStore::new(&dest);
-        assert!(store.exists(&id).await?);
+        assert_eq!(result.scanned_files, 2);
+        assert_eq!(result.scanned_dirs, 1);
+        assert_eq!(result.pending_files, 2);
+        assert_eq!(result.skipped_entries, 1);
+        let log = fs::read_to_string(&result.skipped_files_log)?;
+        assert!(log.contains(&format!("Missing file: {}", missing.display())));
 
         let catalog = SqliteCatalog::open(&cfg.join("t.db"))?;
-        let (wrapped, eph) = catalog
-            .wrapped_content_key(&id)?
-            .ok_or_else(|| anyhow!("no wrapped key for content"))?;
-        let key_vec = decrypt(&wrapped, &eph, &mnemonic, &content_key_aad(&id))?;
-        let key: [u8; 32] = key_vec
-            .as_slice()
-            .try_into()
-            .map_err(|_| anyhow!("bad key length"))?;
-        let blob = store.get(&id).await?;
-        let plaintext = open_content(&blob, &id, &key)?;
-        assert_eq!(plaintext.as_slice(), b"hello world");
-
-        // Re-run with no changes stores nothing new (dedup / idempotent).
-        let again = run(RunBackupRequest {
+        let mut paths: Vec<PathBuf> = catalog
+            .restore_entries(result.version)?
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        paths.sort();
+        let mut expected = vec![src, in_dir, standalone];
+        expected.sort();
+        assert_eq!(paths, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_records_entry_metadata() -> Result<()> {
+        use crate::engine::create::{CreateBackupRequest, create};
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir()?;
+        let cfg = tmp.path().join("cfg");
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&cfg)?;
+        fs::create_dir_all(&src)?;
+        let file = src.join("secret.txt");
+        fs::write(&file, b"secret")?;
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o4750))?;
+        let has_xattrs =
+            xattr::set(&file, "user.b", b"2").is_ok() && xattr::set(&file, "user.a", b"1").is_ok();
+
+        create(CreateBackupRequest {
+            name: "t".to_string(),
+            config_dir: cfg.clone(),
+            directories: vec![src.clone()],
+            files: Vec::new(),
+            destinations: Vec::new(),
+            passphrase: None,
+        })?;
+        let result = run(RunBackupRequest {
             name: "t".to_string(),
             config_dir: cfg.clone(),
             ignore_rules: IgnoreRules::backupignore_only(),
             dry_run: false,
+            rehash: false,
             progress: None,
-            naming_key,
         })
         .await?;
-        assert_eq!(again.stored_blobs, 0);
+
+        let stat = fs::metadata(&file)?;
+        let entries = SqliteCatalog::open(&cfg.join("t.db"))?.restore_entries(result.version)?;
+        let metadata = entries
+            .into_iter()
+            .find(|entry| entry.path == file)
+            .and_then(|entry| entry.metadata)
+            .ok_or_else(|| anyhow!("no metadata recorded"))?;
+        assert_eq!(metadata.mode, 0o4750);
+        assert_eq!((metadata.uid, metadata.gid), (stat.uid(), stat.gid()));
+        assert_eq!(metadata.mtime_ns, stat_signature(&stat)?.mtime_ns);
+        if has_xattrs {
+            // Sorted by name, whatever order they were set in.
+            assert_eq!(
+                metadata.xattrs,
+                vec![
+                    (b"user.a".to_vec(), b"1".to_vec()),
+                    (b"user.b".to_vec(), b"2".to_vec()),
+                ]
+            );
+        }
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn run_overwrites_orphan_blob_from_interrupted_run() -> Result<()> {
-        use crate::{
-            engine::{
-                create::{CreateBackupRequest, create},
-                wkey,
-            },
-            storage::local::LocalStore,
-            utils::crypto::open_content,
+    async fn run_records_symlinks_without_following() -> Result<()> {
+        use crate::engine::create::{CreateBackupRequest, create};
+        use std::os::unix::fs::symlink;
+
+        let tmp = tempfile::tempdir()?;
+        let cfg = tmp.path().join("cfg");
+        let src = tmp.path().join("src");
+        let outside = tmp.path().join("outside");
+        fs::create_dir_all(&cfg)?;
+        fs::create_dir_all(&src)?;
+        fs::create_dir_all(&outside)?;
+        fs::write(src.join("a.txt"), b"alpha")?;
+        fs::write(outside.join("b.txt"), b"beta")?;
+        symlink("a.txt", src.join("to-a"))?;
+        symlink(&outside, src.join("to-outside"))?;
+        // A link back to its own directory would loop if followed.
+        symlink(".", src.join("loop"))?;
+        symlink("nowhere", src.join("dangling"))?;
+
+        create(CreateBackupRequest {
+            name: "t".to_string(),
+            config_dir: cfg.clone(),
+            directories: vec![src.clone()],
+            files: Vec::new(),
+            destinations: Vec::new(),
+            passphrase: None,
+        })?;
+        let request = || RunBackupRequest {
+            name: "t".to_string(),
+            config_dir: cfg.clone(),
+            ignore_rules: IgnoreRules::backupignore_only(),
+            dry_run: false,
+            rehash: false,
+            progress: None,
         };
+        let result = run(request()).await?;
+
+        // Only the regular file has content to upload.
+        assert_eq!(result.scanned_files, 5);
+        assert_eq!(result.pending_files, 1);
+        let catalog = SqliteCatalog::open(&cfg.join("t.db"))?;
+        let kinds: Vec<(PathBuf, EntryKind)> = catalog
+            .restore_entries(result.version)?
+            .into_iter()
+            .map(|entry| (entry.path, entry.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (src.clone(), EntryKind::Dir),
+                (src.join("a.txt"), EntryKind::File),
+                (src.join("dangling"), EntryKind::Symlink("nowhere".into())),
+                (src.join("loop"), EntryKind::Symlink(".".into())),
+                (src.join("to-a"), EntryKind::Symlink("a.txt".into())),
+                (src.join("to-outside"), EntryKind::Symlink(outside.clone())),
+            ]
+        );
+
+        // An untouched link carries forward; a retargeted one gets a new entry,
+        // and so does the directory it was replaced in (its mtime moved).
+        fs::remove_file(src.join("dangling"))?;
+        symlink("somewhere", src.join("dangling"))?;
+        let rerun = run(request()).await?;
+        assert_eq!(catalog.count_rows("FileNames")?, 8);
+        let retargeted = catalog
+            .restore_entries(rerun.version)?
+            .into_iter()
+            .find(|entry| entry.path == src.join("dangling"))
+            .map(|entry| entry.kind);
+        assert_eq!(retargeted, Some(EntryKind::Symlink("somewhere".into())));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_records_special_files_as_entries() -> Result<()> {
+        use crate::engine::create::{CreateBackupRequest, create};
+        use rustix::fs::{CWD, FileType, Mode};
 
         let tmp = tempfile::tempdir()?;
         let cfg = tmp.path().join("cfg");
         let src = tmp.path().join("src");
-        let dest = tmp.path().join("dest");
         fs::create_dir_all(&cfg)?;
         fs::create_dir_all(&src)?;
-        fs::write(src.join("a.txt"), b"hello world")?;
+        rustix::fs::mknodat(
+            CWD,
+            src.join("pipe"),
+            FileType::Fifo,
+            Mode::from_raw_mode(0o640),
+            0,
+        )?;
+        let _listener = std::os::unix::net::UnixListener::bind(src.join("sock"))?;
 
-        let created = create(CreateBackupRequest {
+        create(CreateBackupRequest {
             name: "t".to_string(),
             config_dir: cfg.clone(),
             directories: vec![src.clone()],
             files: Vec::new(),
-            destinations: vec![dest.to_string_lossy().into_owned()],
+            destinations: Vec::new(),
+            passphrase: None,
         })?;
-        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &created.recovery_phrase)?;
-        let naming_key: NamingKey =
-            Arc::new(wkey::load_naming_key(&cfg, "t")?.ok_or_else(|| anyhow!("missing wkey"))?);
+        let request = || RunBackupRequest {
+            name: "t".to_string(),
+            config_dir: cfg.clone(),
+            ignore_rules: IgnoreRules::backupignore_only(),
+            dry_run: false,
+            rehash: false,
+            progress: None,
+        };
+        // Never opened: reading the FIFO would block the scan.
+        let result = run(request()).await?;
+
+        assert_eq!(result.skipped_entries, 0);
+        assert_eq!(result.pending_files, 0);
+        let catalog = SqliteCatalog::open(&cfg.join("t.db"))?;
+        let entries = catalog.restore_entries(result.version)?;
+        let kinds: Vec<(PathBuf, EntryKind)> = entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.kind.clone()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (src.clone(), EntryKind::Dir),
+                (src.join("pipe"), EntryKind::Fifo),
+                (src.join("sock"), EntryKind::Socket),
+            ]
+        );
+        let pipe_mode = entries
+            .iter()
+            .find(|entry| entry.kind == EntryKind::Fifo)
+            .and_then(|entry| entry.metadata.as_ref())
+            .map(|metadata| metadata.mode & 0o7777);
+        assert_eq!(pipe_mode, Some(0o640));
+
+        // Unchanged, they carry forward.
+        run(request()).await?;
+        assert_eq!(catalog.count_rows("FileNames")?, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_records_hardlinks_within_the_tree() -> Result<()> {
+        use crate::engine::create::{CreateBackupRequest, create};
 
-        // Simulate an interrupted run: an orphan blob exists at the content id
-        // (bytes from a lost key), but there is no Files row for it.
-        let id = blake3_keyed_bytes(b"hello world", &naming_key);
-        let store = LocalStore::new(&dest);
-        store.put(&id, b"garbage from a cancelled run").await?;
+        let tmp = tempfile::tempdir()?;
+        let cfg = tmp.path().join("cfg");
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&cfg)?;
+        fs::create_dir_all(&src)?;
+        fs::write(src.join("a.txt"), b"alpha")?;
+        fs::hard_link(src.join("a.txt"), src.join("b.txt"))?;
+        // Its only other link is outside the backup.
+        fs::write(tmp.path().join("c.txt"), b"gamma")?;
+        fs::hard_link(tmp.path().join("c.txt"), src.join("c.txt"))?;
 
-        run(RunBackupRequest {
+        create(CreateBackupRequest {
+            name: "t".to_string(),
+            config_dir: cfg.clone(),
+            directories: vec![src.clone()],
+            files: Vec::new(),
+            destinations: Vec::new(),
+            passphrase: None,
+        })?;
+        let result = run(RunBackupRequest {
             name: "t".to_string(),
             config_dir: cfg.clone(),
             ignore_rules: IgnoreRules::backupignore_only(),
             dry_run: false,
+            rehash: false,
             progress: None,
-            naming_key,
         })
         .await?;
 
-        // The run must overwrite the orphan so the stored blob matches the key
-        // it recorded — i.e. it decrypts to the real content.
         let catalog = SqliteCatalog::open(&cfg.join("t.db"))?;
-        let (wrapped, eph) = catalog
-            .wrapped_content_key(&id)?
-            .ok_or_else(|| anyhow!("no wrapped key"))?;
-        let key_vec = decrypt(&wrapped, &eph, &mnemonic, &content_key_aad(&id))?;
-        let key: [u8; 32] = key_vec
-            .as_slice()
-            .try_into()
-            .map_err(|_| anyhow!("bad key length"))?;
-        let blob = store.get(&id).await?;
-        let plaintext = open_content(&blob, &id, &key)?;
-        assert_eq!(plaintext.as_slice(), b"hello world");
+        let links: HashMap<PathBuf, Option<(u64, u64)>> = catalog
+            .restore_entries(result.version)?
+            .into_iter()
+            .filter_map(|entry| Some((entry.path, entry.metadata?.hardlink)))
+            .collect();
+        let a = fs::metadata(src.join("a.txt"))?;
+        let linked = Some(Some((a.dev(), a.ino())));
+        assert_eq!(links.get(&src.join("a.txt")), linked.as_ref());
+        assert_eq!(links.get(&src.join("b.txt")), linked.as_ref());
+        assert_eq!(links.get(&src.join("c.txt")), Some(&None));
 
         Ok(())
     }
@@ -1499,20 +1639,235 @@ mod tests {
         let version = catalog.create_version()?;
         assert_eq!(catalog.latest_version()?, None);
 
-        // Recording the scan marks it complete.
-        catalog.record_scan(
-            public_key(),
-            &SealedKeys::new(),
+        // Recording the snapshot marks it complete, even with content pending.
+        let pending = catalog.record_snapshot(
             version,
             &[ScannedFile {
                 path: PathBuf::from("/backup/a.txt"),
-                hash: "hash-a".to_string(),
+                kind: EntryKind::File,
+                signature: StatSignature {
+                    size: 1,
+                    mtime_ns: 1,
+                    inode: 1,
+                    ctime_ns: 1,
+                },
+                metadata: EntryMetadata::default(),
             }],
             true,
+            false,
             None,
         )?;
+        assert_eq!(pending, 1);
         assert_eq!(catalog.latest_version()?, Some(version));
+        assert_eq!(
+            catalog
+                .restore_entries(version)?
+                .first()
+                .map(|e| e.hash.clone()),
+            Some(None)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn whole_file_catalog_migrates_to_single_chunk_manifests() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join("legacy.db");
+
+        // A catalog from before chunking: keys lived on `Files`, one blob per file.
+        let conn = rusqlite::Connection::open(&db_path)?;
+        conn.execute_batch(
+            "CREATE TABLE Config (name TEXT PRIMARY KEY, value TEXT NOT NULL);
+             CREATE TABLE Files (
+                 file_id INTEGER PRIMARY KEY,
+                 hash TEXT NOT NULL UNIQUE,
+                 encrypted_key BLOB NOT NULL,
+                 ephemeral_public_key BLOB NOT NULL
+             );
+             CREATE TABLE Paths (path_id INTEGER PRIMARY KEY, path TEXT NOT NULL UNIQUE);
+             CREATE TABLE FileNames (
+                 name_id INTEGER PRIMARY KEY,
+                 path_id INTEGER NOT NULL,
+                 name TEXT NOT NULL,
+                 file_id INTEGER NOT NULL,
+                 first_version INTEGER NOT NULL,
+                 last_version INTEGER,
+                 FOREIGN KEY (path_id) REFERENCES Paths(path_id),
+                 FOREIGN KEY (file_id) REFERENCES Files(file_id)
+             );
+             CREATE TABLE BackupVersions (
+                 version_id INTEGER PRIMARY KEY,
+                 timestamp INTEGER NOT NULL,
+                 completed_at INTEGER
+             );
+             INSERT INTO Files VALUES (7, 'abcd01', x'0102', zeroblob(32));
+             INSERT INTO Paths VALUES (1, '/backup');
+             INSERT INTO FileNames VALUES (1, 1, 'a.txt', 7, 1, NULL);
+             INSERT INTO BackupVersions VALUES (1, 0, 0);",
+        )?;
+        drop(conn);
+
+        let catalog = SqliteCatalog::open(&db_path)?;
+
+        // The blob becomes one chunk with the same id and key; nothing moves.
+        let manifest = catalog
+            .file_manifest("abcd01")?
+            .ok_or_else(|| anyhow!("migrated file has no manifest"))?;
+        assert_eq!(manifest.len(), 1);
+        let chunk = manifest.first().ok_or_else(|| anyhow!("empty manifest"))?;
+        assert_eq!(chunk.id, "abcd01");
+        assert_eq!(chunk.wrapped_key, vec![1, 2]);
+        // Still its own object, not in a pack.
+        assert_eq!(chunk.location, None);
+        assert_eq!(catalog.all_chunk_ids()?, vec!["abcd01".to_string()]);
+        assert_eq!(catalog.loose_chunk_ids()?, vec!["abcd01".to_string()]);
+        assert_eq!(catalog.restore_entries(1)?.len(), 1);
+
+        // Re-opening is a no-op, and new scans still record against the old ids.
+        let catalog = SqliteCatalog::open(&db_path)?;
+        let version = catalog.create_version()?;
+        catalog.record_contents(version, &[(PathBuf::from("/backup/a.txt"), "abcd01")], true)?;
+        assert_eq!(catalog.count_rows("Files")?, 1);
+        assert_eq!(catalog.count_rows("FileNames")?, 1);
+        assert_eq!(catalog.restore_entries(version)?.len(), 1);
+
+        Ok(())
+    }
+
+    /// Record a one-chunk file whose chunk (`hash`) is stored in `pack`, as
+    /// `run` then `upload` would.
+    fn record_packed_file(
+        catalog: &SqliteCatalog,
+        path: &str,
+        hash: &str,
+        pack: &PackLocation,
+    ) -> Result<()> {
+        use crate::db::sqlite::{EntryUpload, SealedChunk, StoredPack, UploadedEntry};
+
+        catalog.record_pack(&StoredPack {
+            id: pack.pack_id.clone(),
+            size: 100,
+            chunks: vec![(
+                hash.to_string(),
+                SealedChunk {
+                    size: 3,
+                    wrapped_key: vec![3],
+                    ephemeral_public_key: [0; 32],
+                    pack: pack.clone(),
+                    recipient_keys: Vec::new(),
+                },
+            )],
+            destinations: vec!["/mnt/backup".to_string()],
+        })?;
+        let version = catalog.create_version()?;
+        catalog.record_snapshot(
+            version,
+            &[ScannedFile {
+                path: PathBuf::from(path),
+                kind: EntryKind::File,
+                signature: StatSignature {
+                    size: 3,
+                    mtime_ns: 1,
+                    inode: 1,
+                    ctime_ns: 1,
+                },
+                metadata: EntryMetadata::default(),
+            }],
+            false,
+            false,
+            None,
+        )?;
+        let pending = catalog.pending_entries()?;
+        let name_id = pending
+            .first()
+            .ok_or_else(|| anyhow!("nothing pending"))?
+            .name_id;
+        catalog.record_uploaded(&[UploadedEntry {
+            name_id,
+            upload: EntryUpload::Stored {
+                hash: hash.to_string(),
+                manifest: vec![hash.to_string()],
+                sparse: None,
+                changed: false,
+                trusted: true,
+            },
+        }])
+    }
+
+    #[test]
+    fn chunk_catalog_gains_pack_locations() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join("chunked.db");
+
+        // A catalog from before packs: every chunk was an object of its own.
+        let conn = rusqlite::Connection::open(&db_path)?;
+        conn.execute_batch(
+            "CREATE TABLE Config (name TEXT PRIMARY KEY, value TEXT NOT NULL);
+             CREATE TABLE Files (
+                 file_id INTEGER PRIMARY KEY,
+                 hash TEXT NOT NULL UNIQUE,
+                 chunk_count INTEGER
+             );
+             CREATE TABLE Paths (path_id INTEGER PRIMARY KEY, path TEXT NOT NULL UNIQUE);
+             CREATE TABLE FileNames (
+                 name_id INTEGER PRIMARY KEY,
+                 path_id INTEGER NOT NULL,
+                 name TEXT NOT NULL,
+                 file_id INTEGER NOT NULL,
+                 first_version INTEGER NOT NULL,
+                 last_version INTEGER
+             );
+             CREATE TABLE BackupVersions (
+                 version_id INTEGER PRIMARY KEY,
+                 timestamp INTEGER NOT NULL,
+                 completed_at INTEGER
+             );
+             CREATE TABLE Chunks (
+                 chunk_id INTEGER PRIMARY KEY,
+                 hash TEXT NOT NULL UNIQUE,
+                 size INTEGER,
+                 encrypted_key BLOB NOT NULL,
+                 ephemeral_public_key BLOB NOT NULL
+             );
+             CREATE TABLE FileChunks (
+                 file_id INTEGER NOT NULL,
+                 seq INTEGER NOT NULL,
+                 chunk_id INTEGER NOT NULL,
+                 PRIMARY KEY (file_id, seq)
+             );
+             INSERT INTO Files VALUES (1, 'abcd01', 1);
+             INSERT INTO Chunks VALUES (1, 'abcd01', 5, x'0102', zeroblob(32));
+             INSERT INTO FileChunks VALUES (1, 0, 1);",
+        )?;
+        drop(conn);
+
+        let catalog = SqliteCatalog::open(&db_path)?;
+        assert_eq!(catalog.loose_chunk_ids()?, vec!["abcd01".to_string()]);
+        assert!(catalog.all_pack_ids()?.is_empty());
+
+        // New chunks record their pack alongside the old loose one.
+        let pack = PackLocation {
+            pack_id: "ee".repeat(32),
+            offset: 10,
+            length: 20,
+        };
+        record_packed_file(&catalog, "/backup/b.txt", "abcd02", &pack)?;
 
+        assert_eq!(catalog.chunk_location("abcd02")?, Some(pack.clone()));
+        assert_eq!(catalog.chunk_location("abcd01")?, None);
+        assert_eq!(catalog.all_pack_ids()?, vec![pack.pack_id.clone()]);
+        assert_eq!(
+            catalog.pack_chunk_ids(&pack.pack_id)?,
+            vec!["abcd02".to_string()]
+        );
+        let manifest = catalog
+            .file_manifest("abcd02")?
+            .ok_or_else(|| anyhow!("no manifest"))?;
+        assert_eq!(
+            manifest.first().and_then(|chunk| chunk.location.clone()),
+            Some(pack)
+        );
         Ok(())
     }
 }
@@ -0,0 +1,97 @@
+//! Split a backup's recovery mnemonic into Shamir shares (DESIGN §5).
+//!
+//! The mnemonic is checked against the backup before it is split, so a
+//! mistyped phrase is never handed out as shares that recombine to it.
+
+use crate::{
+    db::sqlite::SqliteCatalog,
+    utils::{
+        crypto::RecoveryKey,
+        shamir::{self, Share},
+    },
+};
+use anyhow::{Result, anyhow};
+use std::path::PathBuf;
+
+pub struct SplitKeyRequest {
+    pub name: String,
+    pub config_dir: PathBuf,
+    /// The backup's recovery mnemonic, with its passphrase if it has one; only
+    /// the mnemonic is split.
+    pub mnemonic: RecoveryKey,
+    pub threshold: u8,
+    pub shares: u8,
+}
+
+/// Split backup `name`'s mnemonic into `shares` shares, any `threshold` of
+/// which recover it.
+///
+/// # Errors
+/// Returns an error if the split is invalid, the backup is missing, or the
+/// mnemonic does not unlock it.
+pub fn split_key(request: &SplitKeyRequest) -> Result<Vec<Share>> {
+    shamir::check_split(request.threshold, request.shares)?;
+
+    let db_path = request.config_dir.join(format!("{}.db", request.name));
+    if !db_path.exists() {
+        return Err(anyhow!(
+            "No backup named \"{}\" found. Create a new backup first.",
+            request.name
+        ));
+    }
+    let (_, public_key) = request.mnemonic.keypair()?;
+    if public_key != SqliteCatalog::open(&db_path)?.public_key()? {
+        return Err(anyhow!(
+            "Incorrect mnemonic: it does not unlock backup \"{}\"",
+            request.name
+        ));
+    }
+
+    shamir::split(
+        &request.mnemonic.mnemonic,
+        request.threshold,
+        request.shares,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::create::{CreateBackupRequest, create};
+    use bip39::{Language, Mnemonic};
+
+    #[test]
+    fn only_the_backups_own_mnemonic_is_split() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let created = create(CreateBackupRequest {
+            name: "t".to_string(),
+            config_dir: tmp.path().to_path_buf(),
+            directories: Vec::new(),
+            files: Vec::new(),
+            destinations: Vec::new(),
+            passphrase: None,
+        })?;
+        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &created.recovery_phrase)?;
+        let request = |mnemonic: Mnemonic, threshold| SplitKeyRequest {
+            name: "t".to_string(),
+            config_dir: tmp.path().to_path_buf(),
+            mnemonic: mnemonic.into(),
+            threshold,
+            shares: 3,
+        };
+
+        let shares = split_key(&request(mnemonic.clone(), 2))?;
+        assert_eq!(shares.len(), 3);
+        assert_eq!(
+            shamir::combine(shares.get(1..).unwrap_or_default())?,
+            mnemonic
+        );
+
+        assert!(split_key(&request(Mnemonic::generate_in(Language::English, 12)?, 2)).is_err());
+        assert!(split_key(&request(mnemonic.clone(), 4)).is_err());
+        let mut missing = request(mnemonic, 2);
+        missing.name = "other".to_string();
+        assert!(split_key(&missing).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,1305 @@
+//! Store the content `run` left pending (DESIGN §6.7).
+//!
+//! `run` only pins a version's file set from stat signatures; `upload` reads
+//! what is new or changed, chunks and seals it into packs, and fills the pending
+//! entries in. It is resumable at every step:
+//! - a pack is recorded (with the destinations that accepted it) as soon as it
+//!   is stored, so an interrupted upload only repeats the pack it had open;
+//! - entries are committed in batches once every chunk they reference is in a
+//!   recorded pack, so a crash loses at most the files still in flight;
+//! - a destination that missed packs (it was down, or added later) is caught up
+//!   by copying them from one that has them.
+//!
+//! A destination that fails mid-upload is skipped for the rest of it and caught
+//! up next time; the upload only fails if no destination accepts a pack. The
+//! catalog lock is held throughout, so a `prune` cannot drop chunks between a
+//! pack being recorded and the entries that reference them.
+
+use crate::{
+    db::sqlite::{
+        EntryUpload, PackLocation, PendingEntry, RecipientKey, SealedChunk, SqliteCatalog,
+        StatSignature, StoredPack, UploadedEntry,
+    },
+    engine::{
+        replicate::{KEEP_GENERATIONS, ReplicateReport, replicate_catalog},
+        run::{
+            NamingKey, ProgressCallback, RunProgress, acquire_worker_id, cleanup_skipped_log,
+            log_skipped_entry, new_worker_pool, scan_worker_count, stat_signature,
+        },
+    },
+    storage::{NamedStore, open_named, pack::PackBuilder},
+    utils::{
+        chunk::{Chunk, chunk_stream},
+        crypto::{parse_public_key, seal_content_for},
+        sparse::SparseMap,
+    },
+};
+use anyhow::{Result, anyhow};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::{
+    collections::{HashMap, HashSet},
+    io::ErrorKind,
+    mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{fs::write, sync::Semaphore};
+use tracing::{debug, instrument, warn};
+use x25519_dalek::PublicKey;
+
+/// A file modified this recently may be rewritten again within its
+/// filesystem's timestamp granularity (2 s on FAT) without its signature
+/// changing, so its signature is not kept for the next `run` to trust.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Uploaded entries to collect before committing them.
+const COMMIT_BATCH: usize = 256;
+
+pub struct UploadRequest {
+    pub name: String,
+    pub config_dir: PathBuf,
+    pub naming_key: NamingKey,
+    pub progress: Option<ProgressCallback>,
+}
+
+pub struct UploadResult {
+    /// Pending entries whose content was stored.
+    pub uploaded_files: usize,
+    /// Of those, entries whose file had changed since the snapshot: the content
+    /// stored is what was read, not what the snapshot saw.
+    pub changed_files: usize,
+    /// Pending entries whose file was gone by the time it was read.
+    pub vanished_files: usize,
+    /// Files that could not be read; their entries stay pending.
+    pub skipped_entries: usize,
+    pub skipped_files_log: PathBuf,
+    /// New chunks sealed and stored.
+    pub stored_chunks: usize,
+    /// Pack objects those chunks were written in.
+    pub stored_packs: usize,
+    /// Packs copied to a destination that was missing them.
+    pub copied_packs: usize,
+    /// Packs no configured destination could provide to one missing them.
+    pub unavailable_packs: usize,
+    /// Destinations that failed during this upload; they are caught up next time.
+    pub failed_destinations: Vec<String>,
+    pub destination_count: usize,
+    /// Latest version, if any, and how many destinations can fully restore it.
+    pub latest_version: Option<i64>,
+    pub sealed_destinations: usize,
+    /// The catalog sealed to the destinations afterwards, recording the packs
+    /// just stored.
+    pub catalog: ReplicateReport,
+}
+
+/// Upload everything pending for a backup.
+///
+/// # Errors
+/// Returns an error if the backup is missing, has no destinations, another
+/// upload or a prune is running, no destination accepts a pack, or the catalog
+/// cannot be updated.
+#[instrument(skip(request))]
+pub async fn upload(request: UploadRequest) -> Result<UploadResult> {
+    let db_file = request.config_dir.join(format!("{}.db", request.name));
+    if !db_file.exists() {
+        return Err(anyhow!(
+            "No backup named \"{}\" found. Create a new backup first.",
+            request.name
+        ));
+    }
+
+    let catalog = SqliteCatalog::open(&db_file)?;
+    let stores = open_named(&catalog.configured_destinations()?)?;
+    if stores.is_empty() {
+        return Err(anyhow!("no destinations configured"));
+    }
+    let _lock = catalog.lock()?;
+
+    let skipped_files_log = request
+        .config_dir
+        .join(format!("{}-skipped_uploads.log", request.name));
+    debug!("Skipped uploads log: {}", skipped_files_log.display());
+    write(&skipped_files_log, "").await?;
+
+    let catch_up = catch_up(&catalog, &stores).await?;
+    let healthy: Vec<NamedStore> = stores
+        .iter()
+        .filter(|named| !catch_up.failed.contains(&named.destination))
+        .cloned()
+        .collect();
+    if healthy.is_empty() {
+        return Err(anyhow!("no destination is reachable"));
+    }
+
+    let packs = Arc::new(
+        PackWriter::new(
+            catalog.clone(),
+            &healthy,
+            catalog.public_key()?,
+            request.naming_key.clone(),
+        )
+        .with_known(catalog.all_chunk_ids()?)
+        .with_recipients(catalog.recipients()?)?,
+    );
+    let drained = drain_pending(
+        &catalog,
+        packs,
+        &request.naming_key,
+        &skipped_files_log,
+        request.progress.as_ref(),
+    )
+    .await?;
+
+    if drained.skipped == 0 {
+        cleanup_skipped_log(&skipped_files_log).await?;
+    }
+
+    let mut failed_destinations = catch_up.failed;
+    failed_destinations.extend(drained.packs.failed);
+
+    let latest_version = catalog.latest_version()?;
+    let mut sealed_destinations = 0;
+    if let Some(version) = latest_version {
+        for named in &stores {
+            if catalog.is_sealed(version, &named.destination)? {
+                sealed_destinations += 1;
+            }
+        }
+    }
+
+    let catalog = replicate_catalog(
+        &catalog,
+        &request.name,
+        &request.config_dir,
+        KEEP_GENERATIONS,
+    )
+    .await?;
+
+    Ok(UploadResult {
+        uploaded_files: drained.uploaded,
+        changed_files: drained.changed,
+        vanished_files: drained.vanished,
+        skipped_entries: drained.skipped,
+        skipped_files_log,
+        stored_chunks: drained.packs.chunks,
+        stored_packs: drained.packs.packs,
+        copied_packs: catch_up.copied,
+        unavailable_packs: catch_up.unavailable,
+        failed_destinations,
+        destination_count: stores.len(),
+        latest_version,
+        sealed_destinations,
+        catalog,
+    })
+}
+
+/// What [`catch_up`] did.
+struct CatchUp {
+    copied: usize,
+    unavailable: usize,
+    failed: Vec<String>,
+}
+
+/// Copy every recorded pack to the configured destinations that lack it, from
+/// one that has it. A destination that fails a write is left out of the rest of
+/// the upload.
+async fn catch_up(catalog: &SqliteCatalog, stores: &[NamedStore]) -> Result<CatchUp> {
+    let mut outcome = CatchUp {
+        copied: 0,
+        unavailable: 0,
+        failed: Vec::new(),
+    };
+
+    for (pack_id, holders) in catalog.pack_uploads()? {
+        let (sources, missing): (Vec<&NamedStore>, Vec<&NamedStore>) = stores
+            .iter()
+            .filter(|named| !outcome.failed.contains(&named.destination))
+            .partition(|named| holders.contains(&named.destination));
+        if missing.is_empty() {
+            continue;
+        }
+
+        let mut bytes = None;
+        for source in sources {
+            match source.store.get(&pack_id).await {
+                Ok(read) => {
+                    bytes = Some(read);
+                    break;
+                }
+                Err(err) => warn!(
+                    "Cannot read pack {pack_id} from {}: {err}",
+                    source.destination
+                ),
+            }
+        }
+        let Some(bytes) = bytes else {
+            outcome.unavailable += 1;
+            continue;
+        };
+
+        for target in missing {
+            match target.store.put(&pack_id, &bytes).await {
+                Ok(()) => {
+                    catalog.record_pack_upload(&pack_id, &target.destination)?;
+                    outcome.copied += 1;
+                }
+                Err(err) => {
+                    warn!("Cannot write to {}: {err}", target.destination);
+                    outcome.failed.push(target.destination.clone());
+                }
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// What [`drain_pending`] did.
+struct Drained {
+    uploaded: usize,
+    changed: usize,
+    vanished: usize,
+    skipped: usize,
+    packs: PackStats,
+}
+
+/// Read every file with pending entries, store its new chunks and commit the
+/// entries in batches as their chunks become durable.
+async fn drain_pending(
+    catalog: &SqliteCatalog,
+    packs: Arc<PackWriter>,
+    naming_key: &NamingKey,
+    skipped_files_log: &Path,
+    progress: Option<&ProgressCallback>,
+) -> Result<Drained> {
+    let by_path = pending_by_path(catalog.pending_entries()?);
+
+    if let Some(progress) = progress {
+        progress(RunProgress::StorePhaseStarted(by_path.len()));
+    }
+
+    let worker_count = scan_worker_count();
+    let semaphore = Arc::new(Semaphore::new(worker_count));
+    let available_workers = new_worker_pool(worker_count);
+
+    let mut tasks = FuturesUnordered::new();
+    for (path, entries) in by_path {
+        let semaphore = semaphore.clone();
+        let available_workers = available_workers.clone();
+        let packs = packs.clone();
+        let naming_key = naming_key.clone();
+        let progress = progress.cloned();
+
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            // `worker` releases its id to the pool on drop (incl. on panic/error).
+            let worker = acquire_worker_id(&available_workers)?;
+            if let Some(progress) = &progress {
+                progress(RunProgress::ProcessingFile {
+                    worker_id: worker.id(),
+                    path: path.clone(),
+                });
+            }
+
+            let read = read_file(&packs, &naming_key, &path).await;
+
+            if let Some(progress) = &progress {
+                progress(RunProgress::WorkerFinished(worker.id()));
+            }
+
+            read.map(|read| (path, entries, read))
+        }));
+    }
+
+    let mut drained = Drained {
+        uploaded: 0,
+        changed: 0,
+        vanished: 0,
+        skipped: 0,
+        packs: PackStats::default(),
+    };
+    let mut batch = CommitBatch::default();
+    while let Some(joined) = tasks.next().await {
+        let (path, entries, read) = joined??;
+        if let FileRead::Failed(err) = &read {
+            log_skipped_entry(
+                skipped_files_log,
+                &format!("Read error for {}: {err}", path.display()),
+            )
+            .await?;
+        }
+        drained.resolve(entries, read, &mut batch);
+        if let Some(progress) = progress {
+            progress(RunProgress::FileFinished);
+        }
+
+        if batch.ready.len() >= COMMIT_BATCH || batch.packs_seen != packs.stored_packs() {
+            batch.commit(catalog, &packs).await?;
+        }
+    }
+
+    // Every worker is done with the writer; store the last, partly filled pack,
+    // after which every chunk is recorded and everything left can commit.
+    let packs =
+        Arc::try_unwrap(packs).map_err(|_| anyhow!("pack writer still shared after upload"))?;
+    drained.packs = packs.finish().await?;
+    batch.commit_all(catalog).await?;
+
+    Ok(drained)
+}
+
+impl Drained {
+    /// Resolve a file's pending entries from what reading it produced. A failed
+    /// read leaves them pending.
+    fn resolve(&mut self, entries: Vec<PendingEntry>, read: FileRead, batch: &mut CommitBatch) {
+        match read {
+            FileRead::Read {
+                hash,
+                manifest,
+                sparse,
+                before,
+                after,
+                read_at,
+            } => {
+                let changed_during_read = before != after;
+                let trusted = !is_racy(after, read_at);
+                for entry in entries {
+                    let changed = changed_during_read || entry.signature != before;
+                    self.uploaded += 1;
+                    self.changed += usize::from(changed);
+                    batch.push(
+                        UploadedEntry {
+                            name_id: entry.name_id,
+                            upload: EntryUpload::Stored {
+                                hash: hash.clone(),
+                                manifest: manifest.clone(),
+                                sparse: sparse.clone(),
+                                changed,
+                                trusted,
+                            },
+                        },
+                        manifest.clone(),
+                    );
+                }
+            }
+            FileRead::Vanished => {
+                for entry in entries {
+                    self.vanished += 1;
+                    batch.push(
+                        UploadedEntry {
+                            name_id: entry.name_id,
+                            upload: EntryUpload::Vanished,
+                        },
+                        Vec::new(),
+                    );
+                }
+            }
+            FileRead::Failed(_) => self.skipped += 1,
+        }
+    }
+}
+
+/// Group pending entries by path, in the order paths first appear. A path can
+/// be pending in several versions (changed again before it was uploaded); its
+/// file is read once for all of them.
+fn pending_by_path(pending: Vec<PendingEntry>) -> Vec<(PathBuf, Vec<PendingEntry>)> {
+    let mut by_path: Vec<(PathBuf, Vec<PendingEntry>)> = Vec::new();
+    let mut index: HashMap<PathBuf, usize> = HashMap::new();
+    for entry in pending {
+        if let Some((_, entries)) = index.get(&entry.path).and_then(|at| by_path.get_mut(*at)) {
+            entries.push(entry);
+        } else {
+            index.insert(entry.path.clone(), by_path.len());
+            by_path.push((entry.path.clone(), vec![entry]));
+        }
+    }
+    by_path
+}
+
+/// Uploaded entries waiting for their chunks' packs to be recorded.
+#[derive(Default)]
+struct CommitBatch {
+    ready: Vec<(UploadedEntry, Vec<String>)>,
+    /// Packs stored when the batch was last committed.
+    packs_seen: usize,
+}
+
+impl CommitBatch {
+    fn push(&mut self, entry: UploadedEntry, manifest: Vec<String>) {
+        self.ready.push((entry, manifest));
+    }
+
+    /// Commit the entries whose every chunk is in a recorded pack.
+    async fn commit(&mut self, catalog: &SqliteCatalog, packs: &PackWriter) -> Result<()> {
+        self.packs_seen = packs.stored_packs();
+        let (durable, waiting) = mem::take(&mut self.ready)
+            .into_iter()
+            .partition(|(_, manifest)| manifest.iter().all(|id| packs.is_recorded(id)));
+        self.ready = waiting;
+        record(catalog, durable).await
+    }
+
+    async fn commit_all(self, catalog: &SqliteCatalog) -> Result<()> {
+        record(catalog, self.ready).await
+    }
+}
+
+async fn record(catalog: &SqliteCatalog, entries: Vec<(UploadedEntry, Vec<String>)>) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let catalog = catalog.clone();
+    let entries: Vec<UploadedEntry> = entries.into_iter().map(|(entry, _)| entry).collect();
+    tokio::task::spawn_blocking(move || catalog.record_uploaded(&entries)).await?
+}
+
+/// Whether a file modified at `signature.mtime_ns` may still change unnoticed,
+/// as seen at `read_at`.
+fn is_racy(signature: StatSignature, read_at: SystemTime) -> bool {
+    let Ok(now) = read_at.duration_since(UNIX_EPOCH) else {
+        return true;
+    };
+    let Ok(now) = i64::try_from(now.as_nanos()) else {
+        return true;
+    };
+    let window = i64::try_from(RACY_WINDOW.as_nanos()).unwrap_or(i64::MAX);
+    now.saturating_sub(signature.mtime_ns) < window
+}
+
+/// What reading one file produced.
+enum FileRead {
+    Read {
+        /// Whole-file content id of the bytes read.
+        hash: String,
+        manifest: Vec<String>,
+        /// The file's data extents, when it has holes; only its data was read.
+        sparse: Option<SparseMap>,
+        /// Signatures just before and just after the read.
+        before: StatSignature,
+        after: StatSignature,
+        read_at: SystemTime,
+    },
+    Vanished,
+    Failed(anyhow::Error),
+}
+
+/// Chunk one file, sealing every chunk not already known into the shared packs.
+/// A sparse file's holes are neither read nor stored: only its data extents are
+/// chunked, and its content id covers them and the extent map. Chunks sealed
+/// before a read fails are still stored: they are valid content other files
+/// may reference. A file replaced by something else (e.g. a symlink) is gone
+/// as far as its entry goes; the next `run` records what is there now.
+async fn read_file(packs: &PackWriter, naming_key: &NamingKey, path: &Path) -> Result<FileRead> {
+    let before = match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if !metadata.is_file() => return Ok(FileRead::Vanished),
+        Ok(metadata) => stat_signature(&metadata)?,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(FileRead::Vanished),
+        Err(err) => return Ok(FileRead::Failed(err.into())),
+    };
+
+    // A blocking reader chunks the file and hands chunks over one at a time, so
+    // memory stays at a couple of chunks however large the file is.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<Chunk>(1);
+    let reader = {
+        let path = path.to_path_buf();
+        let naming_key = naming_key.clone();
+        tokio::task::spawn_blocking(move || -> Result<Option<(String, Option<SparseMap>)>> {
+            let file = match std::fs::File::open(&path) {
+                Ok(file) => file,
+                Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+            let send = |chunk| {
+                sender
+                    .blocking_send(chunk)
+                    .map_err(|_| anyhow!("chunk upload stopped"))
+            };
+            match SparseMap::detect(&file)? {
+                Some(map) => {
+                    let data_id = chunk_stream(map.reader(file), &naming_key, send)?;
+                    Ok(Some((map.content_id(&data_id, &naming_key), Some(map))))
+                }
+                None => Ok(Some((chunk_stream(file, &naming_key, send)?, None))),
+            }
+        })
+    };
+
+    let mut manifest = Vec::new();
+    while let Some(chunk) = receiver.recv().await {
+        manifest.push(chunk.id.clone());
+        if packs.claim(&chunk.id) {
+            packs.add(chunk).await?;
+        }
+    }
+
+    let (hash, sparse) = match reader.await? {
+        Ok(Some(read)) => read,
+        Ok(None) => return Ok(FileRead::Vanished),
+        Err(err) => return Ok(FileRead::Failed(err)),
+    };
+
+    let read_at = SystemTime::now();
+    // Gone right after it was read: what was read is still a consistent copy,
+    // but not one the snapshot's signature describes.
+    let after = match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) => stat_signature(&metadata)?,
+        Err(_) => StatSignature {
+            mtime_ns: before.mtime_ns.wrapping_add(1),
+            ..before
+        },
+    };
+
+    Ok(FileRead::Read {
+        hash,
+        manifest,
+        sparse,
+        before,
+        after,
+        read_at,
+    })
+}
+
+/// What a [`PackWriter`] stored.
+#[derive(Debug, Default)]
+pub(crate) struct PackStats {
+    pub(crate) packs: usize,
+    pub(crate) chunks: usize,
+    /// Destinations that failed a write and were skipped afterwards.
+    pub(crate) failed: Vec<String>,
+}
+
+/// Chunks already stored, and those claimed this run whose pack is not
+/// recorded yet.
+#[derive(Default)]
+struct ChunkClaims {
+    known: HashSet<String>,
+    unrecorded: HashSet<String>,
+}
+
+/// A sealed chunk's wrapped keys, held until its pack is recorded.
+struct SealedKeys {
+    wrapped_key: Vec<u8>,
+    ephemeral_public_key: [u8; 32],
+    recipient_keys: Vec<RecipientKey>,
+}
+
+/// Seals chunks into packs shared by every upload worker, storing each pack to
+/// every destination as soon as it fills and recording it right after
+/// (DESIGN §6.5).
+pub(crate) struct PackWriter {
+    catalog: SqliteCatalog,
+    stores: Vec<NamedStore>,
+    public_key: PublicKey,
+    /// Derives each pack's index key.
+    naming_key: NamingKey,
+    /// Additional recipients every chunk key is also wrapped to, with their
+    /// public keys in hex as the catalog names them.
+    recipients: Vec<(String, PublicKey)>,
+    open: Mutex<PackBuilder>,
+    claims: Mutex<ChunkClaims>,
+    /// Keys of the chunks in packs not recorded yet, by chunk id.
+    keys: Mutex<HashMap<String, SealedKeys>>,
+    stats: Mutex<PackStats>,
+}
+
+impl PackWriter {
+    pub(crate) fn new(
+        catalog: SqliteCatalog,
+        stores: &[NamedStore],
+        public_key: PublicKey,
+        naming_key: NamingKey,
+    ) -> Self {
+        Self {
+            catalog,
+            stores: stores.to_vec(),
+            public_key,
+            naming_key,
+            recipients: Vec::new(),
+            open: Mutex::new(PackBuilder::new()),
+            claims: Mutex::new(ChunkClaims::default()),
+            keys: Mutex::new(HashMap::new()),
+            stats: Mutex::new(PackStats::default()),
+        }
+    }
+
+    /// Also wrap every chunk key to these recipients (public keys in hex), so
+    /// a chunk stored after a recipient was added opens with their mnemonic.
+    ///
+    /// # Errors
+    /// Returns an error if a recipient is not a valid public key.
+    pub(crate) fn with_recipients(mut self, recipients: Vec<String>) -> Result<Self> {
+        self.recipients = recipients
+            .into_iter()
+            .map(|hex_key| {
+                let key = parse_public_key(&hex_key)?;
+                Ok((hex_key, key))
+            })
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+
+    /// Seed the chunks already stored, which [`Self::claim`] never hands out.
+    pub(crate) fn with_known(self, chunk_ids: Vec<String>) -> Self {
+        self.claims
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .known
+            .extend(chunk_ids);
+        self
+    }
+
+    /// Claim a chunk for upload; `false` if it is already stored or another
+    /// worker has claimed it. Dedup is a local lookup (DESIGN §6.2), so a chunk
+    /// shared by several new files is uploaded once.
+    pub(crate) fn claim(&self, id: &str) -> bool {
+        let mut claims = self.claims.lock().unwrap_or_else(PoisonError::into_inner);
+        let claimed = claims.known.insert(id.to_string());
+        if claimed {
+            claims.unrecorded.insert(id.to_string());
+        }
+        claimed
+    }
+
+    /// Whether a chunk is in the catalog: stored before, or in a pack this
+    /// writer has recorded.
+    pub(crate) fn is_recorded(&self, id: &str) -> bool {
+        !self
+            .claims
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .unrecorded
+            .contains(id)
+    }
+
+    pub(crate) fn stored_packs(&self) -> usize {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .packs
+    }
+
+    /// Compress+encrypt one chunk and append it to the open pack; if that fills
+    /// the pack, this caller stores it while other workers start the next one.
+    pub(crate) async fn add(&self, chunk: Chunk) -> Result<()> {
+        let Chunk { id, data } = chunk;
+        let size = u64::try_from(data.len())?;
+
+        let public_key = self.public_key;
+        let recipients: Vec<PublicKey> = self.recipients.iter().map(|(_, key)| *key).collect();
+        let seal_id = id.clone();
+        let sealed = tokio::task::spawn_blocking(move || {
+            seal_content_for(&data, &public_key, &recipients, &seal_id)
+        })
+        .await??;
+        let recipient_keys = self
+            .recipients
+            .iter()
+            .zip(sealed.recipient_keys)
+            .map(|((recipient, _), wrapped)| RecipientKey {
+                recipient: recipient.clone(),
+                wrapped_key: wrapped.wrapped_key,
+                ephemeral_public_key: wrapped.ephemeral_public_key,
+            })
+            .collect();
+        self.keys
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                id.clone(),
+                SealedKeys {
+                    wrapped_key: sealed.wrapped_key,
+                    ephemeral_public_key: sealed.ephemeral_public_key,
+                    recipient_keys,
+                },
+            );
+
+        let full = {
+            let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+            open.add(&id, &sealed.blob, size)?;
+            open.is_full().then(|| mem::take(&mut *open))
+        };
+        if let Some(full) = full {
+            self.store(full).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Store and record the open pack (if it holds anything).
+    pub(crate) async fn finish(self) -> Result<PackStats> {
+        let open = mem::take(&mut *self.open.lock().unwrap_or_else(PoisonError::into_inner));
+        if !open.is_empty() {
+            self.store(open).await?;
+        }
+        Ok(self
+            .stats
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Seal a pack's index, write it to every destination still healthy, then
+    /// record it with the destinations that accepted it.
+    ///
+    /// The pack is written before it is recorded: a crash in between leaves an
+    /// unreferenced pack (reclaimed by `gc`), never a catalog entry for a pack
+    /// that wasn't written.
+    async fn store(&self, pack: PackBuilder) -> Result<()> {
+        let naming_key = self.naming_key.clone();
+        let pack = tokio::task::spawn_blocking(move || pack.finish(&naming_key)).await??;
+
+        let mut destinations = Vec::new();
+        for named in &self.stores {
+            if self.has_failed(&named.destination) {
+                continue;
+            }
+            match named.store.put(&pack.id, &pack.bytes).await {
+                Ok(()) => destinations.push(named.destination.clone()),
+                Err(err) => {
+                    warn!("Cannot write to {}: {err}", named.destination);
+                    self.stats
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .failed
+                        .push(named.destination.clone());
+                }
+            }
+        }
+        if destinations.is_empty() {
+            return Err(anyhow!("no destination accepted pack {}", pack.id));
+        }
+
+        let chunk_ids: Vec<String> = pack.entries.iter().map(|e| e.chunk_id.clone()).collect();
+        let mut keys: HashMap<String, SealedKeys> = {
+            let mut pending = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+            chunk_ids
+                .iter()
+                .filter_map(|id| pending.remove_entry(id))
+                .collect()
+        };
+        let stored = StoredPack {
+            size: u64::try_from(pack.bytes.len())?,
+            chunks: pack
+                .entries
+                .into_iter()
+                .map(|entry| {
+                    let keys = keys
+                        .remove(&entry.chunk_id)
+                        .ok_or_else(|| anyhow!("no key sealed for chunk {}", entry.chunk_id))?;
+                    let sealed = SealedChunk {
+                        size: entry.size,
+                        wrapped_key: keys.wrapped_key,
+                        ephemeral_public_key: keys.ephemeral_public_key,
+                        pack: PackLocation {
+                            pack_id: pack.id.clone(),
+                            offset: entry.offset,
+                            length: entry.length,
+                        },
+                        recipient_keys: keys.recipient_keys,
+                    };
+                    Ok((entry.chunk_id, sealed))
+                })
+                .collect::<Result<_>>()?,
+            id: pack.id,
+            destinations,
+        };
+        let catalog = self.catalog.clone();
+        tokio::task::spawn_blocking(move || catalog.record_pack(&stored)).await??;
+
+        let mut claims = self.claims.lock().unwrap_or_else(PoisonError::into_inner);
+        for id in &chunk_ids {
+            claims.unrecorded.remove(id);
+        }
+        drop(claims);
+        let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        stats.packs += 1;
+        stats.chunks += chunk_ids.len();
+        Ok(())
+    }
+
+    fn has_failed(&self, destination: &str) -> bool {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .failed
+            .iter()
+            .any(|failed| failed == destination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::sqlite::EntryKind,
+        engine::fixture::Fixture,
+        storage::{
+            Storage,
+            local::LocalStore,
+            pack::{PACK_TARGET_SIZE, read_index},
+        },
+        utils::{
+            chunk::MAX_CHUNK_SIZE,
+            crypto::{content_keypair, generate_naming_key},
+            hash::blake3_keyed_bytes,
+        },
+    };
+    use bip39::{Language, Mnemonic};
+    use std::fs;
+
+    /// Move a file's mtime out of the racy window, as if written an hour ago.
+    fn backdate(path: &Path) -> Result<()> {
+        fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(SystemTime::now() - Duration::from_hours(1))?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_stores_blobs_that_decrypt_and_dedup() -> Result<()> {
+        // Two files with identical content (dedup) + one distinct.
+        let fixture = Fixture::create(
+            &[
+                ("a.txt", b"hello world"),
+                ("b.txt", b"hello world"),
+                ("c.txt", b"different"),
+            ],
+            1,
+            &[],
+        )?;
+
+        let snapshot = fixture.run().await?;
+        assert_eq!(snapshot.scanned_files, 3);
+        assert_eq!(snapshot.pending_files, 3);
+
+        // Three files, two unique contents -> two stored chunks, in one pack, to
+        // one destination.
+        let result = fixture.upload().await?;
+        assert_eq!(result.uploaded_files, 3);
+        assert_eq!(result.stored_chunks, 2);
+        assert_eq!(result.stored_packs, 1);
+        assert_eq!(result.destination_count, 1);
+        assert_eq!(result.latest_version, Some(1));
+        assert_eq!(result.sealed_destinations, 1);
+
+        // The pack is the only object; the chunk for "hello world" is a range of
+        // it that decrypts byte-for-byte.
+        let id = blake3_keyed_bytes(b"hello world", &fixture.naming_key);
+        let store = fixture.store(0)?;
+        assert!(!store.exists(&id).await?);
+
+        let catalog = fixture.catalog()?;
+        let location = catalog
+            .chunk_location(&id)?
+            .ok_or_else(|| anyhow!("chunk not in a pack"))?;
+        assert_eq!(catalog.all_pack_ids()?, vec![location.pack_id.clone()]);
+        let objects = store.list("").await?;
+        assert_eq!(objects.len(), 1);
+        assert_eq!(
+            objects.first().map(|object| object.key.as_str()),
+            Some(location.pack_id.as_str())
+        );
+        assert_eq!(fixture.read_chunk(0, &id).await?.as_slice(), b"hello world");
+
+        // The pack's index holds one entry per distinct chunk, each where the
+        // catalog says it is.
+        let pack = store.get(&location.pack_id).await?;
+        let mut indexed: Vec<String> = read_index(&pack, &location.pack_id, &fixture.naming_key)?
+            .into_iter()
+            .map(|entry| entry.chunk_id)
+            .collect();
+        indexed.sort();
+        let mut expected = vec![
+            id.clone(),
+            blake3_keyed_bytes(b"different", &fixture.naming_key),
+        ];
+        expected.sort();
+        assert_eq!(indexed, expected);
+
+        // And restoring gives back the source bytes of every file.
+        let into = fixture.tmp.path().join("restored");
+        fixture.restore_into(&into, &fixture.mnemonic).await?;
+        for (name, content) in [
+            ("a.txt", &b"hello world"[..]),
+            ("b.txt", b"hello world"),
+            ("c.txt", b"different"),
+        ] {
+            assert_eq!(fs::read(fixture.restored(&into, name))?, content, "{name}");
+        }
+
+        // Backing up again with no changes stores nothing new (dedup /
+        // idempotent).
+        let again = fixture.back_up().await?;
+        assert_eq!(again.stored_chunks, 0);
+        assert_eq!(again.stored_packs, 0);
+        assert_eq!(store.list("").await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_ignores_orphan_blob_from_interrupted_run() -> Result<()> {
+        let fixture = Fixture::create(&[("a.txt", b"hello world")], 1, &[])?;
+
+        // Simulate an interrupted upload: an orphan object exists at the content
+        // id (bytes from a lost key), but there is no Chunks row for it.
+        let id = blake3_keyed_bytes(b"hello world", &fixture.naming_key);
+        fixture
+            .store(0)?
+            .put(&id, b"garbage from a cancelled run")
+            .await?;
+
+        fixture.back_up().await?;
+
+        // The upload must not trust the orphan: the chunk it recorded is in a new
+        // pack, sealed with the recorded key — i.e. it decrypts to the real
+        // content.
+        assert_eq!(fixture.read_chunk(0, &id).await?.as_slice(), b"hello world");
+        let into = fixture.tmp.path().join("restored");
+        fixture.restore_into(&into, &fixture.mnemonic).await?;
+        assert_eq!(fs::read(fixture.restored(&into, "a.txt"))?, b"hello world");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_ignores_orphan_pack_from_interrupted_upload() -> Result<()> {
+        let fixture = Fixture::create(&[("a.txt", b"hello world")], 1, &[])?;
+        fixture.run().await?;
+
+        // Simulate an upload interrupted after its pack was stored but before
+        // the catalog recorded it: the pack stays behind, the catalog does not
+        // know it.
+        let before = fixture.tmp.path().join("before.db");
+        fixture.catalog()?.snapshot_to(&before)?;
+        fixture.upload().await?;
+        let orphan = fixture.catalog()?.all_pack_ids()?;
+        for suffix in ["-wal", "-shm"] {
+            let _ = fs::remove_file(fixture.cfg.join(format!("t.db{suffix}")));
+        }
+        fs::rename(&before, fixture.cfg.join("t.db"))?;
+        assert!(fixture.catalog()?.all_pack_ids()?.is_empty());
+
+        // The next upload stores the chunk again in a pack of its own and
+        // records only that one; the orphan is left for `gc`.
+        let result = fixture.upload().await?;
+        assert_eq!(result.stored_packs, 1);
+        let recorded = fixture.catalog()?.all_pack_ids()?;
+        assert_eq!(recorded.len(), 1);
+        assert!(recorded.iter().all(|pack| !orphan.contains(pack)));
+        assert_eq!(fixture.store(0)?.list("").await?.len(), 2);
+
+        let into = fixture.tmp.path().join("restored");
+        fixture.restore_into(&into, &fixture.mnemonic).await?;
+        assert_eq!(fs::read(fixture.restored(&into, "a.txt"))?, b"hello world");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_stores_only_changed_chunks() -> Result<()> {
+        // Non-repeating data, large enough for several chunks.
+        let mut image = vec![0_u8; 8 * 1024 * 1024];
+        blake3::Hasher::new()
+            .update(b"vm image")
+            .finalize_xof()
+            .fill(&mut image);
+        let fixture = Fixture::create(&[("image.bin", &image)], 1, &[])?;
+
+        let first = fixture.back_up().await?;
+        assert!(first.stored_chunks > 2, "expected several chunks");
+
+        let catalog = fixture.catalog()?;
+        let manifest = catalog
+            .file_manifest(&blake3_keyed_bytes(&image, &fixture.naming_key))?
+            .ok_or_else(|| anyhow!("no manifest for the image"))?;
+        assert_eq!(manifest.len(), first.stored_chunks);
+
+        // A small edit in the middle only re-uploads the chunk(s) around it.
+        image.splice(4_000_000..4_000_000, *b"one small edit");
+        fs::write(fixture.src.join("image.bin"), &image)?;
+
+        let second = fixture.back_up().await?;
+        assert_eq!(second.uploaded_files, 1);
+        assert!(
+            (1..=2).contains(&second.stored_chunks),
+            "{} chunks re-uploaded",
+            second.stored_chunks
+        );
+        let total = i64::try_from(first.stored_chunks + second.stored_chunks)?;
+        assert_eq!(catalog.count_rows("Chunks")?, total);
+
+        // Both versions keep complete manifests that share the unchanged chunks.
+        let edited = catalog
+            .file_manifest(&blake3_keyed_bytes(&image, &fixture.naming_key))?
+            .ok_or_else(|| anyhow!("no manifest for the edited image"))?;
+        let shared = edited
+            .iter()
+            .filter(|chunk| manifest.iter().any(|old| old.id == chunk.id))
+            .count();
+        assert_eq!(shared + second.stored_chunks, edited.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn trusted_signature_skips_unchanged_files() -> Result<()> {
+        let fixture = Fixture::create(
+            &[("old.txt", b"settled"), ("new.txt", b"just written")],
+            1,
+            &[],
+        )?;
+        backdate(&fixture.src.join("old.txt"))?;
+
+        fixture.back_up().await?;
+
+        // The settled file's signature is trusted; the one written moments ago
+        // is racy, so the next run reads it again, finds the same content and
+        // folds it back into the existing entry.
+        let next = fixture.run().await?;
+        assert_eq!(next.pending_files, 1);
+        let again = fixture.upload().await?;
+        assert_eq!(again.uploaded_files, 1);
+        assert_eq!(again.changed_files, 0);
+        assert_eq!(again.stored_chunks, 0);
+        assert_eq!(fixture.catalog()?.count_file_names()?, 2);
+
+        backdate(&fixture.src.join("new.txt"))?;
+        fixture.back_up().await?;
+        assert_eq!(fixture.run().await?.pending_files, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replaced_file_with_same_size_and_mtime_is_pending() -> Result<()> {
+        let fixture = Fixture::create(&[("a.txt", b"original")], 1, &[])?;
+        let path = fixture.src.join("a.txt");
+        backdate(&path)?;
+        fixture.back_up().await?;
+
+        // An atomic replace that keeps size and mtime still gets a new inode.
+        let mtime = fs::metadata(&path)?.modified()?;
+        let replacement = fixture.src.join("a.txt.tmp");
+        fs::write(&replacement, b"replaced")?;
+        fs::File::options()
+            .write(true)
+            .open(&replacement)?
+            .set_modified(mtime)?;
+        fs::rename(&replacement, &path)?;
+
+        assert_eq!(fixture.run().await?.pending_files, 1);
+        let result = fixture.upload().await?;
+        assert_eq!(result.stored_chunks, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rehash_rereads_every_file_and_keeps_unchanged_history() -> Result<()> {
+        let fixture =
+            Fixture::create(&[("a.txt", b"settled"), ("b.txt", b"also settled")], 1, &[])?;
+        backdate(&fixture.src.join("a.txt"))?;
+        backdate(&fixture.src.join("b.txt"))?;
+        fixture.back_up().await?;
+        assert_eq!(fixture.run().await?.pending_files, 0);
+
+        let rehashed = fixture.run_with(true).await?;
+        assert_eq!(rehashed.pending_files, 2);
+        let result = fixture.upload().await?;
+        assert_eq!(result.uploaded_files, 2);
+        assert_eq!(result.changed_files, 0);
+        assert_eq!(result.stored_chunks, 0);
+
+        // Identical content folds back into the existing entries, which span
+        // every version, and their signatures are trusted again.
+        let catalog = fixture.catalog()?;
+        assert_eq!(catalog.count_file_names()?, 2);
+        assert_eq!(
+            catalog.restore_entries(1)?.len(),
+            catalog.restore_entries(rehashed.version)?.len()
+        );
+        assert_eq!(fixture.run().await?.pending_files, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn signature_without_inode_is_completed_not_reread() -> Result<()> {
+        let fixture = Fixture::create(&[("a.txt", b"settled")], 1, &[])?;
+        backdate(&fixture.src.join("a.txt"))?;
+        fixture.back_up().await?;
+
+        // As recorded before inodes and ctimes were part of the signature.
+        let catalog = fixture.catalog()?;
+        let conn = rusqlite::Connection::open(catalog.db_path())?;
+        conn.execute("UPDATE FileNames SET inode = NULL, ctime_ns = NULL", [])?;
+
+        assert_eq!(fixture.run().await?.pending_files, 0);
+        let missing: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM FileNames WHERE inode IS NULL OR ctime_ns IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(missing, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_stores_what_it_reads_after_the_snapshot() -> Result<()> {
+        let fixture = Fixture::create(
+            &[("a.txt", b"as snapshotted"), ("b.txt", b"gone soon")],
+            1,
+            &[],
+        )?;
+
+        let snapshot = fixture.run().await?;
+        fs::write(fixture.src.join("a.txt"), b"rewritten before upload")?;
+        fs::remove_file(fixture.src.join("b.txt"))?;
+
+        let result = fixture.upload().await?;
+        assert_eq!(result.uploaded_files, 1);
+        assert_eq!(result.changed_files, 1);
+        assert_eq!(result.vanished_files, 1);
+
+        // The version keeps what was read, and the vanished file has no content.
+        let catalog = fixture.catalog()?;
+        let mut entries = catalog.restore_entries(snapshot.version)?;
+        entries.retain(|entry| entry.kind == EntryKind::File);
+        entries.sort_by(|left, right| left.path.cmp(&right.path));
+        let hashes: Vec<Option<String>> = entries.into_iter().map(|entry| entry.hash).collect();
+        assert_eq!(
+            hashes,
+            vec![
+                Some(blake3_keyed_bytes(
+                    b"rewritten before upload",
+                    &fixture.naming_key
+                )),
+                None,
+            ]
+        );
+        assert!(catalog.is_sealed(snapshot.version, &fixture.destination(0)?)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_destination_is_caught_up_next_upload() -> Result<()> {
+        let fixture = Fixture::create(&[("a.txt", b"hello world")], 2, &[])?;
+
+        // The second destination's root is a regular file, so every write to it
+        // fails.
+        let broken = fixture
+            .dests
+            .get(1)
+            .ok_or_else(|| anyhow!("no second destination"))?;
+        fs::write(broken, b"not a directory")?;
+
+        let result = fixture.back_up().await?;
+        assert_eq!(result.stored_packs, 1);
+        assert_eq!(result.failed_destinations, vec![fixture.destination(1)?]);
+        assert_eq!(result.destination_count, 2);
+        assert_eq!(result.sealed_destinations, 1);
+
+        // Once it is writable again, the next upload copies the pack it missed.
+        fs::remove_file(broken)?;
+        let again = fixture.upload().await?;
+        assert_eq!(again.copied_packs, 1);
+        assert!(again.failed_destinations.is_empty());
+        assert_eq!(again.sealed_destinations, 2);
+
+        let id = blake3_keyed_bytes(b"hello world", &fixture.naming_key);
+        assert_eq!(fixture.read_chunk(1, &id).await?.as_slice(), b"hello world");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pack_uploads_backfill_from_destinations() -> Result<()> {
+        let fixture = Fixture::create(&[("a.txt", b"hello world")], 1, &[])?;
+        fixture.back_up().await?;
+
+        // A catalog from before PackUploads: every pack is assumed to be on
+        // every configured destination.
+        let catalog = fixture.catalog()?;
+        let conn = rusqlite::Connection::open(catalog.db_path())?;
+        conn.execute_batch("DROP TABLE PackUploads;")?;
+        drop(conn);
+
+        let reopened = fixture.catalog()?;
+        assert_eq!(
+            reopened.pack_uploads()?,
+            vec![(
+                reopened.all_pack_ids()?.concat(),
+                vec![fixture.destination(0)?]
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pack_writer_stores_a_pack_each_time_one_fills() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let catalog = SqliteCatalog::initialize(&temp_dir.path().join("t.db"))?;
+        let store = NamedStore {
+            destination: "local".to_string(),
+            store: Arc::new(LocalStore::new(temp_dir.path().join("dest"))),
+        };
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let (_, public_key) = content_keypair(&mnemonic)?;
+        let naming_key: NamingKey = Arc::new(generate_naming_key());
+
+        // Incompressible max-size chunks, enough to fill one pack and start another.
+        let chunk_size = MAX_CHUNK_SIZE as usize;
+        let count = PACK_TARGET_SIZE / chunk_size + 1;
+        let writer = PackWriter::new(
+            catalog.clone(),
+            std::slice::from_ref(&store),
+            public_key,
+            naming_key.clone(),
+        );
+        for seed in 0..count {
+            let mut data = vec![0_u8; chunk_size];
+            blake3::Hasher::new()
+                .update(&seed.to_le_bytes())
+                .finalize_xof()
+                .fill(&mut data);
+            let id = blake3_keyed_bytes(&data, &naming_key);
+            assert!(writer.claim(&id));
+            writer.add(Chunk { id, data }).await?;
+        }
+        let stats = writer.finish().await?;
+
+        assert_eq!(stats.packs, 2);
+        assert_eq!(stats.chunks, count);
+        let mut listed: Vec<String> = store
+            .store
+            .list("")
+            .await?
+            .into_iter()
+            .map(|object| object.key)
+            .collect();
+        listed.sort();
+        let mut recorded = catalog.all_pack_ids()?;
+        recorded.sort();
+        assert_eq!(listed, recorded);
+
+        // Each pack's own index agrees with where the chunks were recorded.
+        for pack_id in &recorded {
+            let bytes = store.store.get(pack_id).await?;
+            for entry in read_index(&bytes, pack_id, &naming_key)? {
+                let location = catalog
+                    .chunk_location(&entry.chunk_id)?
+                    .ok_or_else(|| anyhow!("index lists an unrecorded chunk"))?;
+                assert_eq!(&location.pack_id, pack_id);
+                assert_eq!(
+                    (location.offset, location.length),
+                    (entry.offset, entry.length)
+                );
+            }
+        }
+        Ok(())
+    }
+}
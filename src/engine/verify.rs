@@ -1,104 +1,164 @@
-//! Verify (and optionally repair) that every content blob the catalog references
+//! Verify (and optionally repair) that every object the catalog references
 //! actually exists in each destination.
 //!
-//! `run` trusts the catalog when deciding what to upload, so if a destination
-//! loses blobs the catalog won't notice. `verify` re-checks the destinations:
-//! - **existence check** (default): is each content id present in each destination?
-//! - **`--repair`**: restore missing blobs. If another destination still has the
-//!   blob, **copy** it over (preserves the recorded key). If it's gone from every
-//!   destination, **re-seal** it from the source file (fresh key → overwrite all
-//!   destinations → update the catalog key). If no copy and no source, it's
+//! `upload` trusts the catalog when deciding what to store, so if a destination
+//! loses objects the catalog won't notice. `verify` re-checks the destinations.
+//! The objects are packs, plus chunks stored before packs as objects of their
+//! own:
+//! - **existence check** (default): is each object present in each destination?
+//! - **`--deep`**: also fetch every copy, unwrap each chunk's content key with
+//!   the recovery mnemonic, open the chunk and re-hash it against its id, so a
+//!   bit-rotted or truncated copy is caught, not only a missing one.
+//! - **`--repair`**: restore missing (and, deep, corrupt) copies. If another
+//!   destination still has a good copy, **copy** it over (preserves the recorded
+//!   keys). If it's gone from every destination, **re-seal** each chunk it held
+//!   from a source file containing it (re-chunk the file → fresh key → a new pack
+//!   in all destinations → point the catalog at it); a chunk corrupt in every
+//!   copy is re-sealed the same way. A chunk with no copy and no source is
 //!   reported as unrecoverable.
+//!
+//! A deep check records when it found every copy of an object intact, so a
+//! **sampled** one (`--sample`, `--max-bytes`) can check just the least recently
+//! verified objects each run and still cover them all over a few runs.
 
 use crate::{
-    db::sqlite::SqliteCatalog,
-    engine::run::{NamingKey, scan_worker_count},
-    storage::local::LocalStore,
-    utils::{crypto::seal_content, hash::blake3_keyed_bytes},
+    db::sqlite::{ManifestChunk, SqliteCatalog},
+    engine::{
+        run::{NamingKey, scan_worker_count},
+        upload::PackWriter,
+    },
+    storage::{NamedStore, open_named},
+    utils::{
+        chunk::{Chunk, chunk_stream},
+        crypto::{RecoveryKey, open_content, unseal_naming_key_with_secret, unwrap_content_key},
+        hash::blake3_keyed_bytes,
+        sparse::SparseMap,
+    },
 };
 use anyhow::{Result, anyhow};
 use futures::stream::{self, StreamExt, TryStreamExt};
 use std::{
-    collections::HashMap,
-    path::{Path, PathBuf},
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
 };
-use x25519_dalek::PublicKey;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+pub struct VerifyRequest {
+    pub name: String,
+    pub config_dir: PathBuf,
+    /// Restore missing (and, with `deep`, corrupt) copies.
+    pub repair: bool,
+    /// Needed only to repair by re-sealing from source (it re-chunks the source
+    /// to find the lost chunk); a deep check derives it from the mnemonic.
+    pub naming_key: Option<NamingKey>,
+    /// Open and re-hash every stored chunk with the keys this recovery mnemonic
+    /// unlocks, not just check that each object exists.
+    pub deep: Option<RecoveryKey>,
+    /// Deep-check only part of the objects, least recently verified first.
+    pub sample: Option<Sample>,
+}
+
+/// How much a sampled deep check covers. With both limits set, it stops at
+/// whichever is reached first; it always checks at least one object.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Sample {
+    /// Percentage (1–100) of the stored objects.
+    pub percent: Option<u8>,
+    /// Bytes to download, across every destination.
+    pub max_bytes: Option<u64>,
+}
 
 pub struct VerifyReport {
     pub destinations: usize,
-    pub content_ids: usize,
-    /// (content, destination) pairs found missing.
+    pub chunks: usize,
+    /// Stored objects checked: packs, plus chunks stored as their own object.
+    pub objects: usize,
+    /// Stored objects a sample left for later runs.
+    pub unchecked: usize,
+    /// (object, destination) pairs found missing.
     pub missing: usize,
+    /// Copies a deep check found damaged, sorted by destination and object.
+    pub corrupt: Vec<CorruptCopy>,
+    /// Missing or corrupt object copies replaced from a healthy destination.
     pub repaired_by_copy: usize,
+    /// Chunks re-sealed from source after their object was lost everywhere.
     pub repaired_by_reseal: usize,
-    /// Content ids missing everywhere with no usable source to re-seal from.
+    /// Chunk ids missing everywhere with no usable source to re-seal from.
     pub unrecoverable: Vec<String>,
 }
 
-/// Verify a backup's destinations, optionally repairing missing blobs.
-///
-/// `naming_key` is required only to repair by re-sealing from source (it verifies
-/// the source still matches the content id); pass `None` for an existence-only check.
+/// A copy of a stored object that failed a deep check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CorruptCopy {
+    pub destination: String,
+    pub object: String,
+    /// The chunks in this copy that do not open or hash back to their id.
+    pub chunks: Vec<String>,
+}
+
+/// The keys a deep check opens and re-hashes chunks with.
+#[derive(Clone)]
+struct DeepKeys {
+    private_key: StaticSecret,
+    naming_key: NamingKey,
+}
+
+/// Verify a backup's destinations, optionally repairing missing or corrupt
+/// copies.
 ///
 /// # Errors
-/// Returns an error if the backup is missing or a destination/catalog op fails.
-pub async fn verify(
-    config_dir: &Path,
-    name: &str,
-    repair: bool,
-    naming_key: Option<NamingKey>,
-) -> Result<VerifyReport> {
-    let db_file = config_dir.join(format!("{name}.db"));
+/// Returns an error if the backup is missing, a sample is asked for without the
+/// mnemonic, the deep-check mnemonic does not unlock the backup, or a
+/// destination/catalog op fails.
+pub async fn verify(request: VerifyRequest) -> Result<VerifyReport> {
+    if request.sample.is_some() && request.deep.is_none() {
+        return Err(anyhow!(
+            "a sampled check is a deep check: it needs the recovery mnemonic"
+        ));
+    }
+
+    let db_file = request.config_dir.join(format!("{}.db", request.name));
     if !db_file.exists() {
         return Err(anyhow!(
-            "No backup named \"{name}\" found. Create a new backup first."
+            "No backup named \"{}\" found. Create a new backup first.",
+            request.name
         ));
     }
 
     let catalog = SqliteCatalog::open(&db_file)?;
 
-    let mut stores = Vec::new();
-    for dest in catalog.configured_destinations()? {
-        if dest.starts_with("s3://") {
-            tracing::warn!("S3 destination not yet supported, skipping: {dest}");
-        } else {
-            stores.push(LocalStore::new(dest));
-        }
-    }
+    let stores = open_named(&catalog.configured_destinations()?)?;
     if stores.is_empty() {
-        return Err(anyhow!("no usable (filesystem) destinations configured"));
+        return Err(anyhow!("no destinations configured"));
     }
 
     let public_key = catalog.public_key()?;
-    // `Files.hash` is UNIQUE, so this is one entry per distinct content blob.
-    let content_ids = catalog.all_content_ids()?;
-
-    // For re-seal repair we need a live source file to regenerate a lost blob.
-    // Build the map only when repairing (it reads the catalog) — an existence-only
-    // verify needs no sources.
-    let source_by_id = if repair {
-        latest_source_paths(&catalog)?
-    } else {
-        HashMap::new()
+    let deep = match &request.deep {
+        Some(mnemonic) => Some(deep_keys(&catalog, public_key, mnemonic, &request.name)?),
+        None => None,
+    };
+    let naming_key = request
+        .naming_key
+        .or_else(|| deep.as_ref().map(|keys| keys.naming_key.clone()));
+
+    let chunk_count = catalog.all_chunk_ids()?.len();
+    let queue = catalog.objects_by_last_verified()?;
+    let stored_count = queue.len();
+    let objects = match request.sample {
+        Some(sample) => select_sample(queue, sample, stores.len()),
+        None => queue.into_iter().map(|(key, _)| key).collect(),
     };
 
-    // Each content id is independent, so check (and repair) them concurrently with
-    // a bounded pool — the same bound the upload phase uses. The work is dominated
-    // by `exists()` stat calls (one per destination per blob), which are I/O-bound;
-    // overlapping them is an order of magnitude faster than awaiting one at a time,
-    // especially on networked destinations. Repairs touch only their own blob file
-    // and catalog row, so distinct ids never race.
-    let outcomes: Vec<ContentOutcome> = stream::iter(content_ids.iter())
-        .map(|id| {
-            check_content(
-                id,
-                &stores,
-                repair,
-                &catalog,
-                public_key,
-                naming_key.as_ref(),
-                source_by_id.get(id).map_or(&[][..], Vec::as_slice),
-            )
+    // Each object is independent, so check (and copy-repair) them concurrently
+    // with a bounded pool — the same bound the upload phase uses. The work is
+    // dominated by `exists()` stat calls (one per destination per object), which
+    // are I/O-bound; overlapping them is an order of magnitude faster than
+    // awaiting one at a time, especially on networked destinations.
+    let outcomes: Vec<(&String, ObjectOutcome)> = stream::iter(objects.iter())
+        .map(|key| {
+            let outcome = check_object(&catalog, key, &stores, request.repair, deep.as_ref());
+            async move { Ok::<_, anyhow::Error>((key, outcome.await?)) }
         })
         .buffer_unordered(scan_worker_count())
         .try_collect()
@@ -106,297 +166,420 @@ pub async fn verify(
 
     let mut report = VerifyReport {
         destinations: stores.len(),
-        content_ids: content_ids.len(),
+        chunks: chunk_count,
+        objects: objects.len(),
+        unchecked: stored_count - objects.len(),
         missing: 0,
+        corrupt: Vec::new(),
         repaired_by_copy: 0,
         repaired_by_reseal: 0,
         unrecoverable: Vec::new(),
     };
-    for outcome in outcomes {
+    let mut lost = Vec::new();
+    let mut verified = Vec::new();
+    for (key, outcome) in outcomes {
         report.missing += outcome.missing;
+        report.corrupt.extend(outcome.corrupt);
         report.repaired_by_copy += outcome.repaired_by_copy;
-        report.repaired_by_reseal += outcome.repaired_by_reseal;
-        if let Some(id) = outcome.unrecoverable {
-            report.unrecoverable.push(id);
+        if outcome.intact {
+            verified.push(key.clone());
         }
+        lost.extend(outcome.lost);
+    }
+    if deep.is_some() {
+        catalog.record_verified(&verified, chrono::Utc::now().timestamp())?;
+    }
+
+    if request.repair && !lost.is_empty() {
+        let (resealed, unrecoverable) =
+            reseal_lost(&catalog, &stores, public_key, naming_key.as_ref(), lost).await?;
+        report.repaired_by_reseal = resealed;
+        report.unrecoverable = unrecoverable;
     }
-    // buffer_unordered completes out of order; sort so the report is deterministic.
+    // Checks and re-sealing complete out of order; sort so the report is
+    // deterministic.
+    report
+        .corrupt
+        .sort_by(|a, b| (&a.destination, &a.object).cmp(&(&b.destination, &b.object)));
     report.unrecoverable.sort();
 
     Ok(report)
 }
 
-/// What checking a single content id produced — folded into the [`VerifyReport`]
-/// after all ids finish. Returning a value (rather than mutating shared state)
-/// keeps the concurrent fan-out race-free.
+/// Derive the deep-check keys from the recovery mnemonic, refusing one that
+/// does not unlock this backup.
+fn deep_keys(
+    catalog: &SqliteCatalog,
+    public_key: PublicKey,
+    mnemonic: &RecoveryKey,
+    name: &str,
+) -> Result<DeepKeys> {
+    let (private_key, derived) = mnemonic.keypair()?;
+    if derived != public_key {
+        return Err(anyhow!(
+            "Incorrect mnemonic: it does not unlock backup \"{name}\""
+        ));
+    }
+    let naming_key = unseal_naming_key_with_secret(&catalog.sealed_naming_key()?, &private_key)?;
+    Ok(DeepKeys {
+        private_key,
+        naming_key: Arc::new(naming_key),
+    })
+}
+
+/// What checking a single object produced — folded into the [`VerifyReport`]
+/// after all objects finish. Returning a value (rather than mutating shared
+/// state) keeps the concurrent fan-out race-free.
 #[derive(Default)]
-struct ContentOutcome {
+struct ObjectOutcome {
     missing: usize,
+    corrupt: Vec<CorruptCopy>,
     repaired_by_copy: usize,
-    repaired_by_reseal: usize,
-    unrecoverable: Option<String>,
+    /// Chunks with no good copy anywhere: only re-sealing can bring them back.
+    lost: Vec<String>,
+    /// A deep check found every copy intact, or replaced the others with one.
+    intact: bool,
 }
 
-/// Check one content id across all destinations and, when `repair` is set, restore
-/// any missing copies (copy from a healthy destination, else re-seal from source).
-async fn check_content(
-    id: &str,
-    stores: &[LocalStore],
-    repair: bool,
+/// Check one object across all destinations (opening every chunk of each copy
+/// when `deep` is set) and, when `repair` is set, copy it from a destination
+/// with a good copy to the ones missing it or holding a corrupt one.
+async fn check_object(
     catalog: &SqliteCatalog,
-    public_key: PublicKey,
-    naming_key: Option<&NamingKey>,
-    sources: &[PathBuf],
-) -> Result<ContentOutcome> {
-    // Which destinations are missing this blob, and is any still healthy?
-    let mut missing_idx = Vec::new();
-    let mut healthy_idx = None;
-    for (idx, store) in stores.iter().enumerate() {
-        if store.exists(id).await? {
-            // Remember the first destination that still has the blob; it becomes
-            // the source for copy-repair.
-            healthy_idx.get_or_insert(idx);
-        } else {
-            missing_idx.push(idx);
+    key: &str,
+    stores: &[NamedStore],
+    repair: bool,
+    deep: Option<&DeepKeys>,
+) -> Result<ObjectOutcome> {
+    let chunks = Arc::new(match deep {
+        Some(_) => catalog.object_chunks(key)?,
+        None => Vec::new(),
+    });
+
+    let mut outcome = ObjectOutcome::default();
+    // Destinations to repair, and the first one with a good copy — the source
+    // for copy-repair — with its bytes, when a deep check already fetched them.
+    let mut bad_idx = Vec::new();
+    let mut healthy: Option<(usize, Option<Vec<u8>>)> = None;
+    // Chunks damaged in every copy checked so far (`None` before the first).
+    let mut damaged_everywhere: Option<HashSet<String>> = None;
+    for (idx, named) in stores.iter().enumerate() {
+        if !named.store.exists(key).await? {
+            outcome.missing += 1;
+            bad_idx.push(idx);
+            continue;
         }
+        let Some(keys) = deep else {
+            healthy.get_or_insert((idx, None));
+            continue;
+        };
+
+        let bytes = named.store.get(key).await?;
+        let (chunks, keys) = (chunks.clone(), keys.clone());
+        let (bytes, damaged) = tokio::task::spawn_blocking(move || {
+            let damaged = damaged_chunks(&bytes, &chunks, &keys);
+            (bytes, damaged)
+        })
+        .await?;
+        if damaged.is_empty() {
+            healthy.get_or_insert((idx, Some(bytes)));
+            continue;
+        }
+        damaged_everywhere = Some(match damaged_everywhere {
+            Some(everywhere) => damaged
+                .iter()
+                .filter(|id| everywhere.contains(*id))
+                .cloned()
+                .collect(),
+            None => damaged.iter().cloned().collect(),
+        });
+        outcome.corrupt.push(CorruptCopy {
+            destination: named.destination.clone(),
+            object: key.to_string(),
+            chunks: damaged,
+        });
+        bad_idx.push(idx);
     }
 
-    let mut outcome = ContentOutcome::default();
-    if missing_idx.is_empty() {
+    if bad_idx.is_empty() {
+        outcome.intact = deep.is_some();
         return Ok(outcome);
     }
-    outcome.missing = missing_idx.len();
 
-    if !repair {
+    let Some((healthy, bytes)) = healthy else {
+        // No good copy to copy from: every chunk if the object is gone from
+        // every destination, else those damaged in every copy there is.
+        outcome.lost = match damaged_everywhere {
+            Some(ids) => ids.into_iter().collect(),
+            None => catalog
+                .object_chunks(key)?
+                .into_iter()
+                .map(|chunk| chunk.id)
+                .collect(),
+        };
         return Ok(outcome);
-    }
+    };
 
-    if let Some(healthy) = healthy_idx {
-        // At least one destination still has the blob: copy it to the others. The
-        // content key is unchanged, so every copy stays byte-identical and the
-        // catalog key keeps decrypting all of them.
-        let blob = store_at(stores, healthy)?.get(id).await?;
-        for idx in &missing_idx {
-            store_at(stores, *idx)?.put(id, &blob).await?;
+    if repair {
+        // Copy it to the others. The content keys are unchanged, so every copy
+        // stays byte-identical and the catalog keeps decrypting all of them.
+        let bytes = match bytes {
+            Some(bytes) => bytes,
+            None => store_at(stores, healthy)?.store.get(key).await?,
+        };
+        for idx in &bad_idx {
+            store_at(stores, *idx)?.store.put(key, &bytes).await?;
             outcome.repaired_by_copy += 1;
         }
-    } else if reseal_and_store(catalog, stores, public_key, naming_key, id, sources).await? {
-        // Gone from every destination — re-sealed from the source file.
-        outcome.repaired_by_reseal = 1;
-    } else {
-        outcome.unrecoverable = Some(id.to_string());
+        outcome.intact = deep.is_some();
     }
 
     Ok(outcome)
 }
 
-fn store_at(stores: &[LocalStore], idx: usize) -> Result<&LocalStore> {
+/// The objects a sampled check covers: from the front of `stored` (least
+/// recently verified first), as many as the percentage allows and whose
+/// copies fit the byte budget, and at least one.
+fn select_sample(stored: Vec<(String, u64)>, sample: Sample, destinations: usize) -> Vec<String> {
+    let count_limit = sample.percent.map_or(stored.len(), |percent| {
+        (stored.len() * usize::from(percent)).div_ceil(100)
+    });
+    let copies = u64::try_from(destinations).unwrap_or(u64::MAX);
+
+    let mut selected = Vec::new();
+    let mut budget = sample.max_bytes.unwrap_or(u64::MAX);
+    for (key, size) in stored.into_iter().take(count_limit.max(1)) {
+        let cost = size.saturating_mul(copies);
+        if cost > budget && !selected.is_empty() {
+            break;
+        }
+        budget = budget.saturating_sub(cost);
+        selected.push(key);
+    }
+    selected
+}
+
+fn store_at(stores: &[NamedStore], idx: usize) -> Result<&NamedStore> {
     stores
         .get(idx)
         .ok_or_else(|| anyhow!("store index {idx} out of range"))
 }
 
-/// Map each content id to the source paths of the latest completed snapshot.
+/// Open each chunk a copy of an object holds and re-hash it against its id;
+/// returns the ids of the ones that fail.
+fn damaged_chunks(bytes: &[u8], chunks: &[ManifestChunk], keys: &DeepKeys) -> Vec<String> {
+    chunks
+        .iter()
+        .filter(|chunk| check_chunk(bytes, chunk, keys).is_err())
+        .map(|chunk| chunk.id.clone())
+        .collect()
+}
+
+/// Open one chunk of an object's bytes — its range of a pack, or the whole
+/// object for a chunk stored before packs — and check it hashes to its id.
+fn check_chunk(bytes: &[u8], chunk: &ManifestChunk, keys: &DeepKeys) -> Result<()> {
+    let blob = match &chunk.location {
+        Some(pack) => {
+            let start = usize::try_from(pack.offset)?;
+            let end = start
+                .checked_add(usize::try_from(pack.length)?)
+                .ok_or_else(|| anyhow!("chunk {} has a bad pack range", chunk.id))?;
+            bytes
+                .get(start..end)
+                .ok_or_else(|| anyhow!("chunk {} lies past the end of its pack", chunk.id))?
+        }
+        None => bytes,
+    };
+    let content_key = unwrap_content_key(
+        &chunk.wrapped_key,
+        &chunk.ephemeral_public_key,
+        &keys.private_key,
+        &chunk.id,
+    )?;
+    let plaintext = open_content(blob, &chunk.id, &content_key)?;
+    if blake3_keyed_bytes(&plaintext, &keys.naming_key) != chunk.id {
+        return Err(anyhow!("chunk {} does not match its id", chunk.id));
+    }
+    Ok(())
+}
+
+/// Map each chunk id to the source paths of the latest completed snapshot.
 ///
-/// A single content id can have several paths (deduplicated identical files), so
-/// values are vectors: if one path was deleted, [`reseal_and_store`] can still
-/// regenerate the blob from a surviving duplicate. Only the latest *completed*
-/// version is considered — that is the state a fresh `run` would reproduce.
+/// A single chunk can appear in several files (deduplicated content), so values
+/// are vectors: if one path was deleted, [`reseal_lost`] can still regenerate
+/// the chunk from a surviving file. Only the latest *completed* version is
+/// considered — that is the state a fresh `run` would reproduce.
 fn latest_source_paths(catalog: &SqliteCatalog) -> Result<HashMap<String, Vec<PathBuf>>> {
     let mut map: HashMap<String, Vec<PathBuf>> = HashMap::new();
     if let Some(version) = catalog.latest_version()? {
-        for entry in catalog.restore_entries(version)? {
-            map.entry(entry.hash).or_default().push(entry.path);
+        for (chunk, path) in catalog.chunk_sources(version)? {
+            map.entry(chunk).or_default().push(path);
         }
     }
     Ok(map)
 }
 
-/// Re-seal a content id from one of its source files and write it to every
-/// destination, updating the catalog key so all copies agree.
-///
-/// Returns `Ok(false)` (repairing nothing) when there is no naming key, no source
-/// path, or none of the candidate paths still reads back with matching content —
-/// i.e. the blob is genuinely unrecoverable.
+/// Re-seal lost chunks from their source files into new packs written to every
+/// destination, each recorded as it is stored (pointing the catalog at it), then
+/// forget the lost packs. Returns the number re-sealed and the ids that could
+/// not be: no naming key, no source path, or none of the candidate files still
+/// contains the chunk — i.e. genuinely unrecoverable.
 ///
-/// Ordering note: the catalog key is updated *before* the blobs are written. The
-/// catalog is the source of truth, and the two effects (catalog row + N store
-/// writes) cannot be made atomic. Updating the key first means a crash mid-repair
-/// leaves the blob *missing* — which a later `verify` detects and repairs — rather
-/// than *present but encrypted with a stale key*, which would look healthy to an
-/// existence check yet fail to decrypt on restore.
-async fn reseal_and_store(
+/// Ordering note: the new packs are written *before* the catalog is updated. The
+/// two effects (N store writes + catalog rows) cannot be made atomic; this order
+/// means a crash mid-repair leaves an unreferenced pack (reclaimed by `gc`)
+/// and the catalog still pointing at the lost object — which a later `verify`
+/// detects and repairs — never a catalog entry for a pack that wasn't written.
+async fn reseal_lost(
     catalog: &SqliteCatalog,
-    stores: &[LocalStore],
+    stores: &[NamedStore],
     public_key: PublicKey,
     naming_key: Option<&NamingKey>,
-    id: &str,
-    sources: &[PathBuf],
-) -> Result<bool> {
+    lost: Vec<String>,
+) -> Result<(usize, Vec<String>)> {
     let Some(naming_key) = naming_key else {
-        return Ok(false);
+        return Ok((0, lost));
     };
 
-    // Find a source file that still exists and whose content matches the id.
-    let mut bytes = None;
-    for path in sources {
-        if let Ok(data) = tokio::fs::read(path).await
-            && blake3_keyed_bytes(&data, naming_key) == id
-        {
-            bytes = Some(data);
-            break;
+    // Re-sealing needs a live source file to regenerate each lost chunk.
+    let source_by_id = latest_source_paths(catalog)?;
+    let packs = PackWriter::new(catalog.clone(), stores, public_key, naming_key.clone())
+        .with_recipients(catalog.recipients()?)?;
+
+    // Finding a chunk re-reads (and re-chunks) a whole source file, so look for
+    // several at once.
+    let found: Vec<(String, Option<Vec<u8>>)> = stream::iter(lost)
+        .map(|id| {
+            let sources = source_by_id.get(&id).cloned().unwrap_or_default();
+            async move {
+                for path in sources {
+                    if let Ok(Some(data)) = find_chunk(path, naming_key.clone(), id.clone()).await {
+                        return (id, Some(data));
+                    }
+                }
+                (id, None)
+            }
+        })
+        .buffer_unordered(scan_worker_count())
+        .collect()
+        .await;
+
+    let mut unrecoverable = Vec::new();
+    for (id, data) in found {
+        match data {
+            Some(data) => packs.add(Chunk { id, data }).await?,
+            None => unrecoverable.push(id),
         }
     }
-    let Some(bytes) = bytes else {
-        return Ok(false);
-    };
 
-    let seal_id = id.to_string();
-    let sealed =
-        tokio::task::spawn_blocking(move || seal_content(&bytes, &public_key, &seal_id)).await??;
+    let resealed = packs.finish().await?;
+    catalog.forget_unreferenced_packs()?;
 
-    catalog.update_content_key(id, &sealed.wrapped_key, &sealed.ephemeral_public_key)?;
-    for store in stores {
-        store.put(id, &sealed.blob).await?;
-    }
+    Ok((resealed.chunks, unrecoverable))
+}
 
-    Ok(true)
+/// Re-chunk `path` (only its data, if it is sparse, as `upload` does) and return
+/// the chunk whose id is `id`, if it still has one.
+async fn find_chunk(path: PathBuf, naming_key: NamingKey, id: String) -> Result<Option<Vec<u8>>> {
+    tokio::task::spawn_blocking(move || {
+        let mut found = None;
+        let keep = |chunk: Chunk| {
+            if found.is_none() && chunk.id == id {
+                found = Some(chunk.data);
+            }
+            Ok(())
+        };
+        let file = std::fs::File::open(path)?;
+        match SparseMap::detect(&file)? {
+            Some(map) => chunk_stream(map.reader(file), &naming_key, keep)?,
+            None => chunk_stream(file, &naming_key, keep)?,
+        };
+        Ok(found)
+    })
+    .await?
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        engine::{
-            create::{CreateBackupRequest, create},
-            run::{IgnoreRules, RunBackupRequest, run},
-            wkey,
-        },
-        utils::crypto::{content_key_aad, decrypt, open_content},
+        engine::fixture::Fixture,
+        storage::{Storage, fake_s3::FakeS3, local::LocalStore, sharded_key},
+        utils::hash::blake3_keyed_bytes,
     };
     use anyhow::anyhow;
     use bip39::{Language, Mnemonic};
-    use std::{fs, sync::Arc};
-
-    struct Fixture {
-        _tmp: tempfile::TempDir,
-        cfg: PathBuf,
-        src: PathBuf,
-        dests: Vec<PathBuf>,
-        mnemonic: Mnemonic,
-        naming_key: NamingKey,
-    }
-
-    /// Build a backup, write `files` into the source dir, and run it so every
-    /// filesystem destination holds the blobs.
-    ///
-    /// `dest_count` filesystem destinations are created (`dest0`, `dest1`, …).
-    /// When `with_s3` is set, an extra `s3://…` destination is also *configured*
-    /// (to exercise the "skip unsupported backend" path) but is not returned in
-    /// `Fixture::dests`, since there is no local store to inspect for it.
-    async fn build(files: &[(&str, &[u8])], dest_count: usize, with_s3: bool) -> Result<Fixture> {
-        let tmp = tempfile::tempdir()?;
-        let cfg = tmp.path().join("cfg");
-        let src = tmp.path().join("src");
-        fs::create_dir_all(&cfg)?;
-        fs::create_dir_all(&src)?;
-        for (name, contents) in files {
-            fs::write(src.join(name), contents)?;
-        }
-
-        let dests: Vec<PathBuf> = (0..dest_count)
-            .map(|i| tmp.path().join(format!("dest{i}")))
-            .collect();
-
-        let mut configured: Vec<String> = dests
-            .iter()
-            .map(|d| d.to_string_lossy().into_owned())
-            .collect();
-        if with_s3 {
-            configured.push("s3://example-bucket/prefix".to_string());
-        }
-
-        let created = create(CreateBackupRequest {
-            name: "t".to_string(),
-            config_dir: cfg.clone(),
-            directories: vec![src.clone()],
-            files: Vec::new(),
-            destinations: configured,
-        })?;
-        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &created.recovery_phrase)?;
-        let naming_key: NamingKey =
-            Arc::new(wkey::load_naming_key(&cfg, "t")?.ok_or_else(|| anyhow!("missing wkey"))?);
-
-        run(RunBackupRequest {
-            name: "t".to_string(),
-            config_dir: cfg.clone(),
-            ignore_rules: IgnoreRules::backupignore_only(),
-            dry_run: false,
-            progress: None,
-            naming_key: naming_key.clone(),
-        })
-        .await?;
-
-        Ok(Fixture {
-            _tmp: tmp,
-            cfg,
-            src,
-            dests,
-            mnemonic,
-            naming_key,
-        })
+    use std::fs;
+
+    /// Build a backup over `files` with `dest_count` filesystem destinations
+    /// (and an `s3://…` one, if given), and back it up.
+    async fn build(
+        files: &[(&str, &[u8])],
+        dest_count: usize,
+        s3: Option<&str>,
+    ) -> Result<Fixture> {
+        let extra: Vec<String> = s3.into_iter().map(str::to_string).collect();
+        let fx = Fixture::create(files, dest_count, &extra)?;
+        fx.back_up().await?;
+        Ok(fx)
     }
 
     /// Convenience: one "hello world" file across `dest_count` filesystem
     /// destinations.
     async fn setup(dest_count: usize) -> Result<Fixture> {
-        build(&[("a.txt", b"hello world")], dest_count, false).await
+        build(&[("a.txt", b"hello world")], dest_count, None).await
+    }
+
+    fn request(fx: &Fixture, repair: bool, naming_key: Option<NamingKey>) -> VerifyRequest {
+        VerifyRequest {
+            name: "t".to_string(),
+            config_dir: fx.cfg.clone(),
+            repair,
+            naming_key,
+            deep: None,
+            sample: None,
+        }
     }
 
-    impl Fixture {
-        fn store(&self, i: usize) -> Result<LocalStore> {
-            self.dests
-                .get(i)
-                .map(LocalStore::new)
-                .ok_or_else(|| anyhow!("no destination {i}"))
+    /// A deep check, unlocked with the backup's mnemonic.
+    fn deep_request(fx: &Fixture, repair: bool) -> VerifyRequest {
+        VerifyRequest {
+            deep: Some(fx.mnemonic.clone().into()),
+            ..request(fx, repair, None)
         }
     }
 
-    /// Decrypt the stored blob for `id` from `store` using the catalog's current
-    /// wrapped key and the recovery mnemonic.
-    async fn decrypt_blob(
-        catalog: &SqliteCatalog,
-        store: &LocalStore,
-        mnemonic: &Mnemonic,
-        id: &str,
-    ) -> Result<Vec<u8>> {
-        let (wrapped, eph) = catalog
-            .wrapped_content_key(id)?
-            .ok_or_else(|| anyhow!("no wrapped key"))?;
-        let key_vec = decrypt(&wrapped, &eph, mnemonic, &content_key_aad(id))?;
-        let key: [u8; 32] = key_vec
-            .as_slice()
-            .try_into()
-            .map_err(|_| anyhow!("bad key length"))?;
-        let blob = store.get(id).await?;
-        Ok(open_content(&blob, id, &key)?.to_vec())
+    /// The pack object holding chunk `id`.
+    fn pack_of(fx: &Fixture, id: &str) -> Result<String> {
+        fx.catalog()?
+            .chunk_location(id)?
+            .map(|location| location.pack_id)
+            .ok_or_else(|| anyhow!("chunk {id} is not in a pack"))
+    }
+
+    /// Write another source file and back it up, so it lands in its own pack.
+    async fn add_and_run(fx: &Fixture, name: &str, contents: &[u8]) -> Result<()> {
+        fs::write(fx.src.join(name), contents)?;
+        fx.back_up().await?;
+        Ok(())
     }
 
     #[tokio::test]
-    async fn verify_detects_missing_blob_without_repairing() -> Result<()> {
+    async fn verify_detects_missing_pack_without_repairing() -> Result<()> {
         let fx = setup(1).await?;
         let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
+        let pack = pack_of(&fx, &id)?;
         let store = fx.store(0)?;
-        store.remove(&id).await?;
+        store.remove(&pack).await?;
 
-        let report = verify(&fx.cfg, "t", false, None).await?;
+        let report = verify(request(&fx, false, None)).await?;
 
         assert_eq!(report.missing, 1);
         assert_eq!(report.repaired_by_copy, 0);
         assert_eq!(report.repaired_by_reseal, 0);
         assert!(report.unrecoverable.is_empty());
         // No repair requested -> still missing.
-        assert!(!store.exists(&id).await?);
+        assert!(!store.exists(&pack).await?);
 
         Ok(())
     }
@@ -405,11 +588,12 @@ mod tests {
     async fn repair_copies_from_healthy_destination() -> Result<()> {
         let fx = setup(2).await?;
         let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
+        let pack = pack_of(&fx, &id)?;
         let broken = fx.store(0)?;
         let healthy = fx.store(1)?;
-        broken.remove(&id).await?;
+        broken.remove(&pack).await?;
 
-        let report = verify(&fx.cfg, "t", true, Some(fx.naming_key.clone())).await?;
+        let report = verify(request(&fx, true, Some(fx.naming_key.clone()))).await?;
 
         assert_eq!(report.missing, 1);
         assert_eq!(report.repaired_by_copy, 1);
@@ -417,13 +601,12 @@ mod tests {
         assert!(report.unrecoverable.is_empty());
 
         // Copied back, and both copies are byte-identical (same key preserved).
-        assert!(broken.exists(&id).await?);
-        assert_eq!(broken.get(&id).await?, healthy.get(&id).await?);
+        assert!(broken.exists(&pack).await?);
+        assert_eq!(broken.get(&pack).await?, healthy.get(&pack).await?);
 
         // Still decrypts with the original (unchanged) catalog key.
-        let catalog = SqliteCatalog::open(&fx.cfg.join("t.db"))?;
-        let plaintext = decrypt_blob(&catalog, &broken, &fx.mnemonic, &id).await?;
-        assert_eq!(plaintext, b"hello world");
+        let plaintext = fx.read_chunk(0, &id).await?;
+        assert_eq!(plaintext.as_slice(), b"hello world");
 
         Ok(())
     }
@@ -432,25 +615,33 @@ mod tests {
     async fn repair_reseals_when_gone_everywhere() -> Result<()> {
         let fx = setup(2).await?;
         let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
+        let pack = pack_of(&fx, &id)?;
         for dest in &fx.dests {
-            LocalStore::new(dest).remove(&id).await?;
+            LocalStore::new(dest).remove(&pack).await?;
         }
 
-        let report = verify(&fx.cfg, "t", true, Some(fx.naming_key.clone())).await?;
+        let report = verify(request(&fx, true, Some(fx.naming_key.clone()))).await?;
 
         assert_eq!(report.repaired_by_copy, 0);
         assert_eq!(report.repaired_by_reseal, 1);
         assert!(report.unrecoverable.is_empty());
 
-        // Re-sealed into every destination; the new (updated) key decrypts it.
-        let catalog = SqliteCatalog::open(&fx.cfg.join("t.db"))?;
-        for dest in &fx.dests {
-            let store = LocalStore::new(dest);
-            assert!(store.exists(&id).await?);
-            let plaintext = decrypt_blob(&catalog, &store, &fx.mnemonic, &id).await?;
-            assert_eq!(plaintext, b"hello world");
+        // Re-sealed into a new pack in every destination; the catalog points at
+        // it and the new (updated) key decrypts it.
+        let catalog = fx.catalog()?;
+        let resealed = pack_of(&fx, &id)?;
+        assert_ne!(resealed, pack);
+        assert_eq!(catalog.all_pack_ids()?, vec![resealed.clone()]);
+        for index in 0..fx.dests.len() {
+            assert!(fx.store(index)?.exists(&resealed).await?);
+            let plaintext = fx.read_chunk(index, &id).await?;
+            assert_eq!(plaintext.as_slice(), b"hello world");
         }
 
+        // And the repaired backup verifies clean.
+        let report = verify(request(&fx, false, None)).await?;
+        assert_eq!(report.missing, 0);
+
         Ok(())
     }
 
@@ -458,10 +649,10 @@ mod tests {
     async fn unrecoverable_when_source_and_all_copies_gone() -> Result<()> {
         let fx = setup(1).await?;
         let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
-        fx.store(0)?.remove(&id).await?;
+        fx.store(0)?.remove(&pack_of(&fx, &id)?).await?;
         fs::remove_file(fx.src.join("a.txt"))?;
 
-        let report = verify(&fx.cfg, "t", true, Some(fx.naming_key.clone())).await?;
+        let report = verify(request(&fx, true, Some(fx.naming_key.clone()))).await?;
 
         assert_eq!(report.repaired_by_copy, 0);
         assert_eq!(report.repaired_by_reseal, 0);
@@ -471,11 +662,13 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn verify_passes_when_all_blobs_present() -> Result<()> {
-        let fx = setup(2).await?;
-        let report = verify(&fx.cfg, "t", false, None).await?;
+    async fn verify_passes_when_all_objects_present() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha"), ("b.txt", b"beta")], 2, None).await?;
+        let report = verify(request(&fx, false, None)).await?;
         assert_eq!(report.missing, 0);
-        assert_eq!(report.content_ids, 1);
+        assert_eq!(report.chunks, 2);
+        // Both chunks went into the run's one pack.
+        assert_eq!(report.objects, 1);
         assert_eq!(report.destinations, 2);
         Ok(())
     }
@@ -488,127 +681,211 @@ mod tests {
         let fx = setup(1).await?;
         let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
 
-        let catalog = SqliteCatalog::open(&fx.cfg.join("t.db"))?;
+        let catalog = fx.catalog()?;
         let before = catalog
-            .wrapped_content_key(&id)?
+            .wrapped_chunk_key(&id)?
             .ok_or_else(|| anyhow!("no key before"))?;
 
-        fx.store(0)?.remove(&id).await?;
-        let report = verify(&fx.cfg, "t", true, Some(fx.naming_key.clone())).await?;
+        fx.store(0)?.remove(&pack_of(&fx, &id)?).await?;
+        let report = verify(request(&fx, true, Some(fx.naming_key.clone()))).await?;
         assert_eq!(report.repaired_by_reseal, 1);
 
         let after = catalog
-            .wrapped_content_key(&id)?
+            .wrapped_chunk_key(&id)?
             .ok_or_else(|| anyhow!("no key after"))?;
         assert_ne!(before, after, "reseal should rewrap with a fresh key");
 
         // And it still decrypts to the original bytes under the new key.
-        let plaintext = decrypt_blob(&catalog, &fx.store(0)?, &fx.mnemonic, &id).await?;
-        assert_eq!(plaintext, b"hello world");
+        let plaintext = fx.read_chunk(0, &id).await?;
+        assert_eq!(plaintext.as_slice(), b"hello world");
         Ok(())
     }
 
     /// Deduplicated content has several source paths; losing one must not make the
-    /// blob unrecoverable as long as a duplicate source survives.
+    /// chunk unrecoverable as long as a duplicate source survives.
     #[tokio::test]
     async fn reseal_uses_a_surviving_duplicate_source() -> Result<()> {
-        let fx = build(&[("dup1.txt", b"same"), ("dup2.txt", b"same")], 1, false).await?;
+        let fx = build(&[("dup1.txt", b"same"), ("dup2.txt", b"same")], 1, None).await?;
         let id = blake3_keyed_bytes(b"same", &fx.naming_key);
 
-        // One blob for both files; remove it and delete only the first source.
-        fx.store(0)?.remove(&id).await?;
+        // One chunk for both files; lose its pack and delete only the first source.
+        fx.store(0)?.remove(&pack_of(&fx, &id)?).await?;
         fs::remove_file(fx.src.join("dup1.txt"))?;
 
-        let report = verify(&fx.cfg, "t", true, Some(fx.naming_key.clone())).await?;
-        assert_eq!(report.content_ids, 1);
+        let report = verify(request(&fx, true, Some(fx.naming_key.clone()))).await?;
+        assert_eq!(report.chunks, 1);
         assert_eq!(report.repaired_by_reseal, 1);
         assert!(report.unrecoverable.is_empty());
-        assert!(fx.store(0)?.exists(&id).await?);
+        assert!(fx.store(0)?.exists(&pack_of(&fx, &id)?).await?);
+        Ok(())
+    }
+
+    /// A lost pack takes all its chunks with it: each is re-sealed from its own
+    /// source, and only those without one are unrecoverable.
+    #[tokio::test]
+    async fn lost_pack_reseals_every_chunk_it_held() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha"), ("b.txt", b"beta")], 1, None).await?;
+        let alpha = blake3_keyed_bytes(b"alpha", &fx.naming_key);
+        let beta = blake3_keyed_bytes(b"beta", &fx.naming_key);
+        let pack = pack_of(&fx, &alpha)?;
+        assert_eq!(pack_of(&fx, &beta)?, pack);
+
+        fx.store(0)?.remove(&pack).await?;
+        fs::remove_file(fx.src.join("b.txt"))?;
+
+        let report = verify(request(&fx, true, Some(fx.naming_key.clone()))).await?;
+        assert_eq!(report.missing, 1);
+        assert_eq!(report.repaired_by_reseal, 1);
+        assert_eq!(report.unrecoverable, vec![beta.clone()]);
+
+        // alpha moved to a new pack; beta still points at the lost one, which
+        // stays recorded (and keeps being reported) rather than being forgotten.
+        let catalog = fx.catalog()?;
+        assert_ne!(pack_of(&fx, &alpha)?, pack);
+        assert_eq!(pack_of(&fx, &beta)?, pack);
+        assert_eq!(catalog.all_pack_ids()?.len(), 2);
+        let plaintext = fx.read_chunk(0, &alpha).await?;
+        assert_eq!(plaintext.as_slice(), b"alpha");
         Ok(())
     }
 
-    /// Verify must flag only the blobs that are actually gone, not every blob.
+    /// Verify must flag only the objects that are actually gone, not every one.
     #[tokio::test]
-    async fn verify_reports_only_the_missing_blob() -> Result<()> {
-        let fx = build(&[("a.txt", b"alpha"), ("b.txt", b"beta")], 1, false).await?;
+    async fn verify_reports_only_the_missing_pack() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha")], 1, None).await?;
+        add_and_run(&fx, "b.txt", b"beta").await?;
         let gone = blake3_keyed_bytes(b"alpha", &fx.naming_key);
-        fx.store(0)?.remove(&gone).await?;
+        fx.store(0)?.remove(&pack_of(&fx, &gone)?).await?;
 
-        let report = verify(&fx.cfg, "t", false, None).await?;
-        assert_eq!(report.content_ids, 2);
+        let report = verify(request(&fx, false, None)).await?;
+        assert_eq!(report.chunks, 2);
+        assert_eq!(report.objects, 2);
         assert_eq!(report.missing, 1);
         Ok(())
     }
 
-    /// `s3://` destinations are not wired yet; verify must skip them rather than
-    /// fail, and count only the filesystem destinations it can actually check.
+    /// Chunks stored before packs are objects of their own: verify checks and
+    /// repairs them alongside packs.
     #[tokio::test]
-    async fn s3_destinations_are_skipped() -> Result<()> {
-        let fx = build(&[("a.txt", b"hello world")], 1, true).await?;
-        let report = verify(&fx.cfg, "t", false, None).await?;
-        assert_eq!(
-            report.destinations, 1,
-            "only the filesystem dest is checked"
-        );
+    async fn loose_chunks_from_before_packs_are_verified() -> Result<()> {
+        let fx = setup(2).await?;
+        let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
+        let location = fx
+            .catalog()?
+            .chunk_location(&id)?
+            .ok_or_else(|| anyhow!("not in a pack"))?;
+
+        // Turn the chunk into a loose object, as a pre-pack run left it.
+        for dest in &fx.dests {
+            let store = LocalStore::new(dest);
+            let blob = store
+                .get_range(&location.pack_id, location.offset, location.length)
+                .await?;
+            store.put(&id, &blob).await?;
+            store.remove(&location.pack_id).await?;
+        }
+        let conn = rusqlite::Connection::open(fx.cfg.join("t.db"))?;
+        conn.execute_batch(
+            "UPDATE Chunks SET pack_id = NULL, pack_offset = NULL, pack_length = NULL;
+             DELETE FROM PackUploads;
+             DELETE FROM Packs;",
+        )?;
+
+        let report = verify(request(&fx, false, None)).await?;
+        assert_eq!(report.objects, 1);
+        assert_eq!(report.missing, 0);
+
+        fx.store(0)?.remove(&id).await?;
+        let report = verify(request(&fx, true, None)).await?;
+        assert_eq!(report.missing, 1);
+        assert_eq!(report.repaired_by_copy, 1);
+        assert!(fx.store(0)?.exists(&id).await?);
+        Ok(())
+    }
+
+    /// An `s3://` destination is written by `run`, checked by verify alongside
+    /// the filesystem one, and repaired by copying from the healthy copy.
+    #[tokio::test]
+    async fn s3_destination_is_verified_and_repaired() -> Result<()> {
+        let server = FakeS3::start().await?;
+        let fx = build(
+            &[("a.txt", b"hello world")],
+            1,
+            Some(&server.destination("bucket", "backups")),
+        )
+        .await?;
+        let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
+        let object = format!("backups/{}", sharded_key(&pack_of(&fx, &id)?)?);
+        assert!(server.has_object("bucket", &object));
+
+        let report = verify(request(&fx, false, None)).await?;
+        assert_eq!(report.destinations, 2);
         assert_eq!(report.missing, 0);
+
+        server.delete_object("bucket", &object);
+        let report = verify(request(&fx, true, None)).await?;
+        assert_eq!(report.missing, 1);
+        assert_eq!(report.repaired_by_copy, 1);
+        assert!(server.has_object("bucket", &object));
         Ok(())
     }
 
     /// Without a naming key, re-seal cannot verify a source against its content id,
-    /// so a blob missing everywhere is reported unrecoverable rather than risk
+    /// so a chunk lost everywhere is reported unrecoverable rather than risk
     /// writing unverified bytes. (The CLI only omits the key for `verify` without
     /// `--repair`; this guards the engine contract directly.)
     #[tokio::test]
     async fn repair_without_naming_key_cannot_reseal() -> Result<()> {
         let fx = setup(1).await?;
         let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
-        fx.store(0)?.remove(&id).await?;
+        fx.store(0)?.remove(&pack_of(&fx, &id)?).await?;
 
-        let report = verify(&fx.cfg, "t", true, None).await?;
+        let report = verify(request(&fx, true, None)).await?;
         assert_eq!(report.repaired_by_reseal, 0);
         assert_eq!(report.unrecoverable, vec![id]);
         Ok(())
     }
 
-    /// Build a backup with `count` distinct files (`f0.txt`…), one blob each.
+    /// Build a backup with `count` distinct files (`f0.txt`…), each backed up by
+    /// its own run so every chunk lands in its own pack.
     async fn build_many(count: usize, dest_count: usize) -> Result<(Fixture, Vec<String>)> {
-        let owned: Vec<(String, Vec<u8>)> = (0..count)
-            .map(|i| (format!("f{i}.txt"), format!("content-{i}").into_bytes()))
+        let contents: Vec<Vec<u8>> = (0..count)
+            .map(|i| format!("content-{i}").into_bytes())
             .collect();
-        let files: Vec<(&str, &[u8])> = owned
-            .iter()
-            .map(|(n, c)| (n.as_str(), c.as_slice()))
-            .collect();
-        let fx = build(&files, dest_count, false).await?;
-        let ids = owned
+        let fx = build(&[], dest_count, None).await?;
+        for (i, content) in contents.iter().enumerate() {
+            add_and_run(&fx, &format!("f{i}.txt"), content).await?;
+        }
+        let ids = contents
             .iter()
-            .map(|(_, c)| blake3_keyed_bytes(c, &fx.naming_key))
+            .map(|content| blake3_keyed_bytes(content, &fx.naming_key))
             .collect();
         Ok((fx, ids))
     }
 
-    /// The concurrent fan-out must count every missing blob exactly once across
-    /// many content ids (guards the per-id outcome fold).
+    /// The concurrent fan-out must count every missing object exactly once across
+    /// many packs (guards the per-object outcome fold).
     #[tokio::test]
-    async fn verify_aggregates_missing_across_many_contents() -> Result<()> {
+    async fn verify_aggregates_missing_across_many_packs() -> Result<()> {
         let (fx, ids) = build_many(12, 1).await?;
         let store = fx.store(0)?;
 
         let mut removed = 0;
         for (i, id) in ids.iter().enumerate() {
             if i % 3 == 0 {
-                store.remove(id).await?;
+                store.remove(&pack_of(&fx, id)?).await?;
                 removed += 1;
             }
         }
 
-        let report = verify(&fx.cfg, "t", false, None).await?;
-        assert_eq!(report.content_ids, 12);
+        let report = verify(request(&fx, false, None)).await?;
+        assert_eq!(report.chunks, 12);
+        assert_eq!(report.objects, 12);
         assert_eq!(report.missing, removed);
         Ok(())
     }
 
-    /// All blobs and all sources gone: every id is unrecoverable, collected from
+    /// All packs and all sources gone: every id is unrecoverable, collected from
     /// the out-of-order fan-out and returned sorted (deterministic output).
     #[tokio::test]
     async fn repair_collects_all_unrecoverable_sorted() -> Result<()> {
@@ -616,17 +893,193 @@ mod tests {
         let store = fx.store(0)?;
 
         for id in &ids {
-            store.remove(id).await?;
+            store.remove(&pack_of(&fx, id)?).await?;
         }
         for entry in fs::read_dir(&fx.src)? {
             fs::remove_file(entry?.path())?;
         }
 
-        let report = verify(&fx.cfg, "t", true, Some(fx.naming_key.clone())).await?;
+        let report = verify(request(&fx, true, Some(fx.naming_key.clone()))).await?;
         assert_eq!(report.repaired_by_reseal, 0);
 
         ids.sort();
         assert_eq!(report.unrecoverable, ids);
         Ok(())
     }
+
+    /// Flip a byte inside chunk `id`'s range of its pack in `store`, leaving
+    /// the rest of the pack intact.
+    async fn rot_chunk(fx: &Fixture, store: &LocalStore, id: &str) -> Result<()> {
+        let location = fx
+            .catalog()?
+            .chunk_location(id)?
+            .ok_or_else(|| anyhow!("chunk {id} is not in a pack"))?;
+        let mut pack = store.get(&location.pack_id).await?;
+        let middle = usize::try_from(location.offset + location.length / 2)?;
+        *pack
+            .get_mut(middle)
+            .ok_or_else(|| anyhow!("chunk outside its pack"))? ^= 0xff;
+        store.put(&location.pack_id, &pack).await
+    }
+
+    /// A truncated copy still exists, so only a deep check catches it; repair
+    /// replaces it from the destination whose copy opens.
+    #[tokio::test]
+    async fn deep_verify_finds_and_repairs_a_corrupt_copy() -> Result<()> {
+        let fx = setup(2).await?;
+        let id = blake3_keyed_bytes(b"hello world", &fx.naming_key);
+        let pack = pack_of(&fx, &id)?;
+        let (broken, healthy) = (fx.store(0)?, fx.store(1)?);
+        let location = fx
+            .catalog()?
+            .chunk_location(&id)?
+            .ok_or_else(|| anyhow!("chunk {id} is not in a pack"))?;
+        let cut = usize::try_from(location.offset + location.length - 1)?;
+        let bytes = broken.get(&pack).await?;
+        broken
+            .put(&pack, bytes.get(..cut).unwrap_or_default())
+            .await?;
+
+        let report = verify(request(&fx, false, None)).await?;
+        assert_eq!(report.missing, 0);
+        assert!(report.corrupt.is_empty());
+
+        let report = verify(deep_request(&fx, false)).await?;
+        assert_eq!(report.missing, 0);
+        assert_eq!(
+            report.corrupt,
+            vec![CorruptCopy {
+                destination: fx
+                    .dests
+                    .first()
+                    .map(|d| d.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                object: pack.clone(),
+                chunks: vec![id.clone()],
+            }]
+        );
+        assert_eq!(report.repaired_by_copy, 0);
+
+        let report = verify(deep_request(&fx, true)).await?;
+        assert_eq!(report.corrupt.len(), 1);
+        assert_eq!(report.repaired_by_copy, 1);
+        assert_eq!(report.repaired_by_reseal, 0);
+        assert_eq!(broken.get(&pack).await?, healthy.get(&pack).await?);
+
+        let report = verify(deep_request(&fx, false)).await?;
+        assert!(report.corrupt.is_empty());
+        Ok(())
+    }
+
+    /// A chunk damaged in every copy is re-sealed from source like a lost one;
+    /// the intact chunks sharing its pack stay where they are.
+    #[tokio::test]
+    async fn deep_repair_reseals_a_chunk_corrupt_everywhere() -> Result<()> {
+        let fx = build(&[("a.txt", b"alpha"), ("b.txt", b"beta")], 1, None).await?;
+        let alpha = blake3_keyed_bytes(b"alpha", &fx.naming_key);
+        let beta = blake3_keyed_bytes(b"beta", &fx.naming_key);
+        let pack = pack_of(&fx, &alpha)?;
+        rot_chunk(&fx, &fx.store(0)?, &alpha).await?;
+
+        let report = verify(deep_request(&fx, true)).await?;
+        assert_eq!(report.corrupt.len(), 1);
+        assert_eq!(
+            report.corrupt.first().map(|copy| copy.chunks.clone()),
+            Some(vec![alpha.clone()])
+        );
+        assert_eq!(report.repaired_by_copy, 0);
+        assert_eq!(report.repaired_by_reseal, 1);
+        assert!(report.unrecoverable.is_empty());
+
+        assert_ne!(pack_of(&fx, &alpha)?, pack);
+        assert_eq!(pack_of(&fx, &beta)?, pack);
+        let plaintext = fx.read_chunk(0, &alpha).await?;
+        assert_eq!(plaintext.as_slice(), b"alpha");
+
+        // The old pack's damaged range is no longer referenced.
+        let report = verify(deep_request(&fx, false)).await?;
+        assert!(report.corrupt.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deep_verify_rejects_the_wrong_mnemonic() -> Result<()> {
+        let fx = setup(1).await?;
+        let mut request = deep_request(&fx, false);
+        request.deep = Some(Mnemonic::generate_in(Language::English, 12)?.into());
+        assert!(verify(request).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sample_takes_a_share_within_the_byte_budget() {
+        let stored: Vec<(String, u64)> = (0..10).map(|i| (format!("{i:04}"), 100)).collect();
+        let sample = |percent, max_bytes| Sample { percent, max_bytes };
+
+        assert_eq!(
+            select_sample(stored.clone(), sample(Some(25), None), 1).len(),
+            3
+        );
+        // Each object is downloaded once per destination.
+        assert_eq!(
+            select_sample(stored.clone(), sample(None, Some(450)), 2).len(),
+            2
+        );
+        assert_eq!(
+            select_sample(stored.clone(), sample(Some(50), Some(250)), 1).len(),
+            2
+        );
+        // However small the budget, a run makes progress.
+        assert_eq!(
+            select_sample(stored, sample(None, Some(1)), 1),
+            vec!["0000".to_string()]
+        );
+    }
+
+    /// Sampled runs check the least recently verified objects, so consecutive
+    /// runs rotate through all of them.
+    #[tokio::test]
+    async fn sampled_runs_rotate_through_every_object() -> Result<()> {
+        let (fx, _) = build_many(4, 1).await?;
+        let unverified = || -> Result<i64> {
+            let conn = rusqlite::Connection::open(fx.cfg.join("t.db"))?;
+            Ok(conn.query_row(
+                "SELECT COUNT(*) FROM Packs WHERE verified_at IS NULL",
+                [],
+                |row| row.get(0),
+            )?)
+        };
+        assert_eq!(unverified()?, 4);
+
+        let sampled = || VerifyRequest {
+            sample: Some(Sample {
+                percent: Some(50),
+                max_bytes: None,
+            }),
+            ..deep_request(&fx, false)
+        };
+        let report = verify(sampled()).await?;
+        assert_eq!(report.objects, 2);
+        assert_eq!(report.unchecked, 2);
+        assert_eq!(unverified()?, 2);
+
+        let report = verify(sampled()).await?;
+        assert_eq!(report.objects, 2);
+        assert_eq!(unverified()?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_sample_needs_the_mnemonic() -> Result<()> {
+        let fx = setup(1).await?;
+        let request = VerifyRequest {
+            sample: Some(Sample {
+                percent: Some(10),
+                max_bytes: None,
+            }),
+            ..request(&fx, false, None)
+        };
+        assert!(verify(request).await.is_err());
+        Ok(())
+    }
 }
@@ -2,8 +2,9 @@
 //!
 //! Surfaces the versioned metadata (`SqliteCatalog::view_entries`) as a
 //! directory tree so a user can browse what a backup contains before restoring.
-//! Files carry their stable id (`FileNames.name_id`) for addressing; directories
-//! are navigated by path. Tree construction is pure and unit-tested here; the
+//! Entries carry their stable id (`FileNames.name_id`) for addressing;
+//! directories are navigated by path, and the ones above the backed-up roots
+//! (never recorded themselves) have no id. Tree construction is pure and unit-tested here; the
 //! CLI action only prints.
 
 use crate::db::sqlite::{SqliteCatalog, ViewEntry};
@@ -34,7 +35,7 @@ pub enum ViewTarget {
 pub struct TreeNode {
     children: BTreeMap<String, TreeNode>,
     is_file: bool,
-    /// File id (`name_id`) for leaf files; `None` for directories.
+    /// Entry id (`name_id`); `None` for directories that were not recorded.
     id: Option<i64>,
     /// Number of file leaves contained anywhere beneath this node.
     file_count: usize,
@@ -109,7 +110,8 @@ pub fn resolve_file(
     Ok(Some((version, path)))
 }
 
-/// Build a tree from snapshot entries; leaf files keep their id.
+/// Build a tree from snapshot entries; files and recorded directories keep
+/// their id.
 #[must_use]
 pub fn build_tree(entries: &[ViewEntry]) -> TreeNode {
     let mut root = TreeNode::default();
@@ -119,7 +121,7 @@ pub fn build_tree(entries: &[ViewEntry]) -> TreeNode {
         for component in path_segments(&entry.path) {
             node = node.children.entry(component).or_default();
         }
-        node.is_file = true;
+        node.is_file = !entry.is_dir;
         node.id = Some(entry.id);
     }
 
@@ -127,7 +129,8 @@ pub fn build_tree(entries: &[ViewEntry]) -> TreeNode {
     root
 }
 
-/// Render a tree as box-drawing lines with a left id gutter for files.
+/// Render a tree as box-drawing lines with a left id gutter for files and
+/// recorded directories.
 ///
 /// `depth` limits how many levels are shown; directories at the limit are
 /// summarized with their file count. `depth == 0` renders the full tree.
@@ -161,7 +164,9 @@ pub fn render_lines(root: &TreeNode, depth: usize) -> Vec<String> {
         .collect()
 }
 
-fn open_at_version(
+/// Open a backup's catalog and resolve `version`, defaulting to the latest
+/// completed one. `Ok(None)` means no version has completed yet.
+pub(crate) fn open_at_version(
     config_dir: &Path,
     name: &str,
     version: Option<i64>,
@@ -214,13 +219,13 @@ fn path_segments(path: &Path) -> Vec<String> {
 }
 
 /// Collapse a chain of single-child directories into one display segment, e.g.
-/// `/` → `home` → `user1` becomes `/home/user1`. Stops at a branch or a file so
-/// the contents stay listable.
+/// `/` → `home` → `user1` becomes `/home/user1`. Stops at a branch, a file or a
+/// recorded directory so the contents (and ids) stay listable.
 fn collapse<'a>(name: &str, node: &'a TreeNode) -> (String, &'a TreeNode) {
     let mut display = name.to_string();
     let mut current = node;
 
-    while !current.is_file && current.children.len() == 1 {
+    while !current.is_file && current.id.is_none() && current.children.len() == 1 {
         let Some((child_name, child)) = current.children.iter().next() else {
             break;
         };
@@ -262,13 +267,13 @@ fn render_node(
         let count = target.file_count;
         let unit = if count == 1 { "file" } else { "files" };
         rows.push((
-            None,
+            target.id,
             format!("{prefix}{connector}{display}/ ({count} {unit})"),
         ));
         return;
     }
 
-    rows.push((None, format!("{prefix}{connector}{display}/")));
+    rows.push((target.id, format!("{prefix}{connector}{display}/")));
 
     let child_prefix = format!("{prefix}{}", if is_last { "    " } else { "│   " });
     let entries: Vec<(&String, &TreeNode)> = target.children.iter().collect();
@@ -297,6 +302,7 @@ mod tests {
             .map(|(id, path)| ViewEntry {
                 id: *id,
                 path: PathBuf::from(path),
+                is_dir: false,
             })
             .collect()
     }
@@ -362,6 +368,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn render_shows_recorded_and_empty_directories_with_ids() {
+        let root = build_tree(&[
+            ViewEntry {
+                id: 1,
+                path: PathBuf::from("/home/user1/src"),
+                is_dir: true,
+            },
+            ViewEntry {
+                id: 2,
+                path: PathBuf::from("/home/user1/src/empty"),
+                is_dir: true,
+            },
+            ViewEntry {
+                id: 3,
+                path: PathBuf::from("/home/user1/src/a.txt"),
+                is_dir: false,
+            },
+        ]);
+
+        // The unrecorded ancestors still collapse; the recorded root does not
+        // swallow its children, and the empty directory is listed.
+        assert_eq!(
+            render_lines(&root, 0),
+            vec![
+                "[1] └── /home/user1/src/".to_string(),
+                "[3]     ├── a.txt".to_string(),
+                "[2]     └── empty/".to_string(),
+            ]
+        );
+    }
+
     #[test]
     fn depth_truncates_directories_with_counts() {
         let root = tree(&[
@@ -392,18 +430,14 @@ mod tests {
 
     // --- DB-backed integration tests ---
 
-    use crate::db::sqlite::ScannedFile;
     use x25519_dalek::{PublicKey, StaticSecret};
 
     fn public_key() -> PublicKey {
         PublicKey::from(&StaticSecret::from([7u8; 32]))
     }
 
-    fn scanned(path: &str, hash: &str) -> ScannedFile {
-        ScannedFile {
-            path: PathBuf::from(path),
-            hash: hash.to_string(),
-        }
+    fn scanned<'a>(path: &str, hash: &'a str) -> (PathBuf, &'a str) {
+        (PathBuf::from(path), hash)
     }
 
     #[test]
@@ -413,9 +447,7 @@ mod tests {
         catalog.save_public_key(&public_key())?;
 
         let version = catalog.create_version()?;
-        catalog.record_scan(
-            public_key(),
-            &crate::db::sqlite::SealedKeys::new(),
+        catalog.record_contents(
             version,
             &[
                 scanned("/srv/a/x.txt", "h1"),
@@ -423,7 +455,6 @@ mod tests {
                 scanned("/srv/b/z.txt", "h3"),
             ],
             true,
-            None,
         )?;
 
         let all = catalog.view_entries(version, None)?;
@@ -445,14 +476,7 @@ mod tests {
 
         // v1: the file exists.
         let v1 = catalog.create_version()?;
-        catalog.record_scan(
-            public_key(),
-            &crate::db::sqlite::SealedKeys::new(),
-            v1,
-            &[scanned("/srv/a/x.txt", "h1")],
-            true,
-            None,
-        )?;
+        catalog.record_contents(v1, &[scanned("/srv/a/x.txt", "h1")], true)?;
 
         let id = catalog
             .view_entries(v1, None)?
@@ -462,14 +486,7 @@ mod tests {
 
         // v2: the file is gone, so it is closed at v1.
         let v2 = catalog.create_version()?;
-        catalog.record_scan(
-            public_key(),
-            &crate::db::sqlite::SealedKeys::new(),
-            v2,
-            &[],
-            true,
-            None,
-        )?;
+        catalog.record_contents(v2, &[], true)?;
 
         assert_eq!(
             catalog.file_path_at_version(id, v1)?,
@@ -0,0 +1,521 @@
+//! In-process S3 stand-in for tests.
+//!
+//! A tiny HTTP/1.1 server speaking the subset of the S3 API that [`S3Store`]
+//! uses (object PUT/GET/HEAD/DELETE and multipart uploads). Every request's
+//! `SigV4` signature is checked against the server's credentials, so a test that
+//! passes here also exercises the request signing a `MinIO` or AWS endpoint would
+//! verify. Buckets are created on first use.
+//!
+//! [`S3Store`]: super::s3::S3Store
+
+use super::s3::{canonical_request, signature};
+use anyhow::{Result, anyhow};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+const ACCESS_KEY_ID: &str = "FAKEACCESSKEY";
+const SECRET_ACCESS_KEY: &str = "fake/secret/access/key";
+const REGION: &str = "us-east-1";
+
+struct State {
+    /// `(bucket, key)` → object bytes.
+    objects: BTreeMap<(String, String), Vec<u8>>,
+    /// Upload id → in-progress multipart upload.
+    uploads: BTreeMap<String, Upload>,
+    completed_uploads: usize,
+    next_upload_id: u64,
+    /// Max keys per `ListObjectsV2` page (S3's own default is 1000).
+    list_page_size: usize,
+}
+
+struct Upload {
+    bucket: String,
+    key: String,
+    parts: BTreeMap<u32, Vec<u8>>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    headers: BTreeMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn empty(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn error(status: u16, code: &str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: format!("<Error><Code>{code}</Code><Message>{code}</Message></Error>")
+                .into_bytes(),
+        }
+    }
+}
+
+pub(crate) struct FakeS3 {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    credentials_file: PathBuf,
+    _dir: tempfile::TempDir,
+    task: JoinHandle<()>,
+}
+
+impl FakeS3 {
+    /// Start a server on an ephemeral localhost port.
+    pub(crate) async fn start() -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let credentials_file = dir.path().join("credentials");
+        std::fs::write(
+            &credentials_file,
+            format!(
+                "[fake]\naws_access_key_id = {ACCESS_KEY_ID}\naws_secret_access_key = {SECRET_ACCESS_KEY}\n"
+            ),
+        )?;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            objects: BTreeMap::new(),
+            uploads: BTreeMap::new(),
+            completed_uploads: 0,
+            next_upload_id: 0,
+            list_page_size: 1000,
+        }));
+
+        let shared = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_connection(stream, shared.clone()));
+            }
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            credentials_file,
+            _dir: dir,
+            task,
+        })
+    }
+
+    /// An `s3://` destination string pointing at this server, with credentials
+    /// read from the server's own credentials file.
+    pub(crate) fn destination(&self, bucket: &str, prefix: &str) -> String {
+        format!(
+            "s3://{bucket}/{prefix}?endpoint=http://{}&region={REGION}&profile=fake&credentials_file={}",
+            self.addr,
+            self.credentials_file.display()
+        )
+    }
+
+    pub(crate) const fn access_key_id() -> &'static str {
+        ACCESS_KEY_ID
+    }
+
+    pub(crate) fn has_object(&self, bucket: &str, key: &str) -> bool {
+        self.lock()
+            .objects
+            .contains_key(&(bucket.to_string(), key.to_string()))
+    }
+
+    /// Delete an object behind the store's back (simulates loss).
+    pub(crate) fn delete_object(&self, bucket: &str, key: &str) {
+        self.lock()
+            .objects
+            .remove(&(bucket.to_string(), key.to_string()));
+    }
+
+    pub(crate) fn completed_multipart_uploads(&self) -> usize {
+        self.lock().completed_uploads
+    }
+
+    /// Shrink listing pages so tests exercise continuation tokens.
+    pub(crate) fn set_list_page_size(&self, size: usize) {
+        self.lock().list_page_size = size;
+    }
+
+    pub(crate) fn open_multipart_uploads(&self) -> usize {
+        self.lock().uploads.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for FakeS3 {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve_connection(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut stream = BufReader::new(stream);
+    // Serve keep-alive requests until the client closes or sends garbage.
+    while let Ok(Some(request)) = read_request(&mut stream).await {
+        let is_head = request.method == "HEAD";
+        let response = match verify_signature(&request) {
+            Ok(()) => handle(&request, &state),
+            Err(_) => Response::error(403, "SignatureDoesNotMatch"),
+        };
+        if write_response(stream.get_mut(), &response, is_head)
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+async fn read_request(stream: &mut BufReader<TcpStream>) -> Result<Option<Request>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts
+        .next()
+        .ok_or_else(|| anyhow!("no method"))?
+        .to_string();
+    let target = parts.next().ok_or_else(|| anyhow!("no target"))?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    let mut headers = BTreeMap::new();
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers
+        .get("content-length")
+        .map_or(Ok(0), |value| value.parse())?;
+    let mut body = vec![0_u8; length];
+    stream.read_exact(&mut body).await?;
+
+    Ok(Some(Request {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    }))
+}
+
+async fn write_response(stream: &mut TcpStream, response: &Response, is_head: bool) -> Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} Fake\r\ncontent-length: {}\r\n",
+        response.status,
+        if is_head { 0 } else { response.body.len() }
+    );
+    for (name, value) in &response.headers {
+        write!(head, "{name}: {value}\r\n")?;
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes()).await?;
+    if !is_head {
+        stream.write_all(&response.body).await?;
+    }
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Recompute the request's `SigV4` signature from what was received.
+fn verify_signature(request: &Request) -> Result<()> {
+    let authorization = request
+        .headers
+        .get("authorization")
+        .ok_or_else(|| anyhow!("unsigned request"))?;
+    let fields: BTreeMap<&str, &str> = authorization
+        .trim_start_matches("AWS4-HMAC-SHA256 ")
+        .split(", ")
+        .filter_map(|field| field.split_once('='))
+        .collect();
+    let credential = fields
+        .get("Credential")
+        .ok_or_else(|| anyhow!("no credential"))?;
+    let signed = fields
+        .get("SignedHeaders")
+        .ok_or_else(|| anyhow!("no signed headers"))?;
+    let claimed = fields
+        .get("Signature")
+        .ok_or_else(|| anyhow!("no signature"))?;
+
+    let (access_key, scope) = credential
+        .split_once('/')
+        .ok_or_else(|| anyhow!("bad credential"))?;
+    let date = scope.split('/').next().unwrap_or_default();
+    if access_key != ACCESS_KEY_ID {
+        return Err(anyhow!("unknown access key"));
+    }
+
+    let payload_hash = request
+        .headers
+        .get("x-amz-content-sha256")
+        .ok_or_else(|| anyhow!("no payload hash"))?;
+    if *payload_hash != hex::encode(Sha256::digest(&request.body)) {
+        return Err(anyhow!("payload hash mismatch"));
+    }
+
+    let headers = signed
+        .split(';')
+        .map(|name| {
+            request
+                .headers
+                .get(name)
+                .map(|value| (name.to_string(), value.clone()))
+                .ok_or_else(|| anyhow!("signed header {name} missing"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut query: Vec<&str> = request.query.split('&').filter(|q| !q.is_empty()).collect();
+    query.sort_unstable();
+
+    let canonical = canonical_request(
+        &request.method,
+        &request.path,
+        &query.join("&"),
+        &headers,
+        payload_hash,
+    );
+    let amz_date = request
+        .headers
+        .get("x-amz-date")
+        .ok_or_else(|| anyhow!("no date"))?;
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical.as_bytes()))
+    );
+
+    if signature(SECRET_ACCESS_KEY, date, REGION, &string_to_sign)? == *claimed {
+        Ok(())
+    } else {
+        Err(anyhow!("signature mismatch"))
+    }
+}
+
+fn handle(request: &Request, state: &Mutex<State>) -> Response {
+    let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+    let path = request.path.trim_start_matches('/');
+    let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+    let object = (bucket.to_string(), key.to_string());
+    let query: BTreeMap<&str, &str> = request
+        .query
+        .split('&')
+        .filter(|q| !q.is_empty())
+        .map(|q| q.split_once('=').unwrap_or((q, "")))
+        .collect();
+    let upload_id = query.get("uploadId").copied();
+
+    match (request.method.as_str(), upload_id) {
+        ("POST", None) if query.contains_key("uploads") => {
+            state.next_upload_id += 1;
+            let id = format!("upload-{}", state.next_upload_id);
+            state.uploads.insert(
+                id.clone(),
+                Upload {
+                    bucket: object.0,
+                    key: object.1,
+                    parts: BTreeMap::new(),
+                },
+            );
+            Response {
+                status: 200,
+                headers: Vec::new(),
+                body: format!(
+                    "<InitiateMultipartUploadResult><UploadId>{id}</UploadId></InitiateMultipartUploadResult>"
+                )
+                .into_bytes(),
+            }
+        }
+        ("PUT", Some(id)) => {
+            let number = query.get("partNumber").and_then(|n| n.parse::<u32>().ok());
+            match (state.uploads.get_mut(id), number) {
+                (Some(upload), Some(number)) => {
+                    upload.parts.insert(number, request.body.clone());
+                    Response {
+                        status: 200,
+                        headers: vec![("etag", part_etag(&request.body))],
+                        body: Vec::new(),
+                    }
+                }
+                _ => Response::error(404, "NoSuchUpload"),
+            }
+        }
+        ("POST", Some(id)) => complete_upload(&mut state, id, &request.body),
+        ("DELETE", Some(id)) => {
+            state.uploads.remove(id);
+            Response::empty(204)
+        }
+        ("PUT", None) => {
+            state.objects.insert(object, request.body.clone());
+            Response::empty(200)
+        }
+        ("GET", None) if key.is_empty() && query.get("list-type") == Some(&"2") => {
+            list_objects(&state, bucket, &query)
+        }
+        ("GET" | "HEAD", None) => match state.objects.get(&object) {
+            Some(bytes) => match request.headers.get("range") {
+                Some(range) => ranged(bytes, range),
+                None => Response {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: bytes.clone(),
+                },
+            },
+            None => Response::error(404, "NoSuchKey"),
+        },
+        ("DELETE", None) => {
+            state.objects.remove(&object);
+            Response::empty(204)
+        }
+        _ => Response::error(400, "NotImplemented"),
+    }
+}
+
+fn complete_upload(state: &mut State, id: &str, manifest: &[u8]) -> Response {
+    let Some(upload) = state.uploads.remove(id) else {
+        return Response::error(404, "NoSuchUpload");
+    };
+    let manifest = String::from_utf8_lossy(manifest);
+
+    let mut bytes = Vec::new();
+    for part in manifest.split("<Part>").skip(1) {
+        let number = between(part, "<PartNumber>", "</PartNumber>").and_then(|n| n.parse().ok());
+        let etag = between(part, "<ETag>", "</ETag>").map(|e| e.replace("&quot;", "\""));
+        match number.and_then(|n: u32| upload.parts.get(&n)) {
+            Some(data) if etag.as_deref() == Some(part_etag(data).as_str()) => {
+                bytes.extend_from_slice(data);
+            }
+            _ => return Response::error(400, "InvalidPart"),
+        }
+    }
+
+    state
+        .objects
+        .insert((upload.bucket.clone(), upload.key.clone()), bytes);
+    state.completed_uploads += 1;
+    Response {
+        status: 200,
+        headers: Vec::new(),
+        body: format!(
+            "<CompleteMultipartUploadResult><Key>{}</Key></CompleteMultipartUploadResult>",
+            upload.key
+        )
+        .into_bytes(),
+    }
+}
+
+/// Serve a `Range: bytes=start-end` request (inclusive end, clamped to size).
+fn ranged(bytes: &[u8], range: &str) -> Response {
+    let bounds = range
+        .strip_prefix("bytes=")
+        .and_then(|r| r.split_once('-'))
+        .and_then(|(start, end)| Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?)));
+    match bounds.and_then(|(start, end)| bytes.get(start..=end.min(bytes.len().saturating_sub(1))))
+    {
+        Some(slice) => Response {
+            status: 206,
+            headers: Vec::new(),
+            body: slice.to_vec(),
+        },
+        None => Response::error(416, "InvalidRange"),
+    }
+}
+
+/// `ListObjectsV2`: keys under `prefix`, paged by `continuation-token` (the
+/// last key of the previous page).
+fn list_objects(state: &State, bucket: &str, query: &BTreeMap<&str, &str>) -> Response {
+    let prefix = decode(query.get("prefix").copied().unwrap_or_default());
+    let after = query.get("continuation-token").map(|token| decode(token));
+
+    let mut matching = state
+        .objects
+        .iter()
+        .filter(|((b, key), _)| b == bucket && key.starts_with(&prefix))
+        .filter(|((_, key), _)| after.as_ref().is_none_or(|after| key > after));
+    let page: Vec<_> = matching.by_ref().take(state.list_page_size).collect();
+    let truncated = matching.next().is_some();
+
+    let mut body = String::from("<ListBucketResult>");
+    for ((_, key), bytes) in &page {
+        let _ = write!(
+            body,
+            "<Contents><Key>{key}</Key><LastModified>2024-01-01T00:00:00.000Z</LastModified><Size>{}</Size></Contents>",
+            bytes.len()
+        );
+    }
+    let _ = write!(body, "<IsTruncated>{truncated}</IsTruncated>");
+    if let (true, Some(((_, last), _))) = (truncated, page.last()) {
+        let _ = write!(
+            body,
+            "<NextContinuationToken>{last}</NextContinuationToken>"
+        );
+    }
+    body.push_str("</ListBucketResult>");
+
+    Response {
+        status: 200,
+        headers: Vec::new(),
+        body: body.into_bytes(),
+    }
+}
+
+/// Percent-decode a query value.
+fn decode(value: &str) -> String {
+    let mut out = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+            out.extend(u8::from_str_radix(&hex, 16).ok());
+        } else {
+            out.push(byte);
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn part_etag(data: &[u8]) -> String {
+    let digest = hex::encode(Sha256::digest(data));
+    format!("\"{}\"", digest.get(..32).unwrap_or_default())
+}
+
+fn between<'a>(haystack: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let start = haystack.find(open)? + open.len();
+    let rest = haystack.get(start..)?;
+    rest.get(..rest.find(close)?)
+}
@@ -10,12 +10,21 @@
 //! (re)written. This is the §6.5 filesystem backend (packs come later); because it
 //! only needs a path, it also covers NFS, external drives, and FUSE mounts.
 
+use super::{
+    CATALOG_DIR, ObjectInfo, QUARANTINE_DIR, Storage, check_catalog_name, is_catalog_name, is_hex,
+    key_from_sharded, sharded_key,
+};
 use anyhow::{Result, anyhow};
+use async_trait::async_trait;
 use std::{
+    io::SeekFrom,
     path::PathBuf,
     sync::atomic::{AtomicU64, Ordering},
 };
-use tokio::{fs, io::AsyncWriteExt};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
 
 /// Per-process counter making temp filenames unique, so two writers targeting the
 /// same object key never share a temp path (which would defeat the atomic rename).
@@ -32,94 +41,223 @@ impl LocalStore {
         Self { root: root.into() }
     }
 
-    /// Sharded path for an object id: `<root>/<id[0..2]>/<id[2..4]>/<id>`.
-    ///
-    /// Object keys are keyed-BLAKE3 content ids (lowercase hex). We reject any
-    /// non-hex key so a crafted id can never escape the store root — `/`, `..`,
-    /// and absolute paths are all non-hex, so this also blocks path traversal.
+    /// Sharded path for an object id: `<root>/<id[0..2]>/<id[2..4]>/<id>` (see
+    /// [`sharded_key`], which also rejects non-hex keys).
     fn object_path(&self, key: &str) -> Result<PathBuf> {
-        if key.len() < 4 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
-            return Err(anyhow!("invalid object key: {key:?}"));
-        }
-        let shard_a = key
-            .get(0..2)
-            .ok_or_else(|| anyhow!("object key too short: {key:?}"))?;
-        let shard_b = key
-            .get(2..4)
-            .ok_or_else(|| anyhow!("object key too short: {key:?}"))?;
+        Ok(self.root.join(sharded_key(key)?))
+    }
+
+    /// Path of a sealed catalog: `<root>/catalog/<name>`.
+    fn catalog_path(&self, name: &str) -> Result<PathBuf> {
+        check_catalog_name(name)?;
+        Ok(self.root.join(CATALOG_DIR).join(name))
+    }
+}
 
-        Ok(self.root.join(shard_a).join(shard_b).join(key))
+#[async_trait]
+impl Storage for LocalStore {
+    async fn put(&self, key: &str, bytes: &[u8]) -> Result<()> {
+        put_atomically(&self.object_path(key)?, key, Contents::Bytes(bytes)).await
     }
 
-    /// Store `bytes` under `key`, atomically replacing any existing object.
-    ///
-    /// # Errors
-    /// Returns an error if the object cannot be written.
-    pub async fn put(&self, key: &str, bytes: &[u8]) -> Result<()> {
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
         let path = self.object_path(key)?;
+        Ok(fs::read(&path).await?)
+    }
 
-        let parent = path
-            .parent()
-            .ok_or_else(|| anyhow!("object path has no parent: {}", path.display()))?;
-        fs::create_dir_all(parent).await?;
-
-        // Write to a unique temp file in the same directory, fsync, then atomically
-        // rename into place (replacing any existing object) so a reader never sees
-        // a partial object. The temp name carries the pid + a per-process counter
-        // so concurrent writers (or a second process) targeting the same key don't
-        // clobber each other's temp file mid-write.
-        let seq = TMP_SEQ.fetch_add(1, Ordering::Relaxed);
-        let tmp = parent.join(format!(".{key}.{}.{seq}.tmp", std::process::id()));
-
-        if let Err(err) = write_then_rename(&tmp, &path, bytes).await {
-            // Best-effort cleanup so a failed write doesn't leave a temp behind.
-            let _ = fs::remove_file(&tmp).await;
-            return Err(err);
-        }
+    async fn get_range(&self, key: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut file = fs::File::open(self.object_path(key)?).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
 
-        Ok(())
+        let mut bytes = vec![0_u8; usize::try_from(len)?];
+        file.read_exact(&mut bytes).await?;
+        Ok(bytes)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(fs::try_exists(self.object_path(key)?).await?)
     }
 
-    /// Read the object stored under `key`.
-    ///
-    /// # Errors
-    /// Returns an error if the object is missing or cannot be read.
-    pub async fn get(&self, key: &str) -> Result<Vec<u8>> {
+    async fn remove(&self, key: &str) -> Result<()> {
+        remove_if_present(&self.object_path(key)?).await
+    }
+
+    async fn quarantine(&self, key: &str) -> Result<()> {
         let path = self.object_path(key)?;
-        Ok(fs::read(&path).await?)
+        let quarantine = self.root.join(QUARANTINE_DIR);
+        fs::create_dir_all(&quarantine).await?;
+        fs::rename(&path, quarantine.join(key)).await?;
+        Ok(())
     }
 
-    /// Whether an object exists under `key`.
-    ///
-    /// # Errors
-    /// Returns an error if existence cannot be determined.
-    pub async fn exists(&self, key: &str) -> Result<bool> {
-        Ok(fs::try_exists(self.object_path(key)?).await?)
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        if !is_hex(prefix) {
+            return Err(anyhow!("invalid object key prefix: {prefix:?}"));
+        }
+
+        // Walk `<root>/ab/cd/<id>`, skipping shard directories that cannot hold
+        // a key starting with `prefix`. Temp files, strays, the quarantine and
+        // the catalogs never parse as a sharded key, so they are not listed.
+        let mut objects = Vec::new();
+        for shard_a in read_dir_names(&self.root).await? {
+            if !is_shard(&shard_a) || !shard_may_match(&shard_a, prefix.get(..2.min(prefix.len())))
+            {
+                continue;
+            }
+            let dir_a = self.root.join(&shard_a);
+            for shard_b in read_dir_names(&dir_a).await? {
+                if !is_shard(&shard_b)
+                    || !shard_may_match(
+                        &shard_b,
+                        prefix.get(2.min(prefix.len())..4.min(prefix.len())),
+                    )
+                {
+                    continue;
+                }
+                let dir_b = dir_a.join(&shard_b);
+                for name in read_dir_names(&dir_b).await? {
+                    let relative = format!("{shard_a}/{shard_b}/{name}");
+                    let Some(key) = key_from_sharded(&relative) else {
+                        continue;
+                    };
+                    if !key.starts_with(prefix) {
+                        continue;
+                    }
+                    let metadata = fs::metadata(dir_b.join(&name)).await?;
+                    objects.push(ObjectInfo {
+                        key: key.to_string(),
+                        size: metadata.len(),
+                        modified: metadata.modified().ok(),
+                    });
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+
+    async fn put_catalog(&self, name: &str, bytes: &[u8]) -> Result<()> {
+        put_atomically(&self.catalog_path(name)?, name, Contents::Bytes(bytes)).await
+    }
+
+    async fn put_catalog_file(&self, name: &str, path: &std::path::Path) -> Result<()> {
+        put_atomically(&self.catalog_path(name)?, name, Contents::File(path)).await
+    }
+
+    async fn get_catalog(&self, name: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.catalog_path(name)?).await?)
+    }
+
+    async fn remove_catalog(&self, name: &str) -> Result<()> {
+        remove_if_present(&self.catalog_path(name)?).await
+    }
+
+    async fn list_catalogs(&self) -> Result<Vec<ObjectInfo>> {
+        let dir = self.root.join(CATALOG_DIR);
+        let mut catalogs = Vec::new();
+        // Temp files start with a dot, so they are not catalog names.
+        for name in read_dir_names(&dir).await? {
+            if !is_catalog_name(&name) {
+                continue;
+            }
+            let metadata = fs::metadata(dir.join(&name)).await?;
+            catalogs.push(ObjectInfo {
+                key: name,
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            });
+        }
+        Ok(catalogs)
+    }
+}
+
+/// Remove the file at `path`; a missing one is not an error.
+async fn remove_if_present(path: &std::path::Path) -> Result<()> {
+    match fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
     }
+}
 
-    /// Remove the object stored under `key`, if present. Missing objects are not
-    /// an error (removal is idempotent).
-    ///
-    /// # Errors
-    /// Returns an error if an existing object cannot be removed.
-    pub async fn remove(&self, key: &str) -> Result<()> {
-        match fs::remove_file(self.object_path(key)?).await {
-            Ok(()) => Ok(()),
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
-            Err(err) => Err(err.into()),
+/// Names of the entries in `dir` (empty if it does not exist).
+async fn read_dir_names(dir: &std::path::Path) -> Result<Vec<String>> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(name) = entry.file_name().to_str() {
+            names.push(name.to_string());
         }
     }
+    Ok(names)
+}
+
+/// Whether a directory name is a shard (two hex digits).
+fn is_shard(name: &str) -> bool {
+    name.len() == 2 && is_hex(name)
+}
+
+/// Whether a shard directory name can hold keys whose corresponding prefix
+/// characters are `part` (`None`/empty matches every shard).
+fn shard_may_match(shard: &str, part: Option<&str>) -> bool {
+    part.is_none_or(|part| shard.starts_with(part))
+}
+
+/// What [`put_atomically`] writes: bytes in memory, or a local file copied
+/// without reading it whole.
+#[derive(Clone, Copy)]
+enum Contents<'a> {
+    Bytes(&'a [u8]),
+    File(&'a std::path::Path),
+}
+
+/// Write `contents` to `path` (named `name`), replacing any existing file.
+async fn put_atomically(path: &std::path::Path, name: &str, contents: Contents<'_>) -> Result<()> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("object path has no parent: {}", path.display()))?;
+    fs::create_dir_all(parent).await?;
+
+    // Write to a unique temp file in the same directory, fsync, then atomically
+    // rename into place (replacing any existing object) so a reader never sees
+    // a partial object. The temp name carries the pid + a per-process counter
+    // so concurrent writers (or a second process) targeting the same key don't
+    // clobber each other's temp file mid-write.
+    let seq = TMP_SEQ.fetch_add(1, Ordering::Relaxed);
+    let tmp = parent.join(format!(".{name}.{}.{seq}.tmp", std::process::id()));
+
+    if let Err(err) = write_then_rename(&tmp, path, contents).await {
+        // Best-effort cleanup so a failed write doesn't leave a temp behind.
+        let _ = fs::remove_file(&tmp).await;
+        return Err(err);
+    }
+
+    Ok(())
 }
 
-/// Write `bytes` to `tmp`, fsync, then rename onto `path`. Split out so `put` can
-/// clean up `tmp` if any step fails.
+/// Write `contents` to `tmp`, fsync, then rename onto `path`. Split out so
+/// [`put_atomically`] can clean up `tmp` if any step fails.
 async fn write_then_rename(
     tmp: &std::path::Path,
     path: &std::path::Path,
-    bytes: &[u8],
+    contents: Contents<'_>,
 ) -> Result<()> {
-    let mut file = fs::File::create(tmp).await?;
-    file.write_all(bytes).await?;
+    let file = match contents {
+        Contents::Bytes(bytes) => {
+            let mut file = fs::File::create(tmp).await?;
+            file.write_all(bytes).await?;
+            file
+        }
+        Contents::File(from) => {
+            fs::copy(from, tmp).await?;
+            fs::File::options().write(true).open(tmp).await?
+        }
+    };
     file.sync_all().await?;
     drop(file);
 
@@ -180,6 +318,20 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn list_skips_temp_and_stray_files() -> Result<()> {
+        let (dir, store) = store()?;
+        store.put("abcd1234", b"x").await?;
+        let shard = dir.path().join("ab").join("cd");
+        std::fs::write(shard.join(".abcd5678.1.0.tmp"), b"partial")?;
+        std::fs::write(shard.join("notes.txt"), b"stray")?;
+
+        let listed = store.list("").await?;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed.first().map(|o| o.key.as_str()), Some("abcd1234"));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn short_key_is_rejected() -> Result<()> {
         let (_dir, store) = store()?;
@@ -1,2 +1,434 @@
+//! Blob storage backends.
+//!
+//! Every destination is a [`Storage`]: a flat, content-addressed object store
+//! keyed by hex content ids (DESIGN §6.5). The engines only ever see
+//! `Arc<dyn Storage>` built by [`open`] from a configured destination string, so
+//! a new backend is a new [`Destination`] variant plus a `Storage` impl — no
+//! engine changes. Chunks are written in [`pack`] objects, so a store mostly
+//! holds packs keyed by pack id, plus the sealed catalogs kept beside the
+//! keyspace ([`Storage::put_catalog`]).
+
 pub mod local;
+pub mod pack;
 pub mod s3;
+
+#[cfg(test)]
+pub(crate) mod fake_s3;
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use local::LocalStore;
+use s3::{S3Credentials, S3Location, S3Store};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+/// Directory (local) or key prefix (S3), under the store's root, that
+/// [`Storage::quarantine`] moves objects into.
+pub(crate) const QUARANTINE_DIR: &str = "quarantine";
+
+/// Directory (local) or key prefix (S3), under the store's root, holding the
+/// sealed catalogs (DESIGN §7). It is outside the hex keyspace, so listing,
+/// `verify` and `gc` never see them.
+pub(crate) const CATALOG_DIR: &str = "catalog";
+
+/// One stored object, as reported by [`Storage::list`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ObjectInfo {
+    /// The object key (content id) it was stored under.
+    pub key: String,
+    pub size: u64,
+    /// Last-modified time, when the backend reports one.
+    pub modified: Option<SystemTime>,
+}
+
+/// A content-addressed object store.
+///
+/// Keys are hex content ids; implementations reject anything else so a crafted
+/// key can never address data outside the store. `put` **overwrites** — the
+/// caller holds the bytes matching the key it is recording, so an orphan from an
+/// interrupted run must be replaced, not kept.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Store `bytes` under `key`, replacing any existing object.
+    ///
+    /// # Errors
+    /// Returns an error if the object cannot be written.
+    async fn put(&self, key: &str, bytes: &[u8]) -> Result<()>;
+
+    /// Read the object stored under `key`.
+    ///
+    /// # Errors
+    /// Returns an error if the object is missing or cannot be read.
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Read `len` bytes of the object under `key`, starting at `offset`.
+    ///
+    /// # Errors
+    /// Returns an error if the object is missing or shorter than the range.
+    async fn get_range(&self, key: &str, offset: u64, len: u64) -> Result<Vec<u8>>;
+
+    /// Whether an object exists under `key`.
+    ///
+    /// # Errors
+    /// Returns an error if existence cannot be determined.
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// Remove the object stored under `key`, if present. Missing objects are not
+    /// an error (removal is idempotent).
+    ///
+    /// # Errors
+    /// Returns an error if an existing object cannot be removed.
+    async fn remove(&self, key: &str) -> Result<()>;
+
+    /// Move the object stored under `key` aside, to `quarantine/<key>` under
+    /// the store's root or prefix. It is then neither listed nor readable by
+    /// key, but can still be inspected or put back by hand.
+    ///
+    /// # Errors
+    /// Returns an error if the object is missing or cannot be moved.
+    async fn quarantine(&self, key: &str) -> Result<()>;
+
+    /// Every object whose key starts with `prefix` (`""` lists everything), in
+    /// no particular order.
+    ///
+    /// # Errors
+    /// Returns an error if the store cannot be listed.
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>>;
+
+    /// Store a sealed catalog as `catalog/<name>` under the store's root or
+    /// prefix, replacing any existing one.
+    ///
+    /// # Errors
+    /// Returns an error if `name` is not a valid catalog name or the object
+    /// cannot be written.
+    async fn put_catalog(&self, name: &str, bytes: &[u8]) -> Result<()>;
+
+    /// Store the sealed catalog in the local file `path` as `catalog/<name>`,
+    /// like [`Storage::put_catalog`] but streamed from the file rather than
+    /// held in memory.
+    ///
+    /// # Errors
+    /// Returns an error if `name` is not a valid catalog name, the file cannot
+    /// be read, or the object cannot be written.
+    async fn put_catalog_file(&self, name: &str, path: &Path) -> Result<()>;
+
+    /// Read the sealed catalog stored as `catalog/<name>`.
+    ///
+    /// # Errors
+    /// Returns an error if the catalog is missing or cannot be read.
+    async fn get_catalog(&self, name: &str) -> Result<Vec<u8>>;
+
+    /// Remove the sealed catalog `catalog/<name>`, if present.
+    ///
+    /// # Errors
+    /// Returns an error if an existing catalog cannot be removed.
+    async fn remove_catalog(&self, name: &str) -> Result<()>;
+
+    /// Every sealed catalog in the store, keyed by name, in no particular order.
+    ///
+    /// # Errors
+    /// Returns an error if the store cannot be listed.
+    async fn list_catalogs(&self) -> Result<Vec<ObjectInfo>>;
+}
+
+/// A parsed destination string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Destination {
+    /// A filesystem path (local disk, NFS, removable drive, FUSE mount, …).
+    Local(PathBuf),
+    /// An `s3://bucket/prefix?…` target.
+    S3(S3Location),
+}
+
+impl Destination {
+    /// Parse a configured destination: `s3://…` or else a filesystem path.
+    ///
+    /// # Errors
+    /// Returns an error if an `s3://` destination is malformed.
+    pub fn parse(destination: &str) -> Result<Self> {
+        if destination.starts_with("s3://") {
+            Ok(Self::S3(S3Location::parse(destination)?))
+        } else if destination.is_empty() {
+            Err(anyhow!("empty destination"))
+        } else {
+            Ok(Self::Local(PathBuf::from(destination)))
+        }
+    }
+
+    /// Open the backend for this destination.
+    ///
+    /// # Errors
+    /// Returns an error if the backend cannot be set up (e.g. no S3 credentials).
+    pub fn open(self) -> Result<Arc<dyn Storage>> {
+        Ok(match self {
+            Self::Local(root) => Arc::new(LocalStore::new(root)),
+            Self::S3(location) => {
+                let credentials = S3Credentials::resolve(&location)?;
+                Arc::new(S3Store::new(location, credentials)?)
+            }
+        })
+    }
+}
+
+/// Open the store for a configured destination string.
+///
+/// # Errors
+/// Returns an error if the destination is malformed or its backend cannot be
+/// set up.
+pub fn open(destination: &str) -> Result<Arc<dyn Storage>> {
+    Destination::parse(destination)
+        .and_then(Destination::open)
+        .with_context(|| format!("cannot open destination {destination}"))
+}
+
+/// Open every configured destination.
+///
+/// # Errors
+/// Returns an error if any destination cannot be opened.
+pub fn open_stores(destinations: &[String]) -> Result<Vec<Arc<dyn Storage>>> {
+    destinations.iter().map(|dest| open(dest)).collect()
+}
+
+/// An opened store with the configured destination string it was opened from,
+/// the key the catalog tracks its uploads under.
+#[derive(Clone)]
+pub struct NamedStore {
+    pub destination: String,
+    pub store: Arc<dyn Storage>,
+}
+
+/// Open every configured destination, keeping each one's name.
+///
+/// # Errors
+/// Returns an error if any destination cannot be opened.
+pub fn open_named(destinations: &[String]) -> Result<Vec<NamedStore>> {
+    destinations
+        .iter()
+        .map(|destination| {
+            Ok(NamedStore {
+                destination: destination.clone(),
+                store: open(destination)?,
+            })
+        })
+        .collect()
+}
+
+/// Sharded object key for a content id: `<id[0..2]>/<id[2..4]>/<id>`.
+///
+/// Object keys are keyed-BLAKE3 content ids (lowercase hex). Any non-hex key is
+/// rejected, so a crafted id can never escape the store root or prefix — `/`,
+/// `..`, and absolute paths are all non-hex, so this also blocks path traversal.
+///
+/// # Errors
+/// Returns an error if `key` is shorter than 4 characters or not hex.
+pub(crate) fn sharded_key(key: &str) -> Result<String> {
+    if key.len() < 4 || !is_hex(key) {
+        return Err(anyhow!("invalid object key: {key:?}"));
+    }
+    let shard_a = key
+        .get(0..2)
+        .ok_or_else(|| anyhow!("object key too short: {key:?}"))?;
+    let shard_b = key
+        .get(2..4)
+        .ok_or_else(|| anyhow!("object key too short: {key:?}"))?;
+
+    Ok(format!("{shard_a}/{shard_b}/{key}"))
+}
+
+/// The sharded-layout prefix covering every key that starts with `prefix`,
+/// so a listing can be narrowed server-side (`"abc"` → `"ab/c"`).
+///
+/// # Errors
+/// Returns an error if `prefix` is not hex.
+pub(crate) fn sharded_prefix(prefix: &str) -> Result<String> {
+    if !is_hex(prefix) {
+        return Err(anyhow!("invalid object key prefix: {prefix:?}"));
+    }
+    Ok(match (prefix.get(..2), prefix.get(2..4)) {
+        (Some(a), Some(b)) if prefix.len() > 4 => format!("{a}/{b}/{prefix}"),
+        (Some(a), Some(b)) => format!("{a}/{b}"),
+        (Some(a), None) => match prefix.get(2..) {
+            Some(rest) if !rest.is_empty() => format!("{a}/{rest}"),
+            _ => a.to_string(),
+        },
+        _ => prefix.to_string(),
+    })
+}
+
+/// Whether a sharded-layout relative path (`ab/cd/<id>`) names a well-formed
+/// object; returns its key.
+pub(crate) fn key_from_sharded(path: &str) -> Option<&str> {
+    let mut parts = path.split('/');
+    let (a, b, key) = (parts.next()?, parts.next()?, parts.next()?);
+    let well_formed = parts.next().is_none()
+        && key.len() >= 4
+        && is_hex(key)
+        && key.get(..2) == Some(a)
+        && key.get(2..4) == Some(b);
+    well_formed.then_some(key)
+}
+
+/// Whether `name` can name a sealed catalog: ASCII alphanumerics, `_`, `-`
+/// and `.`, not starting with a dot — so it can neither escape the catalog
+/// directory nor collide with a temp file.
+pub(crate) fn is_catalog_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.'))
+}
+
+/// Reject a catalog name that [`is_catalog_name`] does not accept.
+///
+/// # Errors
+/// Returns an error if `name` is not a valid catalog name.
+pub(crate) fn check_catalog_name(name: &str) -> Result<()> {
+    if is_catalog_name(name) {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid catalog name: {name:?}"))
+    }
+}
+
+pub(crate) fn is_hex(value: &str) -> bool {
+    value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fake_s3::FakeS3;
+
+    /// The behaviour every backend must share, so the engines can treat them
+    /// interchangeably.
+    async fn exercise(store: &dyn Storage) -> Result<()> {
+        store.put("abcd0001", b"hello world").await?;
+        store.put("abcd0002", b"second").await?;
+        store.put("ef010203", b"third!").await?;
+
+        assert_eq!(store.get_range("abcd0001", 2, 3).await?, b"llo");
+        assert_eq!(store.get_range("abcd0001", 6, 5).await?, b"world");
+        assert!(store.get_range("abcd0001", 8, 10).await.is_err());
+        assert!(store.get_range("abcd9999", 0, 1).await.is_err());
+
+        let keys = |mut objects: Vec<ObjectInfo>| {
+            objects.sort_by(|a, b| a.key.cmp(&b.key));
+            objects
+                .into_iter()
+                .map(|o| (o.key, o.size))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(store.list("").await?),
+            vec![
+                ("abcd0001".to_string(), 11),
+                ("abcd0002".to_string(), 6),
+                ("ef010203".to_string(), 6),
+            ]
+        );
+        assert_eq!(keys(store.list("abcd").await?).len(), 2);
+        assert_eq!(keys(store.list("abc").await?).len(), 2);
+        assert_eq!(keys(store.list("abcd0002").await?).len(), 1);
+        assert_eq!(keys(store.list("e").await?).len(), 1);
+        assert!(store.list("ffff").await?.is_empty());
+        assert!(store.list("../").await.is_err());
+
+        store.remove("abcd0001").await?;
+        assert_eq!(keys(store.list("").await?).len(), 2);
+
+        // A quarantined object leaves the keyspace; a missing one is an error.
+        store.quarantine("abcd0002").await?;
+        assert_eq!(keys(store.list("").await?).len(), 1);
+        assert!(!store.exists("abcd0002").await?);
+        assert!(store.quarantine("abcd0002").await.is_err());
+
+        // Catalogs live beside the keyspace, not in it.
+        store.put_catalog("t.1", b"sealed one").await?;
+        store.put_catalog("t.2", b"sealed two").await?;
+        store.put_catalog("t.2", b"sealed two, again").await?;
+        assert_eq!(store.get_catalog("t.2").await?, b"sealed two, again");
+        let dir = tempfile::tempdir()?;
+        let sealed = dir.path().join("t.sealed");
+        std::fs::write(&sealed, b"sealed two, from a file")?;
+        store.put_catalog_file("t.2", &sealed).await?;
+        assert_eq!(store.get_catalog("t.2").await?, b"sealed two, from a file");
+        store.put_catalog("t.2", b"sealed two, again").await?;
+        assert_eq!(
+            keys(store.list_catalogs().await?),
+            vec![("t.1".to_string(), 10), ("t.2".to_string(), 17)]
+        );
+        assert_eq!(keys(store.list("").await?).len(), 1);
+        store.remove_catalog("t.1").await?;
+        store.remove_catalog("t.1").await?;
+        assert!(store.get_catalog("t.1").await.is_err());
+        assert_eq!(store.list_catalogs().await?.len(), 1);
+        for name in ["", "../t", ".t", "a/b"] {
+            assert!(store.put_catalog(name, b"x").await.is_err(), "{name:?}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn local_store_conforms() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = open(&dir.path().to_string_lossy())?;
+        exercise(store.as_ref()).await
+    }
+
+    #[tokio::test]
+    async fn s3_store_conforms() -> Result<()> {
+        let server = FakeS3::start().await?;
+        // One key per page, so listing has to follow continuation tokens.
+        server.set_list_page_size(1);
+        let store = open(&server.destination("bucket", "some/prefix"))?;
+        exercise(store.as_ref()).await
+    }
+
+    #[tokio::test]
+    async fn missing_local_root_lists_empty() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = open(&dir.path().join("not-yet").to_string_lossy())?;
+        assert!(store.list("").await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_destinations() -> Result<()> {
+        assert_eq!(
+            Destination::parse("/mnt/backup")?,
+            Destination::Local(PathBuf::from("/mnt/backup"))
+        );
+        assert!(matches!(
+            Destination::parse("s3://bucket/prefix?region=eu-west-1")?,
+            Destination::S3(location) if location.bucket == "bucket" && location.prefix == "prefix"
+        ));
+        assert!(Destination::parse("").is_err());
+        assert!(Destination::parse("s3://").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sharded_prefix_narrows_listing() -> Result<()> {
+        assert_eq!(sharded_prefix("")?, "");
+        assert_eq!(sharded_prefix("a")?, "a");
+        assert_eq!(sharded_prefix("ab")?, "ab");
+        assert_eq!(sharded_prefix("abc")?, "ab/c");
+        assert_eq!(sharded_prefix("abcd")?, "ab/cd");
+        assert_eq!(sharded_prefix("abcde")?, "ab/cd/abcde");
+        assert!(sharded_prefix("../x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn key_from_sharded_path() {
+        assert_eq!(key_from_sharded("ab/cd/abcdef"), Some("abcdef"));
+        assert_eq!(key_from_sharded("ab/cd/ffffff"), None);
+        assert_eq!(key_from_sharded("ab/cd/.abcdef.1.0.tmp"), None);
+        assert_eq!(key_from_sharded("ab/cd"), None);
+        assert_eq!(key_from_sharded("ab/cd/abcdef/x"), None);
+    }
+}
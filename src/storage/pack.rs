@@ -0,0 +1,264 @@
+//! Pack objects: many sealed chunk blobs stored as one object (DESIGN §6.5).
+//!
+//! One object per chunk means millions of tiny files on a filesystem
+//! destination and millions of PUTs on S3. A pack concatenates sealed chunk
+//! blobs until it reaches [`PACK_TARGET_SIZE`], and the catalog maps each chunk
+//! to `(pack_id, offset, length)` so restore range-reads just that chunk.
+//!
+//! Layout: `blob || blob || … || index || footer`.
+//! - **index**: a content blob (see `utils::crypto`) sealed under a key derived
+//!   from the naming key and the pack id, bound to the pack id. It lists every
+//!   chunk's id, location and size, so a pack describes its layout without the
+//!   catalog. The chunks' wrapped keys stay in the catalog: a pack holds nothing
+//!   sealed to the backup keypair, so moving to a new one never rewrites it.
+//! - **footer**: `index_offset (u64 LE) || PACK_MAGIC`.
+
+use crate::utils::crypto::{open_content, pack_index_key, seal_content_with_key};
+use anyhow::{Result, anyhow};
+use rand::RngCore;
+
+/// Seal the open pack once it holds at least this many bytes. Chunks are at
+/// most 4 MiB, so a pack stays well under twice this.
+pub const PACK_TARGET_SIZE: usize = 16 * 1024 * 1024;
+
+/// Marks the end of a pack (and the pack format version).
+const PACK_MAGIC: [u8; 8] = *b"bkpack02";
+/// `index_offset (8) || PACK_MAGIC (8)`.
+const FOOTER_LEN: usize = 16;
+/// A chunk id is a 32-byte keyed BLAKE3 digest (64 hex characters).
+const CHUNK_ID_LEN: usize = 32;
+
+/// One chunk in a pack: where its sealed blob sits.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PackEntry {
+    pub chunk_id: String,
+    pub offset: u64,
+    pub length: u64,
+    /// Plaintext size of the chunk.
+    pub size: u64,
+}
+
+/// A finished pack, ready to store under `id`.
+pub struct Pack {
+    pub id: String,
+    pub bytes: Vec<u8>,
+    /// The chunks it holds, as recorded in its index.
+    pub entries: Vec<PackEntry>,
+}
+
+/// An open pack accumulating sealed chunk blobs.
+pub struct PackBuilder {
+    id: String,
+    bytes: Vec<u8>,
+    entries: Vec<PackEntry>,
+}
+
+impl Default for PackBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PackBuilder {
+    /// Start an empty pack under a fresh random id.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut id = [0_u8; 32];
+        rand::rng().fill_bytes(&mut id);
+        Self {
+            id: hex::encode(id),
+            bytes: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether the pack has reached [`PACK_TARGET_SIZE`] and should be sealed.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.bytes.len() >= PACK_TARGET_SIZE
+    }
+
+    /// Append a sealed chunk blob; returns its entry (with its offset and length
+    /// in this pack).
+    ///
+    /// # Errors
+    /// Returns an error if the chunk id is malformed.
+    pub fn add(&mut self, chunk_id: &str, blob: &[u8], size: u64) -> Result<PackEntry> {
+        if hex::decode(chunk_id).map(|id| id.len()) != Ok(CHUNK_ID_LEN) {
+            return Err(anyhow!("invalid chunk id {chunk_id:?}"));
+        }
+
+        let entry = PackEntry {
+            chunk_id: chunk_id.to_string(),
+            offset: u64::try_from(self.bytes.len())?,
+            length: u64::try_from(blob.len())?,
+            size,
+        };
+        self.bytes.extend_from_slice(blob);
+        self.entries.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// Append the index, sealed under the key `naming_key` derives for this
+    /// pack, and the footer.
+    ///
+    /// # Errors
+    /// Returns an error if the index cannot be sealed.
+    pub fn finish(self, naming_key: &[u8; 32]) -> Result<Pack> {
+        let Self {
+            id,
+            mut bytes,
+            entries,
+        } = self;
+        let key = pack_index_key(naming_key, &id)?;
+        let index = seal_content_with_key(&encode_index(&entries)?, &key, &id)?;
+        let index_offset = u64::try_from(bytes.len())?;
+
+        bytes.extend_from_slice(&index);
+        bytes.extend_from_slice(&index_offset.to_le_bytes());
+        bytes.extend_from_slice(&PACK_MAGIC);
+        Ok(Pack { id, bytes, entries })
+    }
+}
+
+/// Read a pack's index with the backup's naming key.
+///
+/// # Errors
+/// Returns an error if the pack is malformed or its index fails to decrypt.
+pub fn read_index(pack: &[u8], pack_id: &str, naming_key: &[u8; 32]) -> Result<Vec<PackEntry>> {
+    let footer_start = pack
+        .len()
+        .checked_sub(FOOTER_LEN)
+        .ok_or_else(|| anyhow!("pack too short"))?;
+    let mut footer = Reader(pack.get(footer_start..).unwrap_or_default());
+    let index_offset = usize::try_from(footer.u64()?)?;
+    if footer.0 != PACK_MAGIC {
+        return Err(anyhow!("not a pack (bad magic)"));
+    }
+    let index = pack
+        .get(index_offset..footer_start)
+        .ok_or_else(|| anyhow!("pack index out of range"))?;
+
+    let key = pack_index_key(naming_key, pack_id)?;
+    decode_index(&open_content(index, pack_id, &key)?)
+}
+
+/// `count (u32 LE)`, then per entry: `chunk_id (32) || offset (u64 LE) ||
+/// length (u64 LE) || size (u64 LE)`.
+fn encode_index(entries: &[PackEntry]) -> Result<Vec<u8>> {
+    let mut index = Vec::new();
+    index.extend_from_slice(&u32::try_from(entries.len())?.to_le_bytes());
+    for entry in entries {
+        index.extend_from_slice(&hex::decode(&entry.chunk_id)?);
+        index.extend_from_slice(&entry.offset.to_le_bytes());
+        index.extend_from_slice(&entry.length.to_le_bytes());
+        index.extend_from_slice(&entry.size.to_le_bytes());
+    }
+    Ok(index)
+}
+
+fn decode_index(index: &[u8]) -> Result<Vec<PackEntry>> {
+    let mut reader = Reader(index);
+    let count = reader.u32()?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        entries.push(PackEntry {
+            chunk_id: hex::encode(reader.take(CHUNK_ID_LEN)?),
+            offset: reader.u64()?,
+            length: reader.u64()?,
+            size: reader.u64()?,
+        });
+    }
+    if !reader.0.is_empty() {
+        return Err(anyhow!("pack index has trailing bytes"));
+    }
+    Ok(entries)
+}
+
+/// Bounds-checked little-endian cursor over a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let (head, rest) = self
+            .0
+            .split_at_checked(len)
+            .ok_or_else(|| anyhow!("pack index truncated"))?;
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        self.take(N)?
+            .try_into()
+            .map_err(|_| anyhow!("pack index truncated"))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::crypto::generate_naming_key;
+
+    #[test]
+    fn index_round_trips_and_locates_each_blob() -> Result<()> {
+        let naming_key = generate_naming_key();
+
+        let mut builder = PackBuilder::new();
+        assert!(builder.is_empty());
+        let mut added = Vec::new();
+        for (seed, blob) in [(1_u8, &b"first blob"[..]), (2, b"second"), (3, b"")] {
+            let id = hex::encode([seed; CHUNK_ID_LEN]);
+            let entry = builder.add(&id, blob, 100)?;
+            added.push((entry, blob));
+        }
+        assert!(!builder.is_full());
+
+        let pack_id = builder.id().to_string();
+        let pack = builder.finish(&naming_key)?;
+        assert_eq!(pack.id, pack_id);
+
+        let entries = read_index(&pack.bytes, &pack.id, &naming_key)?;
+        assert_eq!(entries.len(), added.len());
+        for (entry, (expected, blob)) in entries.iter().zip(&added) {
+            assert_eq!(entry, expected);
+            let start = usize::try_from(entry.offset)?;
+            let end = start + usize::try_from(entry.length)?;
+            assert_eq!(pack.bytes.get(start..end), Some(*blob));
+        }
+
+        // The index is bound to the pack id and needs the naming key.
+        assert!(read_index(&pack.bytes, &"0".repeat(64), &naming_key).is_err());
+        assert!(read_index(&pack.bytes, &pack.id, &generate_naming_key()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let mut builder = PackBuilder::new();
+        assert!(builder.add("not-hex", b"x", 1).is_err());
+        assert!(builder.add("abcd", b"x", 1).is_err());
+
+        let naming_key = generate_naming_key();
+        assert!(read_index(b"short", &"0".repeat(64), &naming_key).is_err());
+        assert!(read_index(&[0; 64], &"0".repeat(64), &naming_key).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::utils::hash::blake3_keyed_bytes;
+use anyhow::Result;
+use fastcdc::v2020::StreamCDC;
+use std::io::Read;
+
+/// Smallest chunk `FastCDC` will cut (except for a file's final chunk).
+pub const MIN_CHUNK_SIZE: u32 = 256 * 1024;
+/// Target average chunk size.
+pub const AVG_CHUNK_SIZE: u32 = 1024 * 1024;
+/// Largest chunk `FastCDC` will emit; bounds the memory one chunk can hold.
+pub const MAX_CHUNK_SIZE: u32 = 4 * 1024 * 1024;
+
+/// One content-defined chunk and its keyed BLAKE3 id.
+pub struct Chunk {
+    pub id: String,
+    pub data: Vec<u8>,
+}
+
+/// Split `reader` into content-defined chunks (DESIGN §6.1), handing each to
+/// `on_chunk` in order, and return the keyed BLAKE3 id of the whole stream.
+///
+/// Chunk ids use the same keying as whole-file ids, so a file smaller than
+/// [`MIN_CHUNK_SIZE`] is a single chunk whose id equals the file's content id.
+/// Only one chunk is held in memory at a time. An empty stream yields no chunks.
+///
+/// # Errors
+/// Returns an error if the reader fails or `on_chunk` returns an error.
+pub fn chunk_stream<R: Read>(
+    reader: R,
+    key: &[u8; 32],
+    mut on_chunk: impl FnMut(Chunk) -> Result<()>,
+) -> Result<String> {
+    let mut whole = blake3::Hasher::new_keyed(key);
+
+    for chunk in StreamCDC::new(reader, MIN_CHUNK_SIZE, AVG_CHUNK_SIZE, MAX_CHUNK_SIZE) {
+        let data = chunk?.data;
+        whole.update(&data);
+        on_chunk(Chunk {
+            id: blake3_keyed_bytes(&data, key),
+            data,
+        })?;
+    }
+
+    Ok(whole.finalize().to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7u8; 32];
+
+    /// Deterministic, non-repeating test data (repeating patterns would give
+    /// `FastCDC` nothing to cut on).
+    fn pseudo_random(seed: &[u8], len: usize) -> Vec<u8> {
+        let mut data = vec![0_u8; len];
+        blake3::Hasher::new()
+            .update(seed)
+            .finalize_xof()
+            .fill(&mut data);
+        data
+    }
+
+    fn chunks_of(data: &[u8]) -> Result<(String, Vec<Chunk>)> {
+        let mut chunks = Vec::new();
+        let whole = chunk_stream(data, &KEY, |chunk| {
+            chunks.push(chunk);
+            Ok(())
+        })?;
+        Ok((whole, chunks))
+    }
+
+    #[test]
+    fn chunks_reassemble_and_whole_id_matches() -> Result<()> {
+        let data = pseudo_random(b"reassemble", 6 * 1024 * 1024);
+        let (whole, chunks) = chunks_of(&data)?;
+
+        assert_eq!(whole, blake3_keyed_bytes(&data, &KEY));
+        assert!(chunks.len() > 1, "expected several chunks");
+        for chunk in &chunks {
+            assert_eq!(chunk.id, blake3_keyed_bytes(&chunk.data, &KEY));
+            assert!(chunk.data.len() <= MAX_CHUNK_SIZE as usize);
+        }
+        let joined: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.data).collect();
+        assert_eq!(joined, data);
+        Ok(())
+    }
+
+    #[test]
+    fn small_input_is_one_chunk_with_the_whole_id() -> Result<()> {
+        let (whole, chunks) = chunks_of(b"small file")?;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(
+            chunks.first().map(|chunk| chunk.id.as_str()),
+            Some(whole.as_str())
+        );
+
+        let (_, empty) = chunks_of(b"")?;
+        assert!(empty.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn insert_only_changes_nearby_chunks() -> Result<()> {
+        let original = pseudo_random(b"resync", 8 * 1024 * 1024);
+        let mut edited = original.clone();
+        edited.splice(3_000_000..3_000_000, *b"a few inserted bytes");
+
+        let (_, before) = chunks_of(&original)?;
+        let (_, after) = chunks_of(&edited)?;
+        let before: std::collections::HashSet<_> =
+            before.into_iter().map(|chunk| chunk.id).collect();
+        let changed = after
+            .iter()
+            .filter(|chunk| !before.contains(&chunk.id))
+            .count();
+
+        // Boundaries re-synchronize after the edit, so only the chunk(s) around
+        // it are new — unlike fixed-size blocks, where everything after shifts.
+        assert!(changed <= 2, "{changed} of {} chunks changed", after.len());
+        Ok(())
+    }
+}
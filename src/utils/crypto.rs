@@ -6,8 +6,10 @@ use chacha20poly1305::{
 use hkdf::Hkdf;
 use rand::RngCore;
 use sha2::Sha256;
+use std::io::{Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
-use zeroize::Zeroizing;
+use zeroize::{Zeroize, Zeroizing};
 
 /// HKDF label for the X25519 content keypair derived from the mnemonic seed.
 const CONTENT_KEY_INFO: &[u8] = b"backup x25519 content v1";
@@ -15,6 +17,8 @@ const CONTENT_KEY_INFO: &[u8] = b"backup x25519 content v1";
 const WRAP_KEK_INFO: &[u8] = b"backup wrap";
 /// AAD bound when wrapping the naming key (domain-separated from content keys).
 const NAMING_KEY_AAD: &[u8] = b"backup:naming-key:v1";
+/// HKDF label for a pack's index key, derived from the naming key.
+const PACK_INDEX_KEY_INFO: &[u8] = b"backup pack index v1";
 
 /// Associated data binding a wrapped content/file key to its content id, so a
 /// wrapped-key row in the catalog can't be reused under a different content id.
@@ -28,8 +32,12 @@ pub fn content_key_aad(content_id: &str) -> Vec<u8> {
     aad
 }
 
-/// Content blob format version (header byte 0).
-const BLOB_VERSION: u8 = 1;
+/// Content blob format version (header byte 0): v1 is one AEAD over the whole
+/// payload (still readable, no longer written).
+const BLOB_VERSION_V1: u8 = 1;
+/// Content blob format version (header byte 0): v2 is the segmented STREAM
+/// format, sealed and opened without holding the whole content in memory.
+const BLOB_VERSION: u8 = 2;
 /// Codec tag (header byte 1): stored uncompressed.
 const CODEC_RAW: u8 = 0;
 /// Codec tag (header byte 1): zstd-compressed.
@@ -38,8 +46,21 @@ const CODEC_ZSTD: u8 = 1;
 const ZSTD_LEVEL: i32 = 3;
 /// ChaCha20-Poly1305 nonce length.
 const NONCE_LEN: usize = 12;
-/// Blob header length: `version || codec`.
+/// ChaCha20-Poly1305 tag length.
+const TAG_LEN: usize = 16;
+/// Blob header length shared by every version: `version || codec`.
 const BLOB_HEADER_LEN: usize = 2;
+/// v2 nonce prefix length: `prefix || counter (4) || last flag (1)` = 12 bytes.
+const NONCE_PREFIX_LEN: usize = 7;
+/// v2 plaintext segment size (of the possibly-compressed payload). Every segment
+/// but the last is exactly this long; the last is shorter (possibly empty).
+const SEGMENT_SIZE: usize = 64 * 1024;
+/// v2 sealed segment size for every segment but the last.
+const SEALED_SEGMENT_SIZE: usize = SEGMENT_SIZE + TAG_LEN;
+/// Sealed catalog envelope magic; the last byte is the envelope version.
+const CATALOG_MAGIC: &[u8; 6] = b"BKCAT\x01";
+/// A wrapped 32-byte key: `nonce || ciphertext || tag`.
+const WRAPPED_KEY_LEN: usize = NONCE_LEN + 32 + TAG_LEN;
 
 #[must_use]
 pub fn generate_file_key() -> Zeroizing<[u8; 32]> {
@@ -57,7 +78,41 @@ pub fn generate_naming_key() -> Zeroizing<[u8; 32]> {
     generate_file_key()
 }
 
-/// Derive the X25519 content keypair from a BIP-39 mnemonic.
+/// A backup's recovery root: its BIP-39 mnemonic and, for a backup created with
+/// one, the BIP-39 passphrase (the "25th word") the seed is derived with. With
+/// a passphrase, the written-down words alone derive an unrelated keypair.
+#[derive(Clone)]
+pub struct RecoveryKey {
+    pub mnemonic: bip39::Mnemonic,
+    pub passphrase: Option<Zeroizing<String>>,
+}
+
+impl From<bip39::Mnemonic> for RecoveryKey {
+    fn from(mnemonic: bip39::Mnemonic) -> Self {
+        Self {
+            mnemonic,
+            passphrase: None,
+        }
+    }
+}
+
+impl RecoveryKey {
+    /// Derive the X25519 content keypair from the mnemonic and passphrase.
+    ///
+    /// # Errors
+    /// Returns an error if key derivation fails.
+    pub fn keypair(&self) -> Result<(StaticSecret, PublicKey)> {
+        derive_content_keypair(
+            &self.mnemonic,
+            self.passphrase
+                .as_ref()
+                .map_or("", |passphrase| passphrase.as_str()),
+        )
+    }
+}
+
+/// Derive the X25519 content keypair from a BIP-39 mnemonic with no
+/// passphrase; [`RecoveryKey::keypair`] takes one into account.
 ///
 /// The full 64-byte seed is run through HKDF-SHA256 with a domain-separation
 /// label, so the entire seed entropy is used (rather than truncating it) and
@@ -66,7 +121,14 @@ pub fn generate_naming_key() -> Zeroizing<[u8; 32]> {
 /// # Errors
 /// Returns an error if key derivation fails.
 pub fn content_keypair(mnemonic: &bip39::Mnemonic) -> Result<(StaticSecret, PublicKey)> {
-    let seed = Zeroizing::new(mnemonic.to_seed(""));
+    derive_content_keypair(mnemonic, "")
+}
+
+fn derive_content_keypair(
+    mnemonic: &bip39::Mnemonic,
+    passphrase: &str,
+) -> Result<(StaticSecret, PublicKey)> {
+    let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
 
     let mut sk_bytes = Zeroizing::new([0u8; 32]);
     Hkdf::<Sha256>::new(None, seed.as_slice())
@@ -138,14 +200,30 @@ pub fn decrypt(
     eph_pub_bytes: &[u8; 32],
     mnemonic: &bip39::Mnemonic,
     aad: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    // Derive the private key from the mnemonic (same derivation as content_keypair).
+    let (private_key, _) = content_keypair(mnemonic)?;
+
+    decrypt_with_secret(encrypted_data, eph_pub_bytes, &private_key, aad)
+}
+
+/// Decrypt (unwrap) a wrapped key with an already-derived content private key.
+///
+/// Same as [`decrypt`], but skips the mnemonic → seed derivation (a PBKDF2 pass)
+/// so callers unwrapping many keys, like restore, derive the secret once.
+///
+/// # Errors
+/// Returns an error if the encrypted payload is malformed or key unwrapping fails.
+pub fn decrypt_with_secret(
+    encrypted_data: &[u8],
+    eph_pub_bytes: &[u8; 32],
+    private_key: &StaticSecret,
+    aad: &[u8],
 ) -> Result<Zeroizing<Vec<u8>>> {
     if encrypted_data.len() < 12 {
         return Err(anyhow!("Encrypted data too short to contain nonce"));
     }
 
-    // Derive the private key from the mnemonic (same derivation as content_keypair).
-    let (private_key, _) = content_keypair(mnemonic)?;
-
     let eph_pub = PublicKey::from(*eph_pub_bytes);
 
     // shared secret
@@ -195,7 +273,19 @@ pub fn seal_naming_key(naming_key: &[u8; 32], public_key: &PublicKey) -> Result<
 ///
 /// # Errors
 /// Returns an error if the sealed blob is malformed or unsealing fails.
-pub fn unseal_naming_key(sealed: &[u8], mnemonic: &bip39::Mnemonic) -> Result<Zeroizing<[u8; 32]>> {
+pub fn unseal_naming_key(sealed: &[u8], recovery: &RecoveryKey) -> Result<Zeroizing<[u8; 32]>> {
+    let (private_key, _) = recovery.keypair()?;
+    unseal_naming_key_with_secret(sealed, &private_key)
+}
+
+/// Recover a sealed naming key with an already-derived content private key.
+///
+/// # Errors
+/// Returns an error if the sealed blob is malformed or unsealing fails.
+pub fn unseal_naming_key_with_secret(
+    sealed: &[u8],
+    private_key: &StaticSecret,
+) -> Result<Zeroizing<[u8; 32]>> {
     let eph_pub: [u8; 32] = sealed
         .get(..32)
         .ok_or_else(|| anyhow!("Sealed naming key too short"))?
@@ -206,7 +296,7 @@ pub fn unseal_naming_key(sealed: &[u8], mnemonic: &bip39::Mnemonic) -> Result<Ze
         .get(32..)
         .ok_or_else(|| anyhow!("Sealed naming key missing payload"))?;
 
-    let plaintext = decrypt(wrapped, &eph_pub, mnemonic, NAMING_KEY_AAD)?;
+    let plaintext = decrypt_with_secret(wrapped, &eph_pub, private_key, NAMING_KEY_AAD)?;
 
     let key: [u8; 32] = plaintext
         .as_slice()
@@ -216,29 +306,66 @@ pub fn unseal_naming_key(sealed: &[u8], mnemonic: &bip39::Mnemonic) -> Result<Ze
     Ok(Zeroizing::new(key))
 }
 
+/// Unwrap a content key recorded in the catalog for `content_id`.
+///
+/// # Errors
+/// Returns an error if unwrapping fails or the key has an unexpected length.
+pub fn unwrap_content_key(
+    wrapped: &[u8],
+    eph_pub_bytes: &[u8; 32],
+    private_key: &StaticSecret,
+    content_id: &str,
+) -> Result<Zeroizing<[u8; 32]>> {
+    let plaintext = decrypt_with_secret(
+        wrapped,
+        eph_pub_bytes,
+        private_key,
+        &content_key_aad(content_id),
+    )?;
+
+    let key: [u8; 32] = plaintext
+        .as_slice()
+        .try_into()
+        .map_err(|_| anyhow!("Unwrapped content key has unexpected length"))?;
+
+    Ok(Zeroizing::new(key))
+}
+
 /// A compressed + encrypted content blob plus the wrapped key to record.
 pub struct SealedContent {
-    /// `version || codec || nonce || ciphertext` — stored opaque in the blob store.
+    /// A v2 blob (`version || codec || nonce prefix || segments`) — stored opaque
+    /// in the blob store.
     pub blob: Vec<u8>,
     /// The content key wrapped to the backup public key (`nonce || ciphertext`).
     pub wrapped_key: Vec<u8>,
     /// Ephemeral public key needed to unwrap `wrapped_key`.
     pub ephemeral_public_key: [u8; 32],
+    /// The same key wrapped to each additional recipient, in the order given
+    /// to [`seal_content_for`].
+    pub recipient_keys: Vec<WrappedKey>,
+}
+
+/// A content key wrapped to the backup public key, returned by the streaming
+/// seal APIs (the blob itself went to the caller's writer).
+pub struct WrappedKey {
+    pub wrapped_key: Vec<u8>,
+    pub ephemeral_public_key: [u8; 32],
 }
 
 /// Associated data binding the blob to its content id, format version and codec
 /// (prevents a tampering store from swapping a blob or forcing a codec downgrade).
-fn content_aad(content_id: &str, codec: u8) -> Vec<u8> {
+fn content_aad(content_id: &str, version: u8, codec: u8) -> Vec<u8> {
     let mut aad = Vec::with_capacity(content_id.len() + 2);
     aad.extend_from_slice(content_id.as_bytes());
-    aad.push(BLOB_VERSION);
+    aad.push(version);
     aad.push(codec);
     aad
 }
 
 /// Compress then encrypt `plaintext` into a storable blob, and wrap a fresh
-/// per-content key to `public_key`. The content key is single-use, so a random
-/// nonce stored in the blob is safe; `content_id` is bound as associated data.
+/// per-content key to `public_key`. The codec is chosen per blob: the raw bytes
+/// are kept if zstd doesn't actually shrink them. `content_id` is bound as
+/// associated data.
 ///
 /// # Errors
 /// Returns an error if compression, encryption, or key wrapping fails.
@@ -246,10 +373,48 @@ pub fn seal_content(
     plaintext: &[u8],
     public_key: &PublicKey,
     content_id: &str,
+) -> Result<SealedContent> {
+    seal_content_for(plaintext, public_key, &[], content_id)
+}
+
+/// [`seal_content`], also wrapping the content key to each of `recipients`
+/// (DESIGN §6.3), so any of their mnemonics can open the blob.
+///
+/// # Errors
+/// Returns an error if compression, encryption, or key wrapping fails.
+pub fn seal_content_for(
+    plaintext: &[u8],
+    public_key: &PublicKey,
+    recipients: &[PublicKey],
+    content_id: &str,
 ) -> Result<SealedContent> {
     let content_key = generate_file_key();
+    let blob = seal_content_with_key(plaintext, &content_key, content_id)?;
 
-    // Compress; keep the raw bytes if zstd doesn't actually shrink them.
+    let wrapped = wrap_content_key(&content_key, public_key, content_id)?;
+    let recipient_keys = recipients
+        .iter()
+        .map(|recipient| wrap_content_key(&content_key, recipient, content_id))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(SealedContent {
+        blob,
+        wrapped_key: wrapped.wrapped_key,
+        ephemeral_public_key: wrapped.ephemeral_public_key,
+        recipient_keys,
+    })
+}
+
+/// [`seal_content`] under a key the caller already holds, which is not wrapped
+/// to anyone: the blob opens with [`open_content`] and that key.
+///
+/// # Errors
+/// Returns an error if compression or encryption fails.
+pub fn seal_content_with_key(
+    plaintext: &[u8],
+    content_key: &[u8; 32],
+    content_id: &str,
+) -> Result<Vec<u8>> {
     let compressed = zstd::encode_all(plaintext, ZSTD_LEVEL)
         .map_err(|err| anyhow!("compression failed: {err}"))?;
     let (codec, payload): (u8, &[u8]) = if compressed.len() < plaintext.len() {
@@ -258,37 +423,61 @@ pub fn seal_content(
         (CODEC_RAW, plaintext)
     };
 
-    let cipher = ChaCha20Poly1305::new(Key::from_slice(content_key.as_ref()));
-    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
-    let aad = content_aad(content_id, codec);
-    let ciphertext = cipher
-        .encrypt(
-            &nonce,
-            Payload {
-                msg: payload,
-                aad: &aad,
-            },
-        )
-        .map_err(|_| anyhow!("content encryption failed"))?;
+    // The payload is already compressed (or deliberately raw), so it goes straight
+    // to the segment layer.
+    let (mut segments, mut blob) = SegmentSealer::new(content_key, content_id, codec);
+    blob.reserve(payload.len() + (payload.len() / SEGMENT_SIZE + 1) * TAG_LEN);
+    segments.push(payload, &mut blob)?;
+    segments.finish(&mut blob)?;
+    Ok(blob)
+}
 
-    let mut blob = Vec::with_capacity(BLOB_HEADER_LEN + NONCE_LEN + ciphertext.len());
-    blob.push(BLOB_VERSION);
-    blob.push(codec);
-    blob.extend_from_slice(nonce.as_slice());
-    blob.extend_from_slice(&ciphertext);
+/// The key a pack's index is sealed under: derived from the naming key and the
+/// pack id, so it is not wrapped to any keypair and moving the backup to a new
+/// one (`rekey`) leaves every stored pack as it is.
+///
+/// # Errors
+/// Returns an error if key derivation fails.
+pub fn pack_index_key(naming_key: &[u8; 32], pack_id: &str) -> Result<Zeroizing<[u8; 32]>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(pack_id.as_bytes()), naming_key)
+        .expand(PACK_INDEX_KEY_INFO, key.as_mut())
+        .map_err(|err| anyhow!("Error during pack index key HKDF expansion: {err}"))?;
+    Ok(key)
+}
 
-    let (wrapped_key, ephemeral_public_key) =
-        encrypt(&content_key, public_key, &content_key_aad(content_id))?;
+/// Re-wrap a content key recorded for `content_id` to another recipient: unwrap
+/// it with `private_key`, then wrap it afresh to `recipient`. The blob is not
+/// touched, so adding a keyholder costs one wrapped key per chunk.
+///
+/// # Errors
+/// Returns an error if the key does not unwrap with `private_key` or wrapping fails.
+pub fn rewrap_content_key(
+    wrapped: &[u8],
+    eph_pub_bytes: &[u8; 32],
+    private_key: &StaticSecret,
+    recipient: &PublicKey,
+    content_id: &str,
+) -> Result<WrappedKey> {
+    let content_key = unwrap_content_key(wrapped, eph_pub_bytes, private_key, content_id)?;
+    wrap_content_key(&content_key, recipient, content_id)
+}
 
-    Ok(SealedContent {
-        blob,
-        wrapped_key,
-        ephemeral_public_key,
-    })
+/// Parse a public key given as 64 hex characters, as `backup new` prints it.
+///
+/// # Errors
+/// Returns an error if `hex_key` is not 32 bytes of hex.
+pub fn parse_public_key(hex_key: &str) -> Result<PublicKey> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim())
+        .map_err(|err| anyhow!("Invalid public key {hex_key:?}: {err}"))?
+        .try_into()
+        .map_err(|_| anyhow!("Invalid public key {hex_key:?}: expected 32 bytes"))?;
+    Ok(PublicKey::from(bytes))
 }
 
-/// Decrypt and decompress a blob produced by [`seal_content`], given the
-/// already-unwrapped content key (see [`decrypt`]) and the blob's `content_id`.
+/// Decrypt and decompress a blob produced by [`seal_content`] or the streaming
+/// seal APIs (or a legacy v1 blob), given the already-unwrapped content key (see
+/// [`decrypt`]) and the blob's `content_id`.
 ///
 /// # Errors
 /// Returns an error if the blob is malformed or authentication/decompression fails.
@@ -298,9 +487,559 @@ pub fn open_content(
     content_key: &[u8; 32],
 ) -> Result<Zeroizing<Vec<u8>>> {
     let version = *blob.first().ok_or_else(|| anyhow!("blob too short"))?;
-    if version != BLOB_VERSION {
-        return Err(anyhow!("unsupported blob version {version}"));
+    if version == BLOB_VERSION_V1 {
+        return open_content_v1(blob, content_id, content_key);
+    }
+
+    let mut plaintext = Zeroizing::new(Vec::new());
+    let header = blob
+        .get(..ContentOpener::HEADER_LEN)
+        .ok_or_else(|| anyhow!("blob too short"))?;
+    let body = blob
+        .get(ContentOpener::HEADER_LEN..)
+        .ok_or_else(|| anyhow!("blob too short"))?;
+
+    let mut opener = ContentOpener::new(header, content_id, content_key)?;
+    opener.push(body, &mut plaintext)?;
+    opener.finish(&mut plaintext)?;
+
+    Ok(plaintext)
+}
+
+/// Seal everything `reader` yields into a v2 blob written to `writer`, holding
+/// at most one segment of plaintext at a time. Streams are always zstd-compressed
+/// (the raw-if-larger choice needs the whole input up front).
+///
+/// # Errors
+/// Returns an error if reading, compression, encryption, key wrapping, or
+/// writing fails.
+pub fn seal_reader<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    public_key: &PublicKey,
+    content_id: &str,
+) -> Result<WrappedKey> {
+    let content_key = generate_file_key();
+    seal_stream(reader, writer, &content_key, content_id)?;
+    wrap_content_key(&content_key, public_key, content_id)
+}
+
+/// [`seal_reader`] under a given `content_key`, left to the caller to wrap.
+fn seal_stream<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    content_key: &[u8; 32],
+    content_id: &str,
+) -> Result<()> {
+    let (mut sealer, header) = ContentSealer::new(content_key, content_id)?;
+    writer.write_all(&header)?;
+
+    let mut buf = Zeroizing::new(vec![0_u8; SEGMENT_SIZE]);
+    let mut out = Vec::new();
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        sealer.push(buf.get(..read).unwrap_or_default(), &mut out)?;
+        writer.write_all(&out)?;
+        out.clear();
     }
+    sealer.finish(&mut out)?;
+    writer.write_all(&out)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Open a v2 blob from `reader`, writing the plaintext to `writer` one segment
+/// at a time. Returns the number of plaintext bytes written. Nothing written is
+/// trustworthy until this returns `Ok` — a later segment may still fail.
+///
+/// # Errors
+/// Returns an error if the blob is malformed, truncated, reordered, or fails
+/// authentication, or if reading, decompression, or writing fails.
+pub fn open_reader<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    content_id: &str,
+    content_key: &[u8; 32],
+) -> Result<u64> {
+    let mut header = [0_u8; ContentOpener::HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|err| anyhow!("blob header unreadable: {err}"))?;
+    let mut opener = ContentOpener::new(&header, content_id, content_key)?;
+
+    let mut buf = vec![0_u8; SEALED_SEGMENT_SIZE];
+    let mut out = Zeroizing::new(Vec::new());
+    let mut written = 0_u64;
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        opener.push(buf.get(..read).unwrap_or_default(), &mut out)?;
+        writer.write_all(&out)?;
+        written += u64::try_from(out.len())?;
+        out.clear();
+    }
+    opener.finish(&mut out)?;
+    writer.write_all(&out)?;
+    writer.flush()?;
+    written += u64::try_from(out.len())?;
+
+    Ok(written)
+}
+
+/// [`seal_reader`] over async I/O.
+///
+/// # Errors
+/// Returns an error if reading, compression, encryption, key wrapping, or
+/// writing fails.
+pub async fn seal_async<R, W>(
+    mut reader: R,
+    mut writer: W,
+    public_key: &PublicKey,
+    content_id: &str,
+) -> Result<WrappedKey>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let content_key = generate_file_key();
+    let (mut sealer, header) = ContentSealer::new(&content_key, content_id)?;
+    writer.write_all(&header).await?;
+
+    let mut buf = vec![0_u8; SEGMENT_SIZE];
+    let mut out = Vec::new();
+    loop {
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        sealer.push(buf.get(..read).unwrap_or_default(), &mut out)?;
+        writer.write_all(&out).await?;
+        out.clear();
+    }
+    sealer.finish(&mut out)?;
+    writer.write_all(&out).await?;
+    writer.flush().await?;
+
+    wrap_content_key(&content_key, public_key, content_id)
+}
+
+/// [`open_reader`] over async I/O.
+///
+/// # Errors
+/// Returns an error if the blob is malformed, truncated, reordered, or fails
+/// authentication, or if reading, decompression, or writing fails.
+pub async fn open_async<R, W>(
+    mut reader: R,
+    mut writer: W,
+    content_id: &str,
+    content_key: &[u8; 32],
+) -> Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut header = [0_u8; ContentOpener::HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .await
+        .map_err(|err| anyhow!("blob header unreadable: {err}"))?;
+    let mut opener = ContentOpener::new(&header, content_id, content_key)?;
+
+    let mut buf = vec![0_u8; SEALED_SEGMENT_SIZE];
+    let mut out = Zeroizing::new(Vec::new());
+    let mut written = 0_u64;
+    loop {
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        opener.push(buf.get(..read).unwrap_or_default(), &mut out)?;
+        writer.write_all(&out).await?;
+        written += u64::try_from(out.len())?;
+        out.clear();
+    }
+    opener.finish(&mut out)?;
+    writer.write_all(&out).await?;
+    writer.flush().await?;
+    written += u64::try_from(out.len())?;
+
+    Ok(written)
+}
+
+/// Seal a catalog snapshot read from `db` for the store (DESIGN §7), writing
+/// the sealed envelope to `writer` one segment at a time.
+///
+/// The database is sealed like content, under a fresh key wrapped to
+/// `public_key`, in a versioned envelope: `magic || ephemeral public key ||
+/// wrapped key || blob`. The blob and key are bound to `backup`, so one
+/// backup's catalog cannot be passed off as another's. Sealing needs no secret.
+///
+/// # Errors
+/// Returns an error if reading, compression, encryption, key wrapping, or
+/// writing fails.
+pub fn seal_catalog<R: Read, W: Write>(
+    db: R,
+    mut writer: W,
+    public_key: &PublicKey,
+    backup: &str,
+) -> Result<()> {
+    let content_id = catalog_content_id(backup);
+    let content_key = generate_file_key();
+    let wrapped = wrap_content_key(&content_key, public_key, &content_id)?;
+    if wrapped.wrapped_key.len() != WRAPPED_KEY_LEN {
+        return Err(anyhow!("wrapped catalog key has unexpected length"));
+    }
+
+    writer.write_all(CATALOG_MAGIC)?;
+    writer.write_all(&wrapped.ephemeral_public_key)?;
+    writer.write_all(&wrapped.wrapped_key)?;
+    seal_stream(db, writer, &content_key, &content_id)
+}
+
+/// Open a catalog sealed with [`seal_catalog`] for backup `backup` from
+/// `envelope`, writing the database to `writer` one segment at a time. Nothing
+/// written is trustworthy until this returns `Ok`.
+///
+/// # Errors
+/// Returns an error if the envelope is malformed or of an unknown version, if
+/// it was not sealed to this key or for this backup, or if writing fails.
+pub fn open_catalog<R: Read, W: Write>(
+    mut envelope: R,
+    writer: W,
+    private_key: &StaticSecret,
+    backup: &str,
+) -> Result<u64> {
+    let mut magic = [0_u8; CATALOG_MAGIC.len()];
+    let mut ephemeral_public_key = [0_u8; 32];
+    let mut wrapped_key = [0_u8; WRAPPED_KEY_LEN];
+    envelope
+        .read_exact(&mut magic)
+        .map_err(|_| anyhow!("sealed catalog too short"))?;
+    if &magic != CATALOG_MAGIC {
+        return Err(anyhow!(
+            "not a sealed catalog, or of an unsupported version"
+        ));
+    }
+    envelope
+        .read_exact(&mut ephemeral_public_key)
+        .and_then(|()| envelope.read_exact(&mut wrapped_key))
+        .map_err(|_| anyhow!("sealed catalog too short"))?;
+
+    let content_id = catalog_content_id(backup);
+    let content_key = unwrap_content_key(
+        &wrapped_key,
+        &ephemeral_public_key,
+        private_key,
+        &content_id,
+    )?;
+    open_reader(envelope, writer, &content_id, &content_key)
+}
+
+/// Content id a sealed catalog of backup `backup` is bound to.
+fn catalog_content_id(backup: &str) -> String {
+    format!("catalog:{backup}")
+}
+
+fn wrap_content_key(
+    content_key: &[u8; 32],
+    public_key: &PublicKey,
+    content_id: &str,
+) -> Result<WrappedKey> {
+    let (wrapped_key, ephemeral_public_key) =
+        encrypt(content_key, public_key, &content_key_aad(content_id))?;
+    Ok(WrappedKey {
+        wrapped_key,
+        ephemeral_public_key,
+    })
+}
+
+/// Nonce for segment `counter` of a v2 blob: `prefix || counter (BE) || last`.
+/// This is the STREAM construction: a segment only authenticates at its own
+/// position, and only the final segment carries the last flag, so reordering,
+/// dropping, or truncating segments (even at a segment boundary) is detected.
+fn segment_nonce(prefix: [u8; NONCE_PREFIX_LEN], counter: u32, last: bool) -> Nonce {
+    let mut nonce = [0_u8; NONCE_LEN];
+    for (slot, byte) in nonce.iter_mut().zip(
+        prefix
+            .iter()
+            .chain(counter.to_be_bytes().iter())
+            .chain(std::iter::once(&u8::from(last))),
+    ) {
+        *slot = *byte;
+    }
+    Nonce::from(nonce)
+}
+
+/// Push-based v2 segment encryptor: buffers payload bytes and emits every full
+/// segment as soon as it is complete, so memory stays at one segment.
+struct SegmentSealer {
+    cipher: ChaCha20Poly1305,
+    prefix: [u8; NONCE_PREFIX_LEN],
+    counter: u32,
+    aad: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl SegmentSealer {
+    /// A sealer plus the blob header to write before its segments.
+    fn new(content_key: &[u8; 32], content_id: &str, codec: u8) -> (Self, Vec<u8>) {
+        // The content key is single-use, so the prefix needn't be random; it is
+        // anyway, at the cost of 7 header bytes.
+        let mut prefix = [0_u8; NONCE_PREFIX_LEN];
+        rand::rng().fill_bytes(&mut prefix);
+
+        let mut header = Vec::with_capacity(BLOB_HEADER_LEN + NONCE_PREFIX_LEN);
+        header.push(BLOB_VERSION);
+        header.push(codec);
+        header.extend_from_slice(&prefix);
+
+        let sealer = Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(content_key)),
+            prefix,
+            counter: 0,
+            aad: content_aad(content_id, BLOB_VERSION, codec),
+            pending: Vec::new(),
+        };
+        (sealer, header)
+    }
+
+    fn push(&mut self, payload: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.pending.extend_from_slice(payload);
+        let full = self.pending.len() / SEGMENT_SIZE * SEGMENT_SIZE;
+        if full == 0 {
+            return Ok(());
+        }
+
+        let pending = std::mem::take(&mut self.pending);
+        let (segments, rest) = pending
+            .split_at_checked(full)
+            .ok_or_else(|| anyhow!("segment split out of range"))?;
+        for segment in segments.chunks(SEGMENT_SIZE) {
+            self.seal_segment(segment, false, out)?;
+        }
+        self.pending = rest.to_vec();
+        Ok(())
+    }
+
+    /// Seal the final segment — always shorter than [`SEGMENT_SIZE`], and empty
+    /// when the payload ends on a segment boundary.
+    fn finish(mut self, out: &mut Vec<u8>) -> Result<()> {
+        let last = std::mem::take(&mut self.pending);
+        self.seal_segment(&last, true, out)
+    }
+
+    fn seal_segment(&mut self, segment: &[u8], last: bool, out: &mut Vec<u8>) -> Result<()> {
+        let nonce = segment_nonce(self.prefix, self.counter, last);
+        let sealed = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: segment,
+                    aad: &self.aad,
+                },
+            )
+            .map_err(|_| anyhow!("content encryption failed"))?;
+        out.extend_from_slice(&sealed);
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("content too large for one blob"))?;
+        Ok(())
+    }
+}
+
+/// Push-based v2 segment decryptor, the mirror of [`SegmentSealer`].
+struct SegmentOpener {
+    cipher: ChaCha20Poly1305,
+    prefix: [u8; NONCE_PREFIX_LEN],
+    counter: u32,
+    aad: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl SegmentOpener {
+    fn push(&mut self, sealed: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.pending.extend_from_slice(sealed);
+        // Only the final segment is shorter than a full one, so every complete
+        // full-size segment buffered here is a non-final segment.
+        let full = self.pending.len() / SEALED_SEGMENT_SIZE * SEALED_SEGMENT_SIZE;
+        if full == 0 {
+            return Ok(());
+        }
+
+        let pending = std::mem::take(&mut self.pending);
+        let (segments, rest) = pending
+            .split_at_checked(full)
+            .ok_or_else(|| anyhow!("segment split out of range"))?;
+        for segment in segments.chunks(SEALED_SEGMENT_SIZE) {
+            self.open_segment(segment, false, out)?;
+        }
+        self.pending = rest.to_vec();
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        if self.pending.len() < TAG_LEN {
+            return Err(anyhow!("blob truncated: final segment missing"));
+        }
+        let last = std::mem::take(&mut self.pending);
+        self.open_segment(&last, true, out)
+    }
+
+    fn open_segment(&mut self, segment: &[u8], last: bool, out: &mut Vec<u8>) -> Result<()> {
+        let nonce = segment_nonce(self.prefix, self.counter, last);
+        let opened = Zeroizing::new(
+            self.cipher
+                .decrypt(
+                    &nonce,
+                    Payload {
+                        msg: segment,
+                        aad: &self.aad,
+                    },
+                )
+                .map_err(|_| anyhow!("content decryption failed"))?,
+        );
+        out.extend_from_slice(&opened);
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("blob has too many segments"))?;
+        Ok(())
+    }
+}
+
+/// Streaming zstd + v2 segment sealer behind [`seal_reader`] and [`seal_async`].
+struct ContentSealer {
+    compressor: zstd::stream::write::Encoder<'static, Vec<u8>>,
+    segments: SegmentSealer,
+}
+
+impl ContentSealer {
+    fn new(content_key: &[u8; 32], content_id: &str) -> Result<(Self, Vec<u8>)> {
+        let compressor = zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)
+            .map_err(|err| anyhow!("compression failed: {err}"))?;
+        let (segments, header) = SegmentSealer::new(content_key, content_id, CODEC_ZSTD);
+        Ok((
+            Self {
+                compressor,
+                segments,
+            },
+            header,
+        ))
+    }
+
+    fn push(&mut self, plaintext: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.compressor
+            .write_all(plaintext)
+            .map_err(|err| anyhow!("compression failed: {err}"))?;
+        let compressed = std::mem::take(self.compressor.get_mut());
+        self.segments.push(&compressed, out)
+    }
+
+    fn finish(self, out: &mut Vec<u8>) -> Result<()> {
+        let Self {
+            compressor,
+            mut segments,
+        } = self;
+        let tail = compressor
+            .finish()
+            .map_err(|err| anyhow!("compression failed: {err}"))?;
+        segments.push(&tail, out)?;
+        segments.finish(out)
+    }
+}
+
+/// Streaming v2 segment opener + decompressor behind [`open_content`],
+/// [`open_reader`] and [`open_async`].
+struct ContentOpener {
+    segments: SegmentOpener,
+    decompressor: Option<zstd::stream::write::Decoder<'static, Vec<u8>>>,
+}
+
+impl ContentOpener {
+    /// v2 header length: `version || codec || nonce prefix`.
+    const HEADER_LEN: usize = BLOB_HEADER_LEN + NONCE_PREFIX_LEN;
+
+    fn new(header: &[u8], content_id: &str, content_key: &[u8; 32]) -> Result<Self> {
+        let version = *header.first().ok_or_else(|| anyhow!("blob too short"))?;
+        if version != BLOB_VERSION {
+            return Err(anyhow!("unsupported blob version {version}"));
+        }
+        let codec = *header.get(1).ok_or_else(|| anyhow!("blob too short"))?;
+        let decompressor = match codec {
+            CODEC_RAW => None,
+            CODEC_ZSTD => Some(
+                zstd::stream::write::Decoder::new(Vec::new())
+                    .map_err(|err| anyhow!("decompression failed: {err}"))?,
+            ),
+            other => return Err(anyhow!("unknown codec tag {other}")),
+        };
+        let prefix: [u8; NONCE_PREFIX_LEN] = header
+            .get(BLOB_HEADER_LEN..Self::HEADER_LEN)
+            .and_then(|prefix| prefix.try_into().ok())
+            .ok_or_else(|| anyhow!("blob missing nonce prefix"))?;
+
+        Ok(Self {
+            segments: SegmentOpener {
+                cipher: ChaCha20Poly1305::new(Key::from_slice(content_key)),
+                prefix,
+                counter: 0,
+                aad: content_aad(content_id, BLOB_VERSION, codec),
+                pending: Vec::new(),
+            },
+            decompressor,
+        })
+    }
+
+    fn push(&mut self, sealed: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let mut payload = Zeroizing::new(Vec::new());
+        self.segments.push(sealed, &mut payload)?;
+        self.decompress(&payload, out)
+    }
+
+    fn finish(mut self, out: &mut Vec<u8>) -> Result<()> {
+        let mut payload = Zeroizing::new(Vec::new());
+        self.segments.finish(&mut payload)?;
+        self.decompress(&payload, out)?;
+
+        if let Some(decompressor) = &mut self.decompressor {
+            decompressor
+                .flush()
+                .map_err(|err| anyhow!("decompression failed: {err}"))?;
+            out.extend_from_slice(decompressor.get_ref());
+            decompressor.get_mut().zeroize();
+        }
+        Ok(())
+    }
+
+    fn decompress(&mut self, payload: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        match &mut self.decompressor {
+            None => out.extend_from_slice(payload),
+            Some(decompressor) => {
+                decompressor
+                    .write_all(payload)
+                    .map_err(|err| anyhow!("decompression failed: {err}"))?;
+                out.extend_from_slice(decompressor.get_ref());
+                decompressor.get_mut().zeroize();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Open a v1 blob (`version || codec || nonce || ciphertext`, one AEAD over the
+/// whole payload), as written before the segmented format.
+fn open_content_v1(
+    blob: &[u8],
+    content_id: &str,
+    content_key: &[u8; 32],
+) -> Result<Zeroizing<Vec<u8>>> {
     let codec = *blob.get(1).ok_or_else(|| anyhow!("blob too short"))?;
 
     let nonce_bytes = blob
@@ -312,7 +1051,7 @@ pub fn open_content(
 
     let cipher = ChaCha20Poly1305::new(Key::from_slice(content_key));
     let nonce = Nonce::from_slice(nonce_bytes);
-    let aad = content_aad(content_id, codec);
+    let aad = content_aad(content_id, BLOB_VERSION_V1, codec);
     let payload = cipher
         .decrypt(
             nonce,
@@ -383,7 +1122,7 @@ mod tests {
         let naming_key = generate_naming_key();
         let sealed = seal_naming_key(&naming_key, &public_key)?;
 
-        let recovered = unseal_naming_key(&sealed, &mnemonic)?;
+        let recovered = unseal_naming_key(&sealed, &mnemonic.into())?;
 
         assert_eq!(*naming_key, *recovered);
 
@@ -399,7 +1138,7 @@ mod tests {
         let sealed = seal_naming_key(&naming_key, &public_key)?;
 
         let wrong = Mnemonic::generate_in(Language::English, 12)?;
-        assert!(unseal_naming_key(&sealed, &wrong).is_err());
+        assert!(unseal_naming_key(&sealed, &wrong.into()).is_err());
 
         Ok(())
     }
@@ -416,6 +1155,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_passphrase_derives_a_different_keypair() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let with = |passphrase: &str| RecoveryKey {
+            mnemonic: mnemonic.clone(),
+            passphrase: Some(Zeroizing::new(passphrase.to_string())),
+        };
+
+        let (_, plain) = content_keypair(&mnemonic)?;
+        let (_, protected) = with("correct horse").keypair()?;
+        assert_ne!(plain.to_bytes(), protected.to_bytes());
+        assert_ne!(
+            protected.to_bytes(),
+            with("correct horse!").keypair()?.1.to_bytes()
+        );
+        assert_eq!(
+            protected.to_bytes(),
+            with("correct horse").keypair()?.1.to_bytes()
+        );
+        // An empty passphrase is the same as none, as BIP-39 defines it.
+        assert_eq!(plain.to_bytes(), with("").keypair()?.1.to_bytes());
+
+        Ok(())
+    }
+
     const TEST_ID: &str = "abcd1234deadbeefabcd1234deadbeefabcd1234deadbeefabcd1234deadbeef";
 
     /// Unwrap the content key from a sealed blob and open it (mirrors restore).
@@ -424,19 +1188,49 @@ mod tests {
         content_id: &str,
         mnemonic: &Mnemonic,
     ) -> Result<Zeroizing<Vec<u8>>> {
-        let key_vec = decrypt(
+        let key = unwrap_key(
             &sealed.wrapped_key,
             &sealed.ephemeral_public_key,
+            content_id,
+            mnemonic,
+        )?;
+        open_content(&sealed.blob, content_id, &key)
+    }
+
+    fn unwrap_key(
+        wrapped_key: &[u8],
+        ephemeral_public_key: &[u8; 32],
+        content_id: &str,
+        mnemonic: &Mnemonic,
+    ) -> Result<[u8; 32]> {
+        let key_vec = decrypt(
+            wrapped_key,
+            ephemeral_public_key,
             mnemonic,
             &content_key_aad(content_id),
         )?;
-        let key: [u8; 32] = key_vec
+        key_vec
             .as_slice()
             .try_into()
-            .map_err(|_| anyhow!("bad content key length"))?;
-        open_content(&sealed.blob, content_id, &key)
+            .map_err(|_| anyhow!("bad content key length"))
     }
 
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        rand::rng().fill_bytes(&mut data);
+        data
+    }
+
+    /// Sizes straddling the segment boundaries, where framing bugs live.
+    const STREAM_SIZES: [usize; 6] = [
+        0,
+        1,
+        SEGMENT_SIZE - 1,
+        SEGMENT_SIZE,
+        SEGMENT_SIZE + 1,
+        3 * SEGMENT_SIZE + 17,
+    ];
+
     #[test]
     fn test_seal_open_roundtrip_compressible() -> Result<()> {
         let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
@@ -491,4 +1285,255 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_seal_open_catalog() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let (secret, public_key) = content_keypair(&mnemonic)?;
+        let db = random_bytes(3 * SEGMENT_SIZE);
+
+        let mut sealed = Vec::new();
+        seal_catalog(db.as_slice(), &mut sealed, &public_key, "daily")?;
+        assert!(sealed.starts_with(CATALOG_MAGIC));
+        let mut opened = Vec::new();
+        let written = open_catalog(sealed.as_slice(), &mut opened, &secret, "daily")?;
+        assert_eq!(written, u64::try_from(db.len())?);
+        assert_eq!(opened, db);
+
+        // Bound to the backup name and the keypair, and versioned.
+        let open = |envelope: &[u8], secret: &StaticSecret, backup: &str| {
+            open_catalog(envelope, std::io::sink(), secret, backup)
+        };
+        assert!(open(&sealed, &secret, "weekly").is_err());
+        let (other, _) = content_keypair(&Mnemonic::generate_in(Language::English, 12)?)?;
+        assert!(open(&sealed, &other, "daily").is_err());
+        assert!(open(sealed.get(..40).unwrap_or_default(), &secret, "daily").is_err());
+        let mut future = sealed;
+        if let Some(version) = future.get_mut(CATALOG_MAGIC.len() - 1) {
+            *version = 2;
+        }
+        assert!(open(&future, &secret, "daily").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_sealed_for_recipients() -> Result<()> {
+        let (secret, public_key) = content_keypair(&Mnemonic::generate_in(Language::English, 12)?)?;
+        let (escrow, escrow_public) =
+            content_keypair(&Mnemonic::generate_in(Language::English, 12)?)?;
+        let (late, late_public) = content_keypair(&Mnemonic::generate_in(Language::English, 12)?)?;
+        let plaintext = random_bytes(2 * SEGMENT_SIZE + 5);
+
+        let sealed = seal_content_for(&plaintext, &public_key, &[escrow_public], TEST_ID)?;
+        let [escrow_key] = sealed.recipient_keys.as_slice() else {
+            return Err(anyhow!("expected one recipient key"));
+        };
+        for (wrapped, eph, key) in [
+            (&sealed.wrapped_key, &sealed.ephemeral_public_key, &secret),
+            (
+                &escrow_key.wrapped_key,
+                &escrow_key.ephemeral_public_key,
+                &escrow,
+            ),
+        ] {
+            let content_key = unwrap_content_key(wrapped, eph, key, TEST_ID)?;
+            assert_eq!(
+                *open_content(&sealed.blob, TEST_ID, &content_key)?,
+                plaintext
+            );
+        }
+
+        // A recipient added later gets the same key, re-wrapped; the re-wrap is
+        // bound to the content id like the original.
+        let rewrapped = rewrap_content_key(
+            &sealed.wrapped_key,
+            &sealed.ephemeral_public_key,
+            &secret,
+            &late_public,
+            TEST_ID,
+        )?;
+        let content_key = unwrap_content_key(
+            &rewrapped.wrapped_key,
+            &rewrapped.ephemeral_public_key,
+            &late,
+            TEST_ID,
+        )?;
+        assert_eq!(
+            *open_content(&sealed.blob, TEST_ID, &content_key)?,
+            plaintext
+        );
+        assert!(
+            unwrap_content_key(
+                &rewrapped.wrapped_key,
+                &rewrapped.ephemeral_public_key,
+                &late,
+                "other"
+            )
+            .is_err()
+        );
+        // Only a keyholder can re-wrap.
+        assert!(
+            rewrap_content_key(
+                &sealed.wrapped_key,
+                &sealed.ephemeral_public_key,
+                &late,
+                &late_public,
+                TEST_ID,
+            )
+            .is_err()
+        );
+
+        assert_eq!(
+            parse_public_key(&hex::encode(late_public.as_bytes()))?.as_bytes(),
+            late_public.as_bytes()
+        );
+        assert!(parse_public_key("abcd").is_err());
+        assert!(parse_public_key("not hex").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_roundtrip_at_segment_boundaries() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let (_, public_key) = content_keypair(&mnemonic)?;
+
+        for size in STREAM_SIZES {
+            let plaintext = random_bytes(size);
+            let mut blob = Vec::new();
+            let wrapped = seal_reader(plaintext.as_slice(), &mut blob, &public_key, TEST_ID)?;
+            assert_eq!(blob.get(1).copied(), Some(CODEC_ZSTD));
+            let key = unwrap_key(
+                &wrapped.wrapped_key,
+                &wrapped.ephemeral_public_key,
+                TEST_ID,
+                &mnemonic,
+            )?;
+
+            let mut opened = Vec::new();
+            let written = open_reader(blob.as_slice(), &mut opened, TEST_ID, &key)?;
+            assert_eq!(written, u64::try_from(size)?);
+            assert_eq!(opened, plaintext, "size {size}");
+            // The in-memory opener reads the same format.
+            assert_eq!(open_content(&blob, TEST_ID, &key)?.as_slice(), plaintext);
+
+            // And the in-memory sealer's (raw, random) blobs stream back out.
+            let sealed = seal_content(&plaintext, &public_key, TEST_ID)?;
+            let key = unwrap_key(
+                &sealed.wrapped_key,
+                &sealed.ephemeral_public_key,
+                TEST_ID,
+                &mnemonic,
+            )?;
+            let mut opened = Vec::new();
+            open_reader(sealed.blob.as_slice(), &mut opened, TEST_ID, &key)?;
+            assert_eq!(opened, plaintext, "size {size}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_stream_roundtrip() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let (_, public_key) = content_keypair(&mnemonic)?;
+
+        for size in STREAM_SIZES {
+            let plaintext = b"compressible ".repeat(size / 13 + 1);
+            let mut blob = Vec::new();
+            let wrapped = seal_async(plaintext.as_slice(), &mut blob, &public_key, TEST_ID).await?;
+            let key = unwrap_key(
+                &wrapped.wrapped_key,
+                &wrapped.ephemeral_public_key,
+                TEST_ID,
+                &mnemonic,
+            )?;
+
+            let mut opened = Vec::new();
+            open_async(blob.as_slice(), &mut opened, TEST_ID, &key).await?;
+            assert_eq!(opened, plaintext, "size {size}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reordered_or_truncated_segments_fail() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let (_, public_key) = content_keypair(&mnemonic)?;
+
+        // Random -> raw codec, so the blob is exactly three segments.
+        let sealed = seal_content(&random_bytes(2 * SEGMENT_SIZE + 100), &public_key, TEST_ID)?;
+        let key = unwrap_key(
+            &sealed.wrapped_key,
+            &sealed.ephemeral_public_key,
+            TEST_ID,
+            &mnemonic,
+        )?;
+        let header_len = BLOB_HEADER_LEN + NONCE_PREFIX_LEN;
+        let (header, body) = sealed
+            .blob
+            .split_at_checked(header_len)
+            .ok_or_else(|| anyhow!("blob too short"))?;
+        let segments: Vec<&[u8]> = body.chunks(SEALED_SEGMENT_SIZE).collect();
+        assert_eq!(segments.len(), 3);
+
+        let blob_of = |order: &[usize]| -> Vec<u8> {
+            let mut blob = header.to_vec();
+            for index in order {
+                blob.extend_from_slice(segments.get(*index).copied().unwrap_or_default());
+            }
+            blob
+        };
+
+        assert!(open_content(&blob_of(&[0, 1, 2]), TEST_ID, &key).is_ok());
+        for order in [
+            &[0, 1][..], // truncated on a segment boundary
+            &[1, 0, 2],  // reordered
+            &[0, 2],     // middle segment dropped
+            &[1, 2],     // first segment dropped
+            &[],         // everything but the header dropped
+        ] {
+            let blob = blob_of(order);
+            assert!(open_content(&blob, TEST_ID, &key).is_err(), "{order:?}");
+            assert!(
+                open_reader(blob.as_slice(), std::io::sink(), TEST_ID, &key).is_err(),
+                "{order:?}"
+            );
+        }
+
+        // Truncated mid-segment.
+        let mut blob = blob_of(&[0, 1, 2]);
+        blob.truncate(blob.len() - 1);
+        assert!(open_content(&blob, TEST_ID, &key).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_v1_blob_still_opens() -> Result<()> {
+        let key = [5u8; 32];
+        let plaintext = b"written before segments ".repeat(100);
+        let payload = zstd::encode_all(plaintext.as_slice(), ZSTD_LEVEL)?;
+
+        let nonce = [3u8; NONCE_LEN];
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &payload,
+                    aad: &content_aad(TEST_ID, BLOB_VERSION_V1, CODEC_ZSTD),
+                },
+            )
+            .map_err(|_| anyhow!("encryption failed"))?;
+        let mut blob = vec![BLOB_VERSION_V1, CODEC_ZSTD];
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+
+        assert_eq!(open_content(&blob, TEST_ID, &key)?.as_slice(), plaintext);
+        // The streaming opener only reads the segmented format.
+        assert!(open_reader(blob.as_slice(), std::io::sink(), TEST_ID, &key).is_err());
+
+        Ok(())
+    }
 }
@@ -1,3 +1,6 @@
+pub mod chunk;
 pub mod crypto;
 pub mod format;
 pub mod hash;
+pub mod shamir;
+pub mod sparse;
@@ -0,0 +1,399 @@
+//! Shamir secret sharing of the recovery mnemonic (DESIGN §5).
+//!
+//! The mnemonic's 128-bit entropy is split byte by byte over GF(2^8): each byte
+//! is the constant term of a random polynomial of degree `threshold - 1`, and
+//! share `x` holds every polynomial evaluated at `x`. Any `threshold` shares
+//! interpolate the entropy back; fewer reveal nothing about it.
+//!
+//! As in SLIP-39, a share is written down as words, but taken from the BIP-39
+//! English list (11 bits each) so it is typed at the same prompts as the
+//! mnemonic. Its [`SHARE_WORDS`] words hold a 15-bit identifier common to one
+//! split, the threshold, the share's number, the 128-bit share and a 25-bit
+//! checksum. The identifier keeps shares of different splits apart; the
+//! checksum catches a mistyped word. It is not SLIP-39 wire-compatible: there
+//! are no groups, and no passphrase encrypts the secret (a backup has its own).
+
+use anyhow::{Result, anyhow};
+use bip39::{Language, Mnemonic};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// Words in a written-down share.
+pub const SHARE_WORDS: usize = 16;
+
+/// Most shares one split can have: the share number is four bits.
+pub const MAX_SHARES: u8 = 16;
+
+const SECRET_LEN: usize = 16;
+const WORD_BITS: usize = 11;
+const ID_BITS: usize = 15;
+const CHECKSUM_BITS: usize = 25;
+/// Bits before the checksum: identifier, threshold, number, share.
+const PAYLOAD_BITS: usize = ID_BITS + 4 + 4 + SECRET_LEN * 8;
+const CHECKSUM_LABEL: &[u8] = b"backup-mnemonic-share-v1";
+
+/// One share of a split mnemonic.
+#[derive(Clone)]
+pub struct Share {
+    identifier: u16,
+    threshold: u8,
+    /// Where the polynomials were evaluated, 1-based; also the share's number.
+    x: u8,
+    value: Zeroizing<[u8; SECRET_LEN]>,
+}
+
+impl Share {
+    /// Shares needed to recover the mnemonic.
+    #[must_use]
+    pub const fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// The share's number within its split, from 1.
+    #[must_use]
+    pub const fn number(&self) -> u8 {
+        self.x
+    }
+
+    /// Whether `phrase` has the length of a share rather than a mnemonic.
+    #[must_use]
+    pub fn is_share(phrase: &str) -> bool {
+        phrase.split_whitespace().count() == SHARE_WORDS
+    }
+
+    /// Read a written-down share.
+    ///
+    /// # Errors
+    /// Returns an error if the phrase has the wrong number of words, a word is
+    /// not in the list, or the checksum does not match.
+    pub fn parse(phrase: &str) -> Result<Self> {
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+        if words.len() != SHARE_WORDS {
+            return Err(anyhow!(
+                "A share has {SHARE_WORDS} words, not {}",
+                words.len()
+            ));
+        }
+
+        let mut bits = Bits::default();
+        for word in words {
+            let index = Language::English
+                .find_word(&word.to_lowercase())
+                .ok_or_else(|| anyhow!("Invalid share: unknown word '{word}'"))?;
+            bits.push(u32::from(index), WORD_BITS);
+        }
+
+        let mut value = Zeroizing::new([0u8; SECRET_LEN]);
+        for (byte, offset) in value.iter_mut().zip((ID_BITS + 8..).step_by(8)) {
+            *byte = u8::try_from(bits.read(offset, 8))?;
+        }
+        let share = Self {
+            identifier: u16::try_from(bits.read(0, ID_BITS))?,
+            threshold: u8::try_from(bits.read(ID_BITS, 4))? + 1,
+            x: u8::try_from(bits.read(ID_BITS + 4, 4))? + 1,
+            value,
+        };
+
+        if bits.read(PAYLOAD_BITS, CHECKSUM_BITS) != share.checksum() {
+            return Err(anyhow!("Invalid share: a word is mistyped or out of order"));
+        }
+        if share.threshold < 2 {
+            return Err(anyhow!("Invalid share: a threshold of 1 is not a split"));
+        }
+        Ok(share)
+    }
+
+    /// The share as words, to write down.
+    ///
+    /// # Errors
+    /// Returns an error only if the word list is inconsistent.
+    pub fn to_phrase(&self) -> Result<Zeroizing<String>> {
+        let mut bits = self.payload();
+        bits.push(self.checksum(), CHECKSUM_BITS);
+
+        let list = Language::English.word_list();
+        let words = (0..SHARE_WORDS)
+            .map(|word| {
+                usize::try_from(bits.read(word * WORD_BITS, WORD_BITS))
+                    .ok()
+                    .and_then(|index| list.get(index).copied())
+                    .ok_or_else(|| anyhow!("word index out of range"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Zeroizing::new(words.join(" ")))
+    }
+
+    fn payload(&self) -> Bits {
+        let mut bits = Bits::default();
+        bits.push(u32::from(self.identifier), ID_BITS);
+        bits.push(u32::from(self.threshold - 1), 4);
+        bits.push(u32::from(self.x - 1), 4);
+        for byte in self.value.iter() {
+            bits.push(u32::from(*byte), 8);
+        }
+        bits
+    }
+
+    /// The first [`CHECKSUM_BITS`] of a labelled SHA-256 of the payload.
+    fn checksum(&self) -> u32 {
+        let digest = Sha256::new()
+            .chain_update(CHECKSUM_LABEL)
+            .chain_update(self.payload().0.as_slice())
+            .finalize();
+        digest
+            .first_chunk::<4>()
+            .map_or(0, |head| u32::from_be_bytes(*head) >> (32 - CHECKSUM_BITS))
+    }
+}
+
+/// Check that `count` shares with a `threshold` make a split.
+///
+/// # Errors
+/// Returns an error unless `2 <= threshold <= count <= MAX_SHARES`.
+pub fn check_split(threshold: u8, count: u8) -> Result<()> {
+    if threshold < 2 || threshold > count || count > MAX_SHARES {
+        return Err(anyhow!(
+            "A split needs 2 <= threshold <= shares <= {MAX_SHARES}, not a threshold of {threshold} with {count} shares"
+        ));
+    }
+    Ok(())
+}
+
+/// Split a 12-word mnemonic into `count` shares, any `threshold` of which
+/// recover it.
+///
+/// # Errors
+/// Returns an error if the split is invalid ([`check_split`]) or the mnemonic
+/// does not hold 128 bits of entropy.
+pub fn split(mnemonic: &Mnemonic, threshold: u8, count: u8) -> Result<Vec<Share>> {
+    check_split(threshold, count)?;
+    let entropy = Zeroizing::new(mnemonic.to_entropy());
+    let secret: Zeroizing<[u8; SECRET_LEN]> = Zeroizing::new(
+        entropy
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("Only a 12-word mnemonic can be split"))?,
+    );
+
+    let mut rng = rand::rng();
+    let identifier = u16::try_from(rng.next_u32() >> (32 - ID_BITS))?;
+    let mut coefficients = Zeroizing::new(vec![[0u8; SECRET_LEN]; usize::from(threshold - 1)]);
+    for coefficient in coefficients.iter_mut() {
+        rng.fill_bytes(coefficient);
+    }
+
+    Ok((1..=count)
+        .map(|x| {
+            // secret + c1·x + c2·x² + …, for every byte at once.
+            let mut value = secret.clone();
+            let mut power = 1;
+            for coefficient in coefficients.iter() {
+                power = gf_mul(power, x);
+                for (byte, c) in value.iter_mut().zip(coefficient) {
+                    *byte ^= gf_mul(*c, power);
+                }
+            }
+            Share {
+                identifier,
+                threshold,
+                x,
+                value,
+            }
+        })
+        .collect())
+}
+
+/// Recover the mnemonic from a threshold of its shares.
+///
+/// # Errors
+/// Returns an error if too few shares are given, they come from different
+/// splits, or one is given twice.
+pub fn combine(shares: &[Share]) -> Result<Mnemonic> {
+    let first = shares.first().ok_or_else(|| anyhow!("No shares given"))?;
+    for (i, share) in shares.iter().enumerate() {
+        if share.identifier != first.identifier || share.threshold != first.threshold {
+            return Err(anyhow!(
+                "Share {} belongs to a different split than share {}",
+                share.x,
+                first.x
+            ));
+        }
+        if shares.iter().take(i).any(|other| other.x == share.x) {
+            return Err(anyhow!("Share {} was given twice", share.x));
+        }
+    }
+    if shares.len() < usize::from(first.threshold) {
+        return Err(anyhow!(
+            "{} shares are needed, only {} given",
+            first.threshold,
+            shares.len()
+        ));
+    }
+
+    // Lagrange interpolation at 0, where subtraction is XOR.
+    let used = || shares.iter().take(usize::from(first.threshold));
+    let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+    for share in used() {
+        let basis = used()
+            .filter(|other| other.x != share.x)
+            .fold(1, |basis, other| {
+                gf_mul(basis, gf_mul(other.x, gf_inv(other.x ^ share.x)))
+            });
+        for (byte, y) in secret.iter_mut().zip(share.value.iter()) {
+            *byte ^= gf_mul(*y, basis);
+        }
+    }
+
+    Ok(Mnemonic::from_entropy_in(
+        Language::English,
+        secret.as_slice(),
+    )?)
+}
+
+/// Multiply in GF(2^8) with the AES polynomial, without branching on either
+/// operand.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Invert in GF(2^8) as `a^254`; zero, which has no inverse, maps to zero.
+fn gf_inv(a: u8) -> u8 {
+    let (mut result, mut base, mut exponent) = (1, a, 254u8);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// A bit string, most significant bit first, one byte per bit so it can be
+/// zeroized with the share it spells.
+#[derive(Default)]
+struct Bits(Zeroizing<Vec<u8>>);
+
+impl Bits {
+    fn push(&mut self, value: u32, width: usize) {
+        for shift in (0..width).rev() {
+            self.0.push(u8::from((value >> shift) & 1 == 1));
+        }
+    }
+
+    fn read(&self, start: usize, width: usize) -> u32 {
+        self.0
+            .iter()
+            .skip(start)
+            .take(width)
+            .fold(0, |value, bit| (value << 1) | u32::from(*bit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrases(shares: &[Share]) -> Result<Vec<Zeroizing<String>>> {
+        shares.iter().map(Share::to_phrase).collect()
+    }
+
+    #[test]
+    fn any_threshold_of_shares_recovers_the_mnemonic() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let shares = split(&mnemonic, 3, 5)?;
+        assert_eq!(shares.len(), 5);
+
+        // Every 3 of the 5, as written down and read back.
+        let phrases = phrases(&shares)?;
+        for a in 0..5 {
+            for b in a + 1..5 {
+                for c in b + 1..5 {
+                    let picked = [a, b, c]
+                        .iter()
+                        .filter_map(|i| phrases.get(*i))
+                        .map(|phrase| Share::parse(phrase))
+                        .collect::<Result<Vec<_>>>()?;
+                    assert_eq!(combine(&picked)?, mnemonic);
+                }
+            }
+        }
+
+        // Two are not enough, and a surplus share is ignored.
+        assert!(combine(shares.get(..2).unwrap_or_default()).is_err());
+        assert_eq!(combine(&shares)?, mnemonic);
+        Ok(())
+    }
+
+    #[test]
+    fn a_share_reads_back_and_a_typo_is_caught() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let shares = split(&mnemonic, 2, 3)?;
+        let share = shares.get(1).ok_or_else(|| anyhow!("missing share"))?;
+        let phrase = share.to_phrase()?;
+        assert!(Share::is_share(&phrase));
+        assert!(!Share::is_share(&mnemonic.to_string()));
+
+        let parsed = Share::parse(&phrase.to_uppercase())?;
+        assert_eq!((parsed.number(), parsed.threshold()), (2, 2));
+        assert_eq!(*parsed.value, *share.value);
+
+        // Swap one word for its neighbour in the list.
+        let mut words: Vec<String> = phrase.split_whitespace().map(str::to_string).collect();
+        let word = words.get_mut(5).ok_or_else(|| anyhow!("short share"))?;
+        let index = Language::English
+            .find_word(word)
+            .ok_or_else(|| anyhow!("unknown word"))?;
+        *word = Language::English
+            .word_list()
+            .get(usize::from((index + 1) % 2048))
+            .ok_or_else(|| anyhow!("no neighbour"))?
+            .to_string();
+        assert!(Share::parse(&words.join(" ")).is_err());
+        assert!(Share::parse("abandon ability able").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn shares_of_different_splits_or_repeated_do_not_combine() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let first = split(&mnemonic, 2, 3)?;
+        let second = split(&mnemonic, 2, 3)?;
+
+        let mixed: Vec<Share> = first
+            .iter()
+            .take(1)
+            .chain(second.iter().skip(1).take(1))
+            .cloned()
+            .collect();
+        assert!(combine(&mixed).is_err());
+
+        let repeated: Vec<Share> = first.iter().take(1).cycle().take(2).cloned().collect();
+        assert!(combine(&repeated).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn split_bounds() -> Result<()> {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        assert!(split(&mnemonic, 1, 3).is_err());
+        assert!(split(&mnemonic, 4, 3).is_err());
+        assert!(split(&mnemonic, 2, MAX_SHARES + 1).is_err());
+        assert_eq!(split(&mnemonic, MAX_SHARES, MAX_SHARES)?.len(), 16);
+        assert!(split(&Mnemonic::generate_in(Language::English, 24)?, 2, 3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn gf_inverse() {
+        assert!((1..=255).all(|a| gf_mul(a, gf_inv(a)) == 1));
+    }
+}
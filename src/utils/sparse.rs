@@ -0,0 +1,303 @@
+use anyhow::{Result, anyhow};
+use rustix::{fs::SeekFrom, io::Errno};
+use std::{
+    fs::File,
+    io::{self, Read, Seek},
+};
+
+/// Whether a `SEEK_DATA`/`SEEK_HOLE` error means the filesystem cannot map
+/// holes at all, rather than that this file failed.
+fn no_hole_support(err: Errno) -> bool {
+    err == Errno::INVAL || err == Errno::OPNOTSUPP
+}
+
+/// Where a sparse file's data lies: its length and its data extents as
+/// `(offset, length)`, in order. Everything else is a hole, read back as zeros.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SparseMap {
+    pub size: u64,
+    pub extents: Vec<(u64, u64)>,
+}
+
+impl SparseMap {
+    /// Map a file's data extents with `SEEK_DATA`/`SEEK_HOLE` (DESIGN §6.8).
+    /// `None` when the file has no holes, or the filesystem reports none or
+    /// cannot seek for them — it is then read whole, like any other file.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be stat'ed or seeked.
+    pub fn detect(file: &File) -> io::Result<Option<Self>> {
+        let size = file.metadata()?.len();
+        let map = Self::map(size, |whence| rustix::fs::seek(file, whence));
+        // Seeking for data and holes moved the offset; read from the start.
+        rustix::fs::seek(file, SeekFrom::Start(0))?;
+        map
+    }
+
+    /// [`SparseMap::detect`] over `seek`, which seeks the file as `lseek(2)`
+    /// does.
+    fn map(
+        size: u64,
+        mut seek: impl FnMut(SeekFrom) -> rustix::io::Result<u64>,
+    ) -> io::Result<Option<Self>> {
+        let mut extents = Vec::new();
+        let mut offset = 0;
+        while offset < size {
+            let start = match seek(SeekFrom::Data(offset)) {
+                Ok(start) => start,
+                // No data past `offset`: the rest is a hole.
+                Err(Errno::NXIO) => break,
+                Err(err) if no_hole_support(err) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+            let end = match seek(SeekFrom::Hole(start)) {
+                Ok(end) => end.min(size),
+                Err(err) if no_hole_support(err) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+            if end <= start {
+                break;
+            }
+            extents.push((start, end - start));
+            offset = end;
+        }
+
+        if extents == [(0, size)] || size == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self { size, extents }))
+    }
+
+    /// Total length of the data extents: what is chunked and stored.
+    #[must_use]
+    pub fn data_len(&self) -> u64 {
+        self.extents.iter().map(|(_, length)| length).sum()
+    }
+
+    /// Content id of a sparse file: its data's keyed id bound to the map, so
+    /// the same bytes laid out differently are different content.
+    #[must_use]
+    pub fn content_id(&self, data_id: &str, key: &[u8; 32]) -> String {
+        let mut hasher = blake3::Hasher::new_keyed(key);
+        hasher.update(b"sparse\0");
+        hasher.update(data_id.as_bytes());
+        hasher.update(&self.encode());
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Catalog encoding (`Files.extents`): the size, then each extent's
+    /// offset and length, all little-endian `u64`.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.extents.len() * 16);
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        for (offset, length) in &self.extents {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&length.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decode [`SparseMap::encode`]'s output.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are not a well-formed map.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let malformed = || anyhow!("malformed sparse extent map");
+        let mut words = bytes.chunks_exact(8);
+        if !words.remainder().is_empty() {
+            return Err(malformed());
+        }
+        let mut next = || {
+            words
+                .next()
+                .and_then(|word| word.try_into().ok())
+                .map(u64::from_le_bytes)
+        };
+        let size = next().ok_or_else(malformed)?;
+        let mut extents = Vec::new();
+        let mut end = 0;
+        while let Some(offset) = next() {
+            let length = next().ok_or_else(malformed)?;
+            let extent_end = offset.checked_add(length).ok_or_else(malformed)?;
+            if offset < end || extent_end > size {
+                return Err(malformed());
+            }
+            extents.push((offset, length));
+            end = extent_end;
+        }
+        Ok(Self { size, extents })
+    }
+
+    /// Read only the data extents of `file`, back to back. A file that shrank
+    /// since it was mapped fails the read rather than yield a short extent.
+    #[must_use]
+    pub fn reader(&self, file: File) -> DataReader {
+        DataReader {
+            file,
+            extents: self.extents.clone().into_iter(),
+            remaining: 0,
+        }
+    }
+
+    /// Place data bytes back at their offsets: splits the next `available`
+    /// bytes of the data stream at extent boundaries.
+    #[must_use]
+    pub fn cursor(&self) -> ExtentCursor {
+        ExtentCursor {
+            extents: self.extents.clone().into_iter(),
+            at: 0,
+            remaining: 0,
+        }
+    }
+}
+
+/// A [`Read`] over a sparse file's data extents; see [`SparseMap::reader`].
+pub struct DataReader {
+    file: File,
+    extents: std::vec::IntoIter<(u64, u64)>,
+    /// Bytes left in the current extent.
+    remaining: u64,
+}
+
+impl Read for DataReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            let Some((offset, length)) = self.extents.next() else {
+                return Ok(0);
+            };
+            self.file.seek(io::SeekFrom::Start(offset))?;
+            self.remaining = length;
+        }
+        let want = usize::try_from(self.remaining).map_or(buf.len(), |left| left.min(buf.len()));
+        let read = self.file.read(buf.get_mut(..want).unwrap_or_default())?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Walks a sparse file's extents as its data stream is written back; see
+/// [`SparseMap::cursor`].
+pub struct ExtentCursor {
+    extents: std::vec::IntoIter<(u64, u64)>,
+    /// File offset the next data byte goes to.
+    at: u64,
+    /// Bytes left in the current extent.
+    remaining: u64,
+}
+
+impl ExtentCursor {
+    /// The file offset for the next data bytes and how many of `available`
+    /// go there; `None` once every extent is full.
+    pub fn next_span(&mut self, available: usize) -> Option<(u64, usize)> {
+        while self.remaining == 0 {
+            let (offset, length) = self.extents.next()?;
+            self.at = offset;
+            self.remaining = length;
+        }
+        let len = usize::try_from(self.remaining).map_or(available, |left| left.min(available));
+        let span = (self.at, len);
+        self.at += len as u64;
+        self.remaining -= len as u64;
+        Some(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn maps_and_reads_only_the_data_of_a_sparse_file() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("disk.img");
+        let mut file = File::create(&path)?;
+        file.set_len(8 << 20)?;
+        file.seek(io::SeekFrom::Start(1 << 20))?;
+        file.write_all(&[7_u8; 4096])?;
+        file.seek(io::SeekFrom::Start(5 << 20))?;
+        file.write_all(&[9_u8; 4096])?;
+        file.sync_all()?;
+
+        let file = File::open(&path)?;
+        let Some(map) = SparseMap::detect(&file)? else {
+            // The filesystem reports no holes; nothing sparse to check.
+            return Ok(());
+        };
+        assert_eq!(map.size, 8 << 20);
+        assert!(map.data_len() < map.size);
+        let mut data = Vec::new();
+        map.reader(file).read_to_end(&mut data)?;
+        assert_eq!(u64::try_from(data.len())?, map.data_len());
+        assert!(data.windows(4096).any(|run| run == [7_u8; 4096]));
+        assert!(data.windows(4096).any(|run| run == [9_u8; 4096]));
+
+        assert_eq!(SparseMap::decode(&map.encode())?, map);
+        Ok(())
+    }
+
+    #[test]
+    fn dense_files_have_no_map() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("dense");
+        std::fs::write(&path, b"no holes here")?;
+        let mut file = File::open(&path)?;
+        assert_eq!(SparseMap::detect(&file)?, None);
+        // Left ready to be read whole.
+        let mut data = String::new();
+        file.read_to_string(&mut data)?;
+        assert_eq!(data, "no holes here");
+        Ok(())
+    }
+
+    #[test]
+    fn filesystems_without_hole_support_read_files_whole() -> Result<()> {
+        for errno in [Errno::INVAL, Errno::OPNOTSUPP] {
+            assert_eq!(SparseMap::map(100, |_| Err(errno))?, None);
+            let mut seeks = 0;
+            let map = SparseMap::map(100, |whence| {
+                seeks += 1;
+                match whence {
+                    SeekFrom::Data(offset) => Ok(offset),
+                    _ => Err(errno),
+                }
+            })?;
+            assert_eq!((map, seeks), (None, 2));
+        }
+        // Any other failure is still the file's.
+        assert!(SparseMap::map(100, |_| Err(Errno::IO)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cursor_splits_data_at_extent_boundaries() {
+        let map = SparseMap {
+            size: 100,
+            extents: vec![(10, 5), (50, 20)],
+        };
+        let mut cursor = map.cursor();
+        assert_eq!(cursor.next_span(8), Some((10, 5)));
+        assert_eq!(cursor.next_span(3), Some((50, 3)));
+        assert_eq!(cursor.next_span(100), Some((53, 17)));
+        assert_eq!(cursor.next_span(1), None);
+    }
+
+    #[test]
+    fn decode_rejects_overlapping_or_oversized_extents() {
+        let overlapping = SparseMap {
+            size: 100,
+            extents: vec![(10, 20), (15, 5)],
+        };
+        assert!(SparseMap::decode(&overlapping.encode()).is_err());
+        let oversized = SparseMap {
+            size: 10,
+            extents: vec![(5, 10)],
+        };
+        assert!(SparseMap::decode(&oversized.encode()).is_err());
+        assert!(SparseMap::decode(b"short").is_err());
+    }
+}